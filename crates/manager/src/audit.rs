@@ -0,0 +1,236 @@
+//! Log of every change made to nodes and the other resources of the
+//! manager: who made it, from where, and what changed, with secrets redacted.
+//! Requests denied by [`crate::rbac`] are logged too.
+
+use std::net::SocketAddr;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth::Principal;
+use crate::revision::{FieldChange, RevisionAction};
+
+/// Rathole settings whose values are never written to the audit log.
+const SECRET_FIELDS: &[&str] = &[
+    "token",
+    "default_token",
+    "previous_tokens",
+    "local_private_key",
+    "pkcs12_password",
+];
+
+const REDACTED: &str = "[redacted]";
+
+/// Who made a change, and from where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    /// Also the author of the revisions made by the change.
+    pub name: String,
+    pub source_ip: Option<String>,
+}
+
+impl Actor {
+    pub fn new(principal: &Principal, source: Option<SocketAddr>) -> Self {
+        Self {
+            name: principal.author(),
+            source_ip: source.map(|addr| addr.ip().to_string()),
+        }
+    }
+
+    /// Changes applied from the configuration file, at startup or by
+    /// `laval-manager apply`.
+    pub fn config_file() -> Self {
+        Self {
+            name: "config-file".to_string(),
+            source_ip: None,
+        }
+    }
+
+    /// Changes the manager makes on its own, such as scheduled rotations.
+    pub fn manager() -> Self {
+        Self {
+            name: "manager".to_string(),
+            source_ip: None,
+        }
+    }
+}
+
+/// What an audit event is about.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditResource {
+    Node,
+    /// The managed Noise key of a node, named after the node.
+    NoiseKey,
+    Tunnel,
+    Certificate,
+    Template,
+    Project,
+    Token,
+}
+
+impl AuditResource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditResource::Node => "node",
+            AuditResource::NoiseKey => "noise_key",
+            AuditResource::Tunnel => "tunnel",
+            AuditResource::Certificate => "certificate",
+            AuditResource::Template => "template",
+            AuditResource::Project => "project",
+            AuditResource::Token => "token",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "node" => AuditResource::Node,
+            "noise_key" => AuditResource::NoiseKey,
+            "tunnel" => AuditResource::Tunnel,
+            "certificate" => AuditResource::Certificate,
+            "template" => AuditResource::Template,
+            "project" => AuditResource::Project,
+            "token" => AuditResource::Token,
+            other => bail!("unknown audit resource '{other}'"),
+        })
+    }
+}
+
+/// The resource a change is logged for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditTarget<'a> {
+    pub resource: AuditResource,
+    /// Unset for user API tokens.
+    pub project: Option<&'a str>,
+    pub name: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Rollback,
+    /// A tunnel token or Noise key was replaced.
+    Rotate,
+    /// A request the role or tags of the actor's token do not allow.
+    Denied,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Rollback => "rollback",
+            AuditAction::Rotate => "rotate",
+            AuditAction::Denied => "denied",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "create" => AuditAction::Create,
+            "update" => AuditAction::Update,
+            "delete" => AuditAction::Delete,
+            "rollback" => AuditAction::Rollback,
+            "rotate" => AuditAction::Rotate,
+            "denied" => AuditAction::Denied,
+            other => bail!("unknown audit action '{other}'"),
+        })
+    }
+}
+
+impl From<RevisionAction> for AuditAction {
+    fn from(action: RevisionAction) -> Self {
+        match action {
+            RevisionAction::Create => AuditAction::Create,
+            RevisionAction::Update => AuditAction::Update,
+            RevisionAction::Delete => AuditAction::Delete,
+            RevisionAction::Rollback => AuditAction::Rollback,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub action: AuditAction,
+    /// Unset for denied requests that did not target anything.
+    pub resource: Option<AuditResource>,
+    /// Project of the resource; unset for user API tokens and denied
+    /// requests without one.
+    pub project: Option<String>,
+    /// Name of the resource; empty for denied requests that did not target
+    /// anything.
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+    /// What was denied and why.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Filters of `GET /audit`. Events are listed newest first.
+#[derive(Debug, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub project: Option<String>,
+    pub resource: Option<AuditResource>,
+    /// Name of the resource, such as a node.
+    pub name: Option<String>,
+    pub action: Option<AuditAction>,
+    /// Only events at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only events before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Defaults to 100, at most 1000.
+    #[serde(default)]
+    pub limit: u64,
+}
+
+/// Replaces the values of secret fields, so that the log shows that a secret
+/// changed without showing the secret.
+pub fn redact(changes: Vec<FieldChange>) -> Vec<FieldChange> {
+    changes
+        .into_iter()
+        .map(|mut change| {
+            let secret = change.path.split('.').any(is_secret);
+            for value in [&mut change.from, &mut change.to].into_iter().flatten() {
+                if secret {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+            change
+        })
+        .collect()
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if !is_secret(key) {
+                    redact_value(field);
+                } else if !field.is_null() {
+                    *field = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+fn is_secret(field: &str) -> bool {
+    SECRET_FIELDS.contains(&field)
+}
@@ -0,0 +1,168 @@
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+use utoipa::ToSchema;
+
+use crate::audit::Actor;
+use crate::config::ManagerState;
+use crate::error::AppError;
+use crate::rbac::{self, Grant, Role};
+
+const TOKEN_PREFIX: &str = "lvl_";
+
+/// Who a request was authenticated as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// A user token, identified by its name, with the access it was granted.
+    User { name: String, grant: Grant },
+    /// A node credential, limited to the node's own configuration and status.
+    Node { project: String, name: String },
+}
+
+impl Principal {
+    /// Recorded as the author of the changes made by this principal.
+    pub fn author(&self) -> String {
+        match self {
+            Principal::User { name, .. } => name.clone(),
+            Principal::Node { project, name } => format!("node:{project}/{name}"),
+        }
+    }
+}
+
+/// Available to handlers behind [`require_user_http`].
+impl FromRequest for Principal {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| AppError::unauthorized("missing or invalid API token")),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ApiToken {
+    pub name: String,
+    /// Project of the node of a per-node credential.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Set for per-node credentials.
+    pub node: Option<String>,
+    /// Role of user tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// Limits a user token to nodes carrying at least one of these tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A freshly created token; the secret is only ever returned once.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IssuedToken {
+    #[serde(flatten)]
+    pub info: ApiToken,
+    pub token: String,
+}
+
+pub fn generate_token() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    format!("{TOKEN_PREFIX}{}", hex::encode(secret))
+}
+
+/// Tokens are long random strings, so a plain SHA-256 is enough to keep them
+/// out of the database.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn bearer_token(header: &str) -> Option<&str> {
+    header
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Rejects HTTP requests that do not carry a user token. What the user may
+/// do is checked by each handler through [`crate::rbac::Caller`].
+pub async fn require_user_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = req
+        .app_data::<web::Data<Arc<ManagerState>>>()
+        .ok_or_else(|| AppError::internal("manager state is not configured"))?;
+    let principal = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+        .and_then(|token| state.authenticate(token));
+
+    match principal {
+        Some(principal @ Principal::User { .. }) => {
+            req.extensions_mut().insert(principal);
+            next.call(req).await
+        }
+        Some(principal @ Principal::Node { .. }) => {
+            let actor = Actor::new(&principal, req.peer_addr());
+            let detail = format!(
+                "{} {}: node credentials cannot use the HTTP API",
+                req.method(),
+                req.path()
+            );
+            Err(rbac::deny(state, &actor, None, &detail).await.into())
+        }
+        None => Err(AppError::unauthorized("missing or invalid API token").into()),
+    }
+}
+
+/// Authenticates gRPC calls and stores the [`Principal`] in the request
+/// extensions; each RPC then checks what the principal may access.
+#[derive(Clone)]
+pub struct GrpcAuth {
+    state: Arc<ManagerState>,
+}
+
+impl GrpcAuth {
+    pub fn new(state: Arc<ManagerState>) -> Self {
+        Self { state }
+    }
+}
+
+impl Interceptor for GrpcAuth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let principal = request
+            .metadata()
+            .get(AUTHORIZATION.as_str())
+            .and_then(|value| value.to_str().ok())
+            .and_then(bearer_token)
+            .and_then(|token| self.state.authenticate(token))
+            .ok_or_else(|| Status::unauthenticated("missing or invalid API token"))?;
+        request.extensions_mut().insert(principal);
+        Ok(request)
+    }
+}
+
+pub(crate) fn principal<T>(request: &Request<T>) -> Result<&Principal, Status> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .ok_or_else(|| Status::unauthenticated("missing or invalid API token"))
+}
@@ -0,0 +1,418 @@
+//! TLS certificates uploaded to the manager as PEM chains. A node's reverse
+//! proxy serves the certificate named by its `reverse_proxy.certificate`,
+//! which the node receives with its config instead of reading local files,
+//! and swaps in without a restart when the certificate is replaced.
+//!
+//! A Rathole server with the TLS transport serves the one named by its
+//! `port_mapping.certificate` the same way. Rathole takes a PKCS#12 identity,
+//! which the node builds from the chain and key in memory, so the key must
+//! be PKCS#8; a replaced certificate restarts the server.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::Pem;
+
+use crate::config::{ManagerState, NodeRecord};
+use crate::error::{AppError, AppResult};
+use crate::validate::FieldError;
+
+/// Certificates expiring within this many days are reported as `expiring`.
+pub const EXPIRY_WARNING_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Expiry {
+    Valid,
+    /// Expires within [`EXPIRY_WARNING_DAYS`] days.
+    Expiring,
+    Expired,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Certificate {
+    pub project: String,
+    pub name: String,
+    /// DNS names of the leaf certificate, which may start with `*.`.
+    pub hostnames: Vec<String>,
+    pub issuer: String,
+    /// SHA-256 of the leaf certificate in DER, in hex.
+    pub fingerprint: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// As of the time of the response.
+    pub expiry: Expiry,
+    /// Nodes whose reverse proxy or port mapping serves the certificate.
+    pub nodes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the chain and key were last replaced.
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub chain_pem: String,
+    /// Only ever sent to the nodes serving the certificate.
+    #[serde(skip)]
+    pub key_pem: String,
+}
+
+impl Certificate {
+    pub fn expiry_at(&self, now: DateTime<Utc>) -> Expiry {
+        if self.not_after <= now {
+            Expiry::Expired
+        } else if self.not_after - now <= chrono::Duration::days(EXPIRY_WARNING_DAYS) {
+            Expiry::Expiring
+        } else {
+            Expiry::Valid
+        }
+    }
+
+    /// Whether the certificate is valid for `hostname`.
+    pub fn covers(&self, hostname: &str) -> bool {
+        self.hostnames
+            .iter()
+            .any(|name| hostname_matches(name, hostname))
+    }
+
+    /// A `Warning` header value for a certificate that is expiring or has
+    /// expired.
+    pub fn warning(&self) -> Option<String> {
+        let message = match self.expiry {
+            Expiry::Valid => return None,
+            Expiry::Expiring => format!(
+                "certificate '{}' expires on {}",
+                self.name,
+                self.not_after.format("%Y-%m-%d")
+            ),
+            Expiry::Expired => format!(
+                "certificate '{}' expired on {}",
+                self.name,
+                self.not_after.format("%Y-%m-%d")
+            ),
+        };
+        Some(format!("299 laval-manager \"{message}\""))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct UploadCertificate {
+    pub name: String,
+    /// PEM certificates, the leaf first and then the intermediates that
+    /// issued it.
+    pub chain: String,
+    /// PEM private key of the leaf certificate, not encrypted.
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct ReplaceCertificate {
+    pub chain: String,
+    pub key: String,
+}
+
+/// What the manager keeps of an uploaded chain and key.
+#[derive(Debug, Clone)]
+pub struct ParsedCertificate {
+    pub hostnames: Vec<String>,
+    pub issuer: String,
+    pub fingerprint: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    pub chain_pem: String,
+    pub key_pem: String,
+}
+
+/// Parses a PEM chain and key, rejecting them with a 422 unless the chain is
+/// in order, the leaf is valid now and names at least one DNS hostname.
+/// Whether the key belongs to the leaf is left to the node.
+pub fn parse(chain: &str, key: &str) -> AppResult<ParsedCertificate> {
+    let mut errors = Vec::new();
+
+    let mut blocks = Vec::new();
+    for pem in Pem::iter_from_buffer(chain.as_bytes()) {
+        match pem {
+            Ok(pem) if pem.label == "CERTIFICATE" => blocks.push(pem),
+            Ok(pem) => errors.push(FieldError::new(
+                "chain",
+                format!("contains a {} instead of certificates only", pem.label),
+            )),
+            Err(err) => {
+                // The rest of the buffer cannot be read either.
+                errors.push(FieldError::new("chain", format!("is not PEM: {err}")));
+                break;
+            }
+        }
+    }
+    let mut certificates = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        match block.parse_x509() {
+            Ok(certificate) => certificates.push(certificate),
+            Err(err) => errors.push(FieldError::new(
+                format!("chain[{index}]"),
+                format!("is not an X.509 certificate: {err}"),
+            )),
+        }
+    }
+    check_key(&mut errors, key);
+    if !errors.is_empty() {
+        return Err(invalid(errors));
+    }
+    let (Some(leaf), Some(leaf_block)) = (certificates.first(), blocks.first()) else {
+        return Err(invalid(vec![FieldError::new(
+            "chain",
+            "contains no certificate",
+        )]));
+    };
+
+    for (index, pair) in certificates.windows(2).enumerate() {
+        if pair[0].issuer().as_raw() != pair[1].subject().as_raw() {
+            errors.push(FieldError::new(
+                format!("chain[{}]", index + 1),
+                format!(
+                    "did not issue chain[{index}]; put the leaf first, then its issuers in order"
+                ),
+            ));
+        }
+    }
+    let hostnames = hostnames_of(leaf);
+    if hostnames.is_empty() {
+        errors.push(FieldError::new(
+            "chain[0]",
+            "names no DNS hostname in its subject alternative names or common name",
+        ));
+    }
+    let not_before = timestamp(leaf.validity().not_before.timestamp());
+    let not_after = timestamp(leaf.validity().not_after.timestamp());
+    let now = Utc::now();
+    if not_after <= now {
+        errors.push(FieldError::new(
+            "chain[0]",
+            format!("expired on {}", not_after.format("%Y-%m-%d")),
+        ));
+    } else if not_before > now {
+        errors.push(FieldError::new(
+            "chain[0]",
+            format!("is not valid before {}", not_before.format("%Y-%m-%d")),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(invalid(errors));
+    }
+
+    Ok(ParsedCertificate {
+        hostnames,
+        issuer: leaf.issuer().to_string(),
+        fingerprint: hex::encode(Sha256::digest(&leaf_block.contents)),
+        not_before,
+        not_after,
+        chain_pem: chain.trim().to_string() + "\n",
+        key_pem: key.trim().to_string() + "\n",
+    })
+}
+
+fn check_key(errors: &mut Vec<FieldError>, key: &str) {
+    let before = errors.len();
+    let mut keys = 0;
+    for pem in Pem::iter_from_buffer(key.as_bytes()) {
+        match pem {
+            Ok(pem) if pem.label == "ENCRYPTED PRIVATE KEY" => {
+                errors.push(FieldError::new("key", "must not be encrypted"));
+            }
+            Ok(pem) if pem.label.ends_with("PRIVATE KEY") => keys += 1,
+            Ok(pem) => errors.push(FieldError::new(
+                "key",
+                format!("contains a {} instead of a private key", pem.label),
+            )),
+            Err(err) => {
+                errors.push(FieldError::new("key", format!("is not PEM: {err}")));
+                break;
+            }
+        }
+    }
+    if keys != 1 && errors.len() == before {
+        errors.push(FieldError::new(
+            "key",
+            format!("must hold exactly one private key, not {keys}"),
+        ));
+    }
+}
+
+// The DNS names of the subject alternative names, or else the common name,
+// as clients only fall back to it without them.
+fn hostnames_of(certificate: &X509Certificate<'_>) -> Vec<String> {
+    let mut hostnames: Vec<String> = match certificate.subject_alternative_name() {
+        Ok(Some(names)) => names
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_lowercase()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if hostnames.is_empty() {
+        hostnames.extend(
+            certificate
+                .subject()
+                .iter_common_name()
+                .filter_map(|name| name.as_str().ok())
+                .filter(|name| name.contains('.'))
+                .map(str::to_lowercase),
+        );
+    }
+    hostnames.sort();
+    hostnames.dedup();
+    hostnames
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+// A wildcard only stands for the whole leftmost label, as in RFC 6125.
+fn hostname_matches(pattern: &str, hostname: &str) -> bool {
+    let hostname = hostname.to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => hostname
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == suffix),
+        None => pattern == hostname,
+    }
+}
+
+fn invalid(errors: Vec<FieldError>) -> AppError {
+    AppError::unprocessable("certificate is invalid", errors)
+}
+
+/// Rejects a node write that names a missing certificate, or one that does
+/// not cover every hostname the node routes.
+pub async fn check_node(state: &ManagerState, node: &NodeRecord) -> AppResult<()> {
+    let errors = node_errors(state, node).await?;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::unprocessable(
+            format!("node '{}' is invalid", node.name),
+            errors,
+        ))
+    }
+}
+
+pub async fn node_errors(state: &ManagerState, node: &NodeRecord) -> Result<Vec<FieldError>> {
+    let mut errors = Vec::new();
+    if let Some(spec) = &node.reverse_proxy {
+        if let Some(name) = &spec.certificate {
+            match state.get_certificate(&node.project, name).await? {
+                Some(certificate) => errors.extend(uncovered(&certificate, spec.routes.keys())),
+                None => errors.push(missing("reverse_proxy.certificate", name)),
+            }
+        }
+    }
+    if let Some(name) = node
+        .port_mapping
+        .as_ref()
+        .and_then(|spec| spec.certificate.as_ref())
+    {
+        match state.get_certificate(&node.project, name).await? {
+            Some(certificate) if !is_pkcs8(&certificate.key_pem) => errors.push(FieldError::new(
+                "port_mapping.certificate",
+                format!("certificate '{name}' must have a PKCS#8 key to be served by Rathole"),
+            )),
+            Some(_) => {}
+            None => errors.push(missing("port_mapping.certificate", name)),
+        }
+    }
+    Ok(errors)
+}
+
+fn missing(field: &str, name: &str) -> FieldError {
+    FieldError::new(field, format!("certificate '{name}' does not exist"))
+}
+
+// What a PKCS#12 identity holds, and all Rathole's rustls transport reads.
+fn is_pkcs8(key_pem: &str) -> bool {
+    Pem::iter_from_buffer(key_pem.as_bytes())
+        .any(|pem| pem.is_ok_and(|pem| pem.label == "PRIVATE KEY"))
+}
+
+/// Rejects a new chain for `certificate` that no longer covers the hostnames
+/// of the nodes serving it.
+pub async fn check_replacement(
+    state: &ManagerState,
+    certificate: &Certificate,
+    parsed: &ParsedCertificate,
+) -> AppResult<()> {
+    let replaced = Certificate {
+        hostnames: parsed.hostnames.clone(),
+        ..certificate.clone()
+    };
+    let mut errors = Vec::new();
+    for name in &certificate.nodes {
+        let Some(node) = state.get(&certificate.project, name).await? else {
+            continue;
+        };
+        let routes = node
+            .reverse_proxy
+            .iter()
+            .filter(|spec| spec.certificate.as_ref() == Some(&certificate.name))
+            .flat_map(|spec| spec.routes.keys());
+        for error in uncovered(&replaced, routes) {
+            errors.push(FieldError::new(
+                "chain[0]",
+                format!("node '{name}': {}", error.reason),
+            ));
+        }
+        let serves_port_mapping = node
+            .port_mapping
+            .as_ref()
+            .is_some_and(|spec| spec.certificate.as_ref() == Some(&certificate.name));
+        if serves_port_mapping && !is_pkcs8(&parsed.key_pem) {
+            errors.push(FieldError::new(
+                "key",
+                format!("node '{name}': must be a PKCS#8 key to be served by Rathole"),
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(errors))
+    }
+}
+
+// Every route of a TLS listener is served with its certificate.
+fn uncovered<'a>(
+    certificate: &Certificate,
+    hostnames: impl IntoIterator<Item = &'a String>,
+) -> Vec<FieldError> {
+    hostnames
+        .into_iter()
+        .filter(|hostname| !certificate.covers(hostname))
+        .map(|hostname| {
+            FieldError::new(
+                format!("reverse_proxy.routes.{hostname}"),
+                format!(
+                    "not covered by certificate '{}', which is for {}",
+                    certificate.name,
+                    certificate.hostnames.join(", ")
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Rejects deleting a certificate that nodes still serve.
+pub fn check_unused(certificate: &Certificate) -> AppResult<()> {
+    if certificate.nodes.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::conflict(format!(
+        "certificate '{}' is served by nodes {}; change their reverse_proxy.certificate or port_mapping.certificate first",
+        certificate.name,
+        certificate.nodes.join(", ")
+    )))
+}
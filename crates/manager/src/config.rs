@@ -1,26 +1,65 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 
-use anyhow::{Context, Result};
-use laval_model::PortMappingSpec;
-use sea_orm::sea_query::TableCreateStatement;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use laval_model::{PortMappingMode, PortMappingSpec, ReverseProxySpec};
+use rathole::config::ServiceType;
+use sea_orm::sea_query::{Expr, OnConflict};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, ConnectionTrait, Database, DatabaseConnection, EntityTrait,
-    IntoActiveModel, QueryFilter, Schema, Set,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, Database, DatabaseConnection,
+    DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::fs;
+use tokio::sync::broadcast;
+use tracing::warn;
+use utoipa::ToSchema;
 
-use crate::entity::node;
+use crate::audit::{self, Actor, AuditAction, AuditEvent, AuditQuery, AuditResource, AuditTarget};
+use crate::auth::{generate_token, hash_token, ApiToken, IssuedToken, Principal};
+use crate::certificate::{Certificate, Expiry, ParsedCertificate};
+use crate::entity::{
+    api_token, audit_event, certificate as certificate_entity, node, node_key, node_revision,
+    node_status, project as project_entity, template as template_entity, tunnel as tunnel_entity,
+};
+use crate::events::{EventBus, EventKind};
+use crate::inventory::{ImportOutcome, ImportStep, NodeImport};
+use crate::metrics::{Metrics, NodeCounts};
+use crate::migration;
+use crate::project::{CreateProject, Project, ProjectRemoval, DEFAULT_PROJECT};
+use crate::query::{self, Cursor, NodePage, NodeQuery};
+use crate::rbac::{Grant, Role};
+use crate::reconcile;
+use crate::revision::{diff, snapshot, FieldChange, NodeRevision, RevisionAction};
+use crate::secrets::{self, NodeKey};
+use crate::template::{self, Template, TemplateSpec};
+use crate::tunnel::{
+    self, generate_token as generate_tunnel_token, CreateTunnel, Tunnel, TunnelCreation,
+};
+use crate::validate::FieldError;
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+/// Name of the token managed through `--admin-token`.
+const BOOTSTRAP_TOKEN_NAME: &str = "admin";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
 pub struct ManagerConfig {
     #[serde(default)]
     pub nodes: HashMap<String, NodeRecord>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
 pub struct NodeRecord {
+    /// Taken from the request path, never from its body.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub project: String,
+    /// Unique within the project.
     pub name: String,
     pub reverse_proxy_bind: Option<String>,
     pub port_mapping_role: Option<String>,
@@ -30,6 +69,135 @@ pub struct NodeRecord {
     pub tags: Vec<String>,
     #[serde(default)]
     pub port_mapping: Option<PortMappingSpec>,
+    /// Routes of the node's reverse proxy; the node uses those of its local
+    /// configuration if unset.
+    #[serde(default)]
+    pub reverse_proxy: Option<ReverseProxySpec>,
+    /// Values of the `{{var}}` placeholders of the template the node is
+    /// bound to by its tags.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Set from where the node was last written, never taken from the request.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub managed_by: ManagedBy,
+    /// Incremented by every change; served as the node's `ETag`.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub version: i64,
+    /// Reported by the node itself, never taken from configuration.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub status: NodeStatus,
+}
+
+/// What a write expects of the stored node, so that concurrent edits do not
+/// silently overwrite each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// Create the node or replace it, whatever is stored.
+    None,
+    /// The node must not exist yet.
+    Absent,
+    /// The node must exist, at any version.
+    Exists,
+    /// The node must exist at this version.
+    Version(i64),
+}
+
+/// Outcome of a node write guarded by a [`Precondition`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The node was stored at, or deleted from, this version.
+    Done(i64),
+    /// The node does not exist.
+    Missing,
+    /// The node exists, although it was expected not to.
+    Exists,
+    /// The node is at this version instead of the expected one.
+    Stale(i64),
+    /// The node cannot be deleted while these tunnels use it.
+    InUse(Vec<String>),
+}
+
+impl Precondition {
+    /// Why a write to a node at `current`, if it exists, must not proceed.
+    pub fn check(self, current: Option<i64>) -> Option<WriteOutcome> {
+        match (self, current) {
+            (Precondition::None, _) => None,
+            (Precondition::Absent, None) => None,
+            (Precondition::Absent, Some(_)) => Some(WriteOutcome::Exists),
+            (Precondition::Exists | Precondition::Version(_), None) => Some(WriteOutcome::Missing),
+            (Precondition::Exists, Some(_)) => None,
+            (Precondition::Version(expected), Some(current)) if expected == current => None,
+            (Precondition::Version(_), Some(current)) => Some(WriteOutcome::Stale(current)),
+        }
+    }
+}
+
+/// Where a node is maintained. `apply --prune` only deletes nodes declared in
+/// the configuration file, so nodes created from the UI are never pruned.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ManagedBy {
+    File,
+    #[default]
+    Ui,
+}
+
+impl ManagedBy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ManagedBy::File => "file",
+            ManagedBy::Ui => "ui",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "file" => ManagedBy::File,
+            "ui" => ManagedBy::Ui,
+            other => bail!("unknown node owner '{other}'"),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeState {
+    Online,
+    #[default]
+    Offline,
+}
+
+/// Control channel state of a Rathole service running on a node.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct NodeStatus {
+    pub state: NodeState,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub version: Option<String>,
+    pub uptime_seconds: Option<u64>,
+    #[serde(default)]
+    pub listen_addrs: Vec<String>,
+    #[serde(default)]
+    pub services: BTreeMap<String, ServiceState>,
+}
+
+/// Heartbeat sent by a running node.
+#[derive(Debug, Clone)]
+pub struct NodeReport {
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub listen_addrs: Vec<String>,
+    pub services: BTreeMap<String, ServiceState>,
 }
 
 impl ManagerConfig {
@@ -46,98 +214,2137 @@ impl ManagerConfig {
     }
 }
 
+/// Connects to a `postgres://` or `sqlite://` database.
+pub async fn connect(database_url: &str) -> Result<DatabaseConnection> {
+    Database::connect(sqlite_create_if_missing(database_url))
+        .await
+        .with_context(|| format!("failed to connect to database at {database_url}"))
+}
+
+// SQLite refuses to open a missing database file unless asked to create it.
+fn sqlite_create_if_missing(database_url: &str) -> String {
+    let is_file = database_url.starts_with("sqlite:") && !database_url.contains(":memory:");
+    if is_file && !database_url.contains("mode=") {
+        let separator = if database_url.contains('?') { '&' } else { '?' };
+        format!("{database_url}{separator}mode=rwc")
+    } else {
+        database_url.to_string()
+    }
+}
+
+/// Emitted whenever a stored node changes or is removed.
+#[derive(Debug, Clone)]
+pub struct NodeChange {
+    pub revision: u64,
+    pub project: String,
+    pub name: String,
+}
+
 pub struct ManagerState {
     db: DatabaseConnection,
+    revision: AtomicU64,
+    changes: broadcast::Sender<NodeChange>,
+    events: EventBus,
+    // Until when nodes that went offline were announced on the event feed.
+    offline_checked: Mutex<DateTime<Utc>>,
+    offline_after: Duration,
+    token_overlap: Duration,
+    noise_key_rotation: Option<Duration>,
+    // Token hashes, cached so that the synchronous gRPC interceptor can check
+    // them. Reloaded on every change made here and polled for the others.
+    tokens: RwLock<HashMap<String, Principal>>,
+    // Version of every stored node as this process last saw it, so that
+    // writes by other processes sharing the database are found by polling.
+    versions: Mutex<HashMap<(String, String), i64>>,
+    // Held shared by node writes until their version is recorded. Held
+    // exclusively while a poll that found changes reads them again, so that
+    // it never mistakes a write of this process for one of another; while
+    // deleting a project, so that no node is created in it meanwhile; and
+    // while creating a tunnel, so that its nodes neither change nor go away
+    // before it is stored.
+    writes: tokio::sync::RwLock<()>,
+    metrics: Metrics,
 }
 
 impl ManagerState {
-    pub async fn initialize(path: PathBuf, database_url: String) -> Result<Self> {
+    /// Opens the database and applies the nodes declared in the configuration
+    /// file at `path`, without pruning any. See [`Self::open`] for the rest.
+    pub async fn initialize(
+        path: PathBuf,
+        database_url: String,
+        offline_after: Duration,
+        token_overlap: Duration,
+        admin_token: Option<String>,
+    ) -> Result<Self> {
         let config = ManagerConfig::load(&path).await?;
-        let db = Database::connect(&database_url)
+        let state = Self::open(&database_url, offline_after, token_overlap, admin_token).await?;
+        let plan = reconcile::plan(&state, config.nodes.into_values().collect())
             .await
-            .with_context(|| format!("failed to connect to database at {database_url}"))?;
-        Self::run_migrations(&db).await?;
-
-        let state = Self { db };
-        for node in config.nodes.into_values() {
-            state.upsert(node).await?;
-        }
+            .with_context(|| format!("invalid nodes in {}", path.display()))?;
+        reconcile::apply(&state, plan, false).await?;
         Ok(state)
     }
 
-    async fn run_migrations(db: &DatabaseConnection) -> Result<()> {
-        let backend = db.get_database_backend();
-        let schema = Schema::new(backend);
-        let mut table: TableCreateStatement = schema.create_table_from_entity(node::Entity);
-        db.execute(backend.build(table.if_not_exists()))
+    /// Connects to the database and brings its schema up to date.
+    /// `offline_after` is how long a node may go without a heartbeat before
+    /// it is reported offline. `token_overlap` is how long a Rathole server
+    /// keeps accepting a tunnel token or Noise key after it was rotated. `admin_token`, if
+    /// set, is stored as the `admin` API token.
+    pub async fn open(
+        database_url: &str,
+        offline_after: Duration,
+        token_overlap: Duration,
+        admin_token: Option<String>,
+    ) -> Result<Self> {
+        let metrics = Metrics::new();
+        let mut db = connect(database_url).await?;
+        db.set_metric_callback({
+            let metrics = metrics.clone();
+            move |info| metrics.observe_query(info)
+        });
+        migration::up(&db, None)
             .await
             .context("failed to run manager migrations")?;
+
+        let (changes, _) = broadcast::channel(256);
+        let state = Self {
+            db,
+            revision: AtomicU64::new(0),
+            changes,
+            events: EventBus::default(),
+            offline_checked: Mutex::new(Utc::now()),
+            offline_after,
+            token_overlap,
+            noise_key_rotation: None,
+            tokens: RwLock::new(HashMap::new()),
+            versions: Mutex::new(HashMap::new()),
+            writes: tokio::sync::RwLock::new(()),
+            metrics,
+        };
+        *state.versions.lock().unwrap_or_else(|err| err.into_inner()) =
+            state.stored_versions().await?;
+        if let Some(token) = admin_token {
+            state.store_bootstrap_token(&token).await?;
+        }
+        state.reload_tokens().await?;
+        Ok(state)
+    }
+
+    /// Latest revision published to node watchers.
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    pub fn token_overlap(&self) -> Duration {
+        self.token_overlap
+    }
+
+    /// Has [`Self::rotate_secrets`] rotate every managed Noise key once it is
+    /// `every` old. Rotations never overlap, so a key is kept for at least
+    /// the token overlap.
+    pub fn with_noise_key_rotation(mut self, every: Option<Duration>) -> Self {
+        self.noise_key_rotation = every;
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Every metric in the Prometheus text format, with the node counts read
+    /// from the database at the time of the call.
+    pub async fn render_metrics(&self) -> Result<String> {
+        let backend = self.db.get_database_backend();
+        let total = node::Entity::find().count(&self.db).await?;
+        let server = node::Entity::find()
+            .filter(query::has_mode(backend, &PortMappingMode::Server))
+            .count(&self.db)
+            .await?;
+        let client = node::Entity::find()
+            .filter(query::has_mode(backend, &PortMappingMode::Client))
+            .count(&self.db)
+            .await?;
+        let offline_after = chrono::Duration::from_std(self.offline_after)?;
+        let online = node_status::Entity::find()
+            .filter(node_status::Column::LastSeen.gte(Utc::now() - offline_after))
+            .count(&self.db)
+            .await?;
+
+        self.metrics.render(NodeCounts {
+            server,
+            client,
+            none: total.saturating_sub(server + client),
+            online,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeChange> {
+        self.changes.subscribe()
+    }
+
+    /// The feed of changes to nodes served by `GET /events`.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    // Announces a committed write of a node on the event feed.
+    async fn announce(&self, kind: EventKind, mut node: NodeRecord) {
+        if kind == EventKind::NodeUpdated {
+            match self.status_of(&node.project, &node.name).await {
+                Ok(status) => node.status = status,
+                Err(err) => warn!(node = %node.name, error = %err, "failed to read node status"),
+            }
+        }
+        self.events.publish(kind, node);
+    }
+
+    /// Announces the nodes that went offline since the last call on the
+    /// event feed, as their status only changes with the passing of time.
+    pub async fn announce_offline(&self) -> Result<()> {
+        let now = Utc::now();
+        let since = *self
+            .offline_checked
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let offline_after = chrono::Duration::from_std(self.offline_after)?;
+        let statuses = node_status::Entity::find()
+            .filter(node_status::Column::LastSeen.gte(since - offline_after))
+            .filter(node_status::Column::LastSeen.lt(now - offline_after))
+            .all(&self.db)
+            .await?;
+        for status in statuses {
+            let Some(mut node) = self.get(&status.project, &status.node_name).await? else {
+                continue;
+            };
+            node.status = self.status_from_model(status)?;
+            self.events.publish(EventKind::NodeStatusChanged, node);
+        }
+        *self
+            .offline_checked
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = now;
+        Ok(())
+    }
+
+    /// Announces the nodes that other processes sharing the database, such
+    /// as `laval-manager apply`, created, changed or deleted since the last
+    /// call, as this process is only told about its own writes.
+    pub async fn announce_external(&self) -> Result<()> {
+        // Most polls find nothing new, and need not hold up the writes of
+        // this process. A difference may still be one of its own writes that
+        // is not tracked yet, so the versions are read again under the lock
+        // before they are trusted.
+        let stored = self.stored_versions().await?;
+        if *self.versions.lock().unwrap_or_else(|err| err.into_inner()) == stored {
+            return Ok(());
+        }
+
+        let (changed, deleted) = {
+            let _writes = self.writes.write().await;
+            let stored = self.stored_versions().await?;
+            let mut versions = self.versions.lock().unwrap_or_else(|err| err.into_inner());
+            let changed: Vec<_> = stored
+                .iter()
+                .filter_map(|(node, version)| match versions.get(node) {
+                    Some(known) if known == version => None,
+                    Some(_) => Some((node.clone(), EventKind::NodeUpdated)),
+                    None => Some((node.clone(), EventKind::NodeCreated)),
+                })
+                .collect();
+            let deleted: Vec<_> = versions
+                .keys()
+                .filter(|node| !stored.contains_key(*node))
+                .cloned()
+                .collect();
+            *versions = stored;
+            (changed, deleted)
+        };
+
+        for ((project, name), kind) in changed {
+            let Some(node) = self.get(&project, &name).await? else {
+                continue;
+            };
+            self.publish(&project, &name);
+            self.announce(kind, node).await;
+        }
+        for (project, name) in deleted {
+            self.publish(&project, &name);
+            self.announce(
+                EventKind::NodeDeleted,
+                NodeRecord {
+                    project,
+                    name,
+                    ..Default::default()
+                },
+            )
+            .await;
+        }
         Ok(())
     }
 
-    pub async fn list(&self) -> Result<Vec<NodeRecord>> {
-        let models = node::Entity::find().all(&self.db).await?;
+    async fn stored_versions(&self) -> Result<HashMap<(String, String), i64>> {
+        Ok(node::Entity::find()
+            .select_only()
+            .column(node::Column::Project)
+            .column(node::Column::Name)
+            .column(node::Column::Version)
+            .into_tuple::<(String, String, i64)>()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|(project, name, version)| ((project, name), version))
+            .collect())
+    }
+
+    // Records the version a write of this process left a node at, `None`
+    // once deleted.
+    fn track(&self, project: &str, name: &str, version: Option<i64>) {
+        let mut versions = self.versions.lock().unwrap_or_else(|err| err.into_inner());
+        let node = (project.to_string(), name.to_string());
+        match version {
+            Some(version) => versions.insert(node, version),
+            None => versions.remove(&node),
+        };
+    }
+
+    fn publish(&self, project: &str, name: &str) {
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        // Sending only fails when nobody is watching.
+        let _ = self.changes.send(NodeChange {
+            revision,
+            project: project.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    pub async fn list_projects(&self) -> Result<Vec<Project>> {
+        Ok(project_entity::Entity::find()
+            .order_by_asc(project_entity::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(project_from_model)
+            .collect())
+    }
+
+    pub async fn get_project(&self, name: &str) -> Result<Option<Project>> {
+        Ok(project_entity::Entity::find()
+            .filter(project_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+            .map(project_from_model))
+    }
+
+    /// Creates a project. Returns `None` if the name is already taken.
+    pub async fn create_project(
+        &self,
+        request: CreateProject,
+        actor: &Actor,
+    ) -> Result<Option<Project>> {
+        let txn = self.db.begin().await?;
+        let existing = project_entity::Entity::find()
+            .filter(project_entity::Column::Name.eq(&request.name))
+            .one(&txn)
+            .await?;
+        if existing.is_some() {
+            return Ok(None);
+        }
+        let active = project_entity::ActiveModel {
+            name: Set(request.name),
+            description: Set(request.description),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        let created = match active.insert(&txn).await {
+            Ok(model) => project_from_model(model),
+            Err(err) if is_unique_violation(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Create,
+            project_target(&created.name),
+            None,
+            Some(&created),
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(Some(created))
+    }
+
+    /// Deletes a project once it has no nodes, and with it the Noise keys and
+    /// node tokens of its former nodes. Their revisions and audit events are
+    /// kept.
+    pub async fn delete_project(&self, name: &str, actor: &Actor) -> Result<ProjectRemoval> {
+        // No node may be created in the project between counting its nodes
+        // and deleting it.
+        let _writes = self.writes.write().await;
+        let txn = self.db.begin().await?;
+        let Some(model) = project_entity::Entity::find()
+            .filter(project_entity::Column::Name.eq(name))
+            .one(&txn)
+            .await?
+        else {
+            return Ok(ProjectRemoval::Missing);
+        };
+        let nodes = node::Entity::find()
+            .filter(node::Column::Project.eq(name))
+            .count(&txn)
+            .await?;
+        if nodes > 0 {
+            return Ok(ProjectRemoval::InUse(nodes));
+        }
+
+        project_entity::Entity::delete_by_id(model.id)
+            .exec(&txn)
+            .await?;
+        node_key::Entity::delete_many()
+            .filter(node_key::Column::Project.eq(name))
+            .exec(&txn)
+            .await?;
+        node_status::Entity::delete_many()
+            .filter(node_status::Column::Project.eq(name))
+            .exec(&txn)
+            .await?;
+        api_token::Entity::delete_many()
+            .filter(api_token::Column::Project.eq(name))
+            .exec(&txn)
+            .await?;
+        certificate_entity::Entity::delete_many()
+            .filter(certificate_entity::Column::Project.eq(name))
+            .exec(&txn)
+            .await?;
+        template_entity::Entity::delete_many()
+            .filter(template_entity::Column::Project.eq(name))
+            .exec(&txn)
+            .await?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Delete,
+            project_target(name),
+            Some(&project_from_model(model)),
+            None,
+        )
+        .await?;
+        txn.commit().await?;
+        self.reload_tokens().await?;
+        Ok(ProjectRemoval::Deleted)
+    }
+
+    pub async fn list(&self, project: &str) -> Result<Vec<NodeRecord>> {
+        let models = node::Entity::find()
+            .filter(node::Column::Project.eq(project))
+            .all(&self.db)
+            .await?;
+        let mut statuses: HashMap<String, node_status::Model> = node_status::Entity::find()
+            .filter(node_status::Column::Project.eq(project))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|status| (status.node_name.clone(), status))
+            .collect();
         models
             .into_iter()
-            .map(model_to_record)
+            .map(|model| {
+                let status = statuses.remove(&model.name);
+                self.to_record(model, status)
+            })
             .collect::<Result<Vec<_>>>()
     }
 
-    pub async fn get(&self, name: &str) -> Result<Option<NodeRecord>> {
-        let model = node::Entity::find()
+    /// One page of the nodes matching `query`.
+    pub async fn query(&self, query: &NodeQuery) -> Result<NodePage> {
+        let backend = self.db.get_database_backend();
+        let mut models = query
+            .apply(node::Entity::find(), backend)
+            .all(&self.db)
+            .await?;
+        let page_size = usize::try_from(query.page_size()).unwrap_or(usize::MAX);
+        let next_cursor = if models.len() > page_size {
+            models.truncate(page_size);
+            models
+                .last()
+                .map(|last| Cursor::after(last, query.sort).encode())
+        } else {
+            None
+        };
+
+        let names: Vec<String> = models.iter().map(|model| model.name.clone()).collect();
+        let mut statuses: HashMap<String, node_status::Model> = node_status::Entity::find()
+            .filter(node_status::Column::Project.eq(query.project.as_str()))
+            .filter(node_status::Column::NodeName.is_in(names))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|status| (status.node_name.clone(), status))
+            .collect();
+        let nodes = models
+            .into_iter()
+            .map(|model| {
+                let status = statuses.remove(&model.name);
+                self.to_record(model, status)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(NodePage { nodes, next_cursor })
+    }
+
+    pub async fn get(&self, project: &str, name: &str) -> Result<Option<NodeRecord>> {
+        let Some(model) = node::Entity::find()
+            .filter(node::Column::Project.eq(project))
             .filter(node::Column::Name.eq(name))
             .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let status = node_status::Entity::find()
+            .filter(node_status::Column::Project.eq(project))
+            .filter(node_status::Column::NodeName.eq(name))
+            .one(&self.db)
             .await?;
-        model.map(model_to_record).transpose()
+        self.to_record(model, status).map(Some)
     }
 
-    pub async fn upsert(&self, node: NodeRecord) -> Result<()> {
-        let tags_value = if node.tags.is_empty() {
-            None
-        } else {
-            Some(serde_json::to_value(&node.tags)?)
+    async fn status_of(&self, project: &str, name: &str) -> Result<NodeStatus> {
+        match node_status::Entity::find()
+            .filter(node_status::Column::Project.eq(project))
+            .filter(node_status::Column::NodeName.eq(name))
+            .one(&self.db)
+            .await?
+        {
+            Some(model) => self.status_from_model(model),
+            None => Ok(NodeStatus::default()),
+        }
+    }
+
+    /// Stores a heartbeat from `name`. Returns `false` if the node is unknown.
+    pub async fn record_status(
+        &self,
+        project: &str,
+        name: &str,
+        report: NodeReport,
+    ) -> Result<bool> {
+        let Some(mut node) = self.get(project, name).await? else {
+            return Ok(false);
         };
-        let port_mapping_value = match &node.port_mapping {
-            Some(spec) => Some(serde_json::to_value(spec)?),
-            None => None,
+        let now = Utc::now();
+        let status = NodeStatus {
+            state: NodeState::Online,
+            last_seen: Some(now),
+            version: Some(report.version.clone()),
+            uptime_seconds: Some(report.uptime_seconds),
+            listen_addrs: report.listen_addrs.clone(),
+            services: report.services.clone(),
         };
+        // Heartbeats that only move the clock forward are not announced.
+        let changed = node.status.state != status.state
+            || node.status.version != status.version
+            || node.status.listen_addrs != status.listen_addrs
+            || node.status.services != status.services;
+
+        let active = node_status::ActiveModel {
+            project: Set(project.to_string()),
+            node_name: Set(name.to_string()),
+            last_seen: Set(now),
+            version: Set(Some(report.version)),
+            uptime_seconds: Set(Some(
+                i64::try_from(report.uptime_seconds).unwrap_or(i64::MAX),
+            )),
+            listen_addrs: Set(Some(serde_json::to_value(&report.listen_addrs)?)),
+            services: Set(Some(serde_json::to_value(&report.services)?)),
+            ..Default::default()
+        };
+        node_status::Entity::insert(active)
+            .on_conflict(
+                OnConflict::columns([node_status::Column::Project, node_status::Column::NodeName])
+                    .update_columns([
+                        node_status::Column::LastSeen,
+                        node_status::Column::Version,
+                        node_status::Column::UptimeSeconds,
+                        node_status::Column::ListenAddrs,
+                        node_status::Column::Services,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await?;
+        if changed {
+            node.status = status;
+            self.events.publish(EventKind::NodeStatusChanged, node);
+        }
+        Ok(true)
+    }
+
+    /// Creates or replaces a node, recording a revision and an audit event
+    /// by `actor` if anything changed.
+    pub async fn upsert(&self, node: NodeRecord, actor: &Actor) -> Result<()> {
+        let name = node.name.clone();
+        match self.store(node, actor, None, Precondition::None).await? {
+            WriteOutcome::Done(_) => Ok(()),
+            _ => bail!("node '{name}' was changed concurrently"),
+        }
+    }
+
+    /// Creates or replaces a node if the stored node meets `precondition`.
+    /// The version is only incremented if anything changed.
+    pub async fn write(
+        &self,
+        node: NodeRecord,
+        actor: &Actor,
+        precondition: Precondition,
+    ) -> Result<WriteOutcome> {
+        self.store(node, actor, None, precondition).await
+    }
+
+    /// Like [`Self::write`], for a node restored from an earlier revision.
+    pub async fn rollback(
+        &self,
+        node: NodeRecord,
+        actor: &Actor,
+        precondition: Precondition,
+    ) -> Result<WriteOutcome> {
+        self.store(node, actor, Some(RevisionAction::Rollback), precondition)
+            .await
+    }
+
+    async fn store(
+        &self,
+        node: NodeRecord,
+        actor: &Actor,
+        action: Option<RevisionAction>,
+        precondition: Precondition,
+    ) -> Result<WriteOutcome> {
+        let _writes = self.writes.read().await;
+        let txn = self.db.begin().await?;
+        match stage_store(&txn, &node, actor, action, precondition).await? {
+            Staged::Written {
+                version,
+                kind,
+                node: written,
+                ..
+            } => {
+                txn.commit().await?;
+                self.track(&node.project, &node.name, Some(version));
+                self.publish(&node.project, &node.name);
+                self.announce(kind, *written).await;
+                Ok(WriteOutcome::Done(version))
+            }
+            Staged::Skipped(outcome) => Ok(outcome),
+            Staged::Raced => {
+                txn.rollback().await?;
+                self.lost_race(&node.project, &node.name).await
+            }
+        }
+    }
+
+    /// Deletes a node if it meets `precondition`.
+    pub async fn remove(
+        &self,
+        project: &str,
+        name: &str,
+        actor: &Actor,
+        precondition: Precondition,
+    ) -> Result<WriteOutcome> {
+        let _writes = self.writes.read().await;
+        let txn = self.db.begin().await?;
+        match stage_remove(&txn, project, name, actor, precondition).await? {
+            Staged::Written {
+                version,
+                kind,
+                node,
+                ..
+            } => {
+                txn.commit().await?;
+                self.track(project, name, None);
+                self.publish(project, name);
+                self.announce(kind, *node).await;
+                Ok(WriteOutcome::Done(version))
+            }
+            Staged::Skipped(outcome) => Ok(outcome),
+            Staged::Raced => {
+                txn.rollback().await?;
+                self.lost_race(project, name).await
+            }
+        }
+    }
+
+    /// Applies the steps of an import into `project` in one transaction, so
+    /// that either all of them are stored or none. `None` if another write
+    /// changed one of the nodes meanwhile, in which case nothing was stored.
+    pub async fn import(
+        &self,
+        project: &str,
+        steps: Vec<ImportStep>,
+        actor: &Actor,
+    ) -> Result<Option<Vec<NodeImport>>> {
+        let _writes = self.writes.read().await;
+        let txn = self.db.begin().await?;
+        let mut results = Vec::with_capacity(steps.len());
+        let mut written = Vec::new();
+        for step in steps {
+            let (name, staged) = match step {
+                ImportStep::Store(node, precondition) => {
+                    let staged = stage_store(&txn, &node, actor, None, precondition).await?;
+                    (node.name, staged)
+                }
+                ImportStep::Remove(name) => {
+                    let staged =
+                        stage_remove(&txn, project, &name, actor, Precondition::None).await?;
+                    (name, staged)
+                }
+            };
+            let outcome = match staged {
+                Staged::Written {
+                    action, kind, node, ..
+                } => {
+                    written.push((action, kind, node));
+                    match action {
+                        RevisionAction::Create => ImportOutcome::Created,
+                        RevisionAction::Delete => ImportOutcome::Deleted,
+                        _ => ImportOutcome::Updated,
+                    }
+                }
+                // Created since the import was validated.
+                Staged::Skipped(WriteOutcome::Exists) => ImportOutcome::Skipped,
+                // A tunnel was created on the node since the import was
+                // validated, which is handled like any other race.
+                Staged::Skipped(WriteOutcome::InUse(_)) | Staged::Raced => {
+                    txn.rollback().await?;
+                    return Ok(None);
+                }
+                Staged::Skipped(_) => ImportOutcome::Unchanged,
+            };
+            results.push(NodeImport {
+                name,
+                outcome,
+                errors: Vec::new(),
+            });
+        }
+        txn.commit().await?;
+
+        for (action, _, node) in &written {
+            let version = (*action != RevisionAction::Delete).then_some(node.version);
+            self.track(project, &node.name, version);
+        }
+        for result in &results {
+            if matches!(
+                result.outcome,
+                ImportOutcome::Created | ImportOutcome::Updated | ImportOutcome::Deleted
+            ) {
+                self.publish(project, &result.name);
+            }
+        }
+        for (_, kind, node) in written {
+            self.announce(kind, *node).await;
+        }
+        Ok(Some(results))
+    }
 
-        if let Some(existing) = node::Entity::find()
-            .filter(node::Column::Name.eq(node.name.clone()))
+    // Outcome of a write that another write got in before.
+    async fn lost_race(&self, project: &str, name: &str) -> Result<WriteOutcome> {
+        Ok(match self.get(project, name).await? {
+            Some(node) => WriteOutcome::Stale(node.version),
+            None => WriteOutcome::Missing,
+        })
+    }
+
+    /// Revisions of `name`, oldest first, without their snapshots. History is
+    /// kept after a node is deleted.
+    pub async fn list_revisions(&self, project: &str, name: &str) -> Result<Vec<NodeRevision>> {
+        node_revision::Entity::find()
+            .filter(node_revision::Column::Project.eq(project))
+            .filter(node_revision::Column::NodeName.eq(name))
+            .order_by_asc(node_revision::Column::Revision)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|model| {
+                let mut revision = revision_from_model(model)?;
+                revision.node = None;
+                Ok(revision)
+            })
+            .collect()
+    }
+
+    pub async fn get_revision(
+        &self,
+        project: &str,
+        name: &str,
+        revision: i64,
+    ) -> Result<Option<NodeRevision>> {
+        node_revision::Entity::find()
+            .filter(node_revision::Column::Project.eq(project))
+            .filter(node_revision::Column::NodeName.eq(name))
+            .filter(node_revision::Column::Revision.eq(revision))
             .one(&self.db)
             .await?
-        {
-            let mut active: node::ActiveModel = existing.into_active_model();
-            active.reverse_proxy_bind = Set(node.reverse_proxy_bind.clone());
-            active.port_mapping_role = Set(node.port_mapping_role.clone());
-            active.management_url = Set(node.management_url.clone());
-            active.description = Set(node.description.clone());
-            active.tags = Set(tags_value.clone());
-            active.port_mapping = Set(port_mapping_value.clone());
-            active.update(&self.db).await?;
-        } else {
-            let active = node::ActiveModel {
-                name: Set(node.name.clone()),
-                reverse_proxy_bind: Set(node.reverse_proxy_bind.clone()),
-                port_mapping_role: Set(node.port_mapping_role.clone()),
-                management_url: Set(node.management_url.clone()),
-                description: Set(node.description.clone()),
-                tags: Set(tags_value.clone()),
-                port_mapping: Set(port_mapping_value.clone()),
-                ..Default::default()
+            .map(revision_from_model)
+            .transpose()
+    }
+
+    /// Field changes from revision `from` to revision `to`, or `None` if
+    /// either revision does not exist.
+    pub async fn diff_revisions(
+        &self,
+        project: &str,
+        name: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Option<Vec<FieldChange>>> {
+        let (Some(from), Some(to)) = (
+            self.get_revision(project, name, from).await?,
+            self.get_revision(project, name, to).await?,
+        ) else {
+            return Ok(None);
+        };
+        let from = from.node.as_ref().map(snapshot).transpose()?;
+        let to = to.node.as_ref().map(snapshot).transpose()?;
+        Ok(Some(diff(from.as_ref(), to.as_ref())))
+    }
+
+    /// Audit events matching `query`, newest first.
+    pub async fn audit_events(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>> {
+        let mut select = audit_event::Entity::find();
+        if let Some(project) = &query.project {
+            select = select.filter(audit_event::Column::Project.eq(project.trim()));
+        }
+        if let Some(resource) = query.resource {
+            select = select.filter(audit_event::Column::Resource.eq(resource.as_str()));
+        }
+        if let Some(name) = &query.name {
+            select = select.filter(audit_event::Column::NodeName.eq(name.trim()));
+        }
+        if let Some(action) = query.action {
+            select = select.filter(audit_event::Column::Action.eq(action.as_str()));
+        }
+        if let Some(since) = query.since {
+            select = select.filter(audit_event::Column::CreatedAt.gte(since));
+        }
+        if let Some(until) = query.until {
+            select = select.filter(audit_event::Column::CreatedAt.lt(until));
+        }
+        let limit = match query.limit {
+            0 => query::DEFAULT_PAGE_SIZE,
+            limit => limit.min(query::MAX_PAGE_SIZE),
+        };
+
+        select
+            .order_by_desc(audit_event::Column::CreatedAt)
+            .order_by_desc(audit_event::Column::Id)
+            .limit(limit)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(audit_event_from_model)
+            .collect()
+    }
+
+    /// Records a request that `actor` was not allowed to make; `detail` says
+    /// what was denied and why.
+    pub async fn record_denial(
+        &self,
+        actor: &Actor,
+        node: Option<&NodeRecord>,
+        detail: &str,
+    ) -> Result<()> {
+        audit_event::ActiveModel {
+            created_at: Set(Utc::now()),
+            actor: Set(actor.name.clone()),
+            source_ip: Set(actor.source_ip.clone()),
+            action: Set(AuditAction::Denied.as_str().to_string()),
+            resource: Set(node.map(|_| AuditResource::Node.as_str().to_string())),
+            project: Set(node.map(|node| node.project.clone())),
+            node_name: Set(node.map(|node| node.name.clone()).unwrap_or_default()),
+            changes: Set(serde_json::to_value(Vec::<FieldChange>::new())?),
+            detail: Set(Some(detail.to_string())),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    // Logs a change to anything but a node, whose changes are logged with its
+    // revisions. `before` and `after` are the resource as the API serves it,
+    // which leaves its secrets out.
+    async fn audit<T: Serialize>(
+        &self,
+        db: &impl ConnectionTrait,
+        actor: &Actor,
+        action: AuditAction,
+        target: AuditTarget<'_>,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<()> {
+        let before = before.map(serde_json::to_value).transpose()?;
+        let after = after.map(serde_json::to_value).transpose()?;
+        record_audit(db, actor, action, target, before.as_ref(), after.as_ref()).await
+    }
+
+    /// The node as it should run: its own port mapping plus the services of
+    /// its tunnels, and the managed Noise keys it does not set itself.
+    pub async fn node_config(&self, project: &str, name: &str) -> Result<Option<NodeRecord>> {
+        let Some(mut node) = self.get(project, name).await? else {
+            return Ok(None);
+        };
+        let tunnels = self.tunnels_of(project, name).await?;
+        if let Some(error) = tunnel::merge(&mut node, &tunnels).into_iter().next() {
+            bail!("{}: {}", error.field, error.reason);
+        }
+
+        if secrets::wants_managed_key(&mut node) {
+            let key = self.ensure_noise_key(project, name).await?;
+            secrets::use_private_key(&mut node, &key);
+        }
+        for tunnel in tunnels.iter().filter(|tunnel| tunnel.client_node == name) {
+            let Some(mut server) = self.get(project, &tunnel.server_node).await? else {
+                continue;
             };
-            active.insert(&self.db).await?;
+            if secrets::wants_managed_key(&mut server) {
+                let key = self.ensure_noise_key(project, &server.name).await?;
+                secrets::use_server_key(&mut node, &key);
+                break;
+            }
+        }
+        Ok(Some(node))
+    }
+
+    pub async fn list_tunnels(&self, project: &str) -> Result<Vec<Tunnel>> {
+        tunnel_entity::Entity::find()
+            .filter(tunnel_entity::Column::Project.eq(project))
+            .order_by_asc(tunnel_entity::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(tunnel_from_model)
+            .collect()
+    }
+
+    pub async fn get_tunnel(&self, project: &str, name: &str) -> Result<Option<Tunnel>> {
+        find_tunnel(&self.db, project, name).await
+    }
+
+    /// Tunnels with `name` on either side.
+    pub async fn tunnels_of(&self, project: &str, name: &str) -> Result<Vec<Tunnel>> {
+        find_tunnels_of(&self.db, project, name).await
+    }
+
+    // The checks of `tunnel::node_errors`, which read the peers of `node`
+    // through the connection.
+    pub(crate) async fn tunnel_errors(&self, node: &NodeRecord) -> Result<Vec<FieldError>> {
+        let tunnels = self.tunnels_of(&node.project, &node.name).await?;
+        tunnel::node_fit_errors(&self.db, node.clone(), &tunnels, None).await
+    }
+
+    /// Creates a tunnel in `project` with a fresh token, unless its name is
+    /// taken there or it does not fit its nodes.
+    pub async fn create_tunnel(
+        &self,
+        project: &str,
+        request: CreateTunnel,
+        actor: &Actor,
+    ) -> Result<TunnelCreation> {
+        // No node may change, nor another tunnel be created, between checking
+        // that the tunnel fits its nodes and storing it.
+        let _writes = self.writes.write().await;
+        let txn = self.db.begin().await?;
+        let name = request.name.trim().to_string();
+        if find_tunnel(&txn, project, &name).await?.is_some() {
+            return Ok(TunnelCreation::Exists);
         }
+        let errors = tunnel::new_errors(&txn, self.token_overlap, project, &request).await?;
+        if !errors.is_empty() {
+            return Ok(TunnelCreation::Invalid(errors));
+        }
+
+        let active = tunnel_entity::ActiveModel {
+            project: Set(project.to_string()),
+            name: Set(name),
+            server_node: Set(request.server_node),
+            client_node: Set(request.client_node),
+            bind_addr: Set(request.bind_addr),
+            local_addr: Set(request.local_addr),
+            service_type: Set(service_type_name(request.service_type).to_string()),
+            token: Set(generate_tunnel_token()),
+            created_at: Set(Utc::now()),
+            rotate_every_secs: Set(request
+                .rotate_every_secs
+                .map(|every| i64::try_from(every).unwrap_or(i64::MAX))),
+            ..Default::default()
+        };
+        let created = match active.insert(&txn).await {
+            Ok(model) => tunnel_from_model(model)?,
+            Err(err) if is_unique_violation(&err) => return Ok(TunnelCreation::Exists),
+            Err(err) => return Err(err.into()),
+        };
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Create,
+            target(AuditResource::Tunnel, project, &created.name),
+            None,
+            Some(&created),
+        )
+        .await?;
+        txn.commit().await?;
+
+        self.publish(project, &created.server_node);
+        self.publish(project, &created.client_node);
+        Ok(TunnelCreation::Created(created))
+    }
+
+    pub async fn delete_tunnel(&self, project: &str, name: &str, actor: &Actor) -> Result<bool> {
+        let Some(existing) = self.get_tunnel(project, name).await? else {
+            return Ok(false);
+        };
+        let txn = self.db.begin().await?;
+        tunnel_entity::Entity::delete_many()
+            .filter(tunnel_entity::Column::Project.eq(project))
+            .filter(tunnel_entity::Column::Name.eq(name))
+            .exec(&txn)
+            .await?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Delete,
+            target(AuditResource::Tunnel, project, name),
+            Some(&existing),
+            None,
+        )
+        .await?;
+        txn.commit().await?;
 
+        self.publish(project, &existing.server_node);
+        self.publish(project, &existing.client_node);
+        Ok(true)
+    }
+
+    /// Gives a tunnel a fresh token. The server keeps accepting the old one
+    /// for the token overlap, so that the client can reconnect with either.
+    pub async fn rotate_tunnel_token(
+        &self,
+        project: &str,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<Option<Tunnel>> {
+        let Some(model) = tunnel_entity::Entity::find()
+            .filter(tunnel_entity::Column::Project.eq(project))
+            .filter(tunnel_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let now = Utc::now();
+        let overlap = chrono::Duration::from_std(self.token_overlap)?;
+        let previous = model.token.clone();
+        let before = tunnel_from_model(model.clone())?;
+        let mut active = model.into_active_model();
+        active.token = Set(generate_tunnel_token());
+        active.previous_token = Set(Some(previous));
+        active.previous_token_expires_at = Set(Some(now + overlap));
+        active.token_rotated_at = Set(Some(now));
+        let txn = self.db.begin().await?;
+        let rotated = tunnel_from_model(active.update(&txn).await?)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Rotate,
+            target(AuditResource::Tunnel, project, name),
+            Some(&before),
+            Some(&rotated),
+        )
+        .await?;
+        txn.commit().await?;
+
+        self.publish(project, &rotated.server_node);
+        self.publish(project, &rotated.client_node);
+        Ok(Some(rotated))
+    }
+
+    /// Rotates the tunnel tokens and Noise keys of every project that are due,
+    /// moves clients to rotated Noise keys halfway through the overlap and
+    /// stops accepting previous tokens and keys whose overlap has ended.
+    pub async fn rotate_secrets(&self) -> Result<()> {
+        let now = Utc::now();
+        let tunnels = tunnel_entity::Entity::find()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(tunnel_from_model)
+            .collect::<Result<Vec<_>>>()?;
+        for tunnel in tunnels {
+            if tunnel.rotation_due(now) {
+                self.rotate_tunnel_token(&tunnel.project, &tunnel.name, &Actor::manager())
+                    .await?;
+            } else if tunnel.previous_token.is_some() && tunnel.live_previous_token(now).is_none() {
+                tunnel_entity::Entity::update_many()
+                    .col_expr(
+                        tunnel_entity::Column::PreviousToken,
+                        Expr::value(Option::<String>::None),
+                    )
+                    .col_expr(
+                        tunnel_entity::Column::PreviousTokenExpiresAt,
+                        Expr::value(Option::<DateTime<Utc>>::None),
+                    )
+                    .filter(tunnel_entity::Column::Project.eq(&tunnel.project))
+                    .filter(tunnel_entity::Column::Name.eq(&tunnel.name))
+                    .exec(&self.db)
+                    .await?;
+                self.publish(&tunnel.project, &tunnel.server_node);
+            }
+        }
+
+        let keys = node_key::Entity::find()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|model| (model.project.clone(), node_key_from_model(model)));
+        for (project, key) in keys {
+            if key.clients_switch_due(now) {
+                node_key::Entity::update_many()
+                    .col_expr(
+                        node_key::Column::PreviousPublicKey,
+                        Expr::value(Option::<String>::None),
+                    )
+                    .filter(node_key::Column::Project.eq(&project))
+                    .filter(node_key::Column::NodeName.eq(&key.node))
+                    .exec(&self.db)
+                    .await?;
+                self.publish_clients(&project, &key.node).await?;
+            } else if key.previous_private_key.is_some()
+                && key.live_previous_private_key(now).is_none()
+            {
+                node_key::Entity::update_many()
+                    .col_expr(
+                        node_key::Column::PreviousPrivateKey,
+                        Expr::value(Option::<String>::None),
+                    )
+                    .col_expr(
+                        node_key::Column::PreviousExpiresAt,
+                        Expr::value(Option::<DateTime<Utc>>::None),
+                    )
+                    .filter(node_key::Column::Project.eq(&project))
+                    .filter(node_key::Column::NodeName.eq(&key.node))
+                    .exec(&self.db)
+                    .await?;
+                self.publish(&project, &key.node);
+            } else if self
+                .noise_key_rotation
+                .is_some_and(|every| key.rotation_due(every, now))
+            {
+                self.rotate_noise_key(&project, &key.node, &Actor::manager())
+                    .await?;
+            }
+        }
         Ok(())
     }
 
-    pub async fn remove(&self, name: &str) -> Result<bool> {
-        let result = node::Entity::delete_many()
-            .filter(node::Column::Name.eq(name))
+    pub async fn list_certificates(&self, project: &str) -> Result<Vec<Certificate>> {
+        let users = self.certificate_users(project).await?;
+        certificate_entity::Entity::find()
+            .filter(certificate_entity::Column::Project.eq(project))
+            .order_by_asc(certificate_entity::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|model| certificate_from_model(model, &users))
+            .collect()
+    }
+
+    pub async fn get_certificate(&self, project: &str, name: &str) -> Result<Option<Certificate>> {
+        let Some(model) = certificate_entity::Entity::find()
+            .filter(certificate_entity::Column::Project.eq(project))
+            .filter(certificate_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let users = self.certificate_users(project).await?;
+        certificate_from_model(model, &users).map(Some)
+    }
+
+    /// The certificate served by the reverse proxy of `node`, if any.
+    pub async fn node_certificate(&self, node: &NodeRecord) -> Result<Option<Certificate>> {
+        let name = node
+            .reverse_proxy
+            .as_ref()
+            .and_then(|spec| spec.certificate.as_deref());
+        match name {
+            Some(name) => self.get_certificate(&node.project, name).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The certificate served by the Rathole server of `node`, if any.
+    pub async fn port_mapping_certificate(&self, node: &NodeRecord) -> Result<Option<Certificate>> {
+        let name = node
+            .port_mapping
+            .as_ref()
+            .and_then(|spec| spec.certificate.as_deref());
+        match name {
+            Some(name) => self.get_certificate(&node.project, name).await,
+            None => Ok(None),
+        }
+    }
+
+    // The nodes of `project` serving each certificate, by certificate name.
+    // A node serving one certificate twice is listed once.
+    async fn certificate_users(&self, project: &str) -> Result<HashMap<String, Vec<String>>> {
+        let models = node::Entity::find()
+            .filter(node::Column::Project.eq(project))
+            .order_by_asc(node::Column::Name)
+            .all(&self.db)
+            .await?;
+        let mut users: HashMap<String, Vec<String>> = HashMap::new();
+        for node in models.into_iter().map(model_to_record) {
+            let node = node?;
+            let served = [
+                node.reverse_proxy.and_then(|spec| spec.certificate),
+                node.port_mapping.and_then(|spec| spec.certificate),
+            ];
+            let mut served: Vec<String> = served.into_iter().flatten().collect();
+            served.dedup();
+            for name in served {
+                users.entry(name).or_default().push(node.name.clone());
+            }
+        }
+        Ok(users)
+    }
+
+    /// Stores a certificate in `project`. Returns `None` if the name is
+    /// already taken there.
+    pub async fn create_certificate(
+        &self,
+        project: &str,
+        name: &str,
+        parsed: ParsedCertificate,
+        actor: &Actor,
+    ) -> Result<Option<Certificate>> {
+        let txn = self.db.begin().await?;
+        let existing = certificate_entity::Entity::find()
+            .filter(certificate_entity::Column::Project.eq(project))
+            .filter(certificate_entity::Column::Name.eq(name))
+            .one(&txn)
+            .await?;
+        if existing.is_some() {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let active = certificate_entity::ActiveModel {
+            project: Set(project.to_string()),
+            name: Set(name.to_string()),
+            chain_pem: Set(parsed.chain_pem),
+            key_pem: Set(parsed.key_pem),
+            hostnames: Set(serde_json::to_value(&parsed.hostnames)?),
+            issuer: Set(parsed.issuer),
+            fingerprint: Set(parsed.fingerprint),
+            not_before: Set(parsed.not_before),
+            not_after: Set(parsed.not_after),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        };
+        let model = match active.insert(&txn).await {
+            Ok(model) => model,
+            Err(err) if is_unique_violation(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let created = certificate_from_model(model, &HashMap::new())?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Create,
+            target(AuditResource::Certificate, project, name),
+            None,
+            Some(&created),
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(Some(created))
+    }
+
+    /// Replaces the chain and key of a certificate, such as once it was
+    /// renewed, and sends them to the nodes serving it. Returns `None` if the
+    /// certificate does not exist.
+    pub async fn replace_certificate(
+        &self,
+        project: &str,
+        name: &str,
+        parsed: ParsedCertificate,
+        actor: &Actor,
+    ) -> Result<Option<Certificate>> {
+        let Some(model) = certificate_entity::Entity::find()
+            .filter(certificate_entity::Column::Project.eq(project))
+            .filter(certificate_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let users = self.certificate_users(project).await?;
+        let before = certificate_from_model(model.clone(), &users)?;
+
+        let mut active = model.into_active_model();
+        active.chain_pem = Set(parsed.chain_pem);
+        active.key_pem = Set(parsed.key_pem);
+        active.hostnames = Set(serde_json::to_value(&parsed.hostnames)?);
+        active.issuer = Set(parsed.issuer);
+        active.fingerprint = Set(parsed.fingerprint);
+        active.not_before = Set(parsed.not_before);
+        active.not_after = Set(parsed.not_after);
+        active.updated_at = Set(Utc::now());
+        let txn = self.db.begin().await?;
+        let model = active.update(&txn).await?;
+        let replaced = certificate_from_model(model, &users)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Update,
+            target(AuditResource::Certificate, project, name),
+            Some(&before),
+            Some(&replaced),
+        )
+        .await?;
+        txn.commit().await?;
+
+        for node in &replaced.nodes {
+            self.publish(project, node);
+        }
+        Ok(Some(replaced))
+    }
+
+    pub async fn delete_certificate(
+        &self,
+        project: &str,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<bool> {
+        let Some(existing) = self.get_certificate(project, name).await? else {
+            return Ok(false);
+        };
+        let txn = self.db.begin().await?;
+        let result = certificate_entity::Entity::delete_many()
+            .filter(certificate_entity::Column::Project.eq(project))
+            .filter(certificate_entity::Column::Name.eq(name))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Ok(false);
+        }
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Delete,
+            target(AuditResource::Certificate, project, name),
+            Some(&existing),
+            None,
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn list_templates(&self, project: &str) -> Result<Vec<Template>> {
+        template_entity::Entity::find()
+            .filter(template_entity::Column::Project.eq(project))
+            .order_by_asc(template_entity::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(template_from_model)
+            .collect()
+    }
+
+    pub async fn get_template(&self, project: &str, name: &str) -> Result<Option<Template>> {
+        template_entity::Entity::find()
+            .filter(template_entity::Column::Project.eq(project))
+            .filter(template_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+            .map(template_from_model)
+            .transpose()
+    }
+
+    /// Stores a template in `project` without applying it. Returns `None` if
+    /// the name is already taken there.
+    pub async fn create_template(
+        &self,
+        project: &str,
+        name: &str,
+        spec: TemplateSpec,
+        actor: &Actor,
+    ) -> Result<Option<Template>> {
+        let txn = self.db.begin().await?;
+        let existing = template_entity::Entity::find()
+            .filter(template_entity::Column::Project.eq(project))
+            .filter(template_entity::Column::Name.eq(name))
+            .one(&txn)
+            .await?;
+        if existing.is_some() {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let active = template_entity::ActiveModel {
+            project: Set(project.to_string()),
+            name: Set(name.to_string()),
+            description: Set(spec.description),
+            selector: Set(serde_json::to_value(&spec.selector)?),
+            port_mapping: Set(spec.port_mapping),
+            reverse_proxy: Set(spec.reverse_proxy),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        };
+        let model = match active.insert(&txn).await {
+            Ok(model) => model,
+            Err(err) if is_unique_violation(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let created = template_from_model(model)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Create,
+            target(AuditResource::Template, project, name),
+            None,
+            Some(&created),
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(Some(created))
+    }
+
+    /// Replaces what a template sets, without applying it. Returns `None` if
+    /// the template does not exist.
+    pub async fn update_template(
+        &self,
+        project: &str,
+        name: &str,
+        spec: TemplateSpec,
+        actor: &Actor,
+    ) -> Result<Option<Template>> {
+        let Some(model) = template_entity::Entity::find()
+            .filter(template_entity::Column::Project.eq(project))
+            .filter(template_entity::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let before = template_from_model(model.clone())?;
+
+        let mut active = model.into_active_model();
+        active.description = Set(spec.description);
+        active.selector = Set(serde_json::to_value(&spec.selector)?);
+        active.port_mapping = Set(spec.port_mapping);
+        active.reverse_proxy = Set(spec.reverse_proxy);
+        active.updated_at = Set(Utc::now());
+        let txn = self.db.begin().await?;
+        let model = active.update(&txn).await?;
+        let updated = template_from_model(model)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Update,
+            target(AuditResource::Template, project, name),
+            Some(&before),
+            Some(&updated),
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(Some(updated))
+    }
+
+    /// Deletes a template. Its nodes keep what it last rendered into them.
+    pub async fn delete_template(&self, project: &str, name: &str, actor: &Actor) -> Result<bool> {
+        let Some(existing) = self.get_template(project, name).await? else {
+            return Ok(false);
+        };
+        let txn = self.db.begin().await?;
+        let result = template_entity::Entity::delete_many()
+            .filter(template_entity::Column::Project.eq(project))
+            .filter(template_entity::Column::Name.eq(name))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Ok(false);
+        }
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Delete,
+            target(AuditResource::Template, project, name),
+            Some(&existing),
+            None,
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// The managed Noise key of `name`, if one was generated.
+    pub async fn noise_key(&self, project: &str, name: &str) -> Result<Option<NodeKey>> {
+        find_noise_key(&self.db, project, name).await
+    }
+
+    async fn ensure_noise_key(&self, project: &str, name: &str) -> Result<NodeKey> {
+        match self.noise_key(project, name).await? {
+            Some(key) => Ok(key),
+            None => self.store_noise_key(&self.db, project, name, None).await,
+        }
+    }
+
+    /// Replaces the managed Noise key of `name`. The node keeps accepting the
+    /// old key for the token overlap, and the clients of its tunnels are only
+    /// given the new public key halfway through it, so that no tunnel is
+    /// dropped. Returns `None` if the node does not exist.
+    pub async fn rotate_noise_key(
+        &self,
+        project: &str,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<Option<NodeKey>> {
+        if self.get(project, name).await?.is_none() {
+            return Ok(None);
+        }
+        let replaced = self.noise_key(project, name).await?;
+        let txn = self.db.begin().await?;
+        let key = self
+            .store_noise_key(&txn, project, name, replaced.as_ref())
+            .await?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Rotate,
+            target(AuditResource::NoiseKey, project, name),
+            replaced.as_ref(),
+            Some(&key),
+        )
+        .await?;
+        txn.commit().await?;
+
+        self.publish(project, name);
+        Ok(Some(key))
+    }
+
+    async fn publish_clients(&self, project: &str, name: &str) -> Result<()> {
+        for tunnel in self.tunnels_of(project, name).await? {
+            if tunnel.server_node == name {
+                self.publish(project, &tunnel.client_node);
+            }
+        }
+        Ok(())
+    }
+
+    async fn store_noise_key(
+        &self,
+        db: &impl ConnectionTrait,
+        project: &str,
+        name: &str,
+        replaced: Option<&NodeKey>,
+    ) -> Result<NodeKey> {
+        let keypair = secrets::generate_keypair()?;
+        let now = Utc::now();
+        // Clients not yet switched to the replaced key still use the one
+        // before it, which must stay accepted instead.
+        let (previous_private_key, previous_public_key) = match replaced {
+            Some(key) if key.previous_public_key.is_some() => (
+                key.previous_private_key.clone(),
+                key.previous_public_key.clone(),
+            ),
+            Some(key) => (Some(key.private_key.clone()), Some(key.public_key.clone())),
+            None => (None, None),
+        };
+        let previous_expires_at = match previous_private_key {
+            Some(_) => Some(now + chrono::Duration::from_std(self.token_overlap)?),
+            None => None,
+        };
+        let active = node_key::ActiveModel {
+            project: Set(project.to_string()),
+            node_name: Set(name.to_string()),
+            private_key: Set(keypair.private),
+            public_key: Set(keypair.public),
+            created_at: Set(now),
+            previous_private_key: Set(previous_private_key),
+            previous_public_key: Set(previous_public_key),
+            previous_expires_at: Set(previous_expires_at),
+            ..Default::default()
+        };
+        node_key::Entity::insert(active)
+            .on_conflict(
+                OnConflict::columns([node_key::Column::Project, node_key::Column::NodeName])
+                    .update_columns([
+                        node_key::Column::PrivateKey,
+                        node_key::Column::PublicKey,
+                        node_key::Column::CreatedAt,
+                        node_key::Column::PreviousPrivateKey,
+                        node_key::Column::PreviousPublicKey,
+                        node_key::Column::PreviousExpiresAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        find_noise_key(db, project, name)
+            .await?
+            .context("managed Noise key vanished after being stored")
+    }
+
+    /// Resolves a bearer token to the principal it was issued for.
+    pub fn authenticate(&self, token: &str) -> Option<Principal> {
+        self.tokens
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&hash_token(token))
+            .cloned()
+    }
+
+    /// Whether any token can manage the manager at all.
+    pub fn has_admin_token(&self) -> bool {
+        let tokens = self.tokens.read().unwrap_or_else(|err| err.into_inner());
+        tokens.values().any(|principal| {
+            matches!(principal, Principal::User { grant, .. }
+                if grant.role == Role::Admin && !grant.is_scoped())
+        })
+    }
+
+    pub async fn list_tokens(&self) -> Result<Vec<ApiToken>> {
+        let models = api_token::Entity::find().all(&self.db).await?;
+        models.into_iter().map(token_from_model).collect()
+    }
+
+    /// Issues a new token, restricted to `node` if set and otherwise a user
+    /// token with `grant`. Returns `None` if a token called `name` already
+    /// exists.
+    pub async fn create_token(
+        &self,
+        name: &str,
+        node: Option<&NodeRecord>,
+        grant: Grant,
+        actor: &Actor,
+    ) -> Result<Option<IssuedToken>> {
+        let exists = api_token::Entity::find()
+            .filter(api_token::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+            .is_some();
+        if exists {
+            return Ok(None);
+        }
+
+        let (role, tags) = match node {
+            Some(_) => (None, None),
+            None if grant.is_scoped() => (
+                Some(grant.role.as_str().to_string()),
+                Some(serde_json::to_value(&grant.tags)?),
+            ),
+            None => (Some(grant.role.as_str().to_string()), None),
+        };
+        let token = generate_token();
+        let active = api_token::ActiveModel {
+            name: Set(name.to_string()),
+            token_hash: Set(hash_token(&token)),
+            node_name: Set(node.map(|node| node.name.clone())),
+            project: Set(node.map(|node| node.project.clone())),
+            role: Set(role),
+            tags: Set(tags),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        let txn = self.db.begin().await?;
+        let model = active.insert(&txn).await?;
+        let info = token_from_model(model)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Create,
+            token_target(&info),
+            None,
+            Some(&info),
+        )
+        .await?;
+        txn.commit().await?;
+        self.reload_tokens().await?;
+
+        Ok(Some(IssuedToken { info, token }))
+    }
+
+    pub async fn revoke_token(&self, name: &str, actor: &Actor) -> Result<bool> {
+        let Some(model) = api_token::Entity::find()
+            .filter(api_token::Column::Name.eq(name))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(false);
+        };
+        let txn = self.db.begin().await?;
+        let result = api_token::Entity::delete_many()
+            .filter(api_token::Column::Name.eq(name))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Ok(false);
+        }
+        let info = token_from_model(model)?;
+        self.audit(
+            &txn,
+            actor,
+            AuditAction::Delete,
+            token_target(&info),
+            Some(&info),
+            None,
+        )
+        .await?;
+        txn.commit().await?;
+        self.reload_tokens().await?;
+        Ok(true)
+    }
+
+    async fn store_bootstrap_token(&self, token: &str) -> Result<()> {
+        let active = api_token::ActiveModel {
+            name: Set(BOOTSTRAP_TOKEN_NAME.to_string()),
+            token_hash: Set(hash_token(token)),
+            node_name: Set(None),
+            project: Set(None),
+            role: Set(Some(Role::Admin.as_str().to_string())),
+            tags: Set(None),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        api_token::Entity::insert(active)
+            .on_conflict(
+                OnConflict::column(api_token::Column::Name)
+                    .update_columns([
+                        api_token::Column::TokenHash,
+                        api_token::Column::NodeName,
+                        api_token::Column::Project,
+                        api_token::Column::Role,
+                        api_token::Column::Tags,
+                    ])
+                    .to_owned(),
+            )
             .exec(&self.db)
+            .await
+            .context("failed to store the admin token")?;
+        Ok(())
+    }
+
+    /// Reloads the tokens that [`ManagerState::authenticate`] accepts, so
+    /// that tokens issued or revoked by other processes on the same database
+    /// apply here too.
+    pub async fn reload_tokens(&self) -> Result<()> {
+        let tokens = api_token::Entity::find()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|model| {
+                let principal = match &model.node_name {
+                    Some(node) => Principal::Node {
+                        project: node_token_project(&model),
+                        name: node.clone(),
+                    },
+                    None => Principal::User {
+                        grant: token_grant(&model)?,
+                        name: model.name,
+                    },
+                };
+                Ok((model.token_hash, principal))
+            })
+            .collect::<Result<_>>()?;
+        *self.tokens.write().unwrap_or_else(|err| err.into_inner()) = tokens;
+        Ok(())
+    }
+
+    fn to_record(
+        &self,
+        model: node::Model,
+        status: Option<node_status::Model>,
+    ) -> Result<NodeRecord> {
+        let mut record = model_to_record(model)?;
+        if let Some(status) = status {
+            record.status = self.status_from_model(status)?;
+        }
+        Ok(record)
+    }
+
+    fn status_from_model(&self, model: node_status::Model) -> Result<NodeStatus> {
+        let online = Utc::now()
+            .signed_duration_since(model.last_seen)
+            .to_std()
+            .map(|elapsed| elapsed <= self.offline_after)
+            // A heartbeat from the future still counts as recent.
+            .unwrap_or(true);
+        let listen_addrs = match model.listen_addrs {
+            Some(value) => serde_json::from_value(value)?,
+            None => Vec::new(),
+        };
+        let services = match model.services {
+            Some(value) => serde_json::from_value(value)?,
+            None => BTreeMap::new(),
+        };
+
+        Ok(NodeStatus {
+            state: if online {
+                NodeState::Online
+            } else {
+                NodeState::Offline
+            },
+            last_seen: Some(model.last_seen),
+            version: model.version,
+            uptime_seconds: model
+                .uptime_seconds
+                .map(|uptime| u64::try_from(uptime).unwrap_or_default()),
+            listen_addrs,
+            services,
+        })
+    }
+}
+
+/// What a node write did within a transaction that is still open.
+enum Staged {
+    /// The node was stored at, or deleted from, this version.
+    Written {
+        version: i64,
+        action: RevisionAction,
+        /// Whether the node was created, updated or deleted, whatever the
+        /// action: a rollback may recreate a deleted node.
+        kind: EventKind,
+        /// The node as stored, or as it was before it was deleted.
+        node: Box<NodeRecord>,
+    },
+    /// Nothing was written: the precondition failed or nothing changed.
+    Skipped(WriteOutcome),
+    /// Another write changed the node after it was read; the transaction
+    /// must be rolled back.
+    Raced,
+}
+
+// Stores `node` within `txn` if the stored node meets `precondition`, with
+// its revision and audit event. Committing is left to the caller.
+async fn stage_store(
+    txn: &DatabaseTransaction,
+    node: &NodeRecord,
+    actor: &Actor,
+    action: Option<RevisionAction>,
+    precondition: Precondition,
+) -> Result<Staged> {
+    let tags_value = if node.tags.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_value(&node.tags)?)
+    };
+    let port_mapping_value = match &node.port_mapping {
+        Some(spec) => Some(serde_json::to_value(spec)?),
+        None => None,
+    };
+    let reverse_proxy_value = match &node.reverse_proxy {
+        Some(spec) => Some(serde_json::to_value(spec)?),
+        None => None,
+    };
+    let variables_value = if node.variables.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_value(&node.variables)?)
+    };
+
+    let existing = node::Entity::find()
+        .filter(node::Column::Project.eq(node.project.clone()))
+        .filter(node::Column::Name.eq(node.name.clone()))
+        .one(txn)
+        .await?;
+    if let Some(failed) = precondition.check(existing.as_ref().map(|model| model.version)) {
+        return Ok(Staged::Skipped(failed));
+    }
+    let before = existing
+        .clone()
+        .map(model_to_record)
+        .transpose()?
+        .map(|node| snapshot(&node))
+        .transpose()?;
+
+    let (action, version, kind) = if let Some(existing) = existing {
+        if existing.reverse_proxy_bind == node.reverse_proxy_bind
+            && existing.port_mapping_role == node.port_mapping_role
+            && existing.management_url == node.management_url
+            && existing.description == node.description
+            && existing.tags == tags_value
+            && existing.port_mapping == port_mapping_value
+            && existing.reverse_proxy == reverse_proxy_value
+            && existing.variables == variables_value
+            && existing.managed_by == node.managed_by.as_str()
+        {
+            return Ok(Staged::Skipped(WriteOutcome::Done(existing.version)));
+        }
+
+        // Only update the version that was read, so that a concurrent
+        // write in between is detected rather than overwritten.
+        let version = existing.version + 1;
+        let active = node::ActiveModel {
+            reverse_proxy_bind: Set(node.reverse_proxy_bind.clone()),
+            port_mapping_role: Set(node.port_mapping_role.clone()),
+            management_url: Set(node.management_url.clone()),
+            description: Set(node.description.clone()),
+            tags: Set(tags_value.clone()),
+            port_mapping: Set(port_mapping_value.clone()),
+            reverse_proxy: Set(reverse_proxy_value.clone()),
+            variables: Set(variables_value.clone()),
+            managed_by: Set(node.managed_by.as_str().to_string()),
+            version: Set(version),
+            ..Default::default()
+        };
+        let result = node::Entity::update_many()
+            .set(active)
+            .filter(node::Column::Id.eq(existing.id))
+            .filter(node::Column::Version.eq(existing.version))
+            .exec(txn)
             .await?;
-        Ok(result.rows_affected > 0)
+        if result.rows_affected == 0 {
+            return Ok(Staged::Raced);
+        }
+        (
+            action.unwrap_or(RevisionAction::Update),
+            version,
+            EventKind::NodeUpdated,
+        )
+    } else {
+        let active = node::ActiveModel {
+            project: Set(node.project.clone()),
+            name: Set(node.name.clone()),
+            reverse_proxy_bind: Set(node.reverse_proxy_bind.clone()),
+            port_mapping_role: Set(node.port_mapping_role.clone()),
+            management_url: Set(node.management_url.clone()),
+            description: Set(node.description.clone()),
+            tags: Set(tags_value.clone()),
+            port_mapping: Set(port_mapping_value.clone()),
+            reverse_proxy: Set(reverse_proxy_value.clone()),
+            variables: Set(variables_value.clone()),
+            managed_by: Set(node.managed_by.as_str().to_string()),
+            version: Set(1),
+            ..Default::default()
+        };
+        active.insert(txn).await?;
+        (
+            action.unwrap_or(RevisionAction::Create),
+            1,
+            EventKind::NodeCreated,
+        )
+    };
+    let after = snapshot(node)?;
+    record_audit(
+        txn,
+        actor,
+        action.into(),
+        AuditTarget {
+            resource: AuditResource::Node,
+            project: Some(&node.project),
+            name: &node.name,
+        },
+        before.as_ref(),
+        Some(&after),
+    )
+    .await?;
+    record_revision(
+        txn,
+        &node.project,
+        &node.name,
+        action,
+        &actor.name,
+        Some(after),
+    )
+    .await?;
+    let mut written = node.clone();
+    written.version = version;
+    Ok(Staged::Written {
+        version,
+        action,
+        kind,
+        node: Box::new(written),
+    })
+}
+
+// Deletes `name` within `txn` if it meets `precondition`, with its status,
+// revision and audit event. Committing is left to the caller.
+async fn stage_remove(
+    txn: &DatabaseTransaction,
+    project: &str,
+    name: &str,
+    actor: &Actor,
+    precondition: Precondition,
+) -> Result<Staged> {
+    let Some(existing) = node::Entity::find()
+        .filter(node::Column::Project.eq(project))
+        .filter(node::Column::Name.eq(name))
+        .one(txn)
+        .await?
+    else {
+        return Ok(Staged::Skipped(WriteOutcome::Missing));
+    };
+    if let Some(failed) = precondition.check(Some(existing.version)) {
+        return Ok(Staged::Skipped(failed));
+    }
+    let tunnels = find_tunnels_of(txn, project, name).await?;
+    if !tunnels.is_empty() {
+        let names = tunnels.into_iter().map(|tunnel| tunnel.name).collect();
+        return Ok(Staged::Skipped(WriteOutcome::InUse(names)));
+    }
+
+    let result = node::Entity::delete_many()
+        .filter(node::Column::Id.eq(existing.id))
+        .filter(node::Column::Version.eq(existing.version))
+        .exec(txn)
+        .await?;
+    if result.rows_affected == 0 {
+        return Ok(Staged::Raced);
+    }
+    node_status::Entity::delete_many()
+        .filter(node_status::Column::Project.eq(project))
+        .filter(node_status::Column::NodeName.eq(name))
+        .exec(txn)
+        .await?;
+    let removed = model_to_record(existing.clone())?;
+    let before = snapshot(&removed)?;
+    record_audit(
+        txn,
+        actor,
+        AuditAction::Delete,
+        AuditTarget {
+            resource: AuditResource::Node,
+            project: Some(project),
+            name,
+        },
+        Some(&before),
+        None,
+    )
+    .await?;
+    record_revision(
+        txn,
+        project,
+        name,
+        RevisionAction::Delete,
+        &actor.name,
+        None,
+    )
+    .await?;
+    Ok(Staged::Written {
+        version: existing.version,
+        action: RevisionAction::Delete,
+        kind: EventKind::NodeDeleted,
+        node: Box::new(removed),
+    })
+}
+
+fn token_from_model(model: api_token::Model) -> Result<ApiToken> {
+    let (project, role, tags) = match model.node_name {
+        Some(_) => (Some(node_token_project(&model)), None, Vec::new()),
+        None => {
+            let grant = token_grant(&model)?;
+            (None, Some(grant.role), grant.tags)
+        }
+    };
+    Ok(ApiToken {
+        name: model.name,
+        project,
+        node: model.node_name,
+        role,
+        tags,
+        created_at: model.created_at,
+    })
+}
+
+// User tokens without a role predate roles, when every token was an admin.
+fn token_grant(model: &api_token::Model) -> Result<Grant> {
+    let role = match &model.role {
+        Some(role) => Role::parse(role)?,
+        None => Role::Admin,
+    };
+    let tags = match &model.tags {
+        Some(value) => serde_json::from_value(value.clone())?,
+        None => Vec::new(),
+    };
+    Ok(Grant { role, tags })
+}
+
+// Node tokens were only issued in the default project before projects existed.
+fn node_token_project(model: &api_token::Model) -> String {
+    model
+        .project
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PROJECT.to_string())
+}
+
+fn service_type_name(service_type: ServiceType) -> &'static str {
+    match service_type {
+        ServiceType::Tcp => "tcp",
+        ServiceType::Udp => "udp",
+    }
+}
+
+fn certificate_from_model(
+    model: certificate_entity::Model,
+    users: &HashMap<String, Vec<String>>,
+) -> Result<Certificate> {
+    let mut certificate = Certificate {
+        nodes: users.get(&model.name).cloned().unwrap_or_default(),
+        project: model.project,
+        name: model.name,
+        hostnames: serde_json::from_value(model.hostnames)?,
+        issuer: model.issuer,
+        fingerprint: model.fingerprint,
+        not_before: model.not_before,
+        not_after: model.not_after,
+        expiry: Expiry::Valid,
+        created_at: model.created_at,
+        updated_at: model.updated_at,
+        chain_pem: model.chain_pem,
+        key_pem: model.key_pem,
+    };
+    certificate.expiry = certificate.expiry_at(Utc::now());
+    Ok(certificate)
+}
+
+fn tunnel_from_model(model: tunnel_entity::Model) -> Result<Tunnel> {
+    let service_type = match model.service_type.as_str() {
+        "tcp" => ServiceType::Tcp,
+        "udp" => ServiceType::Udp,
+        other => bail!("unknown service type '{other}' of tunnel '{}'", model.name),
+    };
+
+    Ok(Tunnel {
+        project: model.project,
+        name: model.name,
+        server_node: model.server_node,
+        client_node: model.client_node,
+        bind_addr: model.bind_addr,
+        local_addr: model.local_addr,
+        service_type,
+        created_at: model.created_at,
+        rotate_every_secs: model
+            .rotate_every_secs
+            .map(|every| u64::try_from(every).unwrap_or_default()),
+        token_rotated_at: model.token_rotated_at.unwrap_or(model.created_at),
+        previous_token_expires_at: model.previous_token_expires_at,
+        token: model.token,
+        previous_token: model.previous_token,
+    })
+}
+
+fn node_key_from_model(model: node_key::Model) -> NodeKey {
+    NodeKey {
+        node: model.node_name,
+        public_key: model.public_key,
+        created_at: model.created_at,
+        previous_expires_at: model.previous_expires_at,
+        private_key: model.private_key,
+        previous_private_key: model.previous_private_key,
+        previous_public_key: model.previous_public_key,
     }
 }
 
@@ -150,8 +2357,17 @@ fn model_to_record(model: node::Model) -> Result<NodeRecord> {
         Some(value) => Some(serde_json::from_value(value)?),
         None => None,
     };
+    let reverse_proxy = match model.reverse_proxy {
+        Some(value) => Some(serde_json::from_value(value)?),
+        None => None,
+    };
+    let variables = match model.variables {
+        Some(value) => serde_json::from_value(value)?,
+        None => BTreeMap::new(),
+    };
 
     Ok(NodeRecord {
+        project: model.project,
         name: model.name,
         reverse_proxy_bind: model.reverse_proxy_bind,
         port_mapping_role: model.port_mapping_role,
@@ -159,5 +2375,222 @@ fn model_to_record(model: node::Model) -> Result<NodeRecord> {
         description: model.description,
         tags,
         port_mapping,
+        reverse_proxy,
+        variables,
+        managed_by: ManagedBy::parse(&model.managed_by)?,
+        version: model.version,
+        status: NodeStatus::default(),
+    })
+}
+
+async fn record_revision(
+    db: &impl ConnectionTrait,
+    project: &str,
+    name: &str,
+    action: RevisionAction,
+    author: &str,
+    snapshot: Option<Value>,
+) -> Result<()> {
+    let latest = node_revision::Entity::find()
+        .filter(node_revision::Column::Project.eq(project))
+        .filter(node_revision::Column::NodeName.eq(name))
+        .order_by_desc(node_revision::Column::Revision)
+        .one(db)
+        .await?
+        .map_or(0, |model| model.revision);
+
+    node_revision::ActiveModel {
+        project: Set(project.to_string()),
+        node_name: Set(name.to_string()),
+        revision: Set(latest + 1),
+        action: Set(action.as_str().to_string()),
+        author: Set(Some(author.to_string())),
+        created_at: Set(Utc::now()),
+        snapshot: Set(snapshot),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+fn target<'a>(resource: AuditResource, project: &'a str, name: &'a str) -> AuditTarget<'a> {
+    AuditTarget {
+        resource,
+        project: Some(project),
+        name,
+    }
+}
+
+fn project_target(name: &str) -> AuditTarget<'_> {
+    target(AuditResource::Project, name, name)
+}
+
+// Node tokens belong to the project of their node; user tokens to none.
+fn token_target(token: &ApiToken) -> AuditTarget<'_> {
+    AuditTarget {
+        resource: AuditResource::Token,
+        project: token.project.as_deref(),
+        name: &token.name,
+    }
+}
+
+async fn find_noise_key(
+    db: &impl ConnectionTrait,
+    project: &str,
+    name: &str,
+) -> Result<Option<NodeKey>> {
+    Ok(node_key::Entity::find()
+        .filter(node_key::Column::Project.eq(project))
+        .filter(node_key::Column::NodeName.eq(name))
+        .one(db)
+        .await?
+        .map(node_key_from_model))
+}
+
+/// The node `name` as stored, without its status.
+pub(crate) async fn find_node(
+    db: &impl ConnectionTrait,
+    project: &str,
+    name: &str,
+) -> Result<Option<NodeRecord>> {
+    node::Entity::find()
+        .filter(node::Column::Project.eq(project))
+        .filter(node::Column::Name.eq(name))
+        .one(db)
+        .await?
+        .map(model_to_record)
+        .transpose()
+}
+
+async fn find_tunnel(
+    db: &impl ConnectionTrait,
+    project: &str,
+    name: &str,
+) -> Result<Option<Tunnel>> {
+    tunnel_entity::Entity::find()
+        .filter(tunnel_entity::Column::Project.eq(project))
+        .filter(tunnel_entity::Column::Name.eq(name))
+        .one(db)
+        .await?
+        .map(tunnel_from_model)
+        .transpose()
+}
+
+/// Tunnels with `name` on either side.
+pub(crate) async fn find_tunnels_of(
+    db: &impl ConnectionTrait,
+    project: &str,
+    name: &str,
+) -> Result<Vec<Tunnel>> {
+    tunnel_entity::Entity::find()
+        .filter(tunnel_entity::Column::Project.eq(project))
+        .filter(
+            Condition::any()
+                .add(tunnel_entity::Column::ServerNode.eq(name))
+                .add(tunnel_entity::Column::ClientNode.eq(name)),
+        )
+        .order_by_asc(tunnel_entity::Column::Name)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(tunnel_from_model)
+        .collect()
+}
+
+// Whether `err` is a unique index rejecting a name another process took
+// since it was looked up.
+fn is_unique_violation(err: &DbErr) -> bool {
+    matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_)))
+}
+
+async fn record_audit(
+    db: &impl ConnectionTrait,
+    actor: &Actor,
+    action: AuditAction,
+    target: AuditTarget<'_>,
+    before: Option<&Value>,
+    after: Option<&Value>,
+) -> Result<()> {
+    let changes = audit::redact(diff(before, after));
+    audit_event::ActiveModel {
+        created_at: Set(Utc::now()),
+        actor: Set(actor.name.clone()),
+        source_ip: Set(actor.source_ip.clone()),
+        action: Set(action.as_str().to_string()),
+        resource: Set(Some(target.resource.as_str().to_string())),
+        project: Set(target.project.map(str::to_string)),
+        node_name: Set(target.name.to_string()),
+        changes: Set(serde_json::to_value(changes)?),
+        detail: Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+fn audit_event_from_model(model: audit_event::Model) -> Result<AuditEvent> {
+    Ok(AuditEvent {
+        id: model.id,
+        created_at: model.created_at,
+        actor: model.actor,
+        source_ip: model.source_ip,
+        action: AuditAction::parse(&model.action)?,
+        resource: model
+            .resource
+            .as_deref()
+            .map(AuditResource::parse)
+            .transpose()?,
+        project: model.project,
+        name: model.node_name,
+        changes: serde_json::from_value(model.changes)?,
+        detail: model.detail,
+    })
+}
+
+fn template_from_model(model: template_entity::Model) -> Result<Template> {
+    // Placeholders were checked when the template was stored.
+    let variables = template::placeholders(
+        model.port_mapping.iter().chain(model.reverse_proxy.iter()),
+        "",
+        &mut Vec::new(),
+    );
+    Ok(Template {
+        project: model.project,
+        name: model.name,
+        description: model.description,
+        selector: serde_json::from_value(model.selector)?,
+        port_mapping: model.port_mapping,
+        reverse_proxy: model.reverse_proxy,
+        variables,
+        created_at: model.created_at,
+        updated_at: model.updated_at,
+    })
+}
+
+fn project_from_model(model: project_entity::Model) -> Project {
+    Project {
+        name: model.name,
+        description: model.description,
+        created_at: model.created_at,
+    }
+}
+
+fn revision_from_model(model: node_revision::Model) -> Result<NodeRevision> {
+    let node = match model.snapshot {
+        Some(value) => Some(NodeRecord {
+            project: model.project,
+            ..serde_json::from_value(value)?
+        }),
+        None => None,
+    };
+
+    Ok(NodeRevision {
+        revision: model.revision,
+        action: RevisionAction::parse(&model.action)?,
+        author: model.author,
+        created_at: model.created_at,
+        node,
     })
 }
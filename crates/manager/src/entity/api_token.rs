@@ -0,0 +1,27 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "api_tokens")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    #[sea_orm(unique)]
+    pub name: String,
+    #[sea_orm(unique)]
+    pub token_hash: String,
+    /// Restricts the token to the node with this name.
+    pub node_name: Option<String>,
+    /// Project of `node_name`; unset for user tokens.
+    pub project: Option<String>,
+    /// Role of user tokens; unset for node tokens.
+    pub role: Option<String>,
+    /// Limits a user token to nodes carrying any of these tags.
+    pub tags: Option<JsonValue>,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
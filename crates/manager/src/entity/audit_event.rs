@@ -0,0 +1,30 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "audit_events")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub created_at: DateTimeUtc,
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub action: String,
+    /// What changed: `node`, `tunnel`, `certificate` and so on; unset for
+    /// denied requests that did not target anything.
+    pub resource: Option<String>,
+    /// Project of the resource; unset for user API tokens and denied
+    /// requests without one.
+    pub project: Option<String>,
+    /// Name of the resource, or empty.
+    pub node_name: String,
+    /// The changed fields, with secrets redacted.
+    pub changes: JsonValue,
+    /// What was denied and why, for denied requests.
+    pub detail: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,33 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "certificates")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub project: String,
+    /// Unique within the project.
+    pub name: String,
+    /// PEM, leaf first.
+    #[sea_orm(column_type = "Text")]
+    pub chain_pem: String,
+    /// PEM, only ever sent to the nodes serving the certificate.
+    #[sea_orm(column_type = "Text")]
+    pub key_pem: String,
+    /// DNS names of the leaf, as a JSON array.
+    pub hostnames: JsonValue,
+    pub issuer: String,
+    /// SHA-256 of the leaf in DER, in hex.
+    pub fingerprint: String,
+    pub not_before: DateTimeUtc,
+    pub not_after: DateTimeUtc,
+    pub created_at: DateTimeUtc,
+    /// When the chain and key were last replaced.
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
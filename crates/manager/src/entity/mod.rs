@@ -1 +1,11 @@
+pub mod api_token;
+pub mod audit_event;
+pub mod certificate;
 pub mod node;
+pub mod node_key;
+pub mod node_revision;
+pub mod node_status;
+pub mod project;
+pub mod schema_history;
+pub mod template;
+pub mod tunnel;
@@ -6,7 +6,8 @@ use sea_orm::JsonValue;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
-    #[sea_orm(unique)]
+    pub project: String,
+    /// Unique within the project.
     pub name: String,
     pub reverse_proxy_bind: Option<String>,
     pub port_mapping_role: Option<String>,
@@ -14,6 +15,13 @@ pub struct Model {
     pub description: Option<String>,
     pub tags: Option<JsonValue>,
     pub port_mapping: Option<JsonValue>,
+    pub reverse_proxy: Option<JsonValue>,
+    /// Values of template placeholders, as a JSON object.
+    pub variables: Option<JsonValue>,
+    /// `file` for nodes declared in the configuration file, otherwise `ui`.
+    pub managed_by: String,
+    /// Starts at 1 and is incremented by every change to the node.
+    pub version: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+
+/// Noise keypair generated by the manager for a node's Rathole transport.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "node_keys")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub project: String,
+    pub node_name: String,
+    /// Base64, as in `local_private_key`.
+    pub private_key: String,
+    /// Base64, as in `remote_public_key`.
+    pub public_key: String,
+    pub created_at: DateTimeUtc,
+    /// The keypair replaced by the last rotation, while the server still
+    /// accepts it.
+    pub previous_private_key: Option<String>,
+    /// Given to clients instead of `public_key` until the rotation reaches
+    /// them.
+    pub previous_public_key: Option<String>,
+    pub previous_expires_at: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "node_revisions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub project: String,
+    pub node_name: String,
+    /// Sequence number of the revision within its node, starting at 1.
+    pub revision: i64,
+    pub action: String,
+    pub author: Option<String>,
+    pub created_at: DateTimeUtc,
+    /// The node after the change; empty when the node was deleted.
+    pub snapshot: Option<JsonValue>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,21 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "node_status")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub project: String,
+    pub node_name: String,
+    pub last_seen: DateTimeUtc,
+    pub version: Option<String>,
+    pub uptime_seconds: Option<i64>,
+    pub listen_addrs: Option<JsonValue>,
+    pub services: Option<JsonValue>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
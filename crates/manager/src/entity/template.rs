@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::JsonValue;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "templates")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub project: String,
+    /// Unique within the project.
+    pub name: String,
+    pub description: Option<String>,
+    /// Tags of the bound nodes, as a JSON array.
+    pub selector: JsonValue,
+    /// With `{{var}}` placeholders, as are `reverse_proxy`.
+    pub port_mapping: Option<JsonValue>,
+    pub reverse_proxy: Option<JsonValue>,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "tunnels")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Also of both of its nodes.
+    pub project: String,
+    /// Also the name of the Rathole service on both nodes; unique within the
+    /// project.
+    pub name: String,
+    pub server_node: String,
+    pub client_node: String,
+    pub bind_addr: String,
+    pub local_addr: String,
+    /// `tcp` or `udp`.
+    pub service_type: String,
+    /// Shared by both sides of the tunnel.
+    pub token: String,
+    pub created_at: DateTimeUtc,
+    /// Token replaced by the last rotation, still accepted by the server
+    /// until `previous_token_expires_at`.
+    pub previous_token: Option<String>,
+    pub previous_token_expires_at: Option<DateTimeUtc>,
+    /// Empty until the token is first rotated.
+    pub token_rotated_at: Option<DateTimeUtc>,
+    pub rotate_every_secs: Option<i64>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -3,16 +3,25 @@ use actix_web::{HttpResponse, ResponseError};
 use anyhow::Error as AnyhowError;
 use serde::Serialize;
 use std::fmt::{Display, Formatter};
+use tonic::Status;
+use utoipa::ToSchema;
+
+use crate::config::WriteOutcome;
+use crate::validate::FieldError;
 
 #[derive(Debug)]
 pub struct AppError {
     status: StatusCode,
     message: String,
+    errors: Vec<FieldError>,
 }
 
-#[derive(Serialize)]
-struct ErrorBody {
+/// Body of every error response of the HTTP API.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorBody {
     message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
 }
 
 impl AppError {
@@ -20,6 +29,7 @@ impl AppError {
         Self {
             status: StatusCode::BAD_REQUEST,
             message: message.into(),
+            errors: Vec::new(),
         }
     }
 
@@ -27,6 +37,58 @@ impl AppError {
         Self {
             status: StatusCode::NOT_FOUND,
             message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The node changed since the client read the version it expects.
+    pub fn precondition_failed(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::PRECONDITION_FAILED,
+            message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// A write that does not say which version of the node it expects.
+    pub fn precondition_required(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::PRECONDITION_REQUIRED,
+            message: message.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// A well-formed request that failed validation, with the fields at fault.
+    pub fn unprocessable(message: impl Into<String>, errors: Vec<FieldError>) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message: message.into(),
+            errors,
         }
     }
 
@@ -34,6 +96,7 @@ impl AppError {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
             message: message.into(),
+            errors: Vec::new(),
         }
     }
 }
@@ -52,10 +115,35 @@ impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status).json(ErrorBody {
             message: self.message.clone(),
+            errors: self.errors.clone(),
         })
     }
 }
 
+impl From<AppError> for Status {
+    fn from(err: AppError) -> Self {
+        match err.status {
+            StatusCode::BAD_REQUEST => Status::invalid_argument(err.message),
+            StatusCode::NOT_FOUND => Status::not_found(err.message),
+            StatusCode::CONFLICT => Status::already_exists(err.message),
+            StatusCode::UNAUTHORIZED => Status::unauthenticated(err.message),
+            StatusCode::FORBIDDEN => Status::permission_denied(err.message),
+            StatusCode::PRECONDITION_FAILED | StatusCode::PRECONDITION_REQUIRED => {
+                Status::failed_precondition(err.message)
+            }
+            StatusCode::UNPROCESSABLE_ENTITY => {
+                let fields: Vec<String> = err
+                    .errors
+                    .iter()
+                    .map(|error| format!("{}: {}", error.field, error.reason))
+                    .collect();
+                Status::invalid_argument(format!("{} ({})", err.message, fields.join("; ")))
+            }
+            _ => Status::internal(err.message),
+        }
+    }
+}
+
 impl From<AnyhowError> for AppError {
     fn from(err: AnyhowError) -> Self {
         Self::internal(err.to_string())
@@ -63,3 +151,19 @@ impl From<AnyhowError> for AppError {
 }
 
 pub type AppResult<T> = Result<T, AppError>;
+
+/// The version a node write left the node at, or why it was refused.
+pub fn written(name: &str, outcome: WriteOutcome) -> AppResult<i64> {
+    match outcome {
+        WriteOutcome::Done(version) => Ok(version),
+        WriteOutcome::Missing => Err(AppError::not_found(format!("node '{name}' not found"))),
+        WriteOutcome::Exists => Err(AppError::conflict(format!("node '{name}' already exists"))),
+        WriteOutcome::Stale(current) => Err(AppError::precondition_failed(format!(
+            "node '{name}' is now at version {current}; fetch it again before changing it"
+        ))),
+        WriteOutcome::InUse(tunnels) => Err(AppError::conflict(format!(
+            "node '{name}' is used by tunnels {}; delete them first",
+            tunnels.join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,326 @@
+//! Live feed of changes to nodes, served as Server-Sent Events by
+//! `GET /events`. Every change is kept in a bounded buffer, so that a client
+//! that reconnects with `Last-Event-ID` receives what it missed meanwhile.
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use chrono::Utc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::config::NodeRecord;
+use crate::rbac::Grant;
+
+/// Events kept for clients that resume the feed.
+const BUFFER_SIZE: usize = 1024;
+
+/// How often an idle feed sends a comment, so that proxies keep it open.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Sent as the `event` field of the Server-Sent Event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    NodeCreated,
+    NodeUpdated,
+    NodeDeleted,
+    /// The node came online or went offline, or reported another version,
+    /// listen addresses or services.
+    NodeStatusChanged,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::NodeCreated => "node_created",
+            EventKind::NodeUpdated => "node_updated",
+            EventKind::NodeDeleted => "node_deleted",
+            EventKind::NodeStatusChanged => "node_status_changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Increases with every event, across restarts of the manager; sent as
+    /// the `id` field of the Server-Sent Event.
+    pub id: u64,
+    pub kind: EventKind,
+    /// The node as stored by the change, or as it was before it was deleted;
+    /// sent as the `data` field.
+    pub node: NodeRecord,
+}
+
+/// Receives the events published after [`EventBus::subscribe`], preceded by
+/// the buffered events the subscriber asked to resume from.
+pub struct Subscription {
+    /// Events after the one the subscriber last received, if some of them
+    /// are no longer buffered; the subscriber should read the nodes again.
+    pub missed: bool,
+    pub backlog: Vec<Arc<Event>>,
+    pub receiver: broadcast::Receiver<Arc<Event>>,
+}
+
+pub struct EventBus {
+    buffer: Mutex<Buffer>,
+    sender: broadcast::Sender<Arc<Event>>,
+}
+
+struct Buffer {
+    next_id: u64,
+    events: VecDeque<Arc<Event>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(BUFFER_SIZE);
+        // Ids start from the current time in microseconds, so that an id
+        // handed out before a restart is older than every id after it.
+        let next_id = u64::try_from(Utc::now().timestamp_micros()).unwrap_or_default();
+        Self {
+            buffer: Mutex::new(Buffer {
+                next_id,
+                events: VecDeque::with_capacity(BUFFER_SIZE),
+            }),
+            sender,
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, kind: EventKind, node: NodeRecord) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(|err| err.into_inner());
+        let event = Arc::new(Event {
+            id: buffer.next_id,
+            kind,
+            node,
+        });
+        buffer.next_id += 1;
+        if buffer.events.len() == BUFFER_SIZE {
+            buffer.events.pop_front();
+        }
+        buffer.events.push_back(event.clone());
+        // Sending only fails when nobody is subscribed. The buffer stays
+        // locked so that subscribers see events in the order of their ids.
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events after `last_id`, or to new events only if
+    /// `last_id` is not set.
+    pub fn subscribe(&self, last_id: Option<u64>) -> Subscription {
+        let buffer = self.buffer.lock().unwrap_or_else(|err| err.into_inner());
+        let receiver = self.sender.subscribe();
+        let Some(last_id) = last_id else {
+            return Subscription {
+                missed: false,
+                backlog: Vec::new(),
+                receiver,
+            };
+        };
+        let oldest = buffer
+            .events
+            .front()
+            .map_or(buffer.next_id, |event| event.id);
+        Subscription {
+            missed: last_id.saturating_add(1) < oldest || last_id >= buffer.next_id,
+            backlog: buffer
+                .events
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+            receiver,
+        }
+    }
+}
+
+/// Which events a feed sends.
+pub struct Filter {
+    pub project: Option<String>,
+    pub grant: Grant,
+}
+
+impl Filter {
+    fn matches(&self, event: &Event) -> bool {
+        let in_project = match &self.project {
+            Some(project) => *project == event.node.project,
+            None => true,
+        };
+        in_project && self.grant.covers(&event.node.tags)
+    }
+}
+
+/// The body of a `text/event-stream` response carrying `subscription`.
+/// A `resync` event tells the client that events were lost, whenever the
+/// buffer no longer holds what it asked for or it fell behind.
+pub fn stream(
+    subscription: Subscription,
+    filter: Filter,
+) -> ReceiverStream<Result<Bytes, Infallible>> {
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let Subscription {
+            missed,
+            backlog,
+            mut receiver,
+        } = subscription;
+        if missed && tx.send(Ok(resync())).await.is_err() {
+            return;
+        }
+        for event in backlog {
+            if filter.matches(&event) && tx.send(Ok(encode(&event))).await.is_err() {
+                return;
+            }
+        }
+
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        keep_alive.tick().await;
+        loop {
+            let chunk = tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) if filter.matches(&event) => encode(&event),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => resync(),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = keep_alive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+                _ = tx.closed() => break,
+            };
+            if tx.send(Ok(chunk)).await.is_err() {
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+fn encode(event: &Event) -> Bytes {
+    // JSON without pretty printing has no line breaks, so it fits in one
+    // `data` line.
+    let data = serde_json::to_string(&event.node).unwrap_or_else(|_| "null".to_string());
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {data}\n\n",
+        event.id,
+        event.kind.as_str()
+    ))
+}
+
+fn resync() -> Bytes {
+    Bytes::from_static(b"event: resync\ndata: {}\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::*;
+    use crate::fixture;
+    use crate::rbac::Role;
+
+    fn node(project: &str, name: &str) -> NodeRecord {
+        NodeRecord {
+            project: project.to_string(),
+            ..fixture::node(name, &[])
+        }
+    }
+
+    fn everything() -> Filter {
+        Filter {
+            project: None,
+            grant: Grant {
+                role: Role::Viewer,
+                tags: Vec::new(),
+            },
+        }
+    }
+
+    // The id of each event sent, or `resync`; keep-alive comments are skipped.
+    async fn read(
+        stream: &mut ReceiverStream<Result<Bytes, Infallible>>,
+        count: usize,
+    ) -> Vec<String> {
+        let mut events = Vec::new();
+        while events.len() < count {
+            let chunk = stream.next().await.unwrap().unwrap();
+            let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+            if chunk.starts_with("event: resync") {
+                events.push("resync".to_string());
+            } else if let Some(id) = chunk.strip_prefix("id: ") {
+                events.push(id.lines().next().unwrap().to_string());
+            }
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn last_event_id_replays_exactly_the_missed_events() {
+        let bus = EventBus::default();
+        for name in ["a", "b", "c", "d"] {
+            bus.publish(EventKind::NodeUpdated, node("default", name));
+        }
+        let ids: Vec<u64> = bus
+            .subscribe(Some(0))
+            .backlog
+            .iter()
+            .map(|event| event.id)
+            .collect();
+
+        let subscription = bus.subscribe(Some(ids[1]));
+        assert!(!subscription.missed);
+        let mut stream = stream(subscription, everything());
+        bus.publish(EventKind::NodeDeleted, node("default", "e"));
+        let expected: Vec<String> = [ids[2], ids[3], ids[3] + 1]
+            .iter()
+            .map(u64::to_string)
+            .collect();
+        assert_eq!(read(&mut stream, 3).await, expected);
+
+        // Nothing was missed after the last event, and nothing is replayed.
+        let subscription = bus.subscribe(Some(ids[3] + 1));
+        assert!(!subscription.missed);
+        assert!(subscription.backlog.is_empty());
+        assert!(bus.subscribe(None).backlog.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resuming_from_an_event_no_longer_buffered_asks_for_a_resync() {
+        let bus = EventBus::default();
+        for _ in 0..BUFFER_SIZE + 2 {
+            bus.publish(EventKind::NodeUpdated, node("default", "a"));
+        }
+        let oldest = bus.subscribe(Some(0)).backlog[0].id;
+
+        let subscription = bus.subscribe(Some(oldest - 1));
+        assert!(!subscription.missed);
+        assert_eq!(subscription.backlog.len(), BUFFER_SIZE);
+
+        let subscription = bus.subscribe(Some(oldest - 2));
+        assert!(subscription.missed);
+        let mut stream = stream(subscription, everything());
+        let events = read(&mut stream, 2).await;
+        assert_eq!(events, ["resync".to_string(), oldest.to_string()]);
+
+        // An id this manager never handed out, such as one of a manager whose
+        // clock was ahead.
+        assert!(bus.subscribe(Some(u64::MAX - 1)).missed);
+    }
+
+    #[tokio::test]
+    async fn feeds_only_send_the_events_of_their_project() {
+        let bus = EventBus::default();
+        bus.publish(EventKind::NodeCreated, node("staging", "a"));
+        bus.publish(EventKind::NodeCreated, node("default", "b"));
+        let backlog = bus.subscribe(Some(0)).backlog;
+
+        let filter = Filter {
+            project: Some("default".to_string()),
+            ..everything()
+        };
+        let mut stream = stream(bus.subscribe(Some(0)), filter);
+        assert_eq!(read(&mut stream, 1).await, [backlog[1].id.to_string()]);
+    }
+}
@@ -0,0 +1,58 @@
+//! Managers, nodes and certificates shared by the tests of the manager.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::certificate::{Certificate, Expiry};
+use crate::config::{ManagerState, NodeRecord};
+use crate::project::DEFAULT_PROJECT;
+
+/// Opens a manager on `database_url`, with `admin_token` as its `admin` API
+/// token if set. Nodes go offline, and rotated tokens expire, after a
+/// minute.
+pub(crate) async fn open(database_url: &str, admin_token: Option<&str>) -> ManagerState {
+    ManagerState::open(
+        database_url,
+        Duration::from_secs(60),
+        Duration::from_secs(60),
+        admin_token.map(str::to_string),
+    )
+    .await
+    .unwrap()
+}
+
+/// A manager on a fresh in-memory SQLite database, without API tokens.
+pub(crate) async fn state() -> ManagerState {
+    open("sqlite::memory:", None).await
+}
+
+/// A node of the default project with `tags` and nothing else set.
+pub(crate) fn node(name: &str, tags: &[&str]) -> NodeRecord {
+    NodeRecord {
+        project: DEFAULT_PROJECT.to_string(),
+        name: name.to_string(),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+/// A certificate of the default project for `example.com`, served by
+/// `edge`. Its chain and key are placeholders, not PEM.
+pub(crate) fn certificate(name: &str) -> Certificate {
+    Certificate {
+        project: DEFAULT_PROJECT.to_string(),
+        name: name.to_string(),
+        hostnames: vec!["example.com".to_string()],
+        issuer: "CN=Example CA".to_string(),
+        fingerprint: String::new(),
+        not_before: Utc::now(),
+        not_after: Utc::now(),
+        expiry: Expiry::Valid,
+        nodes: vec!["edge".to_string()],
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        chain_pem: "chain".to_string(),
+        key_pem: "key".to_string(),
+    }
+}
@@ -0,0 +1,1050 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use laval_model::{PortMappingMode, PortMappingSpec, ReverseProxySpec, ReverseProxyTls};
+use laval_proto::manager::v1::{
+    node_manager_server::NodeManager, ControlChannelState as ProtoControlChannelState,
+    CreateNodeRequest, CreateNodeResponse, CreateTunnelRequest, CreateTunnelResponse,
+    DeleteNodeRequest, DeleteNodeResponse, DeleteTunnelRequest, DeleteTunnelResponse,
+    DiffNodeRevisionsRequest, DiffNodeRevisionsResponse, FieldChange as ProtoFieldChange,
+    GetNodeConfigRequest, GetNodeConfigResponse, GetNodeRevisionRequest, GetNodeRevisionResponse,
+    ListNodeRevisionsRequest, ListNodeRevisionsResponse, ListNodesRequest, ListNodesResponse,
+    ListTunnelsRequest, ListTunnelsResponse, Node as ProtoNode, NodeRevision as ProtoNodeRevision,
+    NodeStatus as ProtoNodeStatus, NodeVariable as ProtoNodeVariable, NoiseKey as ProtoNoiseKey,
+    PortMappingConfig as ProtoPortMappingConfig, PortMappingMode as ProtoPortMappingMode,
+    ProxyRoute as ProtoProxyRoute, ReportStatusRequest, ReportStatusResponse,
+    ReverseProxyConfig as ProtoReverseProxyConfig, ReverseProxyTls as ProtoReverseProxyTls,
+    RollbackNodeRequest, RollbackNodeResponse, RotateNoiseKeyRequest, RotateNoiseKeyResponse,
+    RotateTunnelTokenRequest, RotateTunnelTokenResponse, ServiceStatus as ProtoServiceStatus,
+    TlsCertificate as ProtoTlsCertificate, Tunnel as ProtoTunnel, TunnelType as ProtoTunnelType,
+    UpdateNodeRequest, UpdateNodeResponse, WatchNodeConfigRequest, WatchNodeConfigResponse,
+};
+use rathole::config::ServiceType;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{async_trait, Request, Response, Status};
+
+use crate::certificate::{self, Certificate};
+use crate::config::{
+    ManagedBy, ManagerState, NodeRecord, NodeReport, NodeState, NodeStatus, Precondition,
+    ServiceState, WriteOutcome,
+};
+use crate::error::{written, AppError, AppResult};
+use crate::project;
+use crate::query::{Cursor, ModeFilter, NodeQuery, NodeSort};
+use crate::rbac::{self, require_node_access, Caller, Role, Scope};
+use crate::revision::{FieldChange, NodeRevision};
+use crate::secrets::NodeKey;
+use crate::template;
+use crate::tunnel::{self, CreateTunnel, Tunnel};
+use crate::validate;
+
+#[derive(Clone)]
+pub struct GrpcService {
+    state: Arc<ManagerState>,
+}
+
+impl GrpcService {
+    pub fn new(state: Arc<ManagerState>) -> Self {
+        Self { state }
+    }
+}
+
+#[async_trait]
+impl NodeManager for GrpcService {
+    async fn get_node_config(
+        &self,
+        request: Request<GetNodeConfigRequest>,
+    ) -> Result<Response<GetNodeConfigResponse>, Status> {
+        let project = fetch_project(&self.state, &request.get_ref().project).await?;
+        let name = request.get_ref().name.trim().to_string();
+        require_node_access(
+            &self.state,
+            &request,
+            &project,
+            &name,
+            Role::Viewer,
+            &format!("fetch the config of node '{name}'"),
+        )
+        .await?;
+        let (record, certificates) = fetch_node_config(&self.state, &project, &name).await?;
+        let port_mapping = encode_port_mapping(&record)?;
+        let reverse_proxy = record.reverse_proxy.as_ref().map(reverse_proxy_to_proto);
+
+        Ok(Response::new(GetNodeConfigResponse {
+            name: record.name,
+            port_mapping,
+            reverse_proxy,
+            certificate: certificates.reverse_proxy.map(certificate_to_proto),
+            port_mapping_certificate: certificates.port_mapping.map(certificate_to_proto),
+        }))
+    }
+
+    type WatchNodeConfigStream = ReceiverStream<Result<WatchNodeConfigResponse, Status>>;
+
+    async fn watch_node_config(
+        &self,
+        request: Request<WatchNodeConfigRequest>,
+    ) -> Result<Response<Self::WatchNodeConfigStream>, Status> {
+        let project = fetch_project(&self.state, &request.get_ref().project).await?;
+        let name = request.get_ref().name.trim().to_string();
+        require_node_access(
+            &self.state,
+            &request,
+            &project,
+            &name,
+            Role::Viewer,
+            &format!("watch the config of node '{name}'"),
+        )
+        .await?;
+        // Subscribe before reading the current config so no change slips in between.
+        let mut changes = self.state.subscribe();
+        let revision = self.state.revision();
+        let (record, certificates) = fetch_node_config(&self.state, &project, &name).await?;
+        let first = node_config_revision(revision, record, certificates)?;
+
+        let (tx, rx) = mpsc::channel(4);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            if tx.send(Ok(first)).await.is_err() {
+                return;
+            }
+
+            loop {
+                let revision = tokio::select! {
+                    change = changes.recv() => match change {
+                        Ok(change) if change.project == project && change.name == name => {
+                            change.revision
+                        }
+                        Ok(_) => continue,
+                        // Some changes were missed, so resend the current config.
+                        Err(RecvError::Lagged(_)) => state.revision(),
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = tx.closed() => break,
+                };
+
+                let item = match fetch_node_config(&state, &project, &name).await {
+                    Ok((record, certificates)) => {
+                        node_config_revision(revision, record, certificates)
+                    }
+                    Err(status) => Err(status),
+                };
+                let failed = item.is_err();
+                if tx.send(item).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn list_nodes(
+        &self,
+        request: Request<ListNodesRequest>,
+    ) -> Result<Response<ListNodesResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "list nodes").await?;
+        caller
+            .require(&self.state, Role::Viewer, Scope::Listing, "list nodes")
+            .await?;
+        let request = request.into_inner();
+        let project = fetch_project(&self.state, &request.project).await?;
+        let sort = if request.sort.is_empty() {
+            NodeSort::default()
+        } else {
+            NodeSort::parse(&request.sort)?
+        };
+        let mode = match ProtoPortMappingMode::try_from(request.mode) {
+            Ok(ProtoPortMappingMode::Unspecified) => None,
+            Ok(ProtoPortMappingMode::Server) => Some(ModeFilter::Server),
+            Ok(ProtoPortMappingMode::Client) => Some(ModeFilter::Client),
+            Err(_) => return Err(Status::invalid_argument("unknown port mapping mode")),
+        };
+        let query = NodeQuery {
+            project,
+            tags: request.tags,
+            any_tags: caller.grant.tags.clone(),
+            mode,
+            role: request.role,
+            search: Some(request.query).filter(|query| !query.is_empty()),
+            sort,
+            limit: u64::from(request.limit),
+            cursor: Some(request.cursor)
+                .filter(|cursor| !cursor.is_empty())
+                .map(|cursor| Cursor::decode(&cursor, sort))
+                .transpose()?,
+        };
+
+        let page = self
+            .state
+            .query(&query)
+            .await
+            .map_err(|err| Status::internal(format!("failed to list nodes: {err}")))?;
+        let nodes = page
+            .nodes
+            .into_iter()
+            .map(node_to_proto)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Response::new(ListNodesResponse {
+            nodes,
+            next_cursor: page.next_cursor.unwrap_or_default(),
+        }))
+    }
+
+    async fn create_node(
+        &self,
+        request: Request<CreateNodeRequest>,
+    ) -> Result<Response<CreateNodeResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "create nodes").await?;
+        let CreateNodeRequest { project, node } = request.into_inner();
+        let node = node.ok_or_else(|| Status::invalid_argument("node is required"))?;
+        let mut payload = node_from_proto(node)?;
+        payload.project = fetch_project(&self.state, &project).await?;
+        validate::check_name(&payload.name)?;
+        payload.name = payload.name.trim().to_string();
+        caller.require_write(&self.state, None, &payload).await?;
+        template::check_bind(&self.state, &mut payload).await?;
+        validate::check_node(&payload)?;
+        tunnel::check_node(&self.state, &payload).await?;
+        certificate::check_node(&self.state, &payload).await?;
+        let outcome = self
+            .state
+            .write(payload.clone(), &caller.actor, Precondition::Absent)
+            .await
+            .map_err(|err| Status::internal(format!("failed to store node: {err}")))?;
+        payload.version = written(&payload.name, outcome)?;
+
+        Ok(Response::new(CreateNodeResponse {
+            node: Some(node_to_proto(payload)?),
+        }))
+    }
+
+    async fn update_node(
+        &self,
+        request: Request<UpdateNodeRequest>,
+    ) -> Result<Response<UpdateNodeResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "update nodes").await?;
+        let UpdateNodeRequest {
+            project,
+            name,
+            node,
+            expected_version,
+        } = request.into_inner();
+        let precondition = expected(expected_version)?;
+        let node = node.ok_or_else(|| Status::invalid_argument("node is required"))?;
+        let mut payload = node_from_proto(node)?;
+        validate::check_name(&name)?;
+        payload.project = fetch_project(&self.state, &project).await?;
+        payload.name = name.trim().to_string();
+        let current = fetch_optional_node(&self.state, &payload.project, &payload.name).await?;
+        caller
+            .require_write(&self.state, current.as_ref(), &payload)
+            .await?;
+        template::check_bind(&self.state, &mut payload).await?;
+        validate::check_node(&payload)?;
+        tunnel::check_node(&self.state, &payload).await?;
+        certificate::check_node(&self.state, &payload).await?;
+        let outcome = self
+            .state
+            .write(payload.clone(), &caller.actor, precondition)
+            .await
+            .map_err(|err| Status::internal(format!("failed to store node: {err}")))?;
+        payload.version = written(&payload.name, outcome)?;
+
+        Ok(Response::new(UpdateNodeResponse {
+            node: Some(node_to_proto(payload)?),
+        }))
+    }
+
+    async fn delete_node(
+        &self,
+        request: Request<DeleteNodeRequest>,
+    ) -> Result<Response<DeleteNodeResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "delete nodes").await?;
+        let DeleteNodeRequest {
+            project,
+            name,
+            expected_version,
+        } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        let current = fetch_optional_node(&self.state, &project, name.trim()).await?;
+        caller
+            .require(
+                &self.state,
+                Role::Admin,
+                Scope::node(current.as_ref()),
+                &format!("delete node '{}'", name.trim()),
+            )
+            .await?;
+        let precondition = expected(expected_version)?;
+        let outcome = self
+            .state
+            .remove(&project, name.trim(), &caller.actor, precondition)
+            .await
+            .map_err(|err| Status::internal(format!("failed to delete node '{name}': {err}")))?;
+        written(name.trim(), outcome)?;
+
+        Ok(Response::new(DeleteNodeResponse {}))
+    }
+
+    async fn report_status(
+        &self,
+        request: Request<ReportStatusRequest>,
+    ) -> Result<Response<ReportStatusResponse>, Status> {
+        let project = fetch_project(&self.state, &request.get_ref().project).await?;
+        let name = request.get_ref().name.trim().to_string();
+        require_node_access(
+            &self.state,
+            &request,
+            &project,
+            &name,
+            Role::Admin,
+            &format!("report the status of node '{name}'"),
+        )
+        .await?;
+        let ReportStatusRequest {
+            project: _,
+            name,
+            version,
+            uptime_seconds,
+            listen_addrs,
+            services,
+        } = request.into_inner();
+        let name = name.trim();
+        let report = NodeReport {
+            version,
+            uptime_seconds,
+            listen_addrs,
+            services: services_from_proto(services)?,
+        };
+
+        let known = self
+            .state
+            .record_status(&project, name, report)
+            .await
+            .map_err(|err| {
+                Status::internal(format!("failed to record status of '{name}': {err}"))
+            })?;
+
+        if known {
+            Ok(Response::new(ReportStatusResponse {}))
+        } else {
+            Err(AppError::not_found(format!("node '{name}' not found")).into())
+        }
+    }
+
+    async fn list_node_revisions(
+        &self,
+        request: Request<ListNodeRevisionsRequest>,
+    ) -> Result<Response<ListNodeRevisionsResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "list node revisions").await?;
+        let ListNodeRevisionsRequest { project, name } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        require_history(&self.state, &caller, &project, name.trim()).await?;
+        let revisions = self
+            .state
+            .list_revisions(&project, name.trim())
+            .await
+            .map_err(|err| {
+                Status::internal(format!("failed to list revisions of '{name}': {err}"))
+            })?;
+        if revisions.is_empty() {
+            return Err(Status::not_found(format!("node '{name}' not found")));
+        }
+
+        Ok(Response::new(ListNodeRevisionsResponse {
+            revisions: revisions
+                .into_iter()
+                .map(revision_to_proto)
+                .collect::<Result<Vec<_>, _>>()?,
+        }))
+    }
+
+    async fn get_node_revision(
+        &self,
+        request: Request<GetNodeRevisionRequest>,
+    ) -> Result<Response<GetNodeRevisionResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "read node revisions").await?;
+        let GetNodeRevisionRequest {
+            project,
+            name,
+            revision,
+        } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        require_history(&self.state, &caller, &project, name.trim()).await?;
+        let revision = fetch_revision(&self.state, &project, name.trim(), revision).await?;
+
+        Ok(Response::new(GetNodeRevisionResponse {
+            revision: Some(revision_to_proto(revision)?),
+        }))
+    }
+
+    async fn diff_node_revisions(
+        &self,
+        request: Request<DiffNodeRevisionsRequest>,
+    ) -> Result<Response<DiffNodeRevisionsResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "diff node revisions").await?;
+        let DiffNodeRevisionsRequest {
+            project,
+            name,
+            from,
+            to,
+        } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        require_history(&self.state, &caller, &project, name.trim()).await?;
+        let changes = self
+            .state
+            .diff_revisions(&project, name.trim(), from, to)
+            .await
+            .map_err(|err| {
+                Status::internal(format!("failed to diff revisions of '{name}': {err}"))
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "revisions {from} and {to} of node '{name}' not found"
+                ))
+            })?;
+
+        Ok(Response::new(DiffNodeRevisionsResponse {
+            changes: changes.into_iter().map(field_change_to_proto).collect(),
+        }))
+    }
+
+    async fn rollback_node(
+        &self,
+        request: Request<RollbackNodeRequest>,
+    ) -> Result<Response<RollbackNodeResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "roll back nodes").await?;
+        let RollbackNodeRequest {
+            project,
+            name,
+            revision,
+            expected_version,
+        } = request.into_inner();
+        // Without a version, only a deleted node can be restored.
+        let precondition = match expected_version {
+            0 => Precondition::Absent,
+            version => expected(version)?,
+        };
+        let project = fetch_project(&self.state, &project).await?;
+        let name = name.trim();
+        let Some(mut node) = fetch_revision(&self.state, &project, name, revision)
+            .await?
+            .node
+        else {
+            return Err(Status::invalid_argument(format!(
+                "revision {revision} deleted node '{name}'; roll back to an earlier revision"
+            )));
+        };
+        let current = fetch_optional_node(&self.state, &project, name).await?;
+        caller
+            .require(
+                &self.state,
+                Role::Admin,
+                Scope::Nodes(current.iter().chain([&node]).collect()),
+                &format!("roll back node '{name}'"),
+            )
+            .await?;
+        // Revisions do not record where the node is managed from.
+        node.managed_by = current
+            .as_ref()
+            .map(|current| current.managed_by)
+            .unwrap_or_default();
+        template::check_bind(&self.state, &mut node).await?;
+        validate::check_node(&node)?;
+        tunnel::check_node(&self.state, &node).await?;
+        certificate::check_node(&self.state, &node).await?;
+        let outcome = self
+            .state
+            .rollback(node, &caller.actor, precondition)
+            .await
+            .map_err(|err| Status::internal(format!("failed to roll back '{name}': {err}")))?;
+        if outcome == WriteOutcome::Exists {
+            return Err(Status::failed_precondition(format!(
+                "node '{name}' exists; set expected_version to roll it back"
+            )));
+        }
+        written(name, outcome)?;
+
+        Ok(Response::new(RollbackNodeResponse {
+            node: Some(node_to_proto(
+                fetch_node(&self.state, &project, name).await?,
+            )?),
+        }))
+    }
+
+    async fn list_tunnels(
+        &self,
+        request: Request<ListTunnelsRequest>,
+    ) -> Result<Response<ListTunnelsResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "list tunnels").await?;
+        caller
+            .require(&self.state, Role::Viewer, Scope::Listing, "list tunnels")
+            .await?;
+        let project = fetch_project(&self.state, &request.into_inner().project).await?;
+        let tunnels = self
+            .state
+            .list_tunnels(&project)
+            .await
+            .map_err(|err| Status::internal(format!("failed to list tunnels: {err}")))?;
+        let tunnels = rbac::visible_tunnels(&self.state, &caller.grant, &project, tunnels)
+            .await
+            .map_err(|err| Status::internal(format!("failed to list tunnels: {err}")))?
+            .into_iter()
+            .map(tunnel_to_proto)
+            .collect();
+
+        Ok(Response::new(ListTunnelsResponse { tunnels }))
+    }
+
+    async fn create_tunnel(
+        &self,
+        request: Request<CreateTunnelRequest>,
+    ) -> Result<Response<CreateTunnelResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "create tunnels").await?;
+        let CreateTunnelRequest { project, tunnel } = request.into_inner();
+        let tunnel = tunnel.ok_or_else(|| Status::invalid_argument("tunnel is required"))?;
+        let project = fetch_project(&self.state, &project).await?;
+        let payload = tunnel_from_proto(tunnel)?;
+        caller
+            .require_tunnel(
+                &self.state,
+                Role::Operator,
+                &project,
+                &payload.server_node,
+                &payload.client_node,
+                &format!("create tunnel '{}'", payload.name.trim()),
+            )
+            .await?;
+        let name = payload.name.trim().to_string();
+        let creation = self
+            .state
+            .create_tunnel(&project, payload, &caller.actor)
+            .await
+            .map_err(|err| Status::internal(format!("failed to store tunnel: {err}")))?;
+        let created = tunnel::created(&name, creation)?;
+
+        Ok(Response::new(CreateTunnelResponse {
+            tunnel: Some(tunnel_to_proto(created)),
+        }))
+    }
+
+    async fn delete_tunnel(
+        &self,
+        request: Request<DeleteTunnelRequest>,
+    ) -> Result<Response<DeleteTunnelResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "delete tunnels").await?;
+        let DeleteTunnelRequest { project, name } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        let tunnel = fetch_tunnel(&self.state, &project, name.trim()).await?;
+        caller
+            .require_tunnel(
+                &self.state,
+                Role::Operator,
+                &project,
+                &tunnel.server_node,
+                &tunnel.client_node,
+                &format!("delete tunnel '{}'", tunnel.name),
+            )
+            .await?;
+        let deleted = self
+            .state
+            .delete_tunnel(&project, name.trim(), &caller.actor)
+            .await
+            .map_err(|err| Status::internal(format!("failed to delete tunnel '{name}': {err}")))?;
+
+        if deleted {
+            Ok(Response::new(DeleteTunnelResponse {}))
+        } else {
+            Err(AppError::not_found("tunnel not found").into())
+        }
+    }
+
+    async fn rotate_tunnel_token(
+        &self,
+        request: Request<RotateTunnelTokenRequest>,
+    ) -> Result<Response<RotateTunnelTokenResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "rotate tunnel tokens").await?;
+        let RotateTunnelTokenRequest { project, name } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        let tunnel = fetch_tunnel(&self.state, &project, name.trim()).await?;
+        caller
+            .require_tunnel(
+                &self.state,
+                Role::Admin,
+                &project,
+                &tunnel.server_node,
+                &tunnel.client_node,
+                &format!("rotate the token of tunnel '{}'", tunnel.name),
+            )
+            .await?;
+        let rotated = self
+            .state
+            .rotate_tunnel_token(&project, name.trim(), &caller.actor)
+            .await
+            .map_err(|err| Status::internal(format!("failed to rotate tunnel '{name}': {err}")))?
+            .ok_or_else(|| AppError::not_found(format!("tunnel '{name}' not found")))?;
+
+        Ok(Response::new(RotateTunnelTokenResponse {
+            tunnel: Some(tunnel_to_proto(rotated)),
+        }))
+    }
+
+    async fn rotate_noise_key(
+        &self,
+        request: Request<RotateNoiseKeyRequest>,
+    ) -> Result<Response<RotateNoiseKeyResponse>, Status> {
+        let caller = Caller::of_grpc(&self.state, &request, "rotate Noise keys").await?;
+        let RotateNoiseKeyRequest {
+            project,
+            node: name,
+        } = request.into_inner();
+        let project = fetch_project(&self.state, &project).await?;
+        let node = fetch_optional_node(&self.state, &project, &name).await?;
+        caller
+            .require(
+                &self.state,
+                Role::Admin,
+                Scope::node(node.as_ref()),
+                &format!("rotate the Noise key of node '{name}'"),
+            )
+            .await?;
+        let key = self
+            .state
+            .rotate_noise_key(&project, &name, &caller.actor)
+            .await
+            .map_err(|err| {
+                Status::internal(format!("failed to rotate the Noise key of '{name}': {err}"))
+            })?
+            .ok_or_else(|| AppError::not_found(format!("node '{name}' not found")))?;
+
+        Ok(Response::new(RotateNoiseKeyResponse {
+            key: Some(noise_key_to_proto(key)),
+        }))
+    }
+}
+
+fn port_mapping_to_proto(
+    spec: &PortMappingSpec,
+) -> Result<ProtoPortMappingConfig, serde_json::Error> {
+    let mode = match spec.mode {
+        PortMappingMode::Server => ProtoPortMappingMode::Server,
+        PortMappingMode::Client => ProtoPortMappingMode::Client,
+    } as i32;
+
+    let config_json = serde_json::to_string(&spec.config)?;
+
+    Ok(ProtoPortMappingConfig {
+        mode,
+        config_json,
+        certificate: spec.certificate.clone(),
+    })
+}
+
+fn port_mapping_from_proto(config: ProtoPortMappingConfig) -> AppResult<PortMappingSpec> {
+    let mode = match ProtoPortMappingMode::try_from(config.mode) {
+        Ok(ProtoPortMappingMode::Server) => PortMappingMode::Server,
+        Ok(ProtoPortMappingMode::Client) => PortMappingMode::Client,
+        Ok(ProtoPortMappingMode::Unspecified) | Err(_) => {
+            return Err(AppError::bad_request("port mapping mode must be specified"))
+        }
+    };
+
+    let rathole = serde_json::from_str(&config.config_json).map_err(|err| {
+        AppError::bad_request(format!("invalid port mapping configuration: {err}"))
+    })?;
+
+    Ok(PortMappingSpec {
+        mode,
+        config: rathole,
+        certificate: config.certificate,
+    })
+}
+
+fn reverse_proxy_to_proto(spec: &ReverseProxySpec) -> ProtoReverseProxyConfig {
+    ProtoReverseProxyConfig {
+        routes: spec
+            .routes
+            .iter()
+            .map(|(hostname, upstream)| ProtoProxyRoute {
+                hostname: hostname.clone(),
+                upstream: upstream.clone(),
+            })
+            .collect(),
+        default_upstream: spec.default_upstream.clone(),
+        tls: spec.tls.as_ref().map(|tls| ProtoReverseProxyTls {
+            cert: tls.cert.clone(),
+            key: tls.key.clone(),
+        }),
+        certificate: spec.certificate.clone(),
+    }
+}
+
+fn certificate_to_proto(certificate: Certificate) -> ProtoTlsCertificate {
+    ProtoTlsCertificate {
+        name: certificate.name,
+        chain_pem: certificate.chain_pem,
+        key_pem: certificate.key_pem,
+        not_after: certificate.not_after.timestamp(),
+    }
+}
+
+fn reverse_proxy_from_proto(config: ProtoReverseProxyConfig) -> AppResult<ReverseProxySpec> {
+    let mut routes = BTreeMap::new();
+    for route in config.routes {
+        if routes
+            .insert(route.hostname.clone(), route.upstream)
+            .is_some()
+        {
+            return Err(AppError::bad_request(format!(
+                "reverse proxy route for '{}' is given more than once",
+                route.hostname
+            )));
+        }
+    }
+
+    Ok(ReverseProxySpec {
+        routes,
+        default_upstream: config.default_upstream,
+        tls: config.tls.map(|tls| ReverseProxyTls {
+            cert: tls.cert,
+            key: tls.key,
+        }),
+        certificate: config.certificate,
+    })
+}
+
+// Versions start at 1, so the default of 0 means the field was left out.
+fn expected(version: i64) -> AppResult<Precondition> {
+    if version > 0 {
+        Ok(Precondition::Version(version))
+    } else {
+        Err(AppError::precondition_required(
+            "expected_version must be the version of the node as last read",
+        ))
+    }
+}
+
+// The project named by a request, which must exist.
+async fn fetch_project(state: &ManagerState, project: &str) -> Result<String, Status> {
+    let project = project::or_default(project);
+    state
+        .get_project(project)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch project '{project}': {err}")))?
+        .map(|project| project.name)
+        .ok_or_else(|| Status::not_found(format!("project '{project}' not found")))
+}
+
+async fn fetch_node(state: &ManagerState, project: &str, name: &str) -> Result<NodeRecord, Status> {
+    state
+        .get(project, name)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch node '{name}': {err}")))?
+        .ok_or_else(|| Status::not_found(format!("node '{name}' not found")))
+}
+
+async fn fetch_optional_node(
+    state: &ManagerState,
+    project: &str,
+    name: &str,
+) -> Result<Option<NodeRecord>, Status> {
+    state
+        .get(project, name)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch node '{name}': {err}")))
+}
+
+async fn fetch_tunnel(state: &ManagerState, project: &str, name: &str) -> Result<Tunnel, Status> {
+    state
+        .get_tunnel(project, name)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch tunnel '{name}': {err}")))?
+        .ok_or_else(|| Status::not_found(format!("tunnel '{name}' not found")))
+}
+
+// Reading the revisions of a node needs read access to it; those of deleted
+// nodes need a grant that covers every node.
+async fn require_history(
+    state: &ManagerState,
+    caller: &Caller,
+    project: &str,
+    name: &str,
+) -> Result<(), Status> {
+    let node = fetch_optional_node(state, project, name).await?;
+    caller
+        .require(
+            state,
+            Role::Viewer,
+            Scope::node(node.as_ref()),
+            &format!("read the revisions of node '{name}'"),
+        )
+        .await?;
+    Ok(())
+}
+
+/// The certificates a node serves.
+struct NodeCertificates {
+    reverse_proxy: Option<Certificate>,
+    port_mapping: Option<Certificate>,
+}
+
+/// The node as it should run, with the certificates it serves.
+async fn fetch_node_config(
+    state: &ManagerState,
+    project: &str,
+    name: &str,
+) -> Result<(NodeRecord, NodeCertificates), Status> {
+    let failed =
+        |err: anyhow::Error| Status::internal(format!("failed to build config of '{name}': {err}"));
+    let record = state
+        .node_config(project, name)
+        .await
+        .map_err(failed)?
+        .ok_or_else(|| Status::not_found(format!("node '{name}' not found")))?;
+    let certificates = NodeCertificates {
+        reverse_proxy: state.node_certificate(&record).await.map_err(failed)?,
+        port_mapping: state
+            .port_mapping_certificate(&record)
+            .await
+            .map_err(failed)?,
+    };
+    let wanted = [
+        (
+            record
+                .reverse_proxy
+                .as_ref()
+                .and_then(|spec| spec.certificate.as_ref()),
+            &certificates.reverse_proxy,
+        ),
+        (
+            record
+                .port_mapping
+                .as_ref()
+                .and_then(|spec| spec.certificate.as_ref()),
+            &certificates.port_mapping,
+        ),
+    ];
+    for (wanted, certificate) in wanted {
+        if let (Some(missing), None) = (wanted, certificate) {
+            return Err(failed(anyhow::anyhow!("certificate '{missing}' not found")));
+        }
+    }
+    Ok((record, certificates))
+}
+
+async fn fetch_revision(
+    state: &ManagerState,
+    project: &str,
+    name: &str,
+    revision: i64,
+) -> Result<NodeRevision, Status> {
+    state
+        .get_revision(project, name, revision)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch revision of '{name}': {err}")))?
+        .ok_or_else(|| Status::not_found(format!("revision {revision} of node '{name}' not found")))
+}
+
+fn encode_port_mapping(record: &NodeRecord) -> Result<Option<ProtoPortMappingConfig>, Status> {
+    record
+        .port_mapping
+        .as_ref()
+        .map(port_mapping_to_proto)
+        .transpose()
+        .map_err(|err| Status::internal(format!("failed to serialize port mapping: {err}")))
+}
+
+fn node_config_revision(
+    revision: u64,
+    record: NodeRecord,
+    certificates: NodeCertificates,
+) -> Result<WatchNodeConfigResponse, Status> {
+    let port_mapping = encode_port_mapping(&record)?;
+    let reverse_proxy = record.reverse_proxy.as_ref().map(reverse_proxy_to_proto);
+
+    Ok(WatchNodeConfigResponse {
+        revision,
+        name: record.name,
+        port_mapping,
+        reverse_proxy,
+        certificate: certificates.reverse_proxy.map(certificate_to_proto),
+        port_mapping_certificate: certificates.port_mapping.map(certificate_to_proto),
+    })
+}
+
+fn node_to_proto(record: NodeRecord) -> Result<ProtoNode, Status> {
+    let port_mapping = encode_port_mapping(&record)?;
+    let reverse_proxy = record.reverse_proxy.as_ref().map(reverse_proxy_to_proto);
+
+    Ok(ProtoNode {
+        project: record.project,
+        name: record.name,
+        reverse_proxy_bind: record.reverse_proxy_bind,
+        port_mapping_role: record.port_mapping_role,
+        management_url: record.management_url,
+        description: record.description,
+        tags: record.tags,
+        port_mapping,
+        reverse_proxy,
+        variables: record
+            .variables
+            .into_iter()
+            .map(|(name, value)| ProtoNodeVariable { name, value })
+            .collect(),
+        status: Some(status_to_proto(record.status)),
+        version: record.version,
+    })
+}
+
+fn revision_to_proto(revision: NodeRevision) -> Result<ProtoNodeRevision, Status> {
+    Ok(ProtoNodeRevision {
+        revision: revision.revision,
+        action: revision.action.as_str().to_string(),
+        author: revision.author,
+        created_at: revision.created_at.timestamp(),
+        node: revision.node.map(node_to_proto).transpose()?,
+    })
+}
+
+fn field_change_to_proto(change: FieldChange) -> ProtoFieldChange {
+    ProtoFieldChange {
+        path: change.path,
+        from_json: change.from.map(|value| value.to_string()),
+        to_json: change.to.map(|value| value.to_string()),
+    }
+}
+
+fn tunnel_to_proto(tunnel: Tunnel) -> ProtoTunnel {
+    let r#type = match tunnel.service_type {
+        ServiceType::Tcp => ProtoTunnelType::Tcp,
+        ServiceType::Udp => ProtoTunnelType::Udp,
+    } as i32;
+
+    ProtoTunnel {
+        project: tunnel.project,
+        name: tunnel.name,
+        server_node: tunnel.server_node,
+        client_node: tunnel.client_node,
+        bind_addr: tunnel.bind_addr,
+        local_addr: tunnel.local_addr,
+        r#type,
+        created_at: tunnel.created_at.timestamp(),
+        rotate_every_secs: tunnel.rotate_every_secs,
+        token_rotated_at: tunnel.token_rotated_at.timestamp(),
+        previous_token_expires_at: tunnel
+            .previous_token_expires_at
+            .map(|expires_at| expires_at.timestamp()),
+    }
+}
+
+fn tunnel_from_proto(tunnel: ProtoTunnel) -> AppResult<CreateTunnel> {
+    let service_type = match ProtoTunnelType::try_from(tunnel.r#type) {
+        Ok(ProtoTunnelType::Tcp) | Ok(ProtoTunnelType::Unspecified) => ServiceType::Tcp,
+        Ok(ProtoTunnelType::Udp) => ServiceType::Udp,
+        Err(_) => return Err(AppError::bad_request("unknown tunnel type")),
+    };
+
+    Ok(CreateTunnel {
+        name: tunnel.name,
+        server_node: tunnel.server_node,
+        client_node: tunnel.client_node,
+        bind_addr: tunnel.bind_addr,
+        local_addr: tunnel.local_addr,
+        service_type,
+        rotate_every_secs: tunnel.rotate_every_secs,
+    })
+}
+
+fn noise_key_to_proto(key: NodeKey) -> ProtoNoiseKey {
+    ProtoNoiseKey {
+        node: key.node,
+        public_key: key.public_key,
+        created_at: key.created_at.timestamp(),
+        previous_expires_at: key
+            .previous_expires_at
+            .map(|expires_at| expires_at.timestamp()),
+    }
+}
+
+fn status_to_proto(status: NodeStatus) -> ProtoNodeStatus {
+    ProtoNodeStatus {
+        online: status.state == NodeState::Online,
+        last_seen: status.last_seen.map(|last_seen| last_seen.timestamp()),
+        version: status.version,
+        uptime_seconds: status.uptime_seconds,
+        listen_addrs: status.listen_addrs,
+        services: status
+            .services
+            .into_iter()
+            .map(|(name, state)| {
+                let state = match state {
+                    ServiceState::Connecting => ProtoControlChannelState::Connecting,
+                    ServiceState::Connected => ProtoControlChannelState::Connected,
+                    ServiceState::Disconnected => ProtoControlChannelState::Disconnected,
+                } as i32;
+                ProtoServiceStatus { name, state }
+            })
+            .collect(),
+    }
+}
+
+fn services_from_proto(
+    services: Vec<ProtoServiceStatus>,
+) -> AppResult<BTreeMap<String, ServiceState>> {
+    services
+        .into_iter()
+        .map(|service| {
+            let state = match ProtoControlChannelState::try_from(service.state) {
+                Ok(ProtoControlChannelState::Connecting) => ServiceState::Connecting,
+                Ok(ProtoControlChannelState::Connected) => ServiceState::Connected,
+                Ok(ProtoControlChannelState::Disconnected) => ServiceState::Disconnected,
+                Ok(ProtoControlChannelState::Unspecified) | Err(_) => {
+                    return Err(AppError::bad_request(format!(
+                        "control channel state of service '{}' must be specified",
+                        service.name
+                    )))
+                }
+            };
+            Ok((service.name, state))
+        })
+        .collect()
+}
+
+fn node_from_proto(node: ProtoNode) -> AppResult<NodeRecord> {
+    let port_mapping = node.port_mapping.map(port_mapping_from_proto).transpose()?;
+    let reverse_proxy = node
+        .reverse_proxy
+        .map(reverse_proxy_from_proto)
+        .transpose()?;
+    let mut variables = BTreeMap::new();
+    for variable in node.variables {
+        if variables.contains_key(&variable.name) {
+            return Err(AppError::bad_request(format!(
+                "variable '{}' is set more than once",
+                variable.name
+            )));
+        }
+        variables.insert(variable.name, variable.value);
+    }
+
+    // The project is taken from the request, not from the node.
+    Ok(NodeRecord {
+        project: String::new(),
+        name: node.name,
+        reverse_proxy_bind: node.reverse_proxy_bind,
+        port_mapping_role: node.port_mapping_role,
+        management_url: node.management_url,
+        description: node.description,
+        tags: node.tags,
+        port_mapping,
+        reverse_proxy,
+        variables,
+        managed_by: ManagedBy::Ui,
+        version: 0,
+        status: NodeStatus::default(),
+    })
+}
@@ -0,0 +1,255 @@
+//! Export of every node of a project, and import of an exported inventory
+//! into this or another project or manager. Tunnels, certificates,
+//! templates, Noise keys and API tokens are not included; imported nodes
+//! are bound to the templates of the project they are imported into.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use utoipa::ToSchema;
+
+use crate::audit::Actor;
+use crate::certificate;
+use crate::config::{ManagedBy, ManagerConfig, ManagerState, NodeRecord, Precondition};
+use crate::error::{AppError, AppResult};
+use crate::revision::snapshot;
+use crate::template;
+use crate::tunnel;
+use crate::validate::{self, FieldError};
+
+/// Both formats have the shape of `manager.toml`.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InventoryFormat {
+    #[default]
+    Json,
+    Toml,
+}
+
+impl InventoryFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            InventoryFormat::Json => "application/json",
+            InventoryFormat::Toml => "application/toml",
+        }
+    }
+
+    /// The format of a request body: TOML if declared as such, else JSON.
+    pub fn of_body(content_type: &str) -> Self {
+        if content_type.eq_ignore_ascii_case(InventoryFormat::Toml.content_type()) {
+            InventoryFormat::Toml
+        } else {
+            InventoryFormat::Json
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportStrategy {
+    /// Create new nodes and overwrite existing ones; other nodes are kept.
+    #[default]
+    Merge,
+    /// Make the imported nodes the whole inventory, deleting the others.
+    Replace,
+    /// Only create new nodes; existing ones are left as they are.
+    SkipExisting,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+    Created,
+    Updated,
+    Unchanged,
+    /// The node exists and the strategy is `skip-existing`.
+    Skipped,
+    /// Not among the imported nodes, with the strategy `replace`.
+    Deleted,
+    Invalid,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct NodeImport {
+    pub name: String,
+    pub outcome: ImportOutcome,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ImportReport {
+    pub strategy: ImportStrategy,
+    /// Nothing is imported if any node is invalid; only the invalid nodes are
+    /// listed then.
+    pub applied: bool,
+    pub nodes: Vec<NodeImport>,
+}
+
+/// A write of an import, applied by [`ManagerState::import`].
+#[derive(Debug, Clone)]
+pub enum ImportStep {
+    Store(NodeRecord, Precondition),
+    Remove(String),
+}
+
+/// Every node of `project` in the shape of `manager.toml`, without the
+/// status, version, owner and project of the nodes and without unset fields.
+pub async fn export(state: &ManagerState, project: &str) -> Result<Value> {
+    let mut nodes = Map::new();
+    for node in state.list(project).await? {
+        let mut value = snapshot(&node)?;
+        strip_nulls(&mut value);
+        nodes.insert(node.name, value);
+    }
+    Ok(json!({ "nodes": nodes }))
+}
+
+pub fn render(export: &Value, format: InventoryFormat) -> Result<String> {
+    Ok(match format {
+        InventoryFormat::Json => serde_json::to_string_pretty(export)?,
+        InventoryFormat::Toml => toml::to_string(export)?,
+    })
+}
+
+// TOML has no null, and a missing field reads back as unset anyway.
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, field| !field.is_null());
+            fields.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// The nodes of an exported inventory.
+pub fn parse(body: &[u8], format: InventoryFormat) -> AppResult<Vec<NodeRecord>> {
+    let config: ManagerConfig = match format {
+        InventoryFormat::Json => serde_json::from_slice(body).map_err(malformed)?,
+        InventoryFormat::Toml => {
+            let body = std::str::from_utf8(body).map_err(malformed)?;
+            toml::from_str(body).map_err(malformed)?
+        }
+    };
+    Ok(config.nodes.into_values().collect())
+}
+
+fn malformed(err: impl Display) -> AppError {
+    AppError::bad_request(format!("invalid inventory: {err}"))
+}
+
+/// Validates every node imported into `project` and, if all are valid,
+/// applies them with `strategy` in one transaction.
+pub async fn import(
+    state: &ManagerState,
+    project: &str,
+    nodes: Vec<NodeRecord>,
+    strategy: ImportStrategy,
+    actor: &Actor,
+) -> AppResult<ImportReport> {
+    let stored: HashMap<String, ManagedBy> = state
+        .list(project)
+        .await?
+        .into_iter()
+        .map(|node| (node.name, node.managed_by))
+        .collect();
+
+    let mut invalid = BTreeMap::new();
+    let mut skipped = Vec::new();
+    let mut imported = BTreeSet::new();
+    let mut steps = Vec::new();
+    for mut node in nodes {
+        node.project = project.to_string();
+        node.name = node.name.trim().to_string();
+        let mut errors = Vec::new();
+        if node.name.is_empty() {
+            errors.push(FieldError::new("name", "cannot be empty"));
+        }
+        if !imported.insert(node.name.clone()) {
+            errors.push(FieldError::new("name", "imported more than once"));
+        }
+        errors.extend(template::bind(state, &mut node).await?);
+        errors.extend(validate::node_errors(&node));
+        errors.extend(tunnel::node_errors(state, &node).await?);
+        errors.extend(certificate::node_errors(state, &node).await?);
+        if !errors.is_empty() {
+            invalid.insert(node.name, errors);
+            continue;
+        }
+
+        let existing = stored.get(&node.name).copied();
+        match (strategy, existing) {
+            (ImportStrategy::SkipExisting, Some(_)) => skipped.push(node.name),
+            (ImportStrategy::SkipExisting, None) => {
+                steps.push(ImportStep::Store(node, Precondition::Absent));
+            }
+            (_, existing) => {
+                // Imported nodes keep their owner, so that `apply --prune`
+                // still only deletes the nodes of the configuration file.
+                node.managed_by = existing.unwrap_or_default();
+                steps.push(ImportStep::Store(node, Precondition::None));
+            }
+        }
+    }
+
+    if strategy == ImportStrategy::Replace {
+        for name in stored.keys().filter(|name| !imported.contains(*name)) {
+            let tunnels = state.tunnels_of(project, name).await?;
+            if tunnels.is_empty() {
+                steps.push(ImportStep::Remove(name.clone()));
+                continue;
+            }
+            let names: Vec<&str> = tunnels.iter().map(|tunnel| tunnel.name.as_str()).collect();
+            invalid.insert(
+                name.clone(),
+                vec![FieldError::new(
+                    "name",
+                    format!(
+                        "not imported, but used by tunnels {}; delete them first",
+                        names.join(", ")
+                    ),
+                )],
+            );
+        }
+    }
+
+    if !invalid.is_empty() {
+        return Ok(ImportReport {
+            strategy,
+            applied: false,
+            nodes: invalid
+                .into_iter()
+                .map(|(name, errors)| NodeImport {
+                    name,
+                    outcome: ImportOutcome::Invalid,
+                    errors,
+                })
+                .collect(),
+        });
+    }
+
+    let Some(applied) = state.import(project, steps, actor).await? else {
+        return Err(AppError::conflict(
+            "nodes changed during the import and nothing was imported; try again",
+        ));
+    };
+    let mut nodes: Vec<NodeImport> = applied
+        .into_iter()
+        .chain(skipped.into_iter().map(|name| NodeImport {
+            name,
+            outcome: ImportOutcome::Skipped,
+            errors: Vec::new(),
+        }))
+        .collect();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(ImportReport {
+        strategy,
+        applied: true,
+        nodes,
+    })
+}
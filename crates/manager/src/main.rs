@@ -1,63 +1,91 @@
+mod audit;
+mod auth;
+mod certificate;
 mod config;
 mod entity;
 mod error;
+mod events;
+#[cfg(test)]
+mod fixture;
+mod grpc;
+mod inventory;
+mod metrics;
+mod migration;
+mod openapi;
+mod patch;
+mod project;
+mod query;
+mod rbac;
+mod reconcile;
+mod render;
+mod revision;
+mod secrets;
+mod template;
+mod tunnel;
+mod validate;
 
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpResponse, HttpServer};
-use anyhow::{Error, Result};
-use clap::Parser;
-use config::{ManagerState, NodeRecord};
-use error::{AppError, AppResult};
-use laval_model::{PortMappingMode, PortMappingSpec};
-use laval_proto::manager::v1::{
-    node_manager_server::{NodeManager, NodeManagerServer},
-    GetNodeConfigRequest, GetNodeConfigResponse, PortMappingConfig as ProtoPortMappingConfig,
-    PortMappingMode as ProtoPortMappingMode,
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ContentDisposition, ETag, EntityTag, IfMatch, IfNoneMatch,
+    WARNING,
 };
-use tonic::{async_trait, transport::Server, Request, Response, Status};
+use actix_web::http::Method;
+use actix_web::middleware::from_fn;
+use actix_web::{
+    web, App, FromRequest, Handler, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder,
+    Route,
+};
+use anyhow::{Context, Error, Result};
+use audit::{AuditEvent, AuditQuery};
+use auth::{ApiToken, GrpcAuth, IssuedToken};
+use certificate::{Certificate, ReplaceCertificate, UploadCertificate};
+use clap::{Parser, Subcommand};
+use config::{ManagerConfig, ManagerState, NodeRecord, Precondition, WriteOutcome};
+use error::{written, AppError, AppResult, ErrorBody};
+use grpc::GrpcService;
+use http::HeaderValue;
+use inventory::{ImportReport, ImportStrategy, InventoryFormat};
+use laval_proto::manager::v1::node_manager_server::NodeManagerServer;
+use metrics::GrpcMetricsLayer;
+use project::{CreateProject, Project, ProjectRemoval, DEFAULT_PROJECT};
+use query::{Cursor, ModeFilter, NodePage, NodeQuery, NodeSort};
+use rbac::{Caller, Grant, Role, Scope};
+use reconcile::Plan;
+use render::RenderTarget;
+use revision::{FieldChange, NodeRevision};
+use secrets::NodeKey;
+use serde::Deserialize;
+use serde_json::Value;
+use template::{CreateTemplate, Template, TemplateNode, TemplateReport, TemplateSpec};
+use tonic::transport::Server;
 use tonic_web::GrpcWebLayer;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::{info, Level};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
+use tunnel::{CreateTunnel, Tunnel};
+use utoipa::{IntoParams, ToSchema};
 
 type SharedState = Arc<ManagerState>;
 
-#[derive(Clone)]
-struct GrpcService {
-    state: SharedState,
-}
-
-#[async_trait]
-impl NodeManager for GrpcService {
-    async fn get_node_config(
-        &self,
-        request: Request<GetNodeConfigRequest>,
-    ) -> Result<Response<GetNodeConfigResponse>, Status> {
-        let name = request.into_inner().name;
-        let record = self
-            .state
-            .get(&name)
-            .await
-            .map_err(|err| Status::internal(format!("failed to fetch node '{name}': {err}")))?
-            .ok_or_else(|| Status::not_found(format!("node '{name}' not found")))?;
-
-        let port_mapping = match record.port_mapping.as_ref() {
-            Some(spec) => Some(port_mapping_to_proto(spec).map_err(|err| {
-                Status::internal(format!("failed to serialize port mapping: {err}"))
-            })?),
-            None => None,
-        };
+/// Largest inventory accepted by `POST /projects/{project}/import`.
+const MAX_IMPORT_SIZE: usize = 16 * 1024 * 1024;
 
-        Ok(Response::new(GetNodeConfigResponse {
-            name: record.name,
-            port_mapping,
-        }))
-    }
-}
+/// How often tunnel tokens and Noise keys are checked for scheduled rotation.
+const SECRET_ROTATION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often nodes are checked for having gone offline, to announce it on
+/// the event feed.
+const OFFLINE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the database is checked for nodes changed by other processes,
+/// such as `apply`, to push them to connected nodes, and for API tokens they
+/// issued or revoked.
+const EXTERNAL_CHANGE_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Laval node management service", long_about = None)]
@@ -74,6 +102,80 @@ struct Cli {
     /// Database connection string
     #[arg(long, env = "DATABASE_URL")]
     database_url: String,
+    /// Seconds without a heartbeat after which a node is reported offline
+    #[arg(long, default_value_t = 60)]
+    node_offline_after: u64,
+    /// Seconds a Rathole server keeps accepting a tunnel token or Noise key
+    /// after it was rotated
+    #[arg(long, default_value_t = 600)]
+    token_overlap: u64,
+    /// Seconds after which managed Noise keys are rotated; never if unset
+    #[arg(long)]
+    noise_key_rotate_every: Option<u64>,
+    /// API token with full access, stored (hashed) as the `admin` token
+    #[arg(long, env = "LAVAL_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Origins allowed to call the APIs from a browser; none if empty
+    #[arg(long, env = "LAVAL_ALLOWED_ORIGINS", value_delimiter = ',')]
+    allowed_origins: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the database schema without starting the APIs
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Reconcile the stored nodes with the configuration file
+    ///
+    /// Changes are written to the database directly; running managers find
+    /// them within a few seconds and push them to connected nodes.
+    Apply {
+        /// Print the plan without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete file-managed nodes that the file no longer declares
+        #[arg(long)]
+        prune: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Stop after applying this version
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// Revert the most recently applied migrations
+    Down {
+        /// Number of migrations to revert
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// List all migrations and whether they are applied
+    Status,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateTokenRequest {
+    name: String,
+    /// Restrict the token to this node's configuration and status.
+    #[serde(default)]
+    node: Option<String>,
+    /// Project of `node`; the default project if not set.
+    #[serde(default)]
+    project: Option<String>,
+    /// Role of a user token; `viewer` if not set.
+    #[serde(default)]
+    role: Option<Role>,
+    /// Limit a user token to nodes carrying at least one of these tags.
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 #[tokio::main]
@@ -90,29 +192,104 @@ async fn main() -> Result<()> {
         bind,
         grpc_bind,
         database_url,
+        node_offline_after,
+        token_overlap,
+        noise_key_rotate_every,
+        admin_token,
+        allowed_origins,
+        command,
     } = cli;
 
-    let state = Arc::new(ManagerState::initialize(config, database_url).await?);
+    let offline_after = Duration::from_secs(node_offline_after);
+    let token_overlap = Duration::from_secs(token_overlap);
+    match command {
+        Some(Command::Migrate { action }) => return migrate(&database_url, action).await,
+        Some(Command::Apply { dry_run, prune }) => {
+            return apply(
+                &config,
+                &database_url,
+                offline_after,
+                token_overlap,
+                dry_run,
+                prune,
+            )
+            .await
+        }
+        None => {}
+    }
+
+    let state = Arc::new(
+        ManagerState::initialize(
+            config,
+            database_url,
+            offline_after,
+            token_overlap,
+            admin_token,
+        )
+        .await?
+        .with_noise_key_rotation(noise_key_rotate_every.map(Duration::from_secs)),
+    );
+    if !state.has_admin_token() {
+        warn!("no admin API token exists; set --admin-token to manage nodes");
+    }
+
+    let rotation_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SECRET_ROTATION_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = rotation_state.rotate_secrets().await {
+                warn!(error = %err, "failed to rotate managed secrets");
+            }
+        }
+    });
+
+    let offline_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(OFFLINE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = offline_state.announce_offline().await {
+                warn!(error = %err, "failed to announce offline nodes");
+            }
+        }
+    });
+
+    let external_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXTERNAL_CHANGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = external_state.announce_external().await {
+                warn!(error = %err, "failed to look for nodes changed by other processes");
+            }
+            if let Err(err) = external_state.reload_tokens().await {
+                warn!(error = %err, "failed to reload API tokens");
+            }
+        }
+    });
+
+    let grpc_origins = AllowOrigin::list(
+        allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid allowed origin")?,
+    );
 
     let http_state = state.clone();
     let http_server = HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header();
+        // Without an allowed origin, browsers are refused cross-origin access.
+        let mut cors = Cors::default().allow_any_method().allow_any_header();
+        for origin in &allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
 
         App::new()
             .wrap(cors)
+            .wrap(from_fn(metrics::track_http))
             .app_data(web::Data::new(http_state.clone()))
-            .route("/health", web::get().to(health))
-            .service(
-                web::scope("/nodes")
-                    .route("", web::get().to(list_nodes))
-                    .route("", web::post().to(create_node))
-                    .route("/{name}", web::get().to(get_node))
-                    .route("/{name}", web::put().to(update_node))
-                    .route("/{name}", web::delete().to(delete_node)),
-            )
+            .configure(routes)
     })
     .bind(bind)?
     .run();
@@ -122,7 +299,7 @@ async fn main() -> Result<()> {
     let grpc_server = async move {
         info!(bind = %grpc_bind, "starting manager gRPC API");
         let cors = CorsLayer::new()
-            .allow_origin(Any)
+            .allow_origin(grpc_origins)
             .allow_methods(vec![http::Method::GET, http::Method::POST, http::Method::OPTIONS])
             .allow_headers(Any);
 
@@ -130,9 +307,11 @@ async fn main() -> Result<()> {
             .accept_http1(true)
             .layer(cors)
             .layer(GrpcWebLayer::new())
-            .add_service(NodeManagerServer::new(GrpcService {
-                state: grpc_state,
-            }))
+            .layer(GrpcMetricsLayer::new(grpc_state.metrics().clone()))
+            .add_service(NodeManagerServer::with_interceptor(
+                GrpcService::new(grpc_state.clone()),
+                GrpcAuth::new(grpc_state),
+            ))
             .serve(grpc_bind)
             .await?;
         Ok::<(), Error>(())
@@ -149,85 +328,2643 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn port_mapping_to_proto(
-    spec: &PortMappingSpec,
-) -> Result<ProtoPortMappingConfig, serde_json::Error> {
-    let mode = match spec.mode {
-        PortMappingMode::Server => ProtoPortMappingMode::Server,
-        PortMappingMode::Client => ProtoPortMappingMode::Client,
-    } as i32;
+async fn migrate(database_url: &str, action: MigrateAction) -> Result<()> {
+    let db = config::connect(database_url).await?;
+    match action {
+        MigrateAction::Up { to } => {
+            let applied = migration::up(&db, to).await?;
+            println!("applied {} migration(s)", applied.len());
+        }
+        MigrateAction::Down { steps } => {
+            let reverted = migration::down(&db, steps).await?;
+            println!("reverted {} migration(s)", reverted.len());
+        }
+        MigrateAction::Status => {
+            for entry in migration::status(&db).await? {
+                let state = match entry.applied_at {
+                    Some(applied_at) => format!("applied {}", applied_at.to_rfc3339()),
+                    None => "pending".to_string(),
+                };
+                println!("{:04}_{:<24} {state}", entry.version, entry.name);
+            }
+        }
+    }
+    Ok(())
+}
 
-    let config_json = serde_json::to_string(&spec.config)?;
+async fn apply(
+    config: &Path,
+    database_url: &str,
+    offline_after: Duration,
+    token_overlap: Duration,
+    dry_run: bool,
+    prune: bool,
+) -> Result<()> {
+    let declared = ManagerConfig::load(config).await?;
+    let state = ManagerState::open(database_url, offline_after, token_overlap, None).await?;
+    let plan = reconcile::plan(&state, declared.nodes.into_values().collect())
+        .await
+        .with_context(|| format!("invalid nodes in {}", config.display()))?;
+
+    print_plan(&plan, prune);
+    if dry_run || plan.is_empty(prune) {
+        return Ok(());
+    }
+    reconcile::apply(&state, plan, prune).await?;
+    println!("applied {}", config.display());
+    Ok(())
+}
+
+fn print_plan(plan: &Plan, prune: bool) {
+    let value = |value: &Option<serde_json::Value>| match value {
+        Some(value) => value.to_string(),
+        None => "(unset)".to_string(),
+    };
 
-    Ok(ProtoPortMappingConfig { mode, config_json })
+    for node in &plan.create {
+        println!("+ {}", node.name);
+    }
+    for update in &plan.update {
+        if update.adopted {
+            println!("~ {} (taken over from the UI)", update.node.name);
+        } else {
+            println!("~ {}", update.node.name);
+        }
+        for change in &update.changes {
+            println!(
+                "    {}: {} -> {}",
+                change.path,
+                value(&change.from),
+                value(&change.to)
+            );
+        }
+    }
+    for name in &plan.delete {
+        if prune {
+            println!("- {name}");
+        } else {
+            println!("  {name} is no longer declared; pass --prune to delete it");
+        }
+    }
+    for name in &plan.protected {
+        println!("  {name} is managed from the UI and is kept");
+    }
+    for name in &plan.in_use {
+        println!("  {name} is no longer declared but is used by tunnels and is kept");
+    }
+    if plan.is_empty(prune) {
+        println!("no changes");
+    }
 }
 
+/// Every route of the HTTP API, as listed by [`scopes`].
+fn routes(cfg: &mut web::ServiceConfig) {
+    for scope in scopes() {
+        let ApiScope {
+            path,
+            access,
+            max_payload,
+            endpoints,
+        } = scope;
+        let mut service = web::scope(path).configure(|cfg| {
+            for Endpoint {
+                method,
+                path,
+                route,
+            } in endpoints
+            {
+                cfg.route(path, route.method(method));
+            }
+        });
+        if let Some(limit) = max_payload {
+            service = service.app_data(web::PayloadConfig::new(limit));
+        }
+        match access {
+            Access::Public => cfg.service(service),
+            Access::User => cfg.service(service.wrap(from_fn(auth::require_user_http))),
+            Access::Project => cfg.service(
+                service
+                    .wrap(from_fn(project::require_project))
+                    .wrap(from_fn(auth::require_user_http)),
+            ),
+            Access::DefaultProject => cfg.service(
+                service
+                    .wrap(from_fn(project::default_project))
+                    .wrap(from_fn(auth::require_user_http)),
+            ),
+        };
+    }
+}
+
+/// A route of the HTTP API, relative to its [`ApiScope`].
+struct Endpoint {
+    method: Method,
+    path: &'static str,
+    /// Serves any method; [`routes`] restricts it to `method`.
+    route: Route,
+}
+
+fn endpoint<F, Args>(method: Method, path: &'static str, handler: F) -> Endpoint
+where
+    F: Handler<Args>,
+    Args: FromRequest + 'static,
+    F::Output: Responder + 'static,
+{
+    Endpoint {
+        method,
+        path,
+        route: web::route().to(handler),
+    }
+}
+
+/// Who may call the routes of a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Public,
+    /// Any user token.
+    User,
+    /// A user token, on a project that exists.
+    Project,
+    /// A user token, on the default project. These scopes were mounted
+    /// before projects existed and are left out of the OpenAPI document,
+    /// which has their routes under `/projects/{project}`.
+    DefaultProject,
+}
+
+/// A scope of the HTTP API and its routes. Listing them rather than
+/// registering them directly lets the OpenAPI test check that every route
+/// is documented.
+struct ApiScope {
+    path: &'static str,
+    access: Access,
+    max_payload: Option<usize>,
+    endpoints: Vec<Endpoint>,
+}
+
+/// Every scope of the HTTP API, in the order they are matched: those under
+/// `/projects/{project}` come before `/projects`, which would otherwise
+/// take their requests.
+fn scopes() -> Vec<ApiScope> {
+    let scope = |path, access, endpoints| ApiScope {
+        path,
+        access,
+        max_payload: None,
+        endpoints,
+    };
+    let import = |path, access| ApiScope {
+        max_payload: Some(MAX_IMPORT_SIZE),
+        ..scope(path, access, vec![endpoint(Method::POST, "", import_nodes)])
+    };
+    let export = || vec![endpoint(Method::GET, "", export_nodes)];
+    vec![
+        scope(
+            "/health",
+            Access::Public,
+            vec![endpoint(Method::GET, "", health)],
+        ),
+        scope(
+            "/metrics",
+            Access::Public,
+            vec![endpoint(Method::GET, "", render_metrics)],
+        ),
+        scope(
+            "/openapi.json",
+            Access::Public,
+            vec![endpoint(Method::GET, "", openapi::serve)],
+        ),
+        scope("/projects/{project}/nodes", Access::Project, node_routes()),
+        scope("/projects/{project}/export", Access::Project, export()),
+        import("/projects/{project}/import", Access::Project),
+        scope(
+            "/projects/{project}/tunnels",
+            Access::Project,
+            tunnel_routes(),
+        ),
+        scope(
+            "/projects/{project}/certificates",
+            Access::Project,
+            vec![
+                endpoint(Method::GET, "", list_certificates),
+                endpoint(Method::POST, "", upload_certificate),
+                endpoint(Method::GET, "/{name}", get_certificate),
+                endpoint(Method::PUT, "/{name}", replace_certificate),
+                endpoint(Method::DELETE, "/{name}", delete_certificate),
+            ],
+        ),
+        scope(
+            "/projects/{project}/templates",
+            Access::Project,
+            vec![
+                endpoint(Method::GET, "", list_templates),
+                endpoint(Method::POST, "", create_template),
+                endpoint(Method::GET, "/{name}", get_template),
+                endpoint(Method::PUT, "/{name}", update_template),
+                endpoint(Method::DELETE, "/{name}", delete_template),
+                endpoint(Method::GET, "/{name}/nodes", template_nodes),
+                endpoint(Method::POST, "/{name}/apply", apply_template),
+            ],
+        ),
+        scope(
+            "/projects",
+            Access::User,
+            vec![
+                endpoint(Method::GET, "", list_projects),
+                endpoint(Method::POST, "", create_project),
+                endpoint(Method::GET, "/{project}", get_project),
+                endpoint(Method::DELETE, "/{project}", delete_project),
+            ],
+        ),
+        scope("/nodes", Access::DefaultProject, node_routes()),
+        scope("/export", Access::DefaultProject, export()),
+        import("/import", Access::DefaultProject),
+        scope("/tunnels", Access::DefaultProject, tunnel_routes()),
+        scope(
+            "/events",
+            Access::User,
+            vec![endpoint(Method::GET, "", stream_events)],
+        ),
+        scope(
+            "/audit",
+            Access::User,
+            vec![endpoint(Method::GET, "", list_audit_events)],
+        ),
+        scope(
+            "/tokens",
+            Access::User,
+            vec![
+                endpoint(Method::GET, "", list_tokens),
+                endpoint(Method::POST, "", create_token),
+                endpoint(Method::DELETE, "/{name}", delete_token),
+            ],
+        ),
+    ]
+}
+
+/// The routes under `/projects/{project}/tunnels`, also mounted at
+/// `/tunnels` for the default project.
+fn tunnel_routes() -> Vec<Endpoint> {
+    vec![
+        endpoint(Method::GET, "", list_tunnels),
+        endpoint(Method::POST, "", create_tunnel),
+        endpoint(Method::GET, "/{name}", get_tunnel),
+        endpoint(Method::DELETE, "/{name}", delete_tunnel),
+        endpoint(Method::POST, "/{name}/rotate", rotate_tunnel_token),
+    ]
+}
+
+/// The routes under `/projects/{project}/nodes`, also mounted at `/nodes`
+/// for the default project as they were before projects.
+fn node_routes() -> Vec<Endpoint> {
+    vec![
+        endpoint(Method::GET, "", list_nodes),
+        endpoint(Method::POST, "", create_node),
+        endpoint(Method::GET, "/{name}", get_node),
+        endpoint(Method::PUT, "/{name}", update_node),
+        endpoint(Method::PATCH, "/{name}", patch_node),
+        endpoint(Method::DELETE, "/{name}", delete_node),
+        endpoint(Method::GET, "/{name}/revisions", list_revisions),
+        endpoint(Method::GET, "/{name}/revisions/{revision}", get_revision),
+        endpoint(
+            Method::POST,
+            "/{name}/revisions/{revision}/rollback",
+            rollback_node,
+        ),
+        endpoint(Method::GET, "/{name}/diff", diff_revisions),
+        endpoint(Method::GET, "/{name}/noise-key", get_noise_key),
+        endpoint(Method::POST, "/{name}/noise-key/rotate", rotate_noise_key),
+        endpoint(Method::GET, "/{name}/render", render_node),
+    ]
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "The manager is up", body = String, content_type = "text/plain"),
+    ),
+)]
 async fn health() -> HttpResponse {
     HttpResponse::Ok().body("ok")
 }
 
-async fn list_nodes(state: web::Data<SharedState>) -> AppResult<web::Json<Vec<NodeRecord>>> {
-    let nodes = state.list().await.map_err(AppError::from)?;
-    Ok(web::Json(nodes))
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain"),
+    ),
+)]
+async fn render_metrics(state: web::Data<SharedState>) -> AppResult<HttpResponse> {
+    let body = state.render_metrics().await.map_err(AppError::from)?;
+    Ok(HttpResponse::Ok()
+        .content_type(metrics::TEXT_FORMAT)
+        .body(body))
 }
 
-async fn get_node(
-    name: web::Path<String>,
+#[derive(Debug, Deserialize)]
+struct ProjectPath {
+    project: String,
+}
+
+/// A node, tunnel, certificate or template of a project.
+#[derive(Debug, Deserialize)]
+struct NamedPath {
+    project: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RevisionPath {
+    project: String,
+    name: String,
+    revision: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects",
+    tag = "projects",
+    responses(
+        (status = 200, description = "Every project", body = Vec<Project>),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_projects(
     state: web::Data<SharedState>,
-) -> AppResult<web::Json<NodeRecord>> {
-    let name = name.into_inner();
-    match state.get(&name).await.map_err(AppError::from)? {
-        Some(node) => Ok(web::Json(node)),
-        None => Err(AppError::not_found(format!("node '{name}' not found"))),
+    caller: Caller,
+) -> AppResult<web::Json<Vec<Project>>> {
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "list projects")
+        .await?;
+    let projects = state.list_projects().await.map_err(AppError::from)?;
+    Ok(web::Json(projects))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    responses(
+        (status = 200, description = "The project", body = Project),
+        (status = 404, description = "No such project", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_project(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Project>> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::Listing,
+            &format!("read project '{project}'"),
+        )
+        .await?;
+    match state.get_project(&project).await.map_err(AppError::from)? {
+        Some(project) => Ok(web::Json(project)),
+        None => Err(AppError::not_found(format!(
+            "project '{project}' not found"
+        ))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects",
+    tag = "projects",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "The project was created", body = Project),
+        (status = 400, description = "Invalid project name", body = ErrorBody),
+        (status = 409, description = "A project of this name exists", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn create_project(
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<CreateProject>,
+) -> AppResult<HttpResponse> {
+    caller
+        .require(&state, Role::Admin, Scope::All, "create projects")
+        .await?;
+    let mut payload = payload.into_inner();
+    payload.name = payload.name.trim().to_string();
+    project::check_name(&payload.name)?;
+    let name = payload.name.clone();
+    let created = state
+        .create_project(payload, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    match created {
+        Some(project) => Ok(HttpResponse::Created().json(project)),
+        None => Err(AppError::conflict(format!(
+            "project '{name}' already exists"
+        ))),
     }
 }
 
+/// Deletes a project that has no nodes left, along with any API tokens of
+/// its nodes.
+#[utoipa::path(
+    delete,
+    path = "/projects/{project}",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    responses(
+        (status = 204, description = "The project was deleted"),
+        (status = 404, description = "No such project", body = ErrorBody),
+        (status = 409, description = "The project is the default project or still has nodes", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn delete_project(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("delete project '{project}'"),
+        )
+        .await?;
+    if project == DEFAULT_PROJECT {
+        return Err(AppError::conflict("the default project cannot be deleted"));
+    }
+    match state
+        .delete_project(&project, &caller.actor)
+        .await
+        .map_err(AppError::from)?
+    {
+        ProjectRemoval::Deleted => Ok(HttpResponse::NoContent().finish()),
+        ProjectRemoval::Missing => Err(AppError::not_found(format!(
+            "project '{project}' not found"
+        ))),
+        ProjectRemoval::InUse(nodes) => Err(AppError::conflict(format!(
+            "project '{project}' still has {nodes} nodes; delete them first"
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListNodesQuery {
+    /// Comma separated tags, all of which a node must carry.
+    tag: Option<String>,
+    /// `server`, `client`, or `none` for nodes without a port mapping.
+    mode: Option<ModeFilter>,
+    role: Option<String>,
+    /// Searched for in node names and descriptions.
+    q: Option<String>,
+    /// `name`, `role` or `managed_by`, prefixed with `-` for descending order.
+    sort: Option<String>,
+    #[serde(default)]
+    limit: u64,
+    cursor: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ListNodesQuery,
+    ),
+    responses(
+        (status = 200, description = "A page of nodes", body = NodePage),
+        (status = 400, description = "Malformed filter, sort or cursor", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_nodes(
+    path: web::Path<ProjectPath>,
+    query: web::Query<ListNodesQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<NodePage>> {
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "list nodes")
+        .await?;
+    let ListNodesQuery {
+        tag,
+        mode,
+        role,
+        q,
+        sort,
+        limit,
+        cursor,
+    } = query.into_inner();
+    let sort = match sort {
+        Some(sort) => NodeSort::parse(&sort)?,
+        None => NodeSort::default(),
+    };
+    let query = NodeQuery {
+        project: path.into_inner().project,
+        tags: tag
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        any_tags: caller.grant.tags.clone(),
+        mode,
+        role,
+        search: q,
+        sort,
+        limit,
+        cursor: cursor
+            .map(|cursor| Cursor::decode(&cursor, sort))
+            .transpose()?,
+    };
+
+    let page = state.query(&query).await.map_err(AppError::from)?;
+    Ok(web::Json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+    ),
+    responses(
+        (status = 200, description = "The node", body = NodeRecord, headers(("ETag" = String, description = "Version of the node"))),
+        (status = 404, description = "No such node", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_node(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    let node = state
+        .get(&project, &name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("node '{name}' not found")))?;
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::Nodes(vec![&node]),
+            &format!("read node '{name}'"),
+        )
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(node.version))
+        .json(node))
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/nodes",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    request_body = NodeRecord,
+    responses(
+        (status = 201, description = "The node was created", body = NodeRecord, headers(("ETag" = String, description = "Version of the node"))),
+        (status = 400, description = "Malformed node", body = ErrorBody),
+        (status = 409, description = "A node of this name exists in the project", body = ErrorBody),
+        (status = 422, description = "Invalid node", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
 async fn create_node(
+    path: web::Path<ProjectPath>,
     state: web::Data<SharedState>,
+    caller: Caller,
     payload: web::Json<NodeRecord>,
 ) -> AppResult<HttpResponse> {
     let mut payload = payload.into_inner();
-    validate_name(&payload.name)?;
+    payload.project = path.into_inner().project;
+    validate::check_name(&payload.name)?;
     payload.name = payload.name.trim().to_string();
-    state
-        .upsert(payload.clone())
+    caller.require_write(&state, None, &payload).await?;
+    template::check_bind(&state, &mut payload).await?;
+    validate::check_node(&payload)?;
+    tunnel::check_node(&state, &payload).await?;
+    certificate::check_node(&state, &payload).await?;
+    let outcome = state
+        .write(payload.clone(), &caller.actor, Precondition::Absent)
         .await
         .map_err(AppError::from)?;
-    Ok(HttpResponse::Created().json(payload))
+    payload.version = written(&payload.name, outcome)?;
+    Ok(HttpResponse::Created()
+        .insert_header(etag(payload.version))
+        .json(payload))
 }
 
+/// Replaces a node at the version named by `If-Match`, or creates it with
+/// `If-None-Match: *`.
+#[utoipa::path(
+    put,
+    path = "/projects/{project}/nodes/{name}",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the node as last read, or `*`"),
+        ("If-None-Match" = Option<String>, Header, description = "`*` to create the node"),
+    ),
+    request_body = NodeRecord,
+    responses(
+        (status = 200, description = "The node was stored", body = NodeRecord, headers(("ETag" = String, description = "Version of the node"))),
+        (status = 400, description = "Malformed node or If-Match", body = ErrorBody),
+        (status = 404, description = "No such node", body = ErrorBody),
+        (status = 412, description = "The node is at another version", body = ErrorBody),
+        (status = 422, description = "Invalid node", body = ErrorBody),
+        (status = 428, description = "Neither If-Match nor If-None-Match was sent", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
 async fn update_node(
-    name: web::Path<String>,
+    path: web::Path<NamedPath>,
     state: web::Data<SharedState>,
+    caller: Caller,
+    if_match: Option<web::Header<IfMatch>>,
+    if_none_match: Option<web::Header<IfNoneMatch>>,
     payload: web::Json<NodeRecord>,
 ) -> AppResult<HttpResponse> {
-    let name = name.into_inner();
+    let NamedPath { project, name } = path.into_inner();
+    let precondition = precondition(if_match, if_none_match)?;
     let mut payload = payload.into_inner();
-    validate_name(&name)?;
+    validate::check_name(&name)?;
+    payload.project = project;
     payload.name = name.trim().to_string();
-    state
-        .upsert(payload.clone())
+    let current = state
+        .get(&payload.project, &payload.name)
+        .await
+        .map_err(AppError::from)?;
+    caller
+        .require_write(&state, current.as_ref(), &payload)
+        .await?;
+    // The access check compared against this version of the node.
+    let precondition = match (precondition, &current) {
+        (Precondition::Exists, Some(current)) => Precondition::Version(current.version),
+        (precondition, _) => precondition,
+    };
+    template::check_bind(&state, &mut payload).await?;
+    validate::check_node(&payload)?;
+    tunnel::check_node(&state, &payload).await?;
+    certificate::check_node(&state, &payload).await?;
+    let outcome = state
+        .write(payload.clone(), &caller.actor, precondition)
+        .await
+        .map_err(AppError::from)?;
+    if outcome == WriteOutcome::Exists {
+        return Err(AppError::precondition_failed(format!(
+            "node '{}' already exists",
+            payload.name
+        )));
+    }
+    payload.version = written(&payload.name, outcome)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(payload.version))
+        .json(payload))
+}
+
+/// Applies a JSON Merge Patch to a node. The patch is merged into the node
+/// as read and only stored if the node has not changed since, so If-Match
+/// is required like for any other change.
+#[utoipa::path(
+    patch,
+    path = "/projects/{project}/nodes/{name}",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the node as last read, or `*`"),
+    ),
+    request_body(content = Object, content_type = "application/merge-patch+json", description = "JSON Merge Patch of the node"),
+    responses(
+        (status = 200, description = "The node was patched", body = NodeRecord, headers(("ETag" = String, description = "Version of the node"))),
+        (status = 400, description = "Malformed patch", body = ErrorBody),
+        (status = 404, description = "No such node", body = ErrorBody),
+        (status = 412, description = "The node is at another version", body = ErrorBody),
+        (status = 422, description = "Invalid node", body = ErrorBody),
+        (status = 428, description = "If-Match was not sent", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn patch_node(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    if_match: Option<web::Header<IfMatch>>,
+    patch: web::Json<Value>,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    let name = name.trim();
+    let current = state
+        .get(&project, name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("node '{name}' not found")))?;
+    if let Some(failed) = precondition(if_match, None)?.check(Some(current.version)) {
+        written(name, failed)?;
+    }
+
+    let mut payload = patch::apply(&current, &patch)?;
+    caller
+        .require_write(&state, Some(&current), &payload)
+        .await?;
+    template::check_bind(&state, &mut payload).await?;
+    validate::check_node(&payload)?;
+    tunnel::check_node(&state, &payload).await?;
+    certificate::check_node(&state, &payload).await?;
+    let outcome = state
+        .write(
+            payload.clone(),
+            &caller.actor,
+            Precondition::Version(current.version),
+        )
         .await
         .map_err(AppError::from)?;
-    Ok(HttpResponse::Ok().json(payload))
+    payload.version = written(name, outcome)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(payload.version))
+        .json(payload))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/projects/{project}/nodes/{name}",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the node as last read, or `*`"),
+    ),
+    responses(
+        (status = 204, description = "The node was deleted"),
+        (status = 404, description = "No such node", body = ErrorBody),
+        (status = 409, description = "The node is used by tunnels", body = ErrorBody),
+        (status = 412, description = "The node is at another version", body = ErrorBody),
+        (status = 428, description = "If-Match was not sent", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
 async fn delete_node(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    if_match: Option<web::Header<IfMatch>>,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    let current = state
+        .get(&project, name.trim())
+        .await
+        .map_err(AppError::from)?;
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::node(current.as_ref()),
+            &format!("delete node '{}'", name.trim()),
+        )
+        .await?;
+    let precondition = precondition(if_match, None)?;
+    let outcome = state
+        .remove(&project, name.trim(), &caller.actor, precondition)
+        .await
+        .map_err(AppError::from)?;
+    written(name.trim(), outcome)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}/revisions",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+    ),
+    responses(
+        (status = 200, description = "Revisions of the node, without the node itself", body = Vec<NodeRevision>),
+        (status = 404, description = "No such node", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_revisions(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<NodeRevision>>> {
+    let NamedPath { project, name } = path.into_inner();
+    require_history(&state, &caller, &project, name.trim()).await?;
+    let revisions = state
+        .list_revisions(&project, name.trim())
+        .await
+        .map_err(AppError::from)?;
+    if revisions.is_empty() {
+        return Err(AppError::not_found(format!("node '{name}' not found")));
+    }
+    Ok(web::Json(revisions))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}/revisions/{revision}",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        ("revision" = i64, Path, description = "Revision number"),
+    ),
+    responses(
+        (status = 200, description = "The revision, with the node as stored by it", body = NodeRevision),
+        (status = 404, description = "No such revision", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_revision(
+    path: web::Path<RevisionPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<NodeRevision>> {
+    let RevisionPath {
+        project,
+        name,
+        revision,
+    } = path.into_inner();
+    require_history(&state, &caller, &project, name.trim()).await?;
+    match state
+        .get_revision(&project, name.trim(), revision)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(revision) => Ok(web::Json(revision)),
+        None => Err(AppError::not_found(format!(
+            "revision {revision} of node '{name}' not found"
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DiffQuery {
+    from: i64,
+    to: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}/diff",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        DiffQuery,
+    ),
+    responses(
+        (status = 200, description = "Fields that differ between the revisions", body = Vec<FieldChange>),
+        (status = 404, description = "No such revisions", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn diff_revisions(
+    path: web::Path<NamedPath>,
+    query: web::Query<DiffQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<FieldChange>>> {
+    let NamedPath { project, name } = path.into_inner();
+    require_history(&state, &caller, &project, name.trim()).await?;
+    let DiffQuery { from, to } = query.into_inner();
+    match state
+        .diff_revisions(&project, name.trim(), from, to)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(changes) => Ok(web::Json(changes)),
+        None => Err(AppError::not_found(format!(
+            "revisions {from} and {to} of node '{name}' not found"
+        ))),
+    }
+}
+
+/// Restores a node as stored by an earlier revision, at the version named
+/// by `If-Match`, or recreates a deleted node with `If-None-Match: *`. The
+/// restored node is checked like any other write and keeps being managed
+/// from where it is now.
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/nodes/{name}/revisions/{revision}/rollback",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        ("revision" = i64, Path, description = "Revision number"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the node as last read, or `*`"),
+        ("If-None-Match" = Option<String>, Header, description = "`*` to recreate a deleted node"),
+    ),
+    responses(
+        (status = 200, description = "The node as restored", body = NodeRecord, headers(("ETag" = String, description = "Version of the node"))),
+        (status = 400, description = "The revision deleted the node, or malformed If-Match", body = ErrorBody),
+        (status = 404, description = "No such revision or node", body = ErrorBody),
+        (status = 412, description = "The node is at another version", body = ErrorBody),
+        (status = 422, description = "The restored node is no longer valid", body = ErrorBody),
+        (status = 428, description = "Neither If-Match nor If-None-Match was sent", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn rollback_node(
+    path: web::Path<RevisionPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    if_match: Option<web::Header<IfMatch>>,
+    if_none_match: Option<web::Header<IfNoneMatch>>,
+) -> AppResult<HttpResponse> {
+    let RevisionPath {
+        project,
+        name,
+        revision,
+    } = path.into_inner();
+    let name = name.trim();
+    let precondition = precondition(if_match, if_none_match)?;
+    let target = state
+        .get_revision(&project, name, revision)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| {
+            AppError::not_found(format!("revision {revision} of node '{name}' not found"))
+        })?;
+    let Some(mut node) = target.node else {
+        return Err(AppError::bad_request(format!(
+            "revision {revision} deleted node '{name}'; roll back to an earlier revision"
+        )));
+    };
+
+    let current = state.get(&project, name).await.map_err(AppError::from)?;
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::Nodes(current.iter().chain([&node]).collect()),
+            &format!("roll back node '{name}'"),
+        )
+        .await?;
+    // Revisions do not record where the node is managed from.
+    node.managed_by = current
+        .as_ref()
+        .map(|current| current.managed_by)
+        .unwrap_or_default();
+    let precondition = match (precondition, &current) {
+        (Precondition::Exists, Some(current)) => Precondition::Version(current.version),
+        (precondition, _) => precondition,
+    };
+    template::check_bind(&state, &mut node).await?;
+    validate::check_node(&node)?;
+    tunnel::check_node(&state, &node).await?;
+    certificate::check_node(&state, &node).await?;
+    let outcome = state
+        .rollback(node.clone(), &caller.actor, precondition)
+        .await
+        .map_err(AppError::from)?;
+    if outcome == WriteOutcome::Exists {
+        return Err(AppError::precondition_failed(format!(
+            "node '{name}' exists; send its ETag in If-Match to roll it back"
+        )));
+    }
+    node.version = written(name, outcome)?;
+    node.status = current.map(|current| current.status).unwrap_or_default();
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(node.version))
+        .json(node))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    #[serde(default)]
+    format: InventoryFormat,
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/export",
+    tag = "inventory",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ExportQuery,
+    ),
+    responses(
+        (status = 200, description = "Every node of the project, in the shape of `manager.toml`", content(
+            (ManagerConfig = "application/json"),
+            (String = "application/toml"),
+        )),
+    ),
+    security(("api_token" = [])),
+)]
+async fn export_nodes(
+    path: web::Path<ProjectPath>,
+    query: web::Query<ExportQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("export the nodes of project '{project}'"),
+        )
+        .await?;
+    let export = inventory::export(&state, &project)
+        .await
+        .map_err(AppError::from)?;
+    let body = inventory::render(&export, query.format).map_err(AppError::from)?;
+    Ok(HttpResponse::Ok()
+        .content_type(query.format.content_type())
+        .body(body))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ImportQuery {
+    #[serde(default)]
+    strategy: ImportStrategy,
+}
+
+/// Imports an inventory as exported by `GET /projects/{project}/export`
+/// into a project, as JSON or, with `Content-Type: application/toml`, as
+/// TOML.
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/import",
+    tag = "inventory",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ImportQuery,
+    ),
+    request_body(content(
+        (ManagerConfig = "application/json"),
+        (String = "application/toml"),
+    )),
+    responses(
+        (status = 200, description = "Every node was imported", body = ImportReport),
+        (status = 400, description = "Malformed inventory", body = ErrorBody),
+        (status = 409, description = "Nodes changed during the import", body = ErrorBody),
+        (status = 422, description = "Invalid nodes; nothing was imported", body = ImportReport),
+    ),
+    security(("api_token" = [])),
+)]
+async fn import_nodes(
+    req: HttpRequest,
+    path: web::Path<ProjectPath>,
+    query: web::Query<ImportQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    body: web::Bytes,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("import nodes into project '{project}'"),
+        )
+        .await?;
+    let nodes = inventory::parse(&body, InventoryFormat::of_body(req.content_type()))?;
+    let report = inventory::import(&state, &project, nodes, query.strategy, &caller.actor).await?;
+    let mut response = if report.applied {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::UnprocessableEntity()
+    };
+    Ok(response.json(report))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventsQuery {
+    /// Only events of the nodes of this project.
+    project: Option<String>,
+}
+
+/// Streams changes to nodes as Server-Sent Events, leaving out the nodes
+/// the token does not cover. Reconnecting with `Last-Event-ID` resumes the
+/// feed after that event, as long as the manager still buffers what came
+/// after it.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(
+        EventsQuery,
+        ("Last-Event-ID" = Option<String>, Header, description = "Id of the last event received"),
+    ),
+    responses(
+        (status = 200, description = "Events named `node_created`, `node_updated`, `node_deleted` and `node_status_changed`, each with the node as data, and `resync` when events were lost and the nodes should be read again", body = String, content_type = "text/event-stream"),
+        (status = 400, description = "Malformed Last-Event-ID", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn stream_events(
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "watch node events")
+        .await?;
+    let last_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| AppError::bad_request("Last-Event-ID must be the id of an event"))
+        })
+        .transpose()?;
+    let subscription = state.events().subscribe(last_id);
+    let filter = events::Filter {
+        project: query.into_inner().project,
+        grant: caller.grant,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(events::stream(subscription, filter)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(
+        AuditQuery,
+    ),
+    responses(
+        (status = 200, description = "Changes to nodes, tunnels, certificates, templates, projects, tokens and Noise keys, newest first", body = Vec<AuditEvent>),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_audit_events(
+    query: web::Query<AuditQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<AuditEvent>>> {
+    caller
+        .require(&state, Role::Admin, Scope::All, "read the audit log")
+        .await?;
+    let events = state.audit_events(&query).await.map_err(AppError::from)?;
+    Ok(web::Json(events))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/tunnels",
+    tag = "tunnels",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    responses(
+        (status = 200, description = "Every tunnel of the project", body = Vec<Tunnel>),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_tunnels(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<Tunnel>>> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "list tunnels")
+        .await?;
+    let tunnels = state.list_tunnels(&project).await.map_err(AppError::from)?;
+    let tunnels = rbac::visible_tunnels(&state, &caller.grant, &project, tunnels)
+        .await
+        .map_err(AppError::from)?;
+    Ok(web::Json(tunnels))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/tunnels/{name}",
+    tag = "tunnels",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the tunnel"),
+    ),
+    responses(
+        (status = 200, description = "The tunnel", body = Tunnel),
+        (status = 404, description = "No such tunnel", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_tunnel(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Tunnel>> {
+    let NamedPath { project, name } = path.into_inner();
+    let tunnel = fetch_tunnel(&state, &project, name.trim()).await?;
+    caller
+        .require_tunnel(
+            &state,
+            Role::Viewer,
+            &project,
+            &tunnel.server_node,
+            &tunnel.client_node,
+            &format!("read tunnel '{}'", tunnel.name),
+        )
+        .await?;
+    Ok(web::Json(tunnel))
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/tunnels",
+    tag = "tunnels",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    request_body = CreateTunnel,
+    responses(
+        (status = 201, description = "The tunnel was created", body = Tunnel),
+        (status = 409, description = "A tunnel of this name exists in the project", body = ErrorBody),
+        (status = 422, description = "Invalid tunnel", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn create_tunnel(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<CreateTunnel>,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    let payload = payload.into_inner();
+    caller
+        .require_tunnel(
+            &state,
+            Role::Operator,
+            &project,
+            &payload.server_node,
+            &payload.client_node,
+            &format!("create tunnel '{}'", payload.name.trim()),
+        )
+        .await?;
+    let name = payload.name.trim().to_string();
+    let creation = state
+        .create_tunnel(&project, payload, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    let created = tunnel::created(&name, creation)?;
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{project}/tunnels/{name}",
+    tag = "tunnels",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the tunnel"),
+    ),
+    responses(
+        (status = 204, description = "The tunnel was deleted"),
+        (status = 404, description = "No such tunnel", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn delete_tunnel(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    let tunnel = fetch_tunnel(&state, &project, name.trim()).await?;
+    caller
+        .require_tunnel(
+            &state,
+            Role::Operator,
+            &project,
+            &tunnel.server_node,
+            &tunnel.client_node,
+            &format!("delete tunnel '{}'", tunnel.name),
+        )
+        .await?;
+    let deleted = state
+        .delete_tunnel(&project, name.trim(), &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::not_found("tunnel not found"))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/tunnels/{name}/rotate",
+    tag = "tunnels",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the tunnel"),
+    ),
+    responses(
+        (status = 200, description = "The tunnel with its new token issued", body = Tunnel),
+        (status = 404, description = "No such tunnel", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn rotate_tunnel_token(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Tunnel>> {
+    let NamedPath { project, name } = path.into_inner();
+    let tunnel = fetch_tunnel(&state, &project, name.trim()).await?;
+    caller
+        .require_tunnel(
+            &state,
+            Role::Admin,
+            &project,
+            &tunnel.server_node,
+            &tunnel.client_node,
+            &format!("rotate the token of tunnel '{}'", tunnel.name),
+        )
+        .await?;
+    match state
+        .rotate_tunnel_token(&project, name.trim(), &caller.actor)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(tunnel) => Ok(web::Json(tunnel)),
+        None => Err(AppError::not_found(format!("tunnel '{name}' not found"))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/certificates",
+    tag = "certificates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    responses(
+        (status = 200, description = "Every certificate of the project", body = Vec<Certificate>, headers(("Warning" = String, description = "One per certificate that expires soon or has expired"))),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_certificates(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "list certificates")
+        .await?;
+    let certificates = state
+        .list_certificates(&project)
+        .await
+        .map_err(AppError::from)?;
+    let mut response = HttpResponse::Ok();
+    for warning in certificates.iter().filter_map(Certificate::warning) {
+        response.append_header((WARNING, warning));
+    }
+    Ok(response.json(certificates))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/certificates/{name}",
+    tag = "certificates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the certificate"),
+    ),
+    responses(
+        (status = 200, description = "The certificate, without its key", body = Certificate, headers(("Warning" = String, description = "Set if the certificate expires soon or has expired"))),
+        (status = 404, description = "No such certificate", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_certificate(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::Listing,
+            &format!("read certificate '{name}'"),
+        )
+        .await?;
+    let certificate = fetch_certificate(&state, &project, name.trim()).await?;
+    let mut response = HttpResponse::Ok();
+    if let Some(warning) = certificate.warning() {
+        response.insert_header((WARNING, warning));
+    }
+    Ok(response.json(certificate))
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/certificates",
+    tag = "certificates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    request_body = UploadCertificate,
+    responses(
+        (status = 201, description = "The certificate was stored", body = Certificate),
+        (status = 400, description = "Empty certificate name", body = ErrorBody),
+        (status = 409, description = "A certificate of this name exists in the project", body = ErrorBody),
+        (status = 422, description = "Invalid chain or key", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn upload_certificate(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<UploadCertificate>,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    let payload = payload.into_inner();
+    let name = payload.name.trim().to_string();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("upload certificate '{name}'"),
+        )
+        .await?;
+    if name.is_empty() {
+        return Err(AppError::bad_request("certificate name cannot be empty"));
+    }
+    let parsed = certificate::parse(&payload.chain, &payload.key)?;
+    let created = state
+        .create_certificate(&project, &name, parsed, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    match created {
+        Some(certificate) => Ok(HttpResponse::Created().json(certificate)),
+        None => Err(AppError::conflict(format!(
+            "certificate '{name}' already exists"
+        ))),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/projects/{project}/certificates/{name}",
+    tag = "certificates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the certificate"),
+    ),
+    request_body = ReplaceCertificate,
+    responses(
+        (status = 200, description = "The chain and key were replaced and sent to the nodes serving them", body = Certificate),
+        (status = 404, description = "No such certificate", body = ErrorBody),
+        (status = 422, description = "Invalid chain or key, or one that no longer covers the hostnames of a node", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn replace_certificate(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<ReplaceCertificate>,
+) -> AppResult<web::Json<Certificate>> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("replace certificate '{name}'"),
+        )
+        .await?;
+    let current = fetch_certificate(&state, &project, name.trim()).await?;
+    let parsed = certificate::parse(&payload.chain, &payload.key)?;
+    certificate::check_replacement(&state, &current, &parsed).await?;
+    match state
+        .replace_certificate(&project, &current.name, parsed, &caller.actor)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(certificate) => Ok(web::Json(certificate)),
+        None => Err(AppError::not_found(format!(
+            "certificate '{name}' not found"
+        ))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{project}/certificates/{name}",
+    tag = "certificates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the certificate"),
+    ),
+    responses(
+        (status = 204, description = "The certificate was deleted"),
+        (status = 404, description = "No such certificate", body = ErrorBody),
+        (status = 409, description = "Nodes still serve the certificate", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn delete_certificate(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("delete certificate '{name}'"),
+        )
+        .await?;
+    let certificate = fetch_certificate(&state, &project, name.trim()).await?;
+    certificate::check_unused(&certificate)?;
+    let deleted = state
+        .delete_certificate(&project, &certificate.name, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::not_found("certificate not found"))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/templates",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    responses(
+        (status = 200, description = "Every template of the project", body = Vec<Template>),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_templates(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<Template>>> {
+    let ProjectPath { project } = path.into_inner();
+    caller
+        .require(&state, Role::Viewer, Scope::Listing, "list templates")
+        .await?;
+    let templates = state
+        .list_templates(&project)
+        .await
+        .map_err(AppError::from)?;
+    Ok(web::Json(templates))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/templates/{name}",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the template"),
+    ),
+    responses(
+        (status = 200, description = "The template", body = Template),
+        (status = 404, description = "No such template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_template(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Template>> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::Listing,
+            &format!("read template '{name}'"),
+        )
+        .await?;
+    let template = fetch_template(&state, &project, name.trim()).await?;
+    Ok(web::Json(template))
+}
+
+/// Creates a template and renders it into every node its selector binds.
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/templates",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+    ),
+    request_body = CreateTemplate,
+    responses(
+        (status = 201, description = "The template was created and applied; nodes it does not render into are reported with their errors", body = TemplateReport),
+        (status = 400, description = "Empty template name", body = ErrorBody),
+        (status = 409, description = "A template of this name exists in the project", body = ErrorBody),
+        (status = 422, description = "Invalid template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn create_template(
+    path: web::Path<ProjectPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<CreateTemplate>,
+) -> AppResult<HttpResponse> {
+    let ProjectPath { project } = path.into_inner();
+    let CreateTemplate { name, spec } = payload.into_inner();
+    let name = name.trim().to_string();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("create template '{name}'"),
+        )
+        .await?;
+    if name.is_empty() {
+        return Err(AppError::bad_request("template name cannot be empty"));
+    }
+    template::check_spec(&name, &spec)?;
+    let created = state
+        .create_template(&project, &name, spec, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    let Some(template) = created else {
+        return Err(AppError::conflict(format!(
+            "template '{name}' already exists"
+        )));
+    };
+    let nodes = template::apply(&state, &template, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Created().json(TemplateReport { template, nodes }))
+}
+
+/// Replaces what a template sets and renders it again into every node its
+/// selector binds.
+#[utoipa::path(
+    put,
+    path = "/projects/{project}/templates/{name}",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the template"),
+    ),
+    request_body = TemplateSpec,
+    responses(
+        (status = 200, description = "The template was replaced and applied; nodes it does not render into are reported with their errors", body = TemplateReport),
+        (status = 404, description = "No such template", body = ErrorBody),
+        (status = 422, description = "Invalid template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn update_template(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<TemplateSpec>,
+) -> AppResult<web::Json<TemplateReport>> {
+    let NamedPath { project, name } = path.into_inner();
+    let name = name.trim().to_string();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("update template '{name}'"),
+        )
+        .await?;
+    let spec = payload.into_inner();
+    template::check_spec(&name, &spec)?;
+    let template = state
+        .update_template(&project, &name, spec, &caller.actor)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("template '{name}' not found")))?;
+    let nodes = template::apply(&state, &template, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    Ok(web::Json(TemplateReport { template, nodes }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{project}/templates/{name}",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the template"),
+    ),
+    responses(
+        (status = 204, description = "The template was deleted; its nodes keep what it last rendered into them"),
+        (status = 404, description = "No such template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn delete_template(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("delete template '{name}'"),
+        )
+        .await?;
+    let deleted = state
+        .delete_template(&project, name.trim(), &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::not_found(format!("template '{name}' not found")))
+    }
+}
+
+/// Renders a template into every node its selector binds without writing
+/// them, to show which nodes are out of date and which it fails on.
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/templates/{name}/nodes",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the template"),
+    ),
+    responses(
+        (status = 200, description = "Every node bound to the template, with how it renders", body = Vec<TemplateNode>),
+        (status = 404, description = "No such template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn template_nodes(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<TemplateNode>>> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::Listing,
+            &format!("read the nodes of template '{name}'"),
+        )
+        .await?;
+    let template = fetch_template(&state, &project, name.trim()).await?;
+    let nodes = template::preview(&state, &template)
+        .await
+        .map_err(AppError::from)?;
+    Ok(web::Json(nodes))
+}
+
+/// Renders a template again into every node its selector binds, such as
+/// once the errors it reported were fixed.
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/templates/{name}/apply",
+    tag = "templates",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the template"),
+    ),
+    responses(
+        (status = 200, description = "The template was applied; nodes it does not render into are reported with their errors", body = TemplateReport),
+        (status = 404, description = "No such template", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn apply_template(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<TemplateReport>> {
+    let NamedPath { project, name } = path.into_inner();
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::All,
+            &format!("apply template '{name}'"),
+        )
+        .await?;
+    let template = fetch_template(&state, &project, name.trim()).await?;
+    let nodes = template::apply(&state, &template, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    Ok(web::Json(TemplateReport { template, nodes }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}/noise-key",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+    ),
+    responses(
+        (status = 200, description = "Public half of the Noise key of the node", body = NodeKey),
+        (status = 404, description = "The node has no managed Noise key", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn get_noise_key(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<NodeKey>> {
+    let NamedPath { project, name } = path.into_inner();
+    let node = state.get(&project, &name).await.map_err(AppError::from)?;
+    caller
+        .require(
+            &state,
+            Role::Viewer,
+            Scope::node(node.as_ref()),
+            &format!("read the Noise key of node '{name}'"),
+        )
+        .await?;
+    match state
+        .noise_key(&project, &name)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(key) => Ok(web::Json(key)),
+        None => Err(AppError::not_found(format!(
+            "node '{name}' has no managed Noise key"
+        ))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project}/nodes/{name}/noise-key/rotate",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+    ),
+    responses(
+        (status = 200, description = "The new Noise key of the node", body = NodeKey),
+        (status = 404, description = "No such node", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn rotate_noise_key(
+    path: web::Path<NamedPath>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<NodeKey>> {
+    let NamedPath { project, name } = path.into_inner();
+    let node = state.get(&project, &name).await.map_err(AppError::from)?;
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::node(node.as_ref()),
+            &format!("rotate the Noise key of node '{name}'"),
+        )
+        .await?;
+    match state
+        .rotate_noise_key(&project, &name, &caller.actor)
+        .await
+        .map_err(AppError::from)?
+    {
+        Some(key) => Ok(web::Json(key)),
+        None => Err(AppError::not_found(format!("node '{name}' not found"))),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RenderQuery {
+    /// The file to render; `node` if unset.
+    #[serde(default)]
+    target: RenderTarget,
+    /// Encoding of the `node` and `rathole` files; `toml` if unset.
+    format: Option<InventoryFormat>,
+}
+
+/// Renders a configuration file for a node that cannot reach the manager,
+/// with its tunnels and Noise keys resolved. Its `reverse_proxy.bind` is
+/// the node's `reverse_proxy_bind`, or the node's default if unset.
+///
+/// `format` only picks the encoding, as it does for exports. The node and
+/// Rathole each read a TOML file, and a certificate from the manager comes
+/// as two more PEM files, so which file to render is a parameter of its own,
+/// `target`.
+#[utoipa::path(
+    get,
+    path = "/projects/{project}/nodes/{name}/render",
+    tag = "nodes",
+    params(
+        ("project" = String, Path, description = "Name of the project"),
+        ("name" = String, Path, description = "Name of the node"),
+        RenderQuery,
+    ),
+    responses(
+        (status = 200, description = "The file, which TOML readers of the node and Rathole accept as is", content(
+            (String = "application/toml"),
+            (Object = "application/json"),
+            (String = "application/x-pem-file"),
+        )),
+        (status = 404, description = "No such node, no port mapping to render for Rathole, or no certificate from the manager", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn render_node(
+    path: web::Path<NamedPath>,
+    query: web::Query<RenderQuery>,
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<HttpResponse> {
+    let NamedPath { project, name } = path.into_inner();
+    let node = state.get(&project, &name).await.map_err(AppError::from)?;
+    caller
+        .require(
+            &state,
+            Role::Admin,
+            Scope::node(node.as_ref()),
+            &format!("render the config of node '{name}'"),
+        )
+        .await?;
+    let node = state
+        .node_config(&project, &name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("node '{name}' not found")))?;
+    let certificate = state
+        .node_certificate(&node)
+        .await
+        .map_err(AppError::from)?;
+    let format = query.format.unwrap_or(InventoryFormat::Toml);
+    let body = render::render(node, certificate, query.target, format)?;
+    Ok(HttpResponse::Ok()
+        .content_type(query.target.content_type(format))
+        .insert_header(ContentDisposition::attachment(
+            query.target.file_name(format),
+        ))
+        .body(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "Every API token, without its secret", body = Vec<ApiToken>),
+    ),
+    security(("api_token" = [])),
+)]
+async fn list_tokens(
+    state: web::Data<SharedState>,
+    caller: Caller,
+) -> AppResult<web::Json<Vec<ApiToken>>> {
+    caller
+        .require(&state, Role::Admin, Scope::All, "list API tokens")
+        .await?;
+    let tokens = state.list_tokens().await.map_err(AppError::from)?;
+    Ok(web::Json(tokens))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "tokens",
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 201, description = "The token, with its secret returned only this once", body = IssuedToken),
+        (status = 400, description = "Invalid token name or node", body = ErrorBody),
+        (status = 409, description = "A token of this name exists", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn create_token(
+    state: web::Data<SharedState>,
+    caller: Caller,
+    payload: web::Json<CreateTokenRequest>,
+) -> AppResult<HttpResponse> {
+    caller
+        .require(&state, Role::Admin, Scope::All, "create API tokens")
+        .await?;
+    let CreateTokenRequest {
+        name,
+        node,
+        project,
+        role,
+        tags,
+    } = payload.into_inner();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request("token name cannot be empty"));
+    }
+    let node = match node.map(|node| node.trim().to_string()) {
+        Some(node) => {
+            if role.is_some() || !tags.is_empty() {
+                return Err(AppError::bad_request(
+                    "node tokens cannot have a role or tags",
+                ));
+            }
+            let project = project.as_deref().unwrap_or(DEFAULT_PROJECT).trim();
+            let found = state.get(project, &node).await.map_err(AppError::from)?;
+            Some(found.ok_or_else(|| {
+                AppError::bad_request(format!(
+                    "node '{node}' does not exist in project '{project}'"
+                ))
+            })?)
+        }
+        None if project.is_some() => {
+            return Err(AppError::bad_request("only node tokens have a project"));
+        }
+        None => None,
+    };
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let grant = Grant {
+        role: role.unwrap_or_default(),
+        tags,
+    };
+    let issued = state
+        .create_token(name, node.as_ref(), grant, &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    match issued {
+        Some(issued) => Ok(HttpResponse::Created().json(issued)),
+        None => Err(AppError::conflict(format!("token '{name}' already exists"))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tokens/{name}",
+    tag = "tokens",
+    params(
+        ("name" = String, Path, description = "Name of the token"),
+    ),
+    responses(
+        (status = 204, description = "The token was revoked"),
+        (status = 404, description = "No such token", body = ErrorBody),
+    ),
+    security(("api_token" = [])),
+)]
+async fn delete_token(
     name: web::Path<String>,
     state: web::Data<SharedState>,
+    caller: Caller,
 ) -> AppResult<HttpResponse> {
+    caller
+        .require(&state, Role::Admin, Scope::All, "revoke API tokens")
+        .await?;
     let name = name.into_inner();
-    if state.remove(name.trim()).await.map_err(AppError::from)? {
+    let revoked = state
+        .revoke_token(name.trim(), &caller.actor)
+        .await
+        .map_err(AppError::from)?;
+    if revoked {
         Ok(HttpResponse::NoContent().finish())
     } else {
-        Err(AppError::not_found("node not found"))
+        Err(AppError::not_found("token not found"))
     }
 }
 
-fn validate_name(name: &str) -> AppResult<()> {
-    if name.trim().is_empty() {
-        Err(AppError::bad_request("node name cannot be empty"))
-    } else {
-        Ok(())
+/// Refuses to show the revisions of a node unless the caller may read the
+/// node; those of deleted nodes need a grant that covers every node.
+async fn require_history(
+    state: &ManagerState,
+    caller: &Caller,
+    project: &str,
+    name: &str,
+) -> AppResult<()> {
+    let node = state.get(project, name).await.map_err(AppError::from)?;
+    caller
+        .require(
+            state,
+            Role::Viewer,
+            Scope::node(node.as_ref()),
+            &format!("read the revisions of node '{name}'"),
+        )
+        .await
+}
+
+async fn fetch_tunnel(state: &ManagerState, project: &str, name: &str) -> AppResult<Tunnel> {
+    state
+        .get_tunnel(project, name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("tunnel '{name}' not found")))
+}
+
+async fn fetch_template(state: &ManagerState, project: &str, name: &str) -> AppResult<Template> {
+    state
+        .get_template(project, name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("template '{name}' not found")))
+}
+
+async fn fetch_certificate(
+    state: &ManagerState,
+    project: &str,
+    name: &str,
+) -> AppResult<Certificate> {
+    state
+        .get_certificate(project, name)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::not_found(format!("certificate '{name}' not found")))
+}
+
+fn etag(version: i64) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// What a PUT or DELETE expects of the node. `If-Match` names the version
+/// the client last read, or `*` for any; `If-None-Match: *` asks a PUT to
+/// create the node.
+fn precondition(
+    if_match: Option<web::Header<IfMatch>>,
+    if_none_match: Option<web::Header<IfNoneMatch>>,
+) -> AppResult<Precondition> {
+    match (
+        if_match.map(web::Header::into_inner),
+        if_none_match.map(web::Header::into_inner),
+    ) {
+        (Some(IfMatch::Any), _) => Ok(Precondition::Exists),
+        (Some(IfMatch::Items(tags)), _) => match tags.as_slice() {
+            [tag] => tag
+                .tag()
+                .parse()
+                .ok()
+                .filter(|_| !tag.weak)
+                .map(Precondition::Version)
+                .ok_or_else(|| {
+                    AppError::precondition_failed("If-Match does not name a version of the node")
+                }),
+            _ => Err(AppError::bad_request(
+                "If-Match must name exactly one version of the node",
+            )),
+        },
+        (None, Some(IfNoneMatch::Any)) => Ok(Precondition::Absent),
+        (None, _) => Err(AppError::precondition_required(
+            "send the ETag of the node as read in If-Match, or If-None-Match: * to create it",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderName, AUTHORIZATION, ETAG, IF_MATCH, IF_NONE_MATCH};
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixture;
+
+    const TOKEN: &str = "lvl_sqlite_test";
+    const NODES: &str = "/projects/default/nodes";
+
+    // The whole API runs on SQLite as on PostgreSQL, so these go through the
+    // routes the server uses on an in-memory database.
+    async fn state() -> web::Data<SharedState> {
+        let state = fixture::open("sqlite::memory:", Some(TOKEN)).await;
+        web::Data::new(Arc::new(state))
+    }
+
+    fn request(method: Method, uri: &str) -> test::TestRequest {
+        test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .insert_header((AUTHORIZATION, format!("Bearer {TOKEN}")))
+    }
+
+    fn version(response: &actix_web::dev::ServiceResponse) -> &str {
+        response.headers().get(ETAG).unwrap().to_str().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn node_crud_on_sqlite() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+
+        let created = test::call_service(
+            &app,
+            request(Method::POST, NODES)
+                .set_json(json!({ "name": "edge", "tags": ["eu"] }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        assert_eq!(version(&created), "\"1\"");
+
+        let node: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, &format!("{NODES}/edge")).to_request(),
+        )
+        .await;
+        assert_eq!(node["tags"], json!(["eu"]));
+        assert_eq!(node["version"], 1);
+
+        let updated = test::call_service(
+            &app,
+            request(Method::PUT, &format!("{NODES}/edge"))
+                .insert_header((IF_MATCH, "\"1\""))
+                .set_json(json!({ "name": "edge", "description": "Edge in Paris" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(updated.status(), StatusCode::OK);
+        assert_eq!(version(&updated), "\"2\"");
+        let node: Value = test::read_body_json(updated).await;
+        assert_eq!(node["description"], "Edge in Paris");
+        assert_eq!(node["tags"], json!([]));
+
+        let duplicate = test::call_service(
+            &app,
+            request(Method::POST, NODES)
+                .set_json(json!({ "name": "edge" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+
+        let deleted = test::call_service(
+            &app,
+            request(Method::DELETE, &format!("{NODES}/edge"))
+                .insert_header((IF_MATCH, "\"2\""))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+
+        let missing = test::call_service(
+            &app,
+            request(Method::GET, &format!("{NODES}/edge")).to_request(),
+        )
+        .await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn nodes_path_serves_the_default_project() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        let created = test::call_service(
+            &app,
+            request(Method::POST, "/nodes")
+                .set_json(json!({ "name": "edge" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+
+        let node: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, &format!("{NODES}/edge")).to_request(),
+        )
+        .await;
+        assert_eq!(node["project"], "default");
+        let revisions = test::call_service(
+            &app,
+            request(Method::GET, "/nodes/edge/revisions").to_request(),
+        )
+        .await;
+        assert_eq!(revisions.status(), StatusCode::OK);
+
+        let anonymous =
+            test::call_service(&app, test::TestRequest::get().uri("/nodes").to_request()).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn inventory_and_tunnel_paths_serve_the_default_project() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        let imported = test::call_service(
+            &app,
+            request(Method::POST, "/import")
+                .set_json(json!({ "nodes": { "edge": { "name": "edge" } } }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(imported.status(), StatusCode::OK);
+        let node: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, &format!("{NODES}/edge")).to_request(),
+        )
+        .await;
+        assert_eq!(node["project"], "default");
+
+        let export: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, "/export?format=json").to_request(),
+        )
+        .await;
+        assert_eq!(export["nodes"]["edge"]["name"], "edge");
+
+        let tunnels: Value =
+            test::call_and_read_body_json(&app, request(Method::GET, "/tunnels").to_request())
+                .await;
+        assert_eq!(tunnels, json!([]));
+        let created = test::call_service(
+            &app,
+            request(Method::POST, "/tunnels")
+                .set_json(json!({
+                    "name": "ssh",
+                    "server_node": "edge",
+                    "client_node": "core",
+                    "bind_addr": "0.0.0.0:2222",
+                    "local_addr": "127.0.0.1:22",
+                }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(created).await;
+        assert!(body.to_string().contains("client_node"), "{body}");
+
+        for path in ["/export", "/import", "/tunnels"] {
+            let anonymous =
+                test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED, "{path}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn tunnels_keep_their_name_and_nodes() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        let nodes = [
+            json!({
+                "name": "edge",
+                "port_mapping": {
+                    "mode": "server",
+                    "config": { "server": { "bind_addr": "0.0.0.0:2333", "services": {} } },
+                },
+            }),
+            json!({
+                "name": "core",
+                "port_mapping": {
+                    "mode": "client",
+                    "config": { "client": { "remote_addr": "edge:2333", "services": {} } },
+                },
+            }),
+        ];
+        for node in nodes {
+            let created = test::call_service(
+                &app,
+                request(Method::POST, NODES).set_json(node).to_request(),
+            )
+            .await;
+            assert_eq!(created.status(), StatusCode::CREATED);
+        }
+
+        let tunnel = json!({
+            "name": "ssh",
+            "server_node": "edge",
+            "client_node": "core",
+            "bind_addr": "0.0.0.0:2222",
+            "local_addr": "127.0.0.1:22",
+        });
+        let create = || {
+            request(Method::POST, "/projects/default/tunnels")
+                .set_json(&tunnel)
+                .to_request()
+        };
+        let created = test::call_service(&app, create()).await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let duplicate = test::call_service(&app, create()).await;
+        assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+
+        let deleted = test::call_service(
+            &app,
+            request(Method::DELETE, &format!("{NODES}/edge"))
+                .insert_header((IF_MATCH, "*"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(deleted.status(), StatusCode::CONFLICT);
+        let body: Value = test::read_body_json(deleted).await;
+        assert!(body.to_string().contains("ssh"), "{body}");
+    }
+
+    #[actix_web::test]
+    async fn if_match_on_sqlite() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        let put = |headers: &[(HeaderName, &str)]| {
+            let mut request =
+                request(Method::PUT, &format!("{NODES}/edge")).set_json(json!({ "name": "edge" }));
+            for header in headers {
+                request = request.insert_header(header.clone());
+            }
+            request.to_request()
+        };
+        let patch = |headers: &[(HeaderName, &str)]| {
+            let mut request = request(Method::PATCH, &format!("{NODES}/edge"))
+                .set_json(json!({ "description": "Edge in Paris" }));
+            for header in headers {
+                request = request.insert_header(header.clone());
+            }
+            request.to_request()
+        };
+        let delete = |headers: &[(HeaderName, &str)]| {
+            let mut request = request(Method::DELETE, &format!("{NODES}/edge"));
+            for header in headers {
+                request = request.insert_header(header.clone());
+            }
+            request.to_request()
+        };
+
+        let status = test::call_service(&app, put(&[])).await.status();
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let status = test::call_service(&app, put(&[(IF_MATCH, "*")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let created = test::call_service(&app, put(&[(IF_NONE_MATCH, "*")])).await;
+        assert_eq!(created.status(), StatusCode::OK);
+        assert_eq!(version(&created), "\"1\"");
+        let status = test::call_service(&app, put(&[(IF_NONE_MATCH, "*")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+        let status = test::call_service(&app, put(&[(IF_MATCH, "\"7\"")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let status = test::call_service(&app, put(&[(IF_MATCH, "W/\"1\"")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+        // Only a change moves the node to the next version.
+        let unchanged = test::call_service(&app, put(&[(IF_MATCH, "\"1\"")])).await;
+        assert_eq!(unchanged.status(), StatusCode::OK);
+        assert_eq!(version(&unchanged), "\"1\"");
+
+        let status = test::call_service(&app, patch(&[])).await.status();
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let status = test::call_service(&app, patch(&[(IF_MATCH, "\"7\"")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let patched = test::call_service(&app, patch(&[(IF_MATCH, "\"1\"")])).await;
+        assert_eq!(patched.status(), StatusCode::OK);
+        assert_eq!(version(&patched), "\"2\"");
+
+        let status = test::call_service(&app, delete(&[])).await.status();
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let status = test::call_service(&app, delete(&[(IF_MATCH, "\"1\"")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let status = test::call_service(&app, delete(&[(IF_MATCH, "*")]))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn audit_of_other_resources_on_sqlite() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        let created = test::call_service(
+            &app,
+            request(Method::POST, "/projects")
+                .set_json(json!({ "name": "staging" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let deleted = test::call_service(
+            &app,
+            request(Method::DELETE, "/projects/staging").to_request(),
+        )
+        .await;
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+
+        let events: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, "/audit?resource=project&name=staging").to_request(),
+        )
+        .await;
+        let actions: Vec<_> = events
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["delete", "create"]);
+        assert_eq!(events[0]["project"], "staging");
+        assert_ne!(events[0]["actor"], "");
+    }
+
+    #[actix_web::test]
+    async fn pagination_on_sqlite() {
+        let app = test::init_service(App::new().app_data(state().await).configure(routes)).await;
+        for name in ["n3", "n1", "n5", "n2", "n4"] {
+            let created = test::call_service(
+                &app,
+                request(Method::POST, NODES)
+                    .set_json(json!({ "name": name, "tags": ["edge"] }))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(created.status(), StatusCode::CREATED);
+        }
+
+        for (sort, expected) in [
+            ("name", ["n1", "n2", "n3", "n4", "n5"]),
+            ("-name", ["n5", "n4", "n3", "n2", "n1"]),
+        ] {
+            let mut names = Vec::new();
+            let mut pages = 0;
+            let mut uri = format!("{NODES}?sort={sort}&limit=2&tag=edge");
+            loop {
+                let page: Value =
+                    test::call_and_read_body_json(&app, request(Method::GET, &uri).to_request())
+                        .await;
+                pages += 1;
+                for node in page["nodes"].as_array().unwrap() {
+                    names.push(node["name"].as_str().unwrap().to_string());
+                }
+                match page["next_cursor"].as_str() {
+                    Some(cursor) => {
+                        uri = format!("{NODES}?sort={sort}&limit=2&tag=edge&cursor={cursor}")
+                    }
+                    None => break,
+                }
+            }
+            assert_eq!(names, expected);
+            assert_eq!(pages, 3);
+        }
+
+        // A cursor only continues the listing it came from.
+        let page: Value = test::call_and_read_body_json(
+            &app,
+            request(Method::GET, &format!("{NODES}?limit=2")).to_request(),
+        )
+        .await;
+        let cursor = page["next_cursor"].as_str().unwrap();
+        let status = test::call_service(
+            &app,
+            request(Method::GET, &format!("{NODES}?sort=-name&cursor={cursor}")).to_request(),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 }
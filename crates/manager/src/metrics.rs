@@ -0,0 +1,280 @@
+//! Prometheus metrics of the manager, served at `/metrics`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+use anyhow::Result;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sea_orm::metric::Info;
+use tonic::Code;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::config::ManagerState;
+
+pub use prometheus::TEXT_FORMAT;
+
+const GRPC_SERVICE_PREFIX: &str = "/laval.manager.v1.NodeManager/";
+
+/// Label of requests that match no route or RPC, so that arbitrary paths do
+/// not create new series.
+const UNKNOWN: &str = "unknown";
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: HistogramVec,
+    grpc_requests: HistogramVec,
+    db_queries: HistogramVec,
+    db_errors: IntCounterVec,
+    nodes: IntGaugeVec,
+    nodes_online: IntGauge,
+}
+
+/// Number of nodes by port mapping mode, at the time of a scrape.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NodeCounts {
+    pub server: u64,
+    pub client: u64,
+    /// Nodes without a port mapping.
+    pub none: u64,
+    pub online: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = HistogramVec::new(
+            HistogramOpts::new(
+                "laval_manager_http_request_duration_seconds",
+                "HTTP API requests by route, method and status",
+            ),
+            &["route", "method", "status"],
+        )
+        .expect("valid metric");
+        let grpc_requests = HistogramVec::new(
+            HistogramOpts::new(
+                "laval_manager_grpc_request_duration_seconds",
+                "gRPC API requests by method and status code, until the response headers",
+            ),
+            &["method", "code"],
+        )
+        .expect("valid metric");
+        let db_queries = HistogramVec::new(
+            HistogramOpts::new(
+                "laval_manager_db_query_duration_seconds",
+                "Database statements by operation",
+            ),
+            &["operation"],
+        )
+        .expect("valid metric");
+        let db_errors = IntCounterVec::new(
+            Opts::new(
+                "laval_manager_db_query_errors_total",
+                "Failed database statements by operation",
+            ),
+            &["operation"],
+        )
+        .expect("valid metric");
+        let nodes = IntGaugeVec::new(
+            Opts::new("laval_manager_nodes", "Nodes by port mapping mode"),
+            &["mode"],
+        )
+        .expect("valid metric");
+        let nodes_online = IntGauge::new(
+            "laval_manager_nodes_online",
+            "Nodes that sent a heartbeat recently",
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(grpc_requests.clone()),
+            Box::new(db_queries.clone()),
+            Box::new(db_errors.clone()),
+            Box::new(nodes.clone()),
+            Box::new(nodes_online.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            grpc_requests,
+            db_queries,
+            db_errors,
+            nodes,
+            nodes_online,
+        }
+    }
+
+    pub fn observe_query(&self, info: &Info<'_>) {
+        let operation = operation(&info.statement.sql);
+        self.db_queries
+            .with_label_values(&[operation])
+            .observe(info.elapsed.as_secs_f64());
+        if info.failed {
+            self.db_errors.with_label_values(&[operation]).inc();
+        }
+    }
+
+    fn observe_http(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[route, method, &status.to_string()])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    fn observe_grpc(&self, method: &str, code: Code, elapsed: Duration) {
+        self.grpc_requests
+            .with_label_values(&[method, &format!("{code:?}")])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Encodes every metric, with the node gauges set to `counts`.
+    pub fn render(&self, counts: NodeCounts) -> Result<String> {
+        for (mode, count) in [
+            ("server", counts.server),
+            ("client", counts.client),
+            ("none", counts.none),
+        ] {
+            self.nodes
+                .with_label_values(&[mode])
+                .set(i64::try_from(count).unwrap_or(i64::MAX));
+        }
+        self.nodes_online
+            .set(i64::try_from(counts.online).unwrap_or(i64::MAX));
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The leading keyword of a statement, such as `select` or `insert`.
+fn operation(sql: &str) -> &'static str {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    [
+        "select", "insert", "update", "delete", "begin", "commit", "rollback",
+    ]
+    .into_iter()
+    .find(|operation| keyword.eq_ignore_ascii_case(operation))
+    .unwrap_or("other")
+}
+
+/// Records the route, method, status and latency of every HTTP request.
+pub async fn track_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req
+        .app_data::<web::Data<Arc<ManagerState>>>()
+        .map(|state| state.metrics().clone());
+    let route = req.match_pattern().unwrap_or_else(|| UNKNOWN.to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+    if let Some(metrics) = metrics {
+        metrics.observe_http(&route, &method, status.as_u16(), started.elapsed());
+    }
+    result
+}
+
+/// Records the method, status code and latency of every gRPC call. Streams
+/// are timed until their response headers.
+#[derive(Clone)]
+pub struct GrpcMetricsLayer {
+    metrics: Metrics,
+}
+
+impl GrpcMetricsLayer {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetrics {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcMetrics<S> {
+    inner: S,
+    metrics: Metrics,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcMetrics<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let method = request
+            .uri()
+            .path()
+            .strip_prefix(GRPC_SERVICE_PREFIX)
+            .unwrap_or(UNKNOWN)
+            .to_string();
+        let metrics = self.metrics.clone();
+        let started = Instant::now();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = response.await;
+            // Calls that fail before a message is sent carry their status in
+            // the headers; successful ones only in the trailers.
+            let code = match &result {
+                Ok(response) => response
+                    .headers()
+                    .get("grpc-status")
+                    .map_or(Code::Ok, |status| Code::from_bytes(status.as_bytes())),
+                Err(_) => Code::Unavailable,
+            };
+            let method = if code == Code::Unimplemented {
+                UNKNOWN
+            } else {
+                &method
+            };
+            metrics.observe_grpc(method, code, started.elapsed());
+            result
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    Id,
+    Name,
+    ReverseProxyBind,
+    PortMappingRole,
+    ManagementUrl,
+    Description,
+    Tags,
+    PortMapping,
+}
+
+// `if_not_exists` adopts databases created before migrations existed.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::create()
+            .table(Nodes::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(Nodes::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(Nodes::Name).string().not_null().unique_key())
+            .col(ColumnDef::new(Nodes::ReverseProxyBind).string())
+            .col(ColumnDef::new(Nodes::PortMappingRole).string())
+            .col(ColumnDef::new(Nodes::ManagementUrl).string())
+            .col(ColumnDef::new(Nodes::Description).string())
+            .col(ColumnDef::new(Nodes::Tags).json())
+            .col(ColumnDef::new(Nodes::PortMapping).json())
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(Nodes::Table).to_owned())]
+}
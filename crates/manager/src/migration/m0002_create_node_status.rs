@@ -0,0 +1,49 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum NodeStatus {
+    Table,
+    Id,
+    NodeName,
+    LastSeen,
+    Version,
+    UptimeSeconds,
+    ListenAddrs,
+    Services,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::create()
+            .table(NodeStatus::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(NodeStatus::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(
+                ColumnDef::new(NodeStatus::NodeName)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .col(
+                ColumnDef::new(NodeStatus::LastSeen)
+                    .timestamp_with_time_zone()
+                    .not_null(),
+            )
+            .col(ColumnDef::new(NodeStatus::Version).string())
+            .col(ColumnDef::new(NodeStatus::UptimeSeconds).big_integer())
+            .col(ColumnDef::new(NodeStatus::ListenAddrs).json())
+            .col(ColumnDef::new(NodeStatus::Services).json())
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(NodeStatus::Table).to_owned())]
+}
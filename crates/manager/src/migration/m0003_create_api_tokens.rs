@@ -0,0 +1,50 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum ApiTokens {
+    Table,
+    Id,
+    Name,
+    TokenHash,
+    NodeName,
+    CreatedAt,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::create()
+            .table(ApiTokens::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(ApiTokens::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(
+                ColumnDef::new(ApiTokens::Name)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .col(
+                ColumnDef::new(ApiTokens::TokenHash)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .col(ColumnDef::new(ApiTokens::NodeName).string())
+            .col(
+                ColumnDef::new(ApiTokens::CreatedAt)
+                    .timestamp_with_time_zone()
+                    .not_null(),
+            )
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(ApiTokens::Table).to_owned())]
+}
@@ -0,0 +1,58 @@
+use sea_orm::sea_query::{ColumnDef, Index, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum NodeRevisions {
+    Table,
+    Id,
+    NodeName,
+    Revision,
+    Action,
+    Author,
+    CreatedAt,
+    Snapshot,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::create()
+                .table(NodeRevisions::Table)
+                .col(
+                    ColumnDef::new(NodeRevisions::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(ColumnDef::new(NodeRevisions::NodeName).string().not_null())
+                .col(
+                    ColumnDef::new(NodeRevisions::Revision)
+                        .big_integer()
+                        .not_null(),
+                )
+                .col(ColumnDef::new(NodeRevisions::Action).string().not_null())
+                .col(ColumnDef::new(NodeRevisions::Author).string())
+                .col(
+                    ColumnDef::new(NodeRevisions::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(ColumnDef::new(NodeRevisions::Snapshot).json())
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_node_revisions_node_name_revision")
+                .table(NodeRevisions::Table)
+                .col(NodeRevisions::NodeName)
+                .col(NodeRevisions::Revision)
+                .unique()
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(NodeRevisions::Table).to_owned())]
+}
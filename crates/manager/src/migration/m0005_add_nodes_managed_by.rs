@@ -0,0 +1,33 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    ManagedBy,
+}
+
+// Existing nodes may have been created from the UI, so they are protected
+// from pruning until the configuration file declares them again.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .add_column(
+                ColumnDef::new(Nodes::ManagedBy)
+                    .string()
+                    .not_null()
+                    .default("ui"),
+            )
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .drop_column(Nodes::ManagedBy)
+            .to_owned(),
+    )]
+}
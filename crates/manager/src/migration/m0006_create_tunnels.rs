@@ -0,0 +1,52 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Tunnels {
+    Table,
+    Id,
+    Name,
+    ServerNode,
+    ClientNode,
+    BindAddr,
+    LocalAddr,
+    ServiceType,
+    Token,
+    CreatedAt,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::create()
+            .table(Tunnels::Table)
+            .col(
+                ColumnDef::new(Tunnels::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(
+                ColumnDef::new(Tunnels::Name)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .col(ColumnDef::new(Tunnels::ServerNode).string().not_null())
+            .col(ColumnDef::new(Tunnels::ClientNode).string().not_null())
+            .col(ColumnDef::new(Tunnels::BindAddr).string().not_null())
+            .col(ColumnDef::new(Tunnels::LocalAddr).string().not_null())
+            .col(ColumnDef::new(Tunnels::ServiceType).string().not_null())
+            .col(ColumnDef::new(Tunnels::Token).string().not_null())
+            .col(
+                ColumnDef::new(Tunnels::CreatedAt)
+                    .timestamp_with_time_zone()
+                    .not_null(),
+            )
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(Tunnels::Table).to_owned())]
+}
@@ -0,0 +1,97 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Tunnels {
+    Table,
+    PreviousToken,
+    PreviousTokenExpiresAt,
+    TokenRotatedAt,
+    RotateEverySecs,
+}
+
+#[derive(DeriveIden)]
+enum NodeKeys {
+    Table,
+    Id,
+    NodeName,
+    PrivateKey,
+    PublicKey,
+    CreatedAt,
+}
+
+// SQLite only supports one column per ALTER TABLE statement.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    let columns = [
+        ColumnDef::new(Tunnels::PreviousToken).string().to_owned(),
+        ColumnDef::new(Tunnels::PreviousTokenExpiresAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+        ColumnDef::new(Tunnels::TokenRotatedAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+        ColumnDef::new(Tunnels::RotateEverySecs)
+            .big_integer()
+            .to_owned(),
+    ];
+    let mut statements: Vec<Statement> = columns
+        .into_iter()
+        .map(|mut column| {
+            backend.build(
+                &Table::alter()
+                    .table(Tunnels::Table)
+                    .add_column(&mut column)
+                    .to_owned(),
+            )
+        })
+        .collect();
+
+    statements.push(
+        backend.build(
+            &Table::create()
+                .table(NodeKeys::Table)
+                .col(
+                    ColumnDef::new(NodeKeys::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(
+                    ColumnDef::new(NodeKeys::NodeName)
+                        .string()
+                        .not_null()
+                        .unique_key(),
+                )
+                .col(ColumnDef::new(NodeKeys::PrivateKey).string().not_null())
+                .col(ColumnDef::new(NodeKeys::PublicKey).string().not_null())
+                .col(
+                    ColumnDef::new(NodeKeys::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .to_owned(),
+        ),
+    );
+    statements
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    let mut statements = vec![backend.build(&Table::drop().table(NodeKeys::Table).to_owned())];
+    for column in [
+        Tunnels::PreviousToken,
+        Tunnels::PreviousTokenExpiresAt,
+        Tunnels::TokenRotatedAt,
+        Tunnels::RotateEverySecs,
+    ] {
+        statements.push(
+            backend.build(
+                &Table::alter()
+                    .table(Tunnels::Table)
+                    .drop_column(column)
+                    .to_owned(),
+            ),
+        );
+    }
+    statements
+}
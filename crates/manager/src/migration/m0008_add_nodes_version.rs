@@ -0,0 +1,31 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    Version,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .add_column(
+                ColumnDef::new(Nodes::Version)
+                    .big_integer()
+                    .not_null()
+                    .default(1),
+            )
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .drop_column(Nodes::Version)
+            .to_owned(),
+    )]
+}
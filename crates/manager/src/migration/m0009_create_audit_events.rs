@@ -0,0 +1,60 @@
+use sea_orm::sea_query::{ColumnDef, Index, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Id,
+    CreatedAt,
+    Actor,
+    SourceIp,
+    Action,
+    NodeName,
+    Changes,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::create()
+                .table(AuditEvents::Table)
+                .col(
+                    ColumnDef::new(AuditEvents::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(
+                    ColumnDef::new(AuditEvents::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(ColumnDef::new(AuditEvents::Actor).string().not_null())
+                .col(ColumnDef::new(AuditEvents::SourceIp).string())
+                .col(ColumnDef::new(AuditEvents::Action).string().not_null())
+                .col(ColumnDef::new(AuditEvents::NodeName).string().not_null())
+                .col(ColumnDef::new(AuditEvents::Changes).json().not_null())
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_audit_events_created_at")
+                .table(AuditEvents::Table)
+                .col(AuditEvents::CreatedAt)
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_audit_events_node_name_created_at")
+                .table(AuditEvents::Table)
+                .col(AuditEvents::NodeName)
+                .col(AuditEvents::CreatedAt)
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(AuditEvents::Table).to_owned())]
+}
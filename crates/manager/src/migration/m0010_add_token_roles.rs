@@ -0,0 +1,71 @@
+use sea_orm::sea_query::{ColumnDef, Expr, Query, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum ApiTokens {
+    Table,
+    NodeName,
+    Role,
+    Tags,
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Detail,
+}
+
+// SQLite only supports one column per ALTER TABLE statement. Tokens issued
+// before roles existed had full access, so user tokens become admins.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .add_column(ColumnDef::new(ApiTokens::Role).string())
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .add_column(ColumnDef::new(ApiTokens::Tags).json())
+                .to_owned(),
+        ),
+        backend.build(
+            &Query::update()
+                .table(ApiTokens::Table)
+                .value(ApiTokens::Role, "admin")
+                .and_where(Expr::col(ApiTokens::NodeName).is_null())
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(AuditEvents::Table)
+                .add_column(ColumnDef::new(AuditEvents::Detail).string())
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::alter()
+                .table(AuditEvents::Table)
+                .drop_column(AuditEvents::Detail)
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .drop_column(ApiTokens::Tags)
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .drop_column(ApiTokens::Role)
+                .to_owned(),
+        ),
+    ]
+}
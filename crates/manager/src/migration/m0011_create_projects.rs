@@ -0,0 +1,502 @@
+use chrono::Utc;
+use sea_orm::sea_query::{
+    Alias, ColumnDef, Expr, Index, IntoIden, Query, Table, TableCreateStatement,
+};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+/// Every existing node, tunnel and node token is moved into this project.
+const DEFAULT_PROJECT: &str = "default";
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+    Name,
+    Description,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    Id,
+    Project,
+    Name,
+    ReverseProxyBind,
+    PortMappingRole,
+    ManagementUrl,
+    Description,
+    Tags,
+    PortMapping,
+    ManagedBy,
+    Version,
+}
+
+#[derive(DeriveIden)]
+enum NodeStatus {
+    Table,
+    Id,
+    Project,
+    NodeName,
+    LastSeen,
+    Version,
+    UptimeSeconds,
+    ListenAddrs,
+    Services,
+}
+
+#[derive(DeriveIden)]
+enum NodeKeys {
+    Table,
+    Id,
+    Project,
+    NodeName,
+    PrivateKey,
+    PublicKey,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Tunnels {
+    Table,
+    Id,
+    Project,
+    Name,
+    ServerNode,
+    ClientNode,
+    BindAddr,
+    LocalAddr,
+    ServiceType,
+    Token,
+    CreatedAt,
+    PreviousToken,
+    PreviousTokenExpiresAt,
+    TokenRotatedAt,
+    RotateEverySecs,
+}
+
+#[derive(DeriveIden)]
+enum NodeRevisions {
+    Table,
+    Project,
+    NodeName,
+    Revision,
+}
+
+#[derive(DeriveIden)]
+enum ApiTokens {
+    Table,
+    Project,
+    NodeName,
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Project,
+    NodeName,
+}
+
+/// A table whose names become unique per project instead of globally.
+struct Scoped {
+    table: &'static str,
+    key: &'static str,
+    /// Unique index on the project and `key`.
+    index: &'static str,
+    /// The table with or without the project column. Without it, `key` is
+    /// unique on its own, as created by earlier migrations.
+    define: fn(bool) -> TableCreateStatement,
+}
+
+const SCOPED: &[Scoped] = &[
+    Scoped {
+        table: "nodes",
+        key: "name",
+        index: "idx_nodes_project_name",
+        define: nodes,
+    },
+    Scoped {
+        table: "node_status",
+        key: "node_name",
+        index: "idx_node_status_project_node_name",
+        define: node_status,
+    },
+    Scoped {
+        table: "node_keys",
+        key: "node_name",
+        index: "idx_node_keys_project_node_name",
+        define: node_keys,
+    },
+    Scoped {
+        table: "tunnels",
+        key: "name",
+        index: "idx_tunnels_project_name",
+        define: tunnels,
+    },
+];
+
+// Names were unique across the manager, so existing rows cannot clash within
+// the default project. SQLite cannot drop the old unique constraints, so it
+// rebuilds those tables instead.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    let mut statements = vec![
+        backend.build(
+            &Table::create()
+                .table(Projects::Table)
+                .col(
+                    ColumnDef::new(Projects::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(
+                    ColumnDef::new(Projects::Name)
+                        .string()
+                        .not_null()
+                        .unique_key(),
+                )
+                .col(ColumnDef::new(Projects::Description).string())
+                .col(
+                    ColumnDef::new(Projects::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .to_owned(),
+        ),
+        backend.build(
+            &Query::insert()
+                .into_table(Projects::Table)
+                .columns([Projects::Name, Projects::CreatedAt])
+                .values_panic([DEFAULT_PROJECT.into(), Utc::now().into()])
+                .to_owned(),
+        ),
+    ];
+
+    for scoped in SCOPED {
+        match backend {
+            DbBackend::Sqlite => statements.extend(rebuild(
+                backend,
+                scoped.table,
+                (scoped.define)(true),
+                &(scoped.define)(false),
+            )),
+            _ => {
+                statements.push(
+                    backend.build(
+                        &Table::alter()
+                            .table(Alias::new(scoped.table))
+                            .add_column(project_column(Alias::new("project")))
+                            .to_owned(),
+                    ),
+                );
+                statements.push(Statement::from_string(
+                    backend,
+                    format!(
+                        r#"ALTER TABLE "{0}" DROP CONSTRAINT "{0}_{1}_key""#,
+                        scoped.table, scoped.key
+                    ),
+                ));
+            }
+        }
+        statements.push(
+            backend.build(
+                &Index::create()
+                    .name(scoped.index)
+                    .table(Alias::new(scoped.table))
+                    .col(Alias::new("project"))
+                    .col(Alias::new(scoped.key))
+                    .unique()
+                    .to_owned(),
+            ),
+        );
+    }
+
+    statements.extend([
+        backend.build(
+            &Table::alter()
+                .table(NodeRevisions::Table)
+                .add_column(project_column(NodeRevisions::Project))
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::drop()
+                .name("idx_node_revisions_node_name_revision")
+                .table(NodeRevisions::Table)
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_node_revisions_project_node_name_revision")
+                .table(NodeRevisions::Table)
+                .col(NodeRevisions::Project)
+                .col(NodeRevisions::NodeName)
+                .col(NodeRevisions::Revision)
+                .unique()
+                .to_owned(),
+        ),
+        // Only node tokens and events about a node belong to a project.
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .add_column(ColumnDef::new(ApiTokens::Project).string())
+                .to_owned(),
+        ),
+        backend.build(
+            &Query::update()
+                .table(ApiTokens::Table)
+                .value(ApiTokens::Project, DEFAULT_PROJECT)
+                .and_where(Expr::col(ApiTokens::NodeName).is_not_null())
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(AuditEvents::Table)
+                .add_column(ColumnDef::new(AuditEvents::Project).string())
+                .to_owned(),
+        ),
+        backend.build(
+            &Query::update()
+                .table(AuditEvents::Table)
+                .value(AuditEvents::Project, DEFAULT_PROJECT)
+                .and_where(Expr::col(AuditEvents::NodeName).ne(""))
+                .to_owned(),
+        ),
+    ]);
+    statements
+}
+
+// Fails if names were reused across projects in the meantime.
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    let mut statements = vec![
+        backend.build(
+            &Table::alter()
+                .table(AuditEvents::Table)
+                .drop_column(AuditEvents::Project)
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(ApiTokens::Table)
+                .drop_column(ApiTokens::Project)
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::drop()
+                .name("idx_node_revisions_project_node_name_revision")
+                .table(NodeRevisions::Table)
+                .to_owned(),
+        ),
+        backend.build(
+            &Table::alter()
+                .table(NodeRevisions::Table)
+                .drop_column(NodeRevisions::Project)
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_node_revisions_node_name_revision")
+                .table(NodeRevisions::Table)
+                .col(NodeRevisions::NodeName)
+                .col(NodeRevisions::Revision)
+                .unique()
+                .to_owned(),
+        ),
+    ];
+
+    for scoped in SCOPED.iter().rev() {
+        match backend {
+            DbBackend::Sqlite => statements.extend(rebuild(
+                backend,
+                scoped.table,
+                (scoped.define)(false),
+                &(scoped.define)(false),
+            )),
+            _ => {
+                statements.push(
+                    backend.build(
+                        &Index::drop()
+                            .name(scoped.index)
+                            .table(Alias::new(scoped.table))
+                            .to_owned(),
+                    ),
+                );
+                statements.push(
+                    backend.build(
+                        &Table::alter()
+                            .table(Alias::new(scoped.table))
+                            .drop_column(Alias::new("project"))
+                            .to_owned(),
+                    ),
+                );
+                statements.push(Statement::from_string(
+                    backend,
+                    format!(
+                        r#"ALTER TABLE "{0}" ADD CONSTRAINT "{0}_{1}_key" UNIQUE ("{1}")"#,
+                        scoped.table, scoped.key
+                    ),
+                ));
+            }
+        }
+    }
+
+    statements.push(backend.build(&Table::drop().table(Projects::Table).to_owned()));
+    statements
+}
+
+// Replaces `table` with one created from `definition`, keeping the columns of
+// `previous`, which is how the table is defined before the rebuild.
+fn rebuild(
+    backend: DbBackend,
+    table: &str,
+    mut definition: TableCreateStatement,
+    previous: &TableCreateStatement,
+) -> Vec<Statement> {
+    let rebuilt = format!("{table}_rebuilt");
+    let columns = previous
+        .get_columns()
+        .iter()
+        .map(|column| format!(r#""{}""#, column.get_column_name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    vec![
+        backend.build(definition.table(Alias::new(&rebuilt))),
+        Statement::from_string(
+            backend,
+            format!(r#"INSERT INTO "{rebuilt}" ({columns}) SELECT {columns} FROM "{table}""#),
+        ),
+        backend.build(&Table::drop().table(Alias::new(table)).to_owned()),
+        backend.build(
+            &Table::rename()
+                .table(Alias::new(rebuilt), Alias::new(table))
+                .to_owned(),
+        ),
+    ]
+}
+
+fn project_column(name: impl IntoIden) -> ColumnDef {
+    ColumnDef::new(name)
+        .string()
+        .not_null()
+        .default(DEFAULT_PROJECT)
+        .to_owned()
+}
+
+fn id_column(name: impl IntoIden) -> ColumnDef {
+    ColumnDef::new(name)
+        .integer()
+        .not_null()
+        .auto_increment()
+        .primary_key()
+        .to_owned()
+}
+
+// Adds the project column, or makes `key` unique by itself without one.
+fn scoped_key(
+    table: &mut TableCreateStatement,
+    project: impl IntoIden,
+    key: impl IntoIden,
+    scoped: bool,
+) {
+    let mut key = ColumnDef::new(key).string().not_null().to_owned();
+    if scoped {
+        table.col(project_column(project));
+    } else {
+        key.unique_key();
+    }
+    table.col(key);
+}
+
+fn nodes(scoped: bool) -> TableCreateStatement {
+    let mut table = Table::create()
+        .table(Nodes::Table)
+        .col(id_column(Nodes::Id))
+        .to_owned();
+    scoped_key(&mut table, Nodes::Project, Nodes::Name, scoped);
+    table
+        .col(ColumnDef::new(Nodes::ReverseProxyBind).string())
+        .col(ColumnDef::new(Nodes::PortMappingRole).string())
+        .col(ColumnDef::new(Nodes::ManagementUrl).string())
+        .col(ColumnDef::new(Nodes::Description).string())
+        .col(ColumnDef::new(Nodes::Tags).json())
+        .col(ColumnDef::new(Nodes::PortMapping).json())
+        .col(
+            ColumnDef::new(Nodes::ManagedBy)
+                .string()
+                .not_null()
+                .default("ui"),
+        )
+        .col(
+            ColumnDef::new(Nodes::Version)
+                .big_integer()
+                .not_null()
+                .default(1),
+        )
+        .to_owned()
+}
+
+fn node_status(scoped: bool) -> TableCreateStatement {
+    let mut table = Table::create()
+        .table(NodeStatus::Table)
+        .col(id_column(NodeStatus::Id))
+        .to_owned();
+    scoped_key(
+        &mut table,
+        NodeStatus::Project,
+        NodeStatus::NodeName,
+        scoped,
+    );
+    table
+        .col(
+            ColumnDef::new(NodeStatus::LastSeen)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(ColumnDef::new(NodeStatus::Version).string())
+        .col(ColumnDef::new(NodeStatus::UptimeSeconds).big_integer())
+        .col(ColumnDef::new(NodeStatus::ListenAddrs).json())
+        .col(ColumnDef::new(NodeStatus::Services).json())
+        .to_owned()
+}
+
+fn node_keys(scoped: bool) -> TableCreateStatement {
+    let mut table = Table::create()
+        .table(NodeKeys::Table)
+        .col(id_column(NodeKeys::Id))
+        .to_owned();
+    scoped_key(&mut table, NodeKeys::Project, NodeKeys::NodeName, scoped);
+    table
+        .col(ColumnDef::new(NodeKeys::PrivateKey).string().not_null())
+        .col(ColumnDef::new(NodeKeys::PublicKey).string().not_null())
+        .col(
+            ColumnDef::new(NodeKeys::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .to_owned()
+}
+
+fn tunnels(scoped: bool) -> TableCreateStatement {
+    let mut table = Table::create()
+        .table(Tunnels::Table)
+        .col(id_column(Tunnels::Id))
+        .to_owned();
+    scoped_key(&mut table, Tunnels::Project, Tunnels::Name, scoped);
+    table
+        .col(ColumnDef::new(Tunnels::ServerNode).string().not_null())
+        .col(ColumnDef::new(Tunnels::ClientNode).string().not_null())
+        .col(ColumnDef::new(Tunnels::BindAddr).string().not_null())
+        .col(ColumnDef::new(Tunnels::LocalAddr).string().not_null())
+        .col(ColumnDef::new(Tunnels::ServiceType).string().not_null())
+        .col(ColumnDef::new(Tunnels::Token).string().not_null())
+        .col(
+            ColumnDef::new(Tunnels::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(ColumnDef::new(Tunnels::PreviousToken).string())
+        .col(ColumnDef::new(Tunnels::PreviousTokenExpiresAt).timestamp_with_time_zone())
+        .col(ColumnDef::new(Tunnels::TokenRotatedAt).timestamp_with_time_zone())
+        .col(ColumnDef::new(Tunnels::RotateEverySecs).big_integer())
+        .to_owned()
+}
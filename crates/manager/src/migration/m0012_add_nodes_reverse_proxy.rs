@@ -0,0 +1,28 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    ReverseProxy,
+}
+
+// Existing nodes keep the routes of their local configuration until the
+// manager is given some.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .add_column(ColumnDef::new(Nodes::ReverseProxy).json())
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .drop_column(Nodes::ReverseProxy)
+            .to_owned(),
+    )]
+}
@@ -0,0 +1,80 @@
+use sea_orm::sea_query::{ColumnDef, Index, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Certificates {
+    Table,
+    Id,
+    Project,
+    Name,
+    ChainPem,
+    KeyPem,
+    Hostnames,
+    Issuer,
+    Fingerprint,
+    NotBefore,
+    NotAfter,
+    CreatedAt,
+    UpdatedAt,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::create()
+                .table(Certificates::Table)
+                .col(
+                    ColumnDef::new(Certificates::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(ColumnDef::new(Certificates::Project).string().not_null())
+                .col(ColumnDef::new(Certificates::Name).string().not_null())
+                .col(ColumnDef::new(Certificates::ChainPem).text().not_null())
+                .col(ColumnDef::new(Certificates::KeyPem).text().not_null())
+                .col(ColumnDef::new(Certificates::Hostnames).json().not_null())
+                .col(ColumnDef::new(Certificates::Issuer).string().not_null())
+                .col(
+                    ColumnDef::new(Certificates::Fingerprint)
+                        .string()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(Certificates::NotBefore)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(Certificates::NotAfter)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(Certificates::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(Certificates::UpdatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_certificates_project_name")
+                .table(Certificates::Table)
+                .col(Certificates::Project)
+                .col(Certificates::Name)
+                .unique()
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(Certificates::Table).to_owned())]
+}
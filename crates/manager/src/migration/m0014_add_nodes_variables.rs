@@ -0,0 +1,27 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Nodes {
+    Table,
+    Variables,
+}
+
+// Unset until a node is bound to a template.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .add_column(ColumnDef::new(Nodes::Variables).json())
+            .to_owned(),
+    )]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(Nodes::Table)
+            .drop_column(Nodes::Variables)
+            .to_owned(),
+    )]
+}
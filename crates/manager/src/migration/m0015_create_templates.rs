@@ -0,0 +1,62 @@
+use sea_orm::sea_query::{ColumnDef, Index, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum Templates {
+    Table,
+    Id,
+    Project,
+    Name,
+    Description,
+    Selector,
+    PortMapping,
+    ReverseProxy,
+    CreatedAt,
+    UpdatedAt,
+}
+
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::create()
+                .table(Templates::Table)
+                .col(
+                    ColumnDef::new(Templates::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(ColumnDef::new(Templates::Project).string().not_null())
+                .col(ColumnDef::new(Templates::Name).string().not_null())
+                .col(ColumnDef::new(Templates::Description).string())
+                .col(ColumnDef::new(Templates::Selector).json().not_null())
+                .col(ColumnDef::new(Templates::PortMapping).json())
+                .col(ColumnDef::new(Templates::ReverseProxy).json())
+                .col(
+                    ColumnDef::new(Templates::CreatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(Templates::UpdatedAt)
+                        .timestamp_with_time_zone()
+                        .not_null(),
+                )
+                .to_owned(),
+        ),
+        backend.build(
+            &Index::create()
+                .name("idx_templates_project_name")
+                .table(Templates::Table)
+                .col(Templates::Project)
+                .col(Templates::Name)
+                .unique()
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(&Table::drop().table(Templates::Table).to_owned())]
+}
@@ -0,0 +1,54 @@
+use sea_orm::sea_query::{ColumnDef, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum NodeKeys {
+    Table,
+    PreviousPrivateKey,
+    PreviousPublicKey,
+    PreviousExpiresAt,
+}
+
+// Set while a rotated key is still accepted. SQLite only supports one column
+// per ALTER TABLE statement.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    [
+        ColumnDef::new(NodeKeys::PreviousPrivateKey)
+            .string()
+            .to_owned(),
+        ColumnDef::new(NodeKeys::PreviousPublicKey)
+            .string()
+            .to_owned(),
+        ColumnDef::new(NodeKeys::PreviousExpiresAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+    ]
+    .into_iter()
+    .map(|mut column| {
+        backend.build(
+            &Table::alter()
+                .table(NodeKeys::Table)
+                .add_column(&mut column)
+                .to_owned(),
+        )
+    })
+    .collect()
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    [
+        NodeKeys::PreviousPrivateKey,
+        NodeKeys::PreviousPublicKey,
+        NodeKeys::PreviousExpiresAt,
+    ]
+    .into_iter()
+    .map(|column| {
+        backend.build(
+            &Table::alter()
+                .table(NodeKeys::Table)
+                .drop_column(column)
+                .to_owned(),
+        )
+    })
+    .collect()
+}
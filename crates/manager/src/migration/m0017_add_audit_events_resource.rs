@@ -0,0 +1,38 @@
+use sea_orm::sea_query::{ColumnDef, Expr, Query, Table};
+use sea_orm::{DbBackend, DeriveIden, Statement};
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Resource,
+    NodeName,
+}
+
+// Every event so far was about a node, except denied requests that did not
+// target one.
+pub fn up(backend: DbBackend) -> Vec<Statement> {
+    vec![
+        backend.build(
+            &Table::alter()
+                .table(AuditEvents::Table)
+                .add_column(ColumnDef::new(AuditEvents::Resource).string())
+                .to_owned(),
+        ),
+        backend.build(
+            &Query::update()
+                .table(AuditEvents::Table)
+                .value(AuditEvents::Resource, "node")
+                .and_where(Expr::col(AuditEvents::NodeName).ne(""))
+                .to_owned(),
+        ),
+    ]
+}
+
+pub fn down(backend: DbBackend) -> Vec<Statement> {
+    vec![backend.build(
+        &Table::alter()
+            .table(AuditEvents::Table)
+            .drop_column(AuditEvents::Resource)
+            .to_owned(),
+    )]
+}
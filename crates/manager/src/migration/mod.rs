@@ -0,0 +1,335 @@
+//! Ordered schema migrations for the manager database.
+//!
+//! Applied versions are recorded in the `schema_history` table. New schema
+//! changes get a new `mNNNN_*` module appended to [`MIGRATIONS`]; released
+//! migrations must never be edited.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::TableCreateStatement;
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Schema, Set,
+    Statement, TransactionTrait,
+};
+use tracing::info;
+
+use crate::entity::schema_history;
+
+mod m0001_create_nodes;
+mod m0002_create_node_status;
+mod m0003_create_api_tokens;
+mod m0004_create_node_revisions;
+mod m0005_add_nodes_managed_by;
+mod m0006_create_tunnels;
+mod m0007_add_managed_secrets;
+mod m0008_add_nodes_version;
+mod m0009_create_audit_events;
+mod m0010_add_token_roles;
+mod m0011_create_projects;
+mod m0012_add_nodes_reverse_proxy;
+mod m0013_create_certificates;
+mod m0014_add_nodes_variables;
+mod m0015_create_templates;
+mod m0016_add_node_keys_previous;
+mod m0017_add_audit_events_resource;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    up: fn(DbBackend) -> Vec<Statement>,
+    down: fn(DbBackend) -> Vec<Statement>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_nodes",
+        up: m0001_create_nodes::up,
+        down: m0001_create_nodes::down,
+    },
+    Migration {
+        version: 2,
+        name: "create_node_status",
+        up: m0002_create_node_status::up,
+        down: m0002_create_node_status::down,
+    },
+    Migration {
+        version: 3,
+        name: "create_api_tokens",
+        up: m0003_create_api_tokens::up,
+        down: m0003_create_api_tokens::down,
+    },
+    Migration {
+        version: 4,
+        name: "create_node_revisions",
+        up: m0004_create_node_revisions::up,
+        down: m0004_create_node_revisions::down,
+    },
+    Migration {
+        version: 5,
+        name: "add_nodes_managed_by",
+        up: m0005_add_nodes_managed_by::up,
+        down: m0005_add_nodes_managed_by::down,
+    },
+    Migration {
+        version: 6,
+        name: "create_tunnels",
+        up: m0006_create_tunnels::up,
+        down: m0006_create_tunnels::down,
+    },
+    Migration {
+        version: 7,
+        name: "add_managed_secrets",
+        up: m0007_add_managed_secrets::up,
+        down: m0007_add_managed_secrets::down,
+    },
+    Migration {
+        version: 8,
+        name: "add_nodes_version",
+        up: m0008_add_nodes_version::up,
+        down: m0008_add_nodes_version::down,
+    },
+    Migration {
+        version: 9,
+        name: "create_audit_events",
+        up: m0009_create_audit_events::up,
+        down: m0009_create_audit_events::down,
+    },
+    Migration {
+        version: 10,
+        name: "add_token_roles",
+        up: m0010_add_token_roles::up,
+        down: m0010_add_token_roles::down,
+    },
+    Migration {
+        version: 11,
+        name: "create_projects",
+        up: m0011_create_projects::up,
+        down: m0011_create_projects::down,
+    },
+    Migration {
+        version: 12,
+        name: "add_nodes_reverse_proxy",
+        up: m0012_add_nodes_reverse_proxy::up,
+        down: m0012_add_nodes_reverse_proxy::down,
+    },
+    Migration {
+        version: 13,
+        name: "create_certificates",
+        up: m0013_create_certificates::up,
+        down: m0013_create_certificates::down,
+    },
+    Migration {
+        version: 14,
+        name: "add_nodes_variables",
+        up: m0014_add_nodes_variables::up,
+        down: m0014_add_nodes_variables::down,
+    },
+    Migration {
+        version: 15,
+        name: "create_templates",
+        up: m0015_create_templates::up,
+        down: m0015_create_templates::down,
+    },
+    Migration {
+        version: 16,
+        name: "add_node_keys_previous",
+        up: m0016_add_node_keys_previous::up,
+        down: m0016_add_node_keys_previous::down,
+    },
+    Migration {
+        version: 17,
+        name: "add_audit_events_resource",
+        up: m0017_add_audit_events_resource::up,
+        down: m0017_add_audit_events_resource::down,
+    },
+];
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Applies pending migrations up to and including `target`, or all of them.
+/// Returns the migrations that were applied.
+pub async fn up(db: &DatabaseConnection, target: Option<i64>) -> Result<Vec<&'static Migration>> {
+    let applied = applied_versions(db).await?;
+    let mut ran = Vec::new();
+
+    for migration in MIGRATIONS {
+        if target.is_some_and(|target| migration.version > target) {
+            break;
+        }
+        if applied.contains_key(&migration.version) {
+            continue;
+        }
+
+        info!(
+            version = migration.version,
+            name = migration.name,
+            "applying migration"
+        );
+        let txn = db.begin().await?;
+        for statement in (migration.up)(db.get_database_backend()) {
+            txn.execute(statement).await.with_context(|| {
+                format!(
+                    "migration {:04}_{} failed",
+                    migration.version, migration.name
+                )
+            })?;
+        }
+        schema_history::ActiveModel {
+            version: Set(migration.version),
+            name: Set(migration.name.to_string()),
+            applied_at: Set(Utc::now()),
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        ran.push(migration);
+    }
+
+    Ok(ran)
+}
+
+/// Reverts the `steps` most recently applied migrations, newest first.
+pub async fn down(db: &DatabaseConnection, steps: usize) -> Result<Vec<&'static Migration>> {
+    let applied = applied_versions(db).await?;
+    let mut reverted = Vec::new();
+
+    for version in applied.keys().rev().take(steps) {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.version == *version)
+            .expect("applied_versions only returns known migrations");
+
+        info!(
+            version = migration.version,
+            name = migration.name,
+            "reverting migration"
+        );
+        let txn = db.begin().await?;
+        for statement in (migration.down)(db.get_database_backend()) {
+            txn.execute(statement).await.with_context(|| {
+                format!(
+                    "reverting migration {:04}_{} failed",
+                    migration.version, migration.name
+                )
+            })?;
+        }
+        schema_history::Entity::delete_by_id(migration.version)
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        reverted.push(migration);
+    }
+
+    Ok(reverted)
+}
+
+pub async fn status(db: &DatabaseConnection) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_versions(db).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name,
+            applied_at: applied
+                .get(&migration.version)
+                .map(|entry| entry.applied_at),
+        })
+        .collect())
+}
+
+async fn applied_versions(db: &DatabaseConnection) -> Result<BTreeMap<i64, schema_history::Model>> {
+    let backend = db.get_database_backend();
+    let mut table: TableCreateStatement =
+        Schema::new(backend).create_table_from_entity(schema_history::Entity);
+    db.execute(backend.build(table.if_not_exists()))
+        .await
+        .context("failed to create the schema history table")?;
+
+    let applied: BTreeMap<_, _> = schema_history::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|entry| (entry.version, entry))
+        .collect();
+
+    if let Some(unknown) = applied
+        .values()
+        .find(|entry| !MIGRATIONS.iter().any(|m| m.version == entry.version))
+    {
+        bail!(
+            "database has migration {:04}_{} which this manager does not know; \
+             upgrade laval-manager before using this database",
+            unknown.version,
+            unknown.name
+        );
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::connect;
+
+    // Reverting every migration must leave an empty database that they all
+    // apply to again, with a node in the tables m0011 rebuilds on SQLite.
+    #[tokio::test]
+    async fn up_down_up_on_sqlite() {
+        let db = connect("sqlite::memory:").await.unwrap();
+        up(&db, Some(10)).await.unwrap();
+        execute(&db, r#"INSERT INTO "nodes" ("name") VALUES ('edge')"#).await;
+
+        let ran = up(&db, None).await.unwrap();
+        assert_eq!(ran.len(), MIGRATIONS.len() - 10);
+        assert_eq!(
+            names(
+                &db,
+                r#"SELECT "project" || '/' || "name" AS "name" FROM "nodes""#
+            )
+            .await,
+            ["default/edge"]
+        );
+
+        let reverted = down(&db, MIGRATIONS.len()).await.unwrap();
+        assert_eq!(reverted.len(), MIGRATIONS.len());
+        assert!(status(&db)
+            .await
+            .unwrap()
+            .iter()
+            .all(|migration| migration.applied_at.is_none()));
+        assert_eq!(
+            names(
+                &db,
+                r#"SELECT "name" FROM "sqlite_master" WHERE "type" = 'table' AND "name" NOT LIKE 'sqlite%'"#
+            )
+            .await,
+            ["schema_history"]
+        );
+
+        let ran = up(&db, None).await.unwrap();
+        assert_eq!(ran.len(), MIGRATIONS.len());
+    }
+
+    async fn execute(db: &DatabaseConnection, sql: &str) {
+        db.execute(Statement::from_string(db.get_database_backend(), sql))
+            .await
+            .unwrap();
+    }
+
+    async fn names(db: &DatabaseConnection, sql: &str) -> Vec<String> {
+        db.query_all(Statement::from_string(db.get_database_backend(), sql))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.try_get::<String>("", "name").unwrap())
+            .collect()
+    }
+}
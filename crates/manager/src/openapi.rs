@@ -0,0 +1,266 @@
+//! OpenAPI document of the HTTP API, generated from the handlers and the
+//! serde types they exchange.
+
+use actix_web::web;
+use utoipa::openapi::path::Operation;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, OpenApi as Document, PathItem, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+const SECURITY_SCHEME: &str = "api_token";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Laval manager",
+        description = "Manages the configuration of Laval nodes and the Rathole tunnels between them."
+    ),
+    paths(
+        crate::health,
+        crate::render_metrics,
+        serve,
+        crate::list_projects,
+        crate::create_project,
+        crate::get_project,
+        crate::delete_project,
+        crate::list_nodes,
+        crate::create_node,
+        crate::get_node,
+        crate::update_node,
+        crate::patch_node,
+        crate::delete_node,
+        crate::list_revisions,
+        crate::get_revision,
+        crate::rollback_node,
+        crate::diff_revisions,
+        crate::get_noise_key,
+        crate::rotate_noise_key,
+        crate::render_node,
+        crate::export_nodes,
+        crate::import_nodes,
+        crate::stream_events,
+        crate::list_audit_events,
+        crate::list_tunnels,
+        crate::create_tunnel,
+        crate::get_tunnel,
+        crate::delete_tunnel,
+        crate::rotate_tunnel_token,
+        crate::list_certificates,
+        crate::upload_certificate,
+        crate::get_certificate,
+        crate::replace_certificate,
+        crate::delete_certificate,
+        crate::list_templates,
+        crate::create_template,
+        crate::get_template,
+        crate::update_template,
+        crate::delete_template,
+        crate::template_nodes,
+        crate::apply_template,
+        crate::list_tokens,
+        crate::create_token,
+        crate::delete_token,
+    ),
+    modifiers(&ApiTokenAuth),
+    tags(
+        (name = "system", description = "Health, metrics and this document"),
+        (name = "projects", description = "Projects, which group nodes and the tunnels between them"),
+        (name = "nodes", description = "Nodes, their revisions and Noise keys"),
+        (name = "inventory", description = "Export and import of the nodes of a project"),
+        (name = "events", description = "Live feed of changes to nodes"),
+        (name = "audit", description = "Log of changes to nodes and of denied requests"),
+        (name = "tunnels", description = "Tunnels between a server and a client node"),
+        (name = "certificates", description = "TLS certificates served by the reverse proxy of nodes"),
+        (name = "templates", description = "Port mappings and routes shared by the nodes of a tag selector"),
+        (name = "tokens", description = "API tokens"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token scheme, and the errors it causes on every
+/// operation that requires it.
+struct ApiTokenAuth;
+
+impl Modify for ApiTokenAuth {
+    fn modify(&self, openapi: &mut Document) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                SECURITY_SCHEME,
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some(
+                            "A user API token, as `Bearer lvl_...`. Its role and tags decide what it may do.",
+                        ))
+                        .build(),
+                ),
+            );
+
+        for item in openapi.paths.paths.values_mut() {
+            for operation in operations(item).into_iter().flatten() {
+                if operation.security.is_none() {
+                    continue;
+                }
+                for (status, description) in [
+                    ("401", "Missing or invalid API token"),
+                    (
+                        "403",
+                        "The role or tags of the token do not allow this, or node credentials were used",
+                    ),
+                ] {
+                    let response = ResponseBuilder::new()
+                        .description(description)
+                        .content(
+                            "application/json",
+                            ContentBuilder::new()
+                                .schema(Some(Ref::from_schema_name("ErrorBody")))
+                                .build(),
+                        )
+                        .build();
+                    operation
+                        .responses
+                        .responses
+                        .insert(status.to_string(), response.into());
+                }
+            }
+        }
+    }
+}
+
+fn operations(item: &mut PathItem) -> [Option<&mut Operation>; 5] {
+    [
+        item.get.as_mut(),
+        item.post.as_mut(),
+        item.put.as_mut(),
+        item.patch.as_mut(),
+        item.delete.as_mut(),
+    ]
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "system",
+    responses(
+        (status = 200, description = "This document", content_type = "application/json"),
+    ),
+)]
+pub async fn serve() -> web::Json<Document> {
+    web::Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{test, App, HttpResponse};
+
+    use super::*;
+    use crate::fixture;
+
+    const TOKEN: &str = "lvl_openapi_test";
+    // In the order of [`operations`].
+    const METHODS: [Method; 5] = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+    ];
+
+    // Sends every method to every documented path through the routes the
+    // server uses: documented operations must reach a handler, and anything
+    // else must be refused by the router.
+    #[actix_web::test]
+    async fn spec_matches_routes() {
+        let db = std::env::temp_dir().join(format!("laval-openapi-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let state = fixture::open(&format!("sqlite://{}", db.display()), Some(TOKEN)).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(state)))
+                .configure(crate::routes)
+                .default_service(web::to(|| async { HttpResponse::ImATeapot().finish() })),
+        )
+        .await;
+
+        let spec = ApiDoc::openapi();
+        assert!(!spec.paths.paths.is_empty());
+        for (path, item) in &spec.paths.paths {
+            let uri = fill_parameters(path);
+            let mut item = item.clone();
+            let documented = operations(&mut item).map(|operation| operation.is_some());
+            for (method, documented) in METHODS.into_iter().zip(documented) {
+                let request = test::TestRequest::default()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .insert_header((AUTHORIZATION, format!("Bearer {TOKEN}")))
+                    .to_request();
+                let status = match test::try_call_service(&app, request).await {
+                    Ok(response) => response.status(),
+                    Err(err) => err.as_response_error().status_code(),
+                };
+                let routed =
+                    status != StatusCode::IM_A_TEAPOT && status != StatusCode::METHOD_NOT_ALLOWED;
+                assert_eq!(
+                    routed,
+                    documented,
+                    "{method} {path} is {} but {}",
+                    if documented {
+                        "documented"
+                    } else {
+                        "not documented"
+                    },
+                    if routed { "routed" } else { "not routed" },
+                );
+            }
+        }
+
+        let _ = std::fs::remove_file(db);
+    }
+
+    // The other way round, every route the server mounts must be documented.
+    // The scopes kept from before projects are left out of the document, so
+    // their routes must be documented under `/projects/{project}` instead.
+    #[test]
+    fn routes_are_documented() {
+        let spec = ApiDoc::openapi();
+        for scope in crate::scopes() {
+            let prefix = match scope.access {
+                crate::Access::DefaultProject => format!("/projects/{{project}}{}", scope.path),
+                _ => scope.path.to_string(),
+            };
+            for endpoint in scope.endpoints {
+                let path = format!("{prefix}{}", endpoint.path);
+                let mut item = spec.paths.paths.get(&path).cloned().unwrap_or_default();
+                let documented = METHODS
+                    .into_iter()
+                    .zip(operations(&mut item))
+                    .any(|(method, operation)| method == endpoint.method && operation.is_some());
+                assert!(
+                    documented,
+                    "{} {path} is routed but not documented",
+                    endpoint.method
+                );
+            }
+        }
+    }
+
+    // `/projects/{project}/nodes/{name}/revisions/{revision}` becomes
+    // `/projects/default/nodes/1/revisions/1`: requests to missing projects
+    // never reach their handler, and `1` suits both names and numbers.
+    fn fill_parameters(path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment {
+                "{project}" => "default",
+                _ if segment.starts_with('{') && segment.ends_with('}') => "1",
+                _ => segment,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
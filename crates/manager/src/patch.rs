@@ -0,0 +1,130 @@
+//! JSON Merge Patch (RFC 7396) of nodes, so that one field can be changed
+//! without sending the whole node, secrets included.
+
+use serde_json::{Map, Value};
+
+use crate::config::NodeRecord;
+use crate::error::{AppError, AppResult};
+use crate::revision::snapshot;
+
+/// The node with `patch` merged into its configuration. Objects, such as
+/// `port_mapping.config.server.services`, are merged key by key; `null`
+/// removes a key; anything else, lists included, is replaced as a whole.
+pub fn apply(node: &NodeRecord, patch: &Value) -> AppResult<NodeRecord> {
+    if !patch.is_object() {
+        return Err(AppError::bad_request(
+            "a merge patch of a node must be a JSON object",
+        ));
+    }
+
+    let mut value = snapshot(node)?;
+    merge(&mut value, patch);
+    let mut patched: NodeRecord = serde_json::from_value(value)
+        .map_err(|err| AppError::bad_request(format!("patched node is malformed: {err}")))?;
+    if patched.name != node.name {
+        return Err(AppError::bad_request("a patch cannot rename a node"));
+    }
+    // Nodes never move between projects.
+    patched.project = node.project.clone();
+    Ok(patched)
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!("target was just made an object");
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixture;
+
+    fn merged(mut target: Value, patch: Value) -> Value {
+        merge(&mut target, &patch);
+        target
+    }
+
+    #[test]
+    fn null_removes_a_key() {
+        assert_eq!(
+            merged(json!({ "a": 1, "b": 2 }), json!({ "a": null })),
+            json!({ "b": 2 })
+        );
+        // Removing a missing key is not an error.
+        assert_eq!(
+            merged(json!({ "b": 2 }), json!({ "a": null })),
+            json!({ "b": 2 })
+        );
+    }
+
+    #[test]
+    fn objects_merge_key_by_key() {
+        assert_eq!(
+            merged(
+                json!({ "server": { "services": { "ssh": { "bind_addr": ":22" } }, "bind_addr": ":2333" } }),
+                json!({ "server": { "services": { "web": { "bind_addr": ":80" }, "ssh": { "token": "t" } } } }),
+            ),
+            json!({ "server": {
+                "services": {
+                    "ssh": { "bind_addr": ":22", "token": "t" },
+                    "web": { "bind_addr": ":80" },
+                },
+                "bind_addr": ":2333",
+            } })
+        );
+        // An object replaces anything else, and nulls inside it are dropped.
+        assert_eq!(
+            merged(json!({ "a": [1] }), json!({ "a": { "b": 1, "c": null } })),
+            json!({ "a": { "b": 1 } })
+        );
+    }
+
+    #[test]
+    fn arrays_are_replaced_whole() {
+        assert_eq!(
+            merged(json!({ "tags": ["eu", "edge"] }), json!({ "tags": ["us"] })),
+            json!({ "tags": ["us"] })
+        );
+        assert_eq!(
+            merged(
+                json!({ "tags": [{ "a": 1 }] }),
+                json!({ "tags": [{ "b": 2 }] })
+            ),
+            json!({ "tags": [{ "b": 2 }] })
+        );
+    }
+
+    #[test]
+    fn patches_must_be_objects() {
+        let node = fixture::node("edge", &["eu"]);
+        for patch in [json!(null), json!([]), json!("edge"), json!(1)] {
+            assert!(apply(&node, &patch).is_err(), "{patch} was applied");
+        }
+
+        let patched = apply(&node, &json!({ "tags": ["us"], "description": "Paris" })).unwrap();
+        assert_eq!(patched.tags, ["us"]);
+        assert_eq!(patched.description.as_deref(), Some("Paris"));
+        assert_eq!(patched.project, "default");
+
+        assert!(apply(&node, &json!({ "name": "core" })).is_err());
+        assert!(apply(&node, &json!({ "tags": "eu" })).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Projects group nodes and the tunnels between them, so that several teams
+//! can share one manager: node and tunnel names only need to be unique
+//! within their project.
+
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::ManagerState;
+use crate::error::{AppError, AppResult};
+
+/// Holds the nodes of the configuration file, those created before projects
+/// existed, and those of clients that do not name a project. It always
+/// exists and cannot be deleted.
+pub const DEFAULT_PROJECT: &str = "default";
+
+const MAX_NAME_LEN: usize = 63;
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Project {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct CreateProject {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Outcome of deleting a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectRemoval {
+    Deleted,
+    Missing,
+    /// The project still has this many nodes.
+    InUse(u64),
+}
+
+/// The project named by a gRPC request, where an empty name means the
+/// default project so that clients predating projects keep working.
+pub fn or_default(project: &str) -> &str {
+    match project.trim() {
+        "" => DEFAULT_PROJECT,
+        project => project,
+    }
+}
+
+/// Rejects a project name that is empty, too long or not made of lowercase
+/// letters, digits, `-` and `_`. Project names appear in URL paths, so they
+/// are kept to characters that need no escaping.
+pub fn check_name(name: &str) -> AppResult<()> {
+    if name.is_empty() {
+        return Err(AppError::bad_request("project name cannot be empty"));
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(AppError::bad_request(format!(
+            "project name is longer than {MAX_NAME_LEN} characters"
+        )));
+    }
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::bad_request(format!(
+            "project name '{name}' may only contain lowercase letters, digits, '-' and '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// Answers requests under `/projects/{project}` with a 404 if the project
+/// does not exist, so that nothing is ever stored in a missing project.
+pub async fn require_project(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = req
+        .app_data::<web::Data<Arc<ManagerState>>>()
+        .ok_or_else(|| AppError::internal("manager state is not configured"))?;
+    let project = req.match_info().get("project").unwrap_or_default();
+    if state
+        .get_project(project)
+        .await
+        .map_err(AppError::from)?
+        .is_none()
+    {
+        return Err(AppError::not_found(format!("project '{project}' not found")).into());
+    }
+    next.call(req).await
+}
+
+/// Serves `/nodes`, `/export`, `/import` and `/tunnels`, mounted before
+/// projects existed, from the default project by giving their routes the
+/// `{project}` they expect.
+pub async fn default_project(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    req.match_info_mut().add_static("project", DEFAULT_PROJECT);
+    next.call(req).await
+}
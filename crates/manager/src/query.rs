@@ -0,0 +1,453 @@
+//! Filters, sorting and cursor pagination of node listings. Everything is
+//! pushed down into SQL so that only one page of nodes is loaded at a time.
+
+use laval_model::PortMappingMode;
+use sea_orm::sea_query::{Expr, Func, LikeExpr, SimpleExpr};
+use sea_orm::{
+    ColumnTrait, Condition, DbBackend, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect,
+    Select,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::NodeRecord;
+use crate::entity::node;
+use crate::error::{AppError, AppResult};
+
+pub const DEFAULT_PAGE_SIZE: u64 = 100;
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+#[derive(Debug, Clone, Default)]
+pub struct NodeQuery {
+    /// Only nodes of this project are listed.
+    pub project: String,
+    /// Nodes must carry every one of these tags.
+    pub tags: Vec<String>,
+    /// Nodes must carry at least one of these tags, if any are given.
+    pub any_tags: Vec<String>,
+    pub mode: Option<ModeFilter>,
+    pub role: Option<String>,
+    /// Matched case-insensitively against the name and the description.
+    pub search: Option<String>,
+    pub sort: NodeSort,
+    /// Clamped to [`MAX_PAGE_SIZE`]; [`DEFAULT_PAGE_SIZE`] if zero.
+    pub limit: u64,
+    pub cursor: Option<Cursor>,
+}
+
+/// The port mapping a listed node must have.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeFilter {
+    Server,
+    Client,
+    /// Nodes without a port mapping.
+    None,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Name,
+    Role,
+    ManagedBy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeSort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl NodeSort {
+    /// Parses `name`, `role` or `managed_by`, prefixed with `-` for
+    /// descending order.
+    pub fn parse(value: &str) -> AppResult<Self> {
+        let (descending, field) = match value.strip_prefix('-') {
+            Some(field) => (true, field),
+            None => (false, value),
+        };
+        let field = match field {
+            "name" => SortField::Name,
+            "role" => SortField::Role,
+            "managed_by" => SortField::ManagedBy,
+            other => {
+                return Err(AppError::bad_request(format!(
+                    "cannot sort nodes by '{other}'; use name, role or managed_by"
+                )))
+            }
+        };
+        Ok(Self { field, descending })
+    }
+}
+
+/// Where a page ended: the sort key and name of its last node. Opaque to
+/// clients, which only pass back `next_cursor`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    sort: SortField,
+    descending: bool,
+    key: String,
+    name: String,
+}
+
+impl Cursor {
+    /// Decodes a `next_cursor`, which must come from a listing in the same
+    /// `sort` order.
+    pub fn decode(value: &str, sort: NodeSort) -> AppResult<Self> {
+        let cursor: Cursor = hex::decode(value)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .ok_or_else(|| AppError::bad_request("invalid cursor"))?;
+        if cursor.sort != sort.field || cursor.descending != sort.descending {
+            return Err(AppError::bad_request(
+                "cursor belongs to a listing in another sort order",
+            ));
+        }
+        Ok(cursor)
+    }
+
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).expect("cursor serializes to JSON"))
+    }
+
+    pub(crate) fn after(model: &node::Model, sort: NodeSort) -> Self {
+        let key = match sort.field {
+            SortField::Name => model.name.clone(),
+            SortField::Role => model.port_mapping_role.clone().unwrap_or_default(),
+            SortField::ManagedBy => model.managed_by.clone(),
+        };
+        Self {
+            sort: sort.field,
+            descending: sort.descending,
+            key,
+            name: model.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct NodePage {
+    pub nodes: Vec<NodeRecord>,
+    /// Set when there are more nodes after this page.
+    pub next_cursor: Option<String>,
+}
+
+impl NodeQuery {
+    pub fn page_size(&self) -> u64 {
+        match self.limit {
+            0 => DEFAULT_PAGE_SIZE,
+            limit => limit.min(MAX_PAGE_SIZE),
+        }
+    }
+
+    /// Restricts `select` to the nodes of the requested page, plus one to
+    /// tell whether another page follows.
+    pub(crate) fn apply(
+        &self,
+        mut select: Select<node::Entity>,
+        backend: DbBackend,
+    ) -> Select<node::Entity> {
+        select = select.filter(node::Column::Project.eq(self.project.as_str()));
+        for tag in &self.tags {
+            select = select.filter(has_tag(backend, tag));
+        }
+        if !self.any_tags.is_empty() {
+            select = select.filter(
+                self.any_tags
+                    .iter()
+                    .fold(Condition::any(), |any, tag| any.add(has_tag(backend, tag))),
+            );
+        }
+        match self.mode {
+            Some(ModeFilter::Server) => {
+                select = select.filter(has_mode(backend, &PortMappingMode::Server))
+            }
+            Some(ModeFilter::Client) => {
+                select = select.filter(has_mode(backend, &PortMappingMode::Client))
+            }
+            Some(ModeFilter::None) => select = select.filter(node::Column::PortMapping.is_null()),
+            None => {}
+        }
+        if let Some(role) = &self.role {
+            select = select.filter(node::Column::PortMappingRole.eq(role.as_str()));
+        }
+        if let Some(search) = self.search.as_deref().filter(|search| !search.is_empty()) {
+            let pattern = format!("%{}%", escape_like(&search.to_lowercase()));
+            select = select.filter(
+                Condition::any()
+                    .add(lower(node::Column::Name).like(LikeExpr::new(&pattern).escape('\\')))
+                    .add(
+                        lower(node::Column::Description).like(LikeExpr::new(&pattern).escape('\\')),
+                    ),
+            );
+        }
+
+        let key = sort_key(self.sort.field);
+        if let Some(cursor) = &self.cursor {
+            let name = Expr::col(node::Column::Name);
+            select = select.filter(match (self.sort.field, self.sort.descending) {
+                (SortField::Name, false) => name.gt(cursor.name.as_str()),
+                (SortField::Name, true) => name.lt(cursor.name.as_str()),
+                (_, false) => Expr::expr(key.clone())
+                    .gt(cursor.key.as_str())
+                    .or(Expr::expr(key.clone())
+                        .eq(cursor.key.as_str())
+                        .and(name.gt(cursor.name.as_str()))),
+                (_, true) => Expr::expr(key.clone())
+                    .lt(cursor.key.as_str())
+                    .or(Expr::expr(key.clone())
+                        .eq(cursor.key.as_str())
+                        .and(name.lt(cursor.name.as_str()))),
+            });
+        }
+
+        let order = if self.sort.descending {
+            Order::Desc
+        } else {
+            Order::Asc
+        };
+        if self.sort.field != SortField::Name {
+            select = select.order_by(key, order.clone());
+        }
+        select
+            .order_by(node::Column::Name, order)
+            .limit(self.page_size() + 1)
+    }
+}
+
+// Nodes without a role sort as if their role were empty, so that the cursor
+// never has to compare against NULL.
+fn sort_key(field: SortField) -> SimpleExpr {
+    match field {
+        SortField::Name => Expr::col(node::Column::Name).into(),
+        SortField::Role => Func::coalesce([
+            Expr::col(node::Column::PortMappingRole).into(),
+            Expr::val("").into(),
+        ])
+        .into(),
+        SortField::ManagedBy => Expr::col(node::Column::ManagedBy).into(),
+    }
+}
+
+fn has_tag(backend: DbBackend, tag: &str) -> SimpleExpr {
+    let sql = match backend {
+        DbBackend::Postgres => {
+            r#"EXISTS (SELECT 1 FROM json_array_elements_text("nodes"."tags") AS t(tag) WHERE t.tag = ?)"#
+        }
+        _ => r#"EXISTS (SELECT 1 FROM json_each("nodes"."tags") WHERE json_each.value = ?)"#,
+    };
+    Expr::cust_with_values(sql, [tag])
+}
+
+pub(crate) fn has_mode(backend: DbBackend, mode: &PortMappingMode) -> SimpleExpr {
+    let mode = match mode {
+        PortMappingMode::Server => "server",
+        PortMappingMode::Client => "client",
+    };
+    let sql = match backend {
+        DbBackend::Postgres => r#"("nodes"."port_mapping" ->> 'mode') = ?"#,
+        _ => r#"json_extract("nodes"."port_mapping", '$.mode') = ?"#,
+    };
+    Expr::cust_with_values(sql, [mode])
+}
+
+fn lower(column: node::Column) -> Expr {
+    Expr::expr(Func::lower(Expr::col(column)))
+}
+
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use laval_model::PortMappingSpec;
+    use sea_orm::QueryTrait;
+
+    use super::*;
+    use crate::audit::Actor;
+    use crate::config::ManagerState;
+    use crate::fixture::{self, open, state};
+    use crate::project::CreateProject;
+
+    /// The Postgres database of `listings_on_postgres`, which only runs with
+    /// `cargo test -- --ignored`.
+    const POSTGRES_URL: &str = "LAVAL_TEST_POSTGRES_URL";
+
+    fn query(project: &str) -> NodeQuery {
+        NodeQuery {
+            project: project.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn names(state: &ManagerState, query: &NodeQuery) -> (Vec<String>, Option<String>) {
+        let page = state.query(query).await.unwrap();
+        let names = page.nodes.into_iter().map(|node| node.name).collect();
+        (names, page.next_cursor)
+    }
+
+    /// Every page of `query`, following `next_cursor`.
+    async fn pages(state: &ManagerState, mut query: NodeQuery) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        loop {
+            let (names, next_cursor) = names(state, &query).await;
+            pages.push(names);
+            match next_cursor {
+                Some(cursor) => query.cursor = Some(Cursor::decode(&cursor, query.sort).unwrap()),
+                None => return pages,
+            }
+        }
+    }
+
+    // Stores the same nodes in a fresh project, so that a shared Postgres
+    // database can be reused between runs.
+    async fn listings(state: &ManagerState, project: &str) {
+        let actor = Actor {
+            name: "test".to_string(),
+            source_ip: None,
+        };
+        state
+            .create_project(
+                CreateProject {
+                    name: project.to_string(),
+                    description: None,
+                },
+                &actor,
+            )
+            .await
+            .unwrap();
+        let server = PortMappingSpec {
+            mode: PortMappingMode::Server,
+            config: rathole::Config {
+                server: Some(Default::default()),
+                client: None,
+            },
+            certificate: None,
+        };
+        let nodes: [(&str, Option<&str>, &[&str]); 5] = [
+            ("a", Some("x"), &["eu", "edge"]),
+            ("b", Some("x"), &["eu"]),
+            ("c", Some("x"), &["us", "edge"]),
+            ("d", None, &[]),
+            ("e", Some("w"), &["edge"]),
+        ];
+        for (name, role, tags) in nodes {
+            let node = NodeRecord {
+                project: project.to_string(),
+                port_mapping_role: role.map(str::to_string),
+                port_mapping: (name != "d").then(|| server.clone()),
+                ..fixture::node(name, tags)
+            };
+            state.upsert(node, &actor).await.unwrap();
+        }
+
+        let by = |sort: &str, limit: u64| NodeQuery {
+            sort: NodeSort::parse(sort).unwrap(),
+            limit,
+            ..query(project)
+        };
+        assert_eq!(
+            pages(state, by("-name", 2)).await,
+            [vec!["e", "d"], vec!["c", "b"], vec!["a"]]
+        );
+        // Nodes without a role sort first, and nodes with the same role by
+        // name, even across pages.
+        assert_eq!(
+            pages(state, by("role", 2)).await,
+            [vec!["d", "e"], vec!["a", "b"], vec!["c"]]
+        );
+        assert_eq!(
+            pages(state, by("-role", 2)).await,
+            [vec!["c", "b"], vec!["a", "e"], vec!["d"]]
+        );
+
+        let tagged = |tags: &[&str], any_tags: &[&str]| NodeQuery {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            any_tags: any_tags.iter().map(|tag| tag.to_string()).collect(),
+            ..query(project)
+        };
+        assert_eq!(
+            names(state, &tagged(&["edge"], &[])).await.0,
+            ["a", "c", "e"]
+        );
+        assert_eq!(names(state, &tagged(&["eu", "edge"], &[])).await.0, ["a"]);
+        assert_eq!(
+            names(state, &tagged(&[], &["us", "eu"])).await.0,
+            ["a", "b", "c"]
+        );
+        assert_eq!(names(state, &tagged(&["edge"], &["us"])).await.0, ["c"]);
+
+        let mode = |mode| NodeQuery {
+            mode: Some(mode),
+            ..query(project)
+        };
+        assert_eq!(names(state, &mode(ModeFilter::None)).await.0, ["d"]);
+        assert_eq!(
+            names(state, &mode(ModeFilter::Server)).await.0,
+            ["a", "b", "c", "e"]
+        );
+        assert!(names(state, &mode(ModeFilter::Client)).await.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn listings_on_sqlite() {
+        listings(&state().await, "query").await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres database at LAVAL_TEST_POSTGRES_URL"]
+    async fn listings_on_postgres() {
+        let database_url = std::env::var(POSTGRES_URL)
+            .unwrap_or_else(|_| panic!("{POSTGRES_URL} must be set to run this test"));
+        let project = format!("query-{}", chrono::Utc::now().timestamp_micros());
+        listings(&open(&database_url, None).await, &project).await;
+    }
+
+    #[test]
+    fn tag_filter_sql() {
+        let query = NodeQuery {
+            tags: vec!["edge".to_string()],
+            ..query("default")
+        };
+        let sql = |backend| {
+            query
+                .apply(node::Entity::find(), backend)
+                .build(backend)
+                .to_string()
+        };
+        assert!(sql(DbBackend::Postgres).contains(
+            r#"EXISTS (SELECT 1 FROM json_array_elements_text("nodes"."tags") AS t(tag) WHERE t.tag = 'edge')"#
+        ));
+        assert!(sql(DbBackend::Sqlite).contains(
+            r#"EXISTS (SELECT 1 FROM json_each("nodes"."tags") WHERE json_each.value = 'edge')"#
+        ));
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let sort = NodeSort::parse("-role").unwrap();
+        let cursor = Cursor {
+            sort: sort.field,
+            descending: sort.descending,
+            key: "edge".to_string(),
+            name: "paris".to_string(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode(), sort).unwrap(), cursor);
+
+        // A cursor only continues the listing it came from.
+        assert!(Cursor::decode(&cursor.encode(), NodeSort::parse("role").unwrap()).is_err());
+        assert!(Cursor::decode(&cursor.encode(), NodeSort::parse("-name").unwrap()).is_err());
+        assert!(Cursor::decode("not hex", sort).is_err());
+        assert!(Cursor::decode(&hex::encode("{}"), sort).is_err());
+    }
+}
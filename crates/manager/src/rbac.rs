@@ -0,0 +1,479 @@
+//! Roles of user tokens and the nodes they apply to. Every HTTP handler and
+//! RPC checks its caller's grant before acting; denied requests are written
+//! to the audit log.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::net::SocketAddr;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tonic::{Request, Status};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::audit::Actor;
+use crate::auth::{self, Principal};
+use crate::config::{ManagerState, NodeRecord};
+use crate::error::{AppError, AppResult};
+use crate::tunnel::Tunnel;
+
+/// Each role may do everything the roles before it may.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads nodes, their revisions and Noise public keys, tunnels,
+    /// certificates and templates.
+    #[default]
+    Viewer,
+    /// Also changes the port mapping of existing nodes, and creates and
+    /// deletes tunnels.
+    Operator,
+    /// Also creates, deletes and otherwise changes nodes, and manages API
+    /// tokens, Noise keys, tunnel tokens, TLS certificates, templates,
+    /// imports, rendered node configs and the audit log.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "viewer" => Role::Viewer,
+            "operator" => Role::Operator,
+            "admin" => Role::Admin,
+            other => bail!("unknown role '{other}'"),
+        })
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a user token may do, and to which nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub role: Role,
+    /// Limits the grant to nodes carrying at least one of these tags; it
+    /// covers every node if empty.
+    pub tags: Vec<String>,
+}
+
+impl Grant {
+    pub fn is_scoped(&self) -> bool {
+        !self.tags.is_empty()
+    }
+
+    /// Whether the grant applies to a node carrying `tags`.
+    pub fn covers(&self, tags: &[String]) -> bool {
+        !self.is_scoped() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+/// What a request acts on.
+#[derive(Debug, Clone)]
+pub enum Scope<'a> {
+    /// The manager as a whole, such as its tokens or audit log, or a node
+    /// that does not exist: only grants without tags cover it.
+    All,
+    /// A listing, which leaves out whatever the grant does not cover.
+    Listing,
+    /// Existing or new nodes, each of which the grant must cover.
+    Nodes(Vec<&'a NodeRecord>),
+}
+
+impl<'a> Scope<'a> {
+    /// The node if it exists, otherwise the whole manager.
+    pub fn node(node: Option<&'a NodeRecord>) -> Self {
+        match node {
+            Some(node) => Scope::Nodes(vec![node]),
+            None => Scope::All,
+        }
+    }
+}
+
+/// The user a request was authenticated as.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Also the author of the changes made by the request.
+    pub actor: Actor,
+    pub grant: Grant,
+}
+
+impl Caller {
+    /// Refuses `operation`, such as `update node 'edge-1'`, unless the caller
+    /// has at least `role` and its grant covers `scope`.
+    pub async fn require(
+        &self,
+        state: &ManagerState,
+        role: Role,
+        scope: Scope<'_>,
+        operation: &str,
+    ) -> AppResult<()> {
+        let nodes = match &scope {
+            Scope::Nodes(nodes) => nodes.as_slice(),
+            _ => &[],
+        };
+        let (node, reason) = if self.grant.role < role {
+            (nodes.first().copied(), format!("requires the {role} role"))
+        } else if let Some(node) = nodes.iter().find(|node| !self.grant.covers(&node.tags)) {
+            (
+                Some(*node),
+                format!(
+                    "node '{}' carries none of the tags {}",
+                    node.name,
+                    self.grant.tags.join(", ")
+                ),
+            )
+        } else if matches!(scope, Scope::All) && self.grant.is_scoped() {
+            (
+                None,
+                format!(
+                    "the token is limited to nodes tagged {}",
+                    self.grant.tags.join(", ")
+                ),
+            )
+        } else {
+            return Ok(());
+        };
+        Err(deny(state, &self.actor, node, &format!("{operation}: {reason}")).await)
+    }
+
+    /// Refuses to replace `current` with `new`, or to create `new` if there
+    /// is no current node, unless the caller may make that change.
+    pub async fn require_write(
+        &self,
+        state: &ManagerState,
+        current: Option<&NodeRecord>,
+        new: &NodeRecord,
+    ) -> AppResult<()> {
+        match current {
+            Some(current) => {
+                self.require(
+                    state,
+                    role_to_update(current, new),
+                    Scope::Nodes(vec![current, new]),
+                    &format!("update node '{}'", new.name),
+                )
+                .await
+            }
+            None => {
+                self.require(
+                    state,
+                    Role::Admin,
+                    Scope::Nodes(vec![new]),
+                    &format!("create node '{}'", new.name),
+                )
+                .await
+            }
+        }
+    }
+
+    /// Like [`Self::require`], for a tunnel between two nodes of `project`.
+    pub async fn require_tunnel(
+        &self,
+        state: &ManagerState,
+        role: Role,
+        project: &str,
+        server_node: &str,
+        client_node: &str,
+        operation: &str,
+    ) -> AppResult<()> {
+        let mut nodes = Vec::new();
+        for name in [server_node, client_node] {
+            nodes.extend(state.get(project, name.trim()).await?);
+        }
+        self.require(state, role, Scope::Nodes(nodes.iter().collect()), operation)
+            .await
+    }
+
+    /// The user of a gRPC call. Node credentials are denied `operation`.
+    pub async fn of_grpc<T>(
+        state: &ManagerState,
+        request: &Request<T>,
+        operation: &str,
+    ) -> Result<Self, Status> {
+        let principal = auth::principal(request)?;
+        match Caller::new(principal, request.remote_addr()) {
+            Some(caller) => Ok(caller),
+            None => Err(deny_node(state, principal, request, operation).await),
+        }
+    }
+
+    fn new(principal: &Principal, source: Option<SocketAddr>) -> Option<Self> {
+        match principal {
+            Principal::User { grant, .. } => Some(Self {
+                actor: Actor::new(principal, source),
+                grant: grant.clone(),
+            }),
+            Principal::Node { .. } => None,
+        }
+    }
+}
+
+/// Available to handlers behind [`crate::auth::require_user_http`].
+impl FromRequest for Caller {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .and_then(|principal| Caller::new(principal, req.peer_addr()))
+                .ok_or_else(|| AppError::unauthorized("missing or invalid API token")),
+        )
+    }
+}
+
+/// Lets a node credential access its own node, and users whose grant covers
+/// the node with at least `role`.
+pub async fn require_node_access<T>(
+    state: &ManagerState,
+    request: &Request<T>,
+    project: &str,
+    name: &str,
+    role: Role,
+    operation: &str,
+) -> Result<(), Status> {
+    let principal = auth::principal(request)?;
+    if let Principal::Node {
+        project: own_project,
+        name: own_name,
+    } = principal
+    {
+        if own_project == project && own_name == name {
+            return Ok(());
+        }
+    }
+    let caller = Caller::of_grpc(state, request, operation).await?;
+    let node = state
+        .get(project, name)
+        .await
+        .map_err(|err| Status::internal(format!("failed to fetch node '{name}': {err}")))?;
+    caller
+        .require(state, role, Scope::node(node.as_ref()), operation)
+        .await?;
+    Ok(())
+}
+
+async fn deny_node<T>(
+    state: &ManagerState,
+    principal: &Principal,
+    request: &Request<T>,
+    operation: &str,
+) -> Status {
+    let actor = Actor::new(principal, request.remote_addr());
+    deny(
+        state,
+        &actor,
+        None,
+        &format!("{operation}: node credentials may only access their own node"),
+    )
+    .await
+    .into()
+}
+
+/// Records a denied request in the audit log, and returns the error to
+/// answer it with. `detail` names the operation and why it was denied.
+pub async fn deny(
+    state: &ManagerState,
+    actor: &Actor,
+    node: Option<&NodeRecord>,
+    detail: &str,
+) -> AppError {
+    if let Err(err) = state.record_denial(actor, node, detail).await {
+        warn!(error = %err, "failed to audit a denied request");
+    }
+    AppError::forbidden(format!("not allowed to {detail}"))
+}
+
+// The role needed to replace `current` with `new`: operators may only
+// change the port mapping and its role.
+fn role_to_update(current: &NodeRecord, new: &NodeRecord) -> Role {
+    if current.reverse_proxy_bind == new.reverse_proxy_bind
+        && current.management_url == new.management_url
+        && current.description == new.description
+        && current.tags == new.tags
+    {
+        Role::Operator
+    } else {
+        Role::Admin
+    }
+}
+
+/// The tunnels of `project` whose nodes the grant covers at both ends.
+pub async fn visible_tunnels(
+    state: &ManagerState,
+    grant: &Grant,
+    project: &str,
+    tunnels: Vec<Tunnel>,
+) -> Result<Vec<Tunnel>> {
+    if !grant.is_scoped() {
+        return Ok(tunnels);
+    }
+    let covered: HashSet<String> = state
+        .list(project)
+        .await?
+        .into_iter()
+        .filter(|node| grant.covers(&node.tags))
+        .map(|node| node.name)
+        .collect();
+    Ok(tunnels
+        .into_iter()
+        .filter(|tunnel| {
+            covered.contains(&tunnel.server_node) && covered.contains(&tunnel.client_node)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    use super::*;
+    use crate::audit::{AuditAction, AuditQuery};
+    use crate::fixture::{node, state};
+
+    fn caller(role: Role, tags: &[&str]) -> Caller {
+        Caller {
+            actor: Actor {
+                name: "ops".to_string(),
+                source_ip: None,
+            },
+            grant: Grant {
+                role,
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn scoped_grants_cover_nodes_with_one_of_their_tags() {
+        let grant = caller(Role::Admin, &["eu", "edge"]).grant;
+        assert!(grant.covers(&["edge".to_string()]));
+        assert!(grant.covers(&["us".to_string(), "eu".to_string()]));
+        assert!(!grant.covers(&["us".to_string()]));
+        assert!(!grant.covers(&[]));
+
+        let unscoped = caller(Role::Viewer, &[]).grant;
+        assert!(unscoped.covers(&[]));
+        assert!(unscoped.covers(&["us".to_string()]));
+    }
+
+    #[test]
+    fn only_port_mapping_changes_are_left_to_operators() {
+        let current = node("edge", &["eu"]);
+
+        let mut new = current.clone();
+        new.port_mapping_role = Some("server".to_string());
+        assert_eq!(role_to_update(&current, &new), Role::Operator);
+
+        let admin_changes: [fn(&mut NodeRecord); 4] = [
+            |node| node.description = Some("Edge in Paris".to_string()),
+            |node| node.tags.push("us".to_string()),
+            |node| node.reverse_proxy_bind = Some("0.0.0.0:443".to_string()),
+            |node| node.management_url = Some("https://edge".to_string()),
+        ];
+        for change in admin_changes {
+            let mut new = current.clone();
+            change(&mut new);
+            assert_eq!(role_to_update(&current, &new), Role::Admin);
+        }
+    }
+
+    async fn denials(state: &ManagerState) -> Vec<String> {
+        let query = AuditQuery {
+            action: Some(AuditAction::Denied),
+            ..Default::default()
+        };
+        state
+            .audit_events(&query)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|event| event.detail)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn operators_cannot_change_admin_fields() {
+        let state = state().await;
+        let current = node("edge", &["eu"]);
+        let mut new = current.clone();
+        new.description = Some("Edge in Paris".to_string());
+
+        let operator = caller(Role::Operator, &[]);
+        let err = operator
+            .require_write(&state, Some(&current), &new)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            denials(&state).await,
+            ["update node 'edge': requires the admin role"]
+        );
+
+        let mut new = current.clone();
+        new.port_mapping_role = Some("server".to_string());
+        operator
+            .require_write(&state, Some(&current), &new)
+            .await
+            .unwrap();
+        caller(Role::Admin, &[])
+            .require_write(&state, Some(&current), &new)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn scoped_tokens_only_act_on_their_nodes() {
+        let state = state().await;
+        let eu = node("edge-eu", &["eu"]);
+        let us = node("edge-us", &["us"]);
+        let scoped = caller(Role::Admin, &["eu"]);
+
+        scoped
+            .require(&state, Role::Admin, Scope::Nodes(vec![&eu]), "delete node")
+            .await
+            .unwrap();
+        for scope in [
+            Scope::Nodes(vec![&us]),
+            Scope::Nodes(vec![&eu, &us]),
+            Scope::All,
+        ] {
+            let err = scoped
+                .require(&state, Role::Viewer, scope, "read")
+                .await
+                .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        }
+
+        // Retagging a covered node out of the grant is a change to a node it
+        // no longer covers.
+        let mut moved = eu.clone();
+        moved.tags = vec!["us".to_string()];
+        let err = scoped
+            .require_write(&state, Some(&eu), &moved)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(denials(&state).await.len(), 4);
+    }
+}
@@ -0,0 +1,134 @@
+//! Brings the stored nodes in line with the nodes declared in the
+//! configuration file, which all belong to the default project.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+
+use crate::audit::Actor;
+use crate::certificate;
+use crate::config::{ManagedBy, ManagerState, NodeRecord, Precondition};
+use crate::project::DEFAULT_PROJECT;
+use crate::revision::{diff, snapshot, FieldChange};
+use crate::template;
+use crate::tunnel;
+use crate::validate;
+
+/// What applying the configuration file would change.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub create: Vec<NodeRecord>,
+    pub update: Vec<NodeUpdate>,
+    /// File-managed nodes that are no longer declared, deleted when pruning.
+    pub delete: Vec<String>,
+    /// Undeclared nodes created from the UI, which are never pruned.
+    pub protected: Vec<String>,
+    /// Undeclared file-managed nodes that tunnels still use, which are not
+    /// pruned until those tunnels are deleted.
+    pub in_use: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct NodeUpdate {
+    pub node: NodeRecord,
+    pub changes: Vec<FieldChange>,
+    /// The node was created from the UI and the file now takes it over.
+    pub adopted: bool,
+}
+
+impl Plan {
+    pub fn is_empty(&self, prune: bool) -> bool {
+        self.create.is_empty() && self.update.is_empty() && (!prune || self.delete.is_empty())
+    }
+}
+
+/// Compares the declared nodes with the stored ones. Fails if any declared
+/// node is invalid, so that nothing is applied from a broken file.
+pub async fn plan(state: &ManagerState, declared: Vec<NodeRecord>) -> Result<Plan> {
+    let mut problems = Vec::new();
+    let mut desired = BTreeMap::new();
+    for mut node in declared {
+        node.project = DEFAULT_PROJECT.to_string();
+        node.name = node.name.trim().to_string();
+        node.managed_by = ManagedBy::File;
+        if node.name.is_empty() {
+            problems.push("a node has an empty name".to_string());
+            continue;
+        }
+        for error in template::bind(state, &mut node).await? {
+            problems.push(format!("{}: {}: {}", node.name, error.field, error.reason));
+        }
+        for error in validate::node_errors(&node) {
+            problems.push(format!("{}: {}: {}", node.name, error.field, error.reason));
+        }
+        for error in tunnel::node_errors(state, &node).await? {
+            problems.push(format!("{}: {}: {}", node.name, error.field, error.reason));
+        }
+        for error in certificate::node_errors(state, &node).await? {
+            problems.push(format!("{}: {}: {}", node.name, error.field, error.reason));
+        }
+        if let Some(duplicate) = desired.insert(node.name.clone(), node) {
+            problems.push(format!("{}: declared more than once", duplicate.name));
+        }
+    }
+    if !problems.is_empty() {
+        bail!("{}", problems.join("; "));
+    }
+
+    let mut plan = Plan::default();
+    let mut stored: BTreeSet<String> = BTreeSet::new();
+    for current in state.list(DEFAULT_PROJECT).await? {
+        stored.insert(current.name.clone());
+        match desired.get(&current.name) {
+            Some(node) => {
+                let changes = diff(Some(&snapshot(&current)?), Some(&snapshot(node)?));
+                let adopted = current.managed_by != ManagedBy::File;
+                if !changes.is_empty() || adopted {
+                    plan.update.push(NodeUpdate {
+                        node: node.clone(),
+                        changes,
+                        adopted,
+                    });
+                }
+            }
+            None if current.managed_by != ManagedBy::File => plan.protected.push(current.name),
+            None if !state
+                .tunnels_of(DEFAULT_PROJECT, &current.name)
+                .await?
+                .is_empty() =>
+            {
+                plan.in_use.push(current.name)
+            }
+            None => plan.delete.push(current.name),
+        }
+    }
+    plan.create = desired
+        .into_values()
+        .filter(|node| !stored.contains(&node.name))
+        .collect();
+
+    plan.update.sort_by(|a, b| a.node.name.cmp(&b.node.name));
+    plan.delete.sort();
+    plan.protected.sort();
+    plan.in_use.sort();
+    Ok(plan)
+}
+
+/// Applies `plan`, deleting undeclared file-managed nodes only if `prune`.
+pub async fn apply(state: &ManagerState, plan: Plan, prune: bool) -> Result<()> {
+    let actor = Actor::config_file();
+    for node in plan.create {
+        state.upsert(node, &actor).await?;
+    }
+    for update in plan.update {
+        state.upsert(update.node, &actor).await?;
+    }
+    if prune {
+        for name in plan.delete {
+            state
+                .remove(DEFAULT_PROJECT, &name, &actor, Precondition::None)
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Complete configuration files of a node, for nodes that cannot reach the
+//! manager's gRPC port. Tunnels and Noise keys are resolved as they would be
+//! for a linked node, so the files hold secrets. A certificate the node's
+//! reverse proxy serves from the manager is rendered as two more files,
+//! which its `node.toml` reads from the same directory; one its Rathole
+//! server serves is only ever built on a linked node.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use laval_model::node::{NodeConfig, ReverseProxyConfig, TlsConfig};
+use laval_model::PortMappingSpec;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::certificate::Certificate;
+use crate::config::NodeRecord;
+use crate::error::{AppError, AppResult};
+use crate::inventory::InventoryFormat;
+use crate::validate::FieldError;
+
+/// Where a rendered `node.toml` reads the certificate served from the
+/// manager, relative to its own directory.
+const CERTIFICATE_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// Which file of the node to render.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderTarget {
+    /// The node's `node.toml`, without a `[manager]` link.
+    #[default]
+    Node,
+    /// A standalone Rathole config of the node's port mapping.
+    Rathole,
+    /// The PEM chain of the certificate the node serves from the manager,
+    /// to be saved as `cert.pem` next to `node.toml`.
+    Certificate,
+    /// Its private key, to be saved as `key.pem`.
+    Key,
+}
+
+impl RenderTarget {
+    /// PEM files ignore the format.
+    pub fn file_name(self, format: InventoryFormat) -> String {
+        let stem = match self {
+            RenderTarget::Node => "node",
+            RenderTarget::Rathole => "rathole",
+            RenderTarget::Certificate => return CERTIFICATE_FILE.to_string(),
+            RenderTarget::Key => return KEY_FILE.to_string(),
+        };
+        let extension = match format {
+            InventoryFormat::Json => "json",
+            InventoryFormat::Toml => "toml",
+        };
+        format!("{stem}.{extension}")
+    }
+
+    pub fn content_type(self, format: InventoryFormat) -> &'static str {
+        match self {
+            RenderTarget::Node | RenderTarget::Rathole => format.content_type(),
+            RenderTarget::Certificate | RenderTarget::Key => "application/x-pem-file",
+        }
+    }
+}
+
+/// Renders `node`, as returned by `ManagerState::node_config`, with the
+/// certificate its reverse proxy serves. TOML output is parsed back the way
+/// the node or Rathole would read it before it is returned.
+pub fn render(
+    node: NodeRecord,
+    certificate: Option<Certificate>,
+    target: RenderTarget,
+    format: InventoryFormat,
+) -> AppResult<String> {
+    match target {
+        RenderTarget::Node => render_node(node, format),
+        RenderTarget::Rathole => render_rathole(node, format),
+        RenderTarget::Certificate | RenderTarget::Key => {
+            let certificate = certificate.ok_or_else(|| {
+                AppError::not_found(format!(
+                    "node '{}' serves no certificate from the manager",
+                    node.name
+                ))
+            })?;
+            Ok(match target {
+                RenderTarget::Key => certificate.key_pem,
+                _ => certificate.chain_pem,
+            })
+        }
+    }
+}
+
+fn render_node(node: NodeRecord, format: InventoryFormat) -> AppResult<String> {
+    let spec = node.reverse_proxy.unwrap_or_default();
+    let tls = match spec.certificate {
+        Some(_) => Some(TlsConfig {
+            cert: PathBuf::from(CERTIFICATE_FILE),
+            key: PathBuf::from(KEY_FILE),
+        }),
+        None => spec.tls.map(|tls| TlsConfig {
+            cert: PathBuf::from(tls.cert),
+            key: PathBuf::from(tls.key),
+        }),
+    };
+    let port_mapping = match node.port_mapping {
+        Some(spec) => {
+            check_no_identity(&node.name, &spec)?;
+            let mode = spec.mode.clone();
+            let (config, _) = spec.into_rathole().map_err(AppError::from)?;
+            Some(PortMappingSpec {
+                mode,
+                config,
+                certificate: None,
+            })
+        }
+        None => None,
+    };
+    let mut reverse_proxy = ReverseProxyConfig {
+        tls,
+        routes: spec.routes,
+        default_upstream: spec.default_upstream,
+        ..Default::default()
+    };
+    if let Some(bind) = node.reverse_proxy_bind {
+        reverse_proxy.bind = bind;
+    }
+    let file = NodeConfig {
+        reverse_proxy,
+        port_mapping,
+        manager: None,
+    };
+    write(&file, format, check_node_file).map_err(AppError::from)
+}
+
+fn render_rathole(node: NodeRecord, format: InventoryFormat) -> AppResult<String> {
+    let Some(spec) = node.port_mapping else {
+        return Err(AppError::not_found(format!(
+            "node '{}' has no port mapping",
+            node.name
+        )));
+    };
+    check_no_identity(&node.name, &spec)?;
+    let (config, _) = spec.into_rathole().map_err(AppError::from)?;
+    write(&config, format, check_rathole_file).map_err(AppError::from)
+}
+
+// A linked node builds the identity of a certificate its Rathole server
+// serves in memory, so there is no file a rendered config could name.
+fn check_no_identity(node: &str, spec: &PortMappingSpec) -> AppResult<()> {
+    match &spec.certificate {
+        Some(name) => Err(AppError::unprocessable(
+            format!("node '{node}' cannot be rendered"),
+            vec![FieldError::new(
+                "port_mapping.certificate",
+                format!(
+                    "certificate '{name}' is only served by nodes linked to the manager; \
+                     give the server a pkcs12 file to render it"
+                ),
+            )],
+        )),
+        None => Ok(()),
+    }
+}
+
+// Only TOML is read by the node and Rathole, so only TOML is checked.
+fn write<T: Serialize>(
+    value: &T,
+    format: InventoryFormat,
+    check: fn(&str) -> Result<()>,
+) -> Result<String> {
+    Ok(match format {
+        InventoryFormat::Json => serde_json::to_string_pretty(value)?,
+        InventoryFormat::Toml => {
+            let body = toml::to_string(value)?;
+            check(&body)?;
+            body
+        }
+    })
+}
+
+fn check_rathole_file(body: &str) -> Result<()> {
+    rathole::Config::parse(body).context("rendered Rathole config does not parse")?;
+    Ok(())
+}
+
+// What the node does with `node.toml` short of binding its listeners.
+fn check_node_file(body: &str) -> Result<()> {
+    let config = NodeConfig::parse(body).context("rendered node config does not parse")?;
+    if let Some(spec) = config.port_mapping {
+        spec.into_rathole()
+            .context("rendered port mapping is invalid")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+    use laval_model::ReverseProxySpec;
+
+    use super::*;
+    use crate::fixture::{self, certificate};
+
+    fn node(certificate: Option<&str>) -> NodeRecord {
+        NodeRecord {
+            reverse_proxy: Some(ReverseProxySpec {
+                certificate: certificate.map(str::to_string),
+                ..Default::default()
+            }),
+            ..fixture::node("edge", &[])
+        }
+    }
+
+    #[test]
+    fn certificates_from_the_manager_are_read_next_to_the_config() {
+        let body = render(
+            node(Some("example")),
+            Some(certificate("example")),
+            RenderTarget::Node,
+            InventoryFormat::Toml,
+        )
+        .unwrap();
+        let file = NodeConfig::parse(&body).unwrap();
+        assert_eq!(
+            file.reverse_proxy.tls,
+            Some(TlsConfig {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+            })
+        );
+
+        for (target, expected) in [
+            (RenderTarget::Certificate, "chain"),
+            (RenderTarget::Key, "key"),
+        ] {
+            let body = render(
+                node(Some("example")),
+                Some(certificate("example")),
+                target,
+                InventoryFormat::Toml,
+            )
+            .unwrap();
+            assert_eq!(body, expected);
+        }
+        assert_eq!(RenderTarget::Key.file_name(InventoryFormat::Json), KEY_FILE);
+    }
+
+    #[test]
+    fn nodes_without_a_certificate_have_none_to_render() {
+        let err = render(node(None), None, RenderTarget::Key, InventoryFormat::Toml).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let body = render(node(None), None, RenderTarget::Node, InventoryFormat::Toml).unwrap();
+        let file = NodeConfig::parse(&body).unwrap();
+        assert_eq!(file.reverse_proxy.tls, None);
+    }
+
+    #[test]
+    fn port_mapping_certificates_are_not_rendered() {
+        let node = NodeRecord {
+            port_mapping: Some(PortMappingSpec {
+                mode: laval_model::PortMappingMode::Server,
+                config: rathole::Config {
+                    server: Some(Default::default()),
+                    client: None,
+                },
+                certificate: Some("example".to_string()),
+            }),
+            ..node(None)
+        };
+        for target in [RenderTarget::Node, RenderTarget::Rathole] {
+            let err = render(node.clone(), None, target, InventoryFormat::Toml).unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! History of node configuration changes.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::config::NodeRecord;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionAction {
+    Create,
+    Update,
+    Delete,
+    Rollback,
+}
+
+impl RevisionAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RevisionAction::Create => "create",
+            RevisionAction::Update => "update",
+            RevisionAction::Delete => "delete",
+            RevisionAction::Rollback => "rollback",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "create" => RevisionAction::Create,
+            "update" => RevisionAction::Update,
+            "delete" => RevisionAction::Delete,
+            "rollback" => RevisionAction::Rollback,
+            other => bail!("unknown revision action '{other}'"),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct NodeRevision {
+    pub revision: i64,
+    pub action: RevisionAction,
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The node as stored by this revision. Only included when a single
+    /// revision is requested, and empty for deletions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<NodeRecord>,
+}
+
+/// A field that differs between two revisions. Nested fields are joined with
+/// `.`; an empty path stands for the whole node, e.g. when it was deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct FieldChange {
+    pub path: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+/// The configuration of a node, without the status reported by the node,
+/// where it is managed from, its version or its project.
+pub fn snapshot(node: &NodeRecord) -> Result<Value> {
+    let mut value = serde_json::to_value(node)?;
+    if let Value::Object(fields) = &mut value {
+        fields.remove("project");
+        fields.remove("status");
+        fields.remove("managed_by");
+        fields.remove("version");
+    }
+    Ok(value)
+}
+
+/// Lists the fields that changed from one snapshot to the other. Objects are
+/// compared field by field; any other value, including lists, as a whole.
+pub fn diff(from: Option<&Value>, to: Option<&Value>) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_into(String::new(), from, to, &mut changes);
+    changes
+}
+
+fn diff_into(path: String, from: Option<&Value>, to: Option<&Value>, out: &mut Vec<FieldChange>) {
+    match (from, to) {
+        (Some(Value::Object(from)), Some(Value::Object(to))) => {
+            for key in keys(from, to) {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff_into(path, present(from.get(&key)), present(to.get(&key)), out);
+            }
+        }
+        (from, to) if from != to => out.push(FieldChange {
+            path,
+            from: from.cloned(),
+            to: to.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+fn keys(from: &Map<String, Value>, to: &Map<String, Value>) -> Vec<String> {
+    let mut keys: Vec<String> = from.keys().chain(to.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+// A null field and a missing one mean the same thing for a node.
+fn present(value: Option<&Value>) -> Option<&Value> {
+    value.filter(|value| !value.is_null())
+}
@@ -0,0 +1,185 @@
+//! Noise keys generated by the manager for Rathole servers that do not set
+//! their own `local_private_key`. Their clients are given the matching public
+//! key unless they set `remote_public_key` themselves.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rathole::config::{MaskedString, NoiseConfig, TransportConfig, TransportType};
+use rathole::KeypairType;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::NodeRecord;
+use crate::tunnel::{client_config, server_config};
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct NodeKey {
+    pub node: String,
+    /// Base64, as expected in `remote_public_key`.
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+    /// Until when the server still accepts the key replaced by the last
+    /// rotation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_expires_at: Option<DateTime<Utc>>,
+    /// Only ever sent to the node itself.
+    #[serde(skip)]
+    pub private_key: String,
+    #[serde(skip)]
+    pub previous_private_key: Option<String>,
+    /// Set until clients are switched to `public_key`.
+    #[serde(skip)]
+    pub previous_public_key: Option<String>,
+}
+
+impl NodeKey {
+    /// Whether the scheduled rotation of the key is due at `now`. A key is not
+    /// rotated again while the one it replaced is still accepted.
+    pub fn rotation_due(&self, every: Duration, now: DateTime<Utc>) -> bool {
+        self.previous_private_key.is_none()
+            && now
+                .signed_duration_since(self.created_at)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= every)
+    }
+
+    /// Whether clients should be switched to the new public key at `now`:
+    /// halfway through the overlap, which leaves the server time to pick up
+    /// the new private key before any client uses it.
+    pub fn clients_switch_due(&self, now: DateTime<Utc>) -> bool {
+        match (&self.previous_public_key, self.previous_expires_at) {
+            (Some(_), Some(expires_at)) => {
+                now >= self.created_at + (expires_at - self.created_at) / 2
+            }
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+
+    /// The private key replaced by the last rotation, while it is still
+    /// accepted.
+    pub fn live_previous_private_key(&self, now: DateTime<Utc>) -> Option<&str> {
+        match (&self.previous_private_key, self.previous_expires_at) {
+            (Some(key), Some(expires_at)) if expires_at > now => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The public key clients reach the server with.
+    pub fn client_public_key(&self) -> &str {
+        self.previous_public_key
+            .as_deref()
+            .unwrap_or(&self.public_key)
+    }
+}
+
+/// A keypair for the X25519 curve, as with `rathole --genkey`.
+pub fn generate_keypair() -> Result<rathole::Keypair> {
+    rathole::generate_keypair(KeypairType::X25519)
+}
+
+/// Whether `node` runs a Noise server without a private key of its own.
+pub fn wants_managed_key(node: &mut NodeRecord) -> bool {
+    server_config(node).is_some_and(|server| {
+        is_noise(&server.transport)
+            && server
+                .transport
+                .noise
+                .as_ref()
+                .is_none_or(|noise| noise.local_private_key.is_none())
+    })
+}
+
+/// Sets the private key of a node for which [`wants_managed_key`] holds.
+pub fn use_private_key(node: &mut NodeRecord, key: &NodeKey) {
+    if let Some(server) = server_config(node) {
+        let noise = server
+            .transport
+            .noise
+            .get_or_insert_with(NoiseConfig::default);
+        noise.local_private_key = Some(MaskedString::from(key.private_key.as_str()));
+        noise.previous_private_keys = key
+            .live_previous_private_key(Utc::now())
+            .map(MaskedString::from)
+            .into_iter()
+            .collect();
+    }
+}
+
+/// Points a Noise client without a `remote_public_key` at the managed key of
+/// its server.
+pub fn use_server_key(node: &mut NodeRecord, key: &NodeKey) {
+    if let Some(client) = client_config(node) {
+        if !is_noise(&client.transport) {
+            return;
+        }
+        let noise = client
+            .transport
+            .noise
+            .get_or_insert_with(NoiseConfig::default);
+        if noise.remote_public_key.is_none() {
+            noise.remote_public_key = Some(key.client_public_key().to_string());
+        }
+    }
+}
+
+fn is_noise(transport: &TransportConfig) -> bool {
+    transport.transport_type == TransportType::Noise
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotated(created_at: DateTime<Utc>, overlap: chrono::Duration) -> NodeKey {
+        NodeKey {
+            node: "edge".to_string(),
+            public_key: "new-public".to_string(),
+            created_at,
+            previous_expires_at: Some(created_at + overlap),
+            private_key: "new-private".to_string(),
+            previous_private_key: Some("old-private".to_string()),
+            previous_public_key: Some("old-public".to_string()),
+        }
+    }
+
+    #[test]
+    fn rotation_is_staged_over_the_overlap() {
+        let start = Utc::now();
+        let overlap = chrono::Duration::seconds(600);
+        let mut key = rotated(start, overlap);
+
+        // The server accepts both keys while clients still use the old one.
+        assert_eq!(key.client_public_key(), "old-public");
+        assert_eq!(key.live_previous_private_key(start), Some("old-private"));
+        assert!(!key.clients_switch_due(start + chrono::Duration::seconds(299)));
+
+        // Halfway through, clients are moved to the new key...
+        assert!(key.clients_switch_due(start + chrono::Duration::seconds(300)));
+        key.previous_public_key = None;
+        assert_eq!(key.client_public_key(), "new-public");
+        assert!(!key.clients_switch_due(start + chrono::Duration::seconds(300)));
+
+        // ...and the old one is only dropped once the overlap has ended.
+        let end = start + overlap;
+        assert_eq!(
+            key.live_previous_private_key(end - chrono::Duration::seconds(1)),
+            Some("old-private")
+        );
+        assert_eq!(key.live_previous_private_key(end), None);
+    }
+
+    #[test]
+    fn rotations_do_not_overlap() {
+        let start = Utc::now();
+        let every = Duration::from_secs(60);
+        let mut key = rotated(start, chrono::Duration::seconds(600));
+
+        assert!(!key.rotation_due(every, start + chrono::Duration::seconds(120)));
+        key.previous_private_key = None;
+        assert!(!key.rotation_due(every, start + chrono::Duration::seconds(30)));
+        assert!(key.rotation_due(every, start + chrono::Duration::seconds(120)));
+    }
+}
@@ -0,0 +1,622 @@
+//! Templates give every node carrying the tags of their selector the same
+//! port mapping and reverse proxy, with `{{var}}` placeholders in strings
+//! and keys filled in from the node's own `variables`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::audit::Actor;
+use crate::certificate;
+use crate::config::{ManagerState, NodeRecord, Precondition, WriteOutcome};
+use crate::error::{AppError, AppResult};
+use crate::revision::snapshot;
+use crate::tunnel;
+use crate::validate::{self, FieldError};
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Template {
+    pub project: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Nodes carrying every one of these tags are bound to the template.
+    pub selector: Vec<String>,
+    /// The `port_mapping` of the bound nodes.
+    #[schema(value_type = Option<Object>)]
+    pub port_mapping: Option<Value>,
+    /// The `reverse_proxy` of the bound nodes.
+    #[schema(value_type = Option<Object>)]
+    pub reverse_proxy: Option<Value>,
+    /// Placeholders of the template, which every bound node must set in its
+    /// `variables`.
+    pub variables: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Template {
+    pub fn selects(&self, node: &NodeRecord) -> bool {
+        self.selector.iter().all(|tag| node.tags.contains(tag))
+    }
+}
+
+/// What a template sets; parts left unset are kept from each node.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct TemplateSpec {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub selector: Vec<String>,
+    /// A port mapping with placeholders, such as `"0.0.0.0:{{port}}"`.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub port_mapping: Option<Value>,
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub reverse_proxy: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct CreateTemplate {
+    pub name: String,
+    #[serde(flatten)]
+    pub spec: TemplateSpec,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderOutcome {
+    /// The node was written with the rendered template.
+    Updated,
+    /// The node already matches the rendered template.
+    Unchanged,
+    /// The node differs from the rendered template, which was not applied.
+    Outdated,
+    /// The template does not render into a valid node; the node was left
+    /// as it is.
+    Failed,
+}
+
+/// How the template fared on one of its bound nodes.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct TemplateNode {
+    pub node: String,
+    pub outcome: RenderOutcome,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct TemplateReport {
+    pub template: Template,
+    pub nodes: Vec<TemplateNode>,
+}
+
+/// Whether `name` may be used in a `{{name}}` placeholder.
+pub fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Rejects a template spec with a 422 listing every invalid field.
+pub fn check_spec(name: &str, spec: &TemplateSpec) -> AppResult<()> {
+    let mut errors = Vec::new();
+    if spec.selector.is_empty() {
+        errors.push(FieldError::new("selector", "must name at least one tag"));
+    }
+    if spec.selector.iter().any(|tag| tag.trim().is_empty()) {
+        errors.push(FieldError::new("selector", "tags cannot be empty"));
+    }
+    if spec.port_mapping.is_none() && spec.reverse_proxy.is_none() {
+        errors.push(FieldError::new(
+            "port_mapping",
+            "a template must set port_mapping, reverse_proxy or both",
+        ));
+    }
+    for (field, value) in [
+        ("port_mapping", &spec.port_mapping),
+        ("reverse_proxy", &spec.reverse_proxy),
+    ] {
+        match value {
+            Some(Value::Object(_)) => {
+                placeholders(value.iter(), field, &mut errors);
+            }
+            Some(_) => errors.push(FieldError::new(field, "must be an object")),
+            None => {}
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::unprocessable(
+            format!("template '{name}' is invalid"),
+            errors,
+        ))
+    }
+}
+
+/// The placeholders of `values`, recording malformed ones in `errors` under
+/// `field`.
+pub fn placeholders<'a>(
+    values: impl IntoIterator<Item = &'a Value>,
+    field: &str,
+    errors: &mut Vec<FieldError>,
+) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for value in values {
+        collect(value, field, &mut names, errors);
+    }
+    names.into_iter().collect()
+}
+
+fn collect(value: &Value, path: &str, names: &mut BTreeSet<String>, errors: &mut Vec<FieldError>) {
+    match value {
+        Value::String(text) => scan(text, path, names, errors),
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect(item, &format!("{path}[{index}]"), names, errors);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let path = format!("{path}.{key}");
+                scan(key, &path, names, errors);
+                collect(value, &path, names, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn scan(text: &str, path: &str, names: &mut BTreeSet<String>, errors: &mut Vec<FieldError>) {
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            errors.push(FieldError::new(path, "has a '{{' without a closing '}}'"));
+            return;
+        };
+        let name = rest[start + 2..start + 2 + length].trim();
+        if is_variable_name(name) {
+            names.insert(name.to_string());
+        } else {
+            errors.push(FieldError::new(
+                path,
+                format!(
+                    "has a placeholder named '{name}', which is not letters, digits, '_' and '-'"
+                ),
+            ));
+        }
+        rest = &rest[start + 4 + length..];
+    }
+}
+
+// Placeholders without a value are left as they are and listed in `missing`.
+fn fill(
+    value: &Value,
+    variables: &BTreeMap<String, String>,
+    missing: &mut BTreeSet<String>,
+) -> Value {
+    match value {
+        Value::String(text) => Value::String(fill_text(text, variables, missing)),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| fill(item, variables, missing))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        fill_text(key, variables, missing),
+                        fill(value, variables, missing),
+                    )
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn fill_text(
+    text: &str,
+    variables: &BTreeMap<String, String>,
+    missing: &mut BTreeSet<String>,
+) -> String {
+    let mut filled = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 4 + length;
+        let name = rest[start + 2..start + 2 + length].trim();
+        filled.push_str(&rest[..start]);
+        match variables.get(name) {
+            Some(value) => filled.push_str(value),
+            None => {
+                missing.insert(name.to_string());
+                filled.push_str(&rest[start..end]);
+            }
+        }
+        rest = &rest[end..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// `node` with the parts set by `template` rendered with its variables.
+pub fn render(template: &Template, node: &NodeRecord) -> Result<NodeRecord, Vec<FieldError>> {
+    let mut missing = BTreeSet::new();
+    let port_mapping = template
+        .port_mapping
+        .as_ref()
+        .map(|value| fill(value, &node.variables, &mut missing));
+    let reverse_proxy = template
+        .reverse_proxy
+        .as_ref()
+        .map(|value| fill(value, &node.variables, &mut missing));
+    if !missing.is_empty() {
+        return Err(missing
+            .into_iter()
+            .map(|name| {
+                FieldError::new(
+                    format!("variables.{name}"),
+                    format!("is not set, but template '{}' uses it", template.name),
+                )
+            })
+            .collect());
+    }
+
+    let mut rendered = node.clone();
+    let mut errors = Vec::new();
+    if let Some(value) = port_mapping {
+        match serde_json::from_value(value) {
+            Ok(spec) => rendered.port_mapping = Some(spec),
+            Err(err) => errors.push(FieldError::new(
+                "port_mapping",
+                format!(
+                    "template '{}' does not render to a port mapping: {err}",
+                    template.name
+                ),
+            )),
+        }
+    }
+    if let Some(value) = reverse_proxy {
+        match serde_json::from_value(value) {
+            Ok(spec) => rendered.reverse_proxy = Some(spec),
+            Err(err) => errors.push(FieldError::new(
+                "reverse_proxy",
+                format!(
+                    "template '{}' does not render to a reverse proxy: {err}",
+                    template.name
+                ),
+            )),
+        }
+    }
+    if errors.is_empty() {
+        Ok(rendered)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Renders the template that `node` is bound to by its tags into it, if
+/// any. Returns why it could not.
+pub async fn bind(state: &ManagerState, node: &mut NodeRecord) -> Result<Vec<FieldError>> {
+    let templates: Vec<_> = state
+        .list_templates(&node.project)
+        .await?
+        .into_iter()
+        .filter(|template| template.selects(node))
+        .collect();
+    match templates.as_slice() {
+        [] => Ok(Vec::new()),
+        [template] => match render(template, node) {
+            Ok(rendered) => {
+                *node = rendered;
+                Ok(Vec::new())
+            }
+            Err(errors) => Ok(errors),
+        },
+        _ => Ok(vec![several(&templates)]),
+    }
+}
+
+/// Like [`bind`], rejecting the node with a 422 if its template does not
+/// render.
+pub async fn check_bind(state: &ManagerState, node: &mut NodeRecord) -> AppResult<()> {
+    let errors = bind(state, node).await?;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::unprocessable(
+            format!("node '{}' is invalid", node.name),
+            errors,
+        ))
+    }
+}
+
+fn several(templates: &[Template]) -> FieldError {
+    let names: Vec<_> = templates
+        .iter()
+        .map(|template| template.name.as_str())
+        .collect();
+    FieldError::new(
+        "tags",
+        format!(
+            "select templates {}; a node can only be bound to one",
+            names.join(", ")
+        ),
+    )
+}
+
+/// Renders `template` into every node it binds and writes those that
+/// changed. Nodes it does not render into are left as they are and
+/// reported with their errors.
+pub async fn apply(
+    state: &ManagerState,
+    template: &Template,
+    actor: &Actor,
+) -> Result<Vec<TemplateNode>> {
+    let mut report = Vec::new();
+    for (node, rendered) in render_all(state, template).await? {
+        let name = node.name.clone();
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(errors) => {
+                report.push(TemplateNode {
+                    node: name,
+                    outcome: RenderOutcome::Failed,
+                    errors,
+                });
+                continue;
+            }
+        };
+        let entry = match state
+            .write(rendered, actor, Precondition::Version(node.version))
+            .await?
+        {
+            WriteOutcome::Done(version) if version == node.version => TemplateNode {
+                node: name,
+                outcome: RenderOutcome::Unchanged,
+                errors: Vec::new(),
+            },
+            WriteOutcome::Done(_) => TemplateNode {
+                node: name,
+                outcome: RenderOutcome::Updated,
+                errors: Vec::new(),
+            },
+            _ => TemplateNode {
+                node: name,
+                outcome: RenderOutcome::Failed,
+                errors: vec![FieldError::new(
+                    "version",
+                    "the node changed while the template was applied; apply it again",
+                )],
+            },
+        };
+        report.push(entry);
+    }
+    Ok(report)
+}
+
+/// What [`apply`] would do to the nodes `template` binds, without writing
+/// any.
+pub async fn preview(state: &ManagerState, template: &Template) -> Result<Vec<TemplateNode>> {
+    let mut report = Vec::new();
+    for (node, rendered) in render_all(state, template).await? {
+        let (outcome, errors) = match rendered {
+            Ok(rendered) if snapshot(&rendered)? == snapshot(&node)? => {
+                (RenderOutcome::Unchanged, Vec::new())
+            }
+            Ok(_) => (RenderOutcome::Outdated, Vec::new()),
+            Err(errors) => (RenderOutcome::Failed, errors),
+        };
+        report.push(TemplateNode {
+            node: node.name,
+            outcome,
+            errors,
+        });
+    }
+    Ok(report)
+}
+
+// Every node bound to `template`, rendered and checked as a write of the
+// node would be.
+async fn render_all(
+    state: &ManagerState,
+    template: &Template,
+) -> Result<Vec<(NodeRecord, Result<NodeRecord, Vec<FieldError>>)>> {
+    let templates = state.list_templates(&template.project).await?;
+    let mut rendered = Vec::new();
+    for node in state.list(&template.project).await? {
+        if !template.selects(&node) {
+            continue;
+        }
+        let mut others: Vec<_> = templates
+            .iter()
+            .filter(|other| other.name != template.name && other.selects(&node))
+            .cloned()
+            .collect();
+        if !others.is_empty() {
+            others.push(template.clone());
+            others.sort_by(|a, b| a.name.cmp(&b.name));
+            rendered.push((node, Err(vec![several(&others)])));
+            continue;
+        }
+        let result = match render(template, &node) {
+            Ok(candidate) => {
+                let mut errors = validate::node_errors(&candidate);
+                errors.extend(tunnel::node_errors(state, &candidate).await?);
+                errors.extend(certificate::node_errors(state, &candidate).await?);
+                if errors.is_empty() {
+                    Ok(candidate)
+                } else {
+                    Err(errors)
+                }
+            }
+            Err(errors) => Err(errors),
+        };
+        rendered.push((node, result));
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixture::{self, state};
+
+    fn spec(selector: &[&str], upstream: &str) -> TemplateSpec {
+        TemplateSpec {
+            description: None,
+            selector: selector.iter().map(|tag| tag.to_string()).collect(),
+            port_mapping: None,
+            reverse_proxy: Some(json!({ "default_upstream": upstream })),
+        }
+    }
+
+    fn node(name: &str, tags: &[&str], variables: &[(&str, &str)]) -> NodeRecord {
+        NodeRecord {
+            variables: variables
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..fixture::node(name, tags)
+        }
+    }
+
+    fn upstream(node: &NodeRecord) -> Option<&str> {
+        node.reverse_proxy.as_ref()?.default_upstream.as_deref()
+    }
+
+    #[test]
+    fn missing_variables_are_reported_by_name() {
+        let now = Utc::now();
+        let template = Template {
+            project: "default".to_string(),
+            name: "edge".to_string(),
+            description: None,
+            selector: vec!["edge".to_string()],
+            port_mapping: None,
+            reverse_proxy: Some(json!({ "default_upstream": "http://{{host}}:{{port}}" })),
+            variables: vec!["host".to_string(), "port".to_string()],
+            created_at: now,
+            updated_at: now,
+        };
+
+        let errors = render(&template, &node("paris", &["edge"], &[])).unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, ["variables.host", "variables.port"]);
+        assert_eq!(errors[0].reason, "is not set, but template 'edge' uses it");
+
+        let partial = node("paris", &["edge"], &[("host", "10.0.0.1")]);
+        let errors = render(&template, &partial).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "variables.port");
+
+        let complete = node(
+            "paris",
+            &["edge"],
+            &[("host", "10.0.0.1"), ("port", "8080")],
+        );
+        let rendered = render(&template, &complete).unwrap();
+        assert_eq!(upstream(&rendered), Some("http://10.0.0.1:8080"));
+    }
+
+    #[tokio::test]
+    async fn nodes_matched_by_two_templates_are_bound_to_neither() {
+        let state = state().await;
+        let actor = Actor::manager();
+        for (name, tag) in [("edge", "edge"), ("europe", "eu")] {
+            state
+                .create_template("default", name, spec(&[tag], "http://{{host}}"), &actor)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let mut both = node("paris", &["edge", "eu"], &[("host", "10.0.0.1")]);
+        let errors = bind(&state, &mut both).await.unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "tags");
+        assert_eq!(
+            errors[0].reason,
+            "select templates edge, europe; a node can only be bound to one"
+        );
+        assert_eq!(upstream(&both), None);
+
+        state
+            .write(both, &actor, Precondition::Absent)
+            .await
+            .unwrap();
+        let edge = state
+            .get_template("default", "edge")
+            .await
+            .unwrap()
+            .unwrap();
+        let report = preview(&state, &edge).await.unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].outcome, RenderOutcome::Failed);
+        assert_eq!(report[0].errors[0].field, "tags");
+    }
+
+    #[tokio::test]
+    async fn updating_a_template_re_renders_its_nodes() {
+        let state = state().await;
+        let actor = Actor::manager();
+        state
+            .create_template(
+                "default",
+                "edge",
+                spec(&["edge"], "http://{{host}}:80"),
+                &actor,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let mut paris = node("paris", &["edge"], &[("host", "10.0.0.1")]);
+        assert!(bind(&state, &mut paris).await.unwrap().is_empty());
+        state
+            .write(paris, &actor, Precondition::Absent)
+            .await
+            .unwrap();
+        state
+            .write(node("lyon", &["core"], &[]), &actor, Precondition::Absent)
+            .await
+            .unwrap();
+
+        let template = state
+            .update_template(
+                "default",
+                "edge",
+                spec(&["edge"], "http://{{host}}:8080"),
+                &actor,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let report = preview(&state, &template).await.unwrap();
+        assert_eq!(report[0].outcome, RenderOutcome::Outdated);
+
+        let report = apply(&state, &template, &actor).await.unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].node, "paris");
+        assert_eq!(report[0].outcome, RenderOutcome::Updated);
+        let paris = state.get("default", "paris").await.unwrap().unwrap();
+        assert_eq!(upstream(&paris), Some("http://10.0.0.1:8080"));
+
+        let report = apply(&state, &template, &actor).await.unwrap();
+        assert_eq!(report[0].outcome, RenderOutcome::Unchanged);
+    }
+}
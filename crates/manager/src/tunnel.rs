@@ -0,0 +1,367 @@
+//! Tunnels pair a Rathole service on a server node with the matching service
+//! on a client node. Both services are derived from the tunnel and merged
+//! into the nodes' own port mappings when the nodes fetch their config.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use laval_model::{PortMappingMode, PortMappingSpec};
+use rand::RngCore;
+use rathole::config::{
+    ClientConfig, ClientServiceConfig, MaskedString, ServerConfig, ServerServiceConfig, ServiceType,
+};
+use sea_orm::ConnectionTrait;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::{find_node, find_tunnels_of, ManagerState, NodeRecord};
+use crate::error::{AppError, AppResult};
+use crate::validate::{check_host_port, FieldError};
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Tunnel {
+    /// Also the project of both nodes.
+    pub project: String,
+    /// Also the name of the Rathole service on both nodes.
+    pub name: String,
+    pub server_node: String,
+    pub client_node: String,
+    /// Where the server node exposes the service.
+    pub bind_addr: String,
+    /// Where the client node forwards the service to.
+    pub local_addr: String,
+    #[serde(rename = "type")]
+    pub service_type: ServiceType,
+    pub created_at: DateTime<Utc>,
+    /// How often the manager replaces the token, if at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate_every_secs: Option<u64>,
+    /// When the current token was issued.
+    pub token_rotated_at: DateTime<Utc>,
+    /// Until when the server still accepts the token replaced by the last
+    /// rotation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_token_expires_at: Option<DateTime<Utc>>,
+    /// Generated by the manager and only ever sent to the two nodes.
+    #[serde(skip)]
+    pub token: String,
+    #[serde(skip)]
+    pub previous_token: Option<String>,
+}
+
+impl Tunnel {
+    /// Whether the scheduled rotation of the token is due at `now`.
+    pub fn rotation_due(&self, now: DateTime<Utc>) -> bool {
+        self.rotate_every_secs.is_some_and(|every| {
+            now.signed_duration_since(self.token_rotated_at)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed.as_secs() >= every)
+        })
+    }
+
+    /// The token replaced by the last rotation, while it is still accepted.
+    pub fn live_previous_token(&self, now: DateTime<Utc>) -> Option<&str> {
+        match (&self.previous_token, self.previous_token_expires_at) {
+            (Some(token), Some(expires_at)) if expires_at > now => Some(token),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct CreateTunnel {
+    pub name: String,
+    pub server_node: String,
+    pub client_node: String,
+    pub bind_addr: String,
+    pub local_addr: String,
+    #[serde(rename = "type", default)]
+    pub service_type: ServiceType,
+    #[serde(default)]
+    pub rotate_every_secs: Option<u64>,
+}
+
+/// A random service token shared by both sides of a tunnel.
+pub fn generate_token() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Adds the services of `tunnels` to the port mapping of `node`. Reports the
+/// tunnels the node cannot carry, e.g. because it does not run the right
+/// side of Rathole.
+pub fn merge(node: &mut NodeRecord, tunnels: &[Tunnel]) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let now = Utc::now();
+
+    for tunnel in tunnels {
+        let token = Some(MaskedString::from(tunnel.token.as_str()));
+
+        if tunnel.server_node == node.name {
+            let Some(server) = server_config(node) else {
+                errors.push(FieldError::new(
+                    "port_mapping",
+                    format!(
+                        "tunnel '{}' needs node '{}' to run a Rathole server",
+                        tunnel.name, node.name
+                    ),
+                ));
+                continue;
+            };
+            if server.services.contains_key(&tunnel.name) {
+                errors.push(clashing_service("server", &tunnel.name));
+                continue;
+            }
+            server.services.insert(
+                tunnel.name.clone(),
+                ServerServiceConfig {
+                    service_type: tunnel.service_type,
+                    name: tunnel.name.clone(),
+                    bind_addr: tunnel.bind_addr.clone(),
+                    token,
+                    // The client switches to a new token as soon as it gets
+                    // it, so only the server needs the old one for a while.
+                    previous_tokens: tunnel
+                        .live_previous_token(now)
+                        .map(MaskedString::from)
+                        .into_iter()
+                        .collect(),
+                    nodelay: None,
+                },
+            );
+        } else if tunnel.client_node == node.name {
+            let Some(client) = client_config(node) else {
+                errors.push(FieldError::new(
+                    "port_mapping",
+                    format!(
+                        "tunnel '{}' needs node '{}' to run a Rathole client",
+                        tunnel.name, node.name
+                    ),
+                ));
+                continue;
+            };
+            if client.services.contains_key(&tunnel.name) {
+                errors.push(clashing_service("client", &tunnel.name));
+                continue;
+            }
+            client.services.insert(
+                tunnel.name.clone(),
+                ClientServiceConfig {
+                    service_type: tunnel.service_type,
+                    name: tunnel.name.clone(),
+                    local_addr: tunnel.local_addr.clone(),
+                    prefer_ipv6: false,
+                    token,
+                    nodelay: None,
+                    retry_interval: None,
+                },
+            );
+        }
+    }
+
+    errors
+}
+
+/// Outcome of creating a tunnel.
+#[derive(Debug)]
+pub enum TunnelCreation {
+    Created(Tunnel),
+    /// The name is already taken in the project.
+    Exists,
+    /// Nothing was stored because of these invalid fields.
+    Invalid(Vec<FieldError>),
+}
+
+/// The tunnel `name` as created, or why it was refused: a 409 if the name is
+/// taken, a 422 listing every invalid field otherwise.
+pub fn created(name: &str, creation: TunnelCreation) -> AppResult<Tunnel> {
+    match creation {
+        TunnelCreation::Created(tunnel) => Ok(tunnel),
+        TunnelCreation::Exists => Err(AppError::conflict(format!(
+            "tunnel '{name}' already exists"
+        ))),
+        TunnelCreation::Invalid(errors) => Err(AppError::unprocessable(
+            format!("tunnel '{name}' is invalid"),
+            errors,
+        )),
+    }
+}
+
+/// Why a new tunnel in `project` cannot be stored, as read through `db`, so
+/// that the tunnel is checked in the transaction that stores it.
+pub async fn new_errors(
+    db: &impl ConnectionTrait,
+    token_overlap: Duration,
+    project: &str,
+    tunnel: &CreateTunnel,
+) -> Result<Vec<FieldError>> {
+    let name = tunnel.name.trim();
+    let mut errors = Vec::new();
+    if name.is_empty() {
+        errors.push(FieldError::new("name", "cannot be empty"));
+    }
+    check_host_port(&mut errors, "bind_addr", &tunnel.bind_addr);
+    check_host_port(&mut errors, "local_addr", &tunnel.local_addr);
+    if tunnel.server_node == tunnel.client_node {
+        errors.push(FieldError::new(
+            "client_node",
+            "must differ from server_node",
+        ));
+    }
+    if let Some(every) = tunnel.rotate_every_secs {
+        let overlap = token_overlap.as_secs();
+        if every <= overlap {
+            errors.push(FieldError::new(
+                "rotate_every_secs",
+                format!("must be longer than the {overlap}s token overlap"),
+            ));
+        }
+    }
+
+    let server = find_node(db, project, &tunnel.server_node).await?;
+    if server.is_none() {
+        errors.push(missing_node("server_node", &tunnel.server_node));
+    }
+    let client = find_node(db, project, &tunnel.client_node).await?;
+    if client.is_none() {
+        errors.push(missing_node("client_node", &tunnel.client_node));
+    }
+
+    if let (Some(server), Some(client), true) = (server, client, errors.is_empty()) {
+        let now = Utc::now();
+        let new = Tunnel {
+            project: project.to_string(),
+            name: name.to_string(),
+            server_node: tunnel.server_node.clone(),
+            client_node: tunnel.client_node.clone(),
+            bind_addr: tunnel.bind_addr.clone(),
+            local_addr: tunnel.local_addr.clone(),
+            service_type: tunnel.service_type,
+            created_at: now,
+            rotate_every_secs: tunnel.rotate_every_secs,
+            token_rotated_at: now,
+            previous_token_expires_at: None,
+            token: String::new(),
+            previous_token: None,
+        };
+        for (field, node) in [("server_node", server), ("client_node", client)] {
+            let mut tunnels = find_tunnels_of(db, project, &node.name).await?;
+            tunnels.push(new.clone());
+            errors.extend(node_fit_errors(db, node, &tunnels, Some(field)).await?);
+        }
+    }
+
+    Ok(errors)
+}
+
+/// Rejects a node write that would break one of the node's tunnels.
+pub async fn check_node(state: &ManagerState, node: &NodeRecord) -> AppResult<()> {
+    let errors = node_errors(state, node).await?;
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::unprocessable(
+        format!("node '{}' does not fit its tunnels", node.name),
+        errors,
+    ))
+}
+
+pub async fn node_errors(state: &ManagerState, node: &NodeRecord) -> Result<Vec<FieldError>> {
+    state.tunnel_errors(node).await
+}
+
+// Checks that `node` can carry `tunnels` and speaks the same transport as the
+// other end of each of them, as read through `db`. Errors are reported
+// against `field` if given, otherwise against the node's own fields.
+pub(crate) async fn node_fit_errors(
+    db: &impl ConnectionTrait,
+    mut node: NodeRecord,
+    tunnels: &[Tunnel],
+    field: Option<&str>,
+) -> Result<Vec<FieldError>> {
+    let mut errors: Vec<FieldError> = merge(&mut node, tunnels)
+        .into_iter()
+        .map(|error| match field {
+            Some(field) => FieldError::new(field, error.reason),
+            None => error,
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    for tunnel in tunnels {
+        let peer_name = if tunnel.server_node == node.name {
+            &tunnel.client_node
+        } else {
+            &tunnel.server_node
+        };
+        let Some(mut peer) = find_node(db, &node.project, peer_name).await? else {
+            continue;
+        };
+        let server_side = tunnel.server_node == node.name;
+        let (server, client) = if server_side {
+            (
+                server_config(&mut node).cloned(),
+                client_config(&mut peer).cloned(),
+            )
+        } else {
+            (
+                server_config(&mut peer).cloned(),
+                client_config(&mut node).cloned(),
+            )
+        };
+        if let (Some(server), Some(client)) = (server, client) {
+            if server.transport.transport_type != client.transport.transport_type {
+                errors.push(FieldError::new(
+                    field.unwrap_or("port_mapping"),
+                    format!(
+                        "tunnel '{}' joins a {:?} server on '{}' to a {:?} client on '{}'",
+                        tunnel.name,
+                        server.transport.transport_type,
+                        tunnel.server_node,
+                        client.transport.transport_type,
+                        tunnel.client_node
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
+pub(crate) fn server_config(node: &mut NodeRecord) -> Option<&mut ServerConfig> {
+    match node.port_mapping.as_mut()? {
+        PortMappingSpec {
+            mode: PortMappingMode::Server,
+            config,
+            ..
+        } => config.server.as_mut(),
+        _ => None,
+    }
+}
+
+pub(crate) fn client_config(node: &mut NodeRecord) -> Option<&mut ClientConfig> {
+    match node.port_mapping.as_mut()? {
+        PortMappingSpec {
+            mode: PortMappingMode::Client,
+            config,
+            ..
+        } => config.client.as_mut(),
+        _ => None,
+    }
+}
+
+fn clashing_service(side: &str, name: &str) -> FieldError {
+    FieldError::new(
+        format!("port_mapping.config.{side}.services.{name}"),
+        format!("clashes with tunnel '{name}'"),
+    )
+}
+
+fn missing_node(field: &str, name: &str) -> FieldError {
+    FieldError::new(field, format!("node '{name}' does not exist"))
+}
@@ -0,0 +1,282 @@
+//! Semantic checks run on node records before they are stored, so that a
+//! configuration the node cannot run is rejected by the manager instead.
+
+use std::net::SocketAddr;
+
+use laval_model::{PortMappingMode, PortMappingSpec, ReverseProxySpec};
+use rathole::config::{TransportConfig, TransportType};
+use serde::Serialize;
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::config::NodeRecord;
+use crate::error::{AppError, AppResult};
+use crate::template;
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct FieldError {
+    /// Dotted path of the field, as in the JSON form of the node.
+    pub field: String,
+    pub reason: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Rejects a blank node name, before anything else is looked at.
+pub fn check_name(name: &str) -> AppResult<()> {
+    if name.trim().is_empty() {
+        Err(AppError::bad_request("node name cannot be empty"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Rejects `node` with a 422 listing every invalid field.
+pub fn check_node(node: &NodeRecord) -> AppResult<()> {
+    let errors = node_errors(node);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::unprocessable(
+            format!("node '{}' is invalid", node.name),
+            errors,
+        ))
+    }
+}
+
+pub fn node_errors(node: &NodeRecord) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if let Some(bind) = &node.reverse_proxy_bind {
+        if bind.parse::<SocketAddr>().is_err() {
+            errors.push(FieldError::new(
+                "reverse_proxy_bind",
+                format!("'{bind}' is not a socket address such as 0.0.0.0:8080"),
+            ));
+        }
+    }
+
+    if let Some(spec) = &node.port_mapping {
+        errors.extend(port_mapping_errors(spec));
+    }
+
+    if let Some(spec) = &node.reverse_proxy {
+        errors.extend(reverse_proxy_errors(spec));
+    }
+
+    for name in node.variables.keys() {
+        if !template::is_variable_name(name) {
+            errors.push(FieldError::new(
+                format!("variables.{name}"),
+                "names may only contain letters, digits, '_' and '-'",
+            ));
+        }
+    }
+
+    errors
+}
+
+fn reverse_proxy_errors(spec: &ReverseProxySpec) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    for (hostname, upstream) in &spec.routes {
+        let field = format!("reverse_proxy.routes.{hostname}");
+        if hostname.trim().is_empty() || hostname.contains(char::is_whitespace) {
+            errors.push(FieldError::new(
+                &field,
+                "hostname must not be empty or contain spaces",
+            ));
+        }
+        check_upstream(&mut errors, &field, upstream);
+    }
+    if let Some(upstream) = &spec.default_upstream {
+        check_upstream(&mut errors, "reverse_proxy.default_upstream", upstream);
+    }
+    match (&spec.tls, &spec.certificate) {
+        (Some(_), Some(_)) => errors.push(FieldError::new(
+            "reverse_proxy.certificate",
+            "cannot be combined with tls",
+        )),
+        (None, Some(name)) if name.trim().is_empty() => errors.push(FieldError::new(
+            "reverse_proxy.certificate",
+            "cannot be empty",
+        )),
+        _ => {}
+    }
+    if let Some(tls) = &spec.tls {
+        for (field, path) in [("cert", &tls.cert), ("key", &tls.key)] {
+            if path.trim().is_empty() {
+                errors.push(FieldError::new(
+                    format!("reverse_proxy.tls.{field}"),
+                    "must be a path on the node",
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+// Mirrors what the node needs to connect to an upstream.
+fn check_upstream(errors: &mut Vec<FieldError>, field: &str, upstream: &str) {
+    let reason = match Url::parse(upstream) {
+        Ok(url) if url.host_str().is_none() => "has no host",
+        Ok(url) if url.port_or_known_default().is_none() => "has no port",
+        Ok(_) => return,
+        Err(_) => "is not a URL",
+    };
+    errors.push(FieldError::new(
+        field,
+        format!("upstream '{upstream}' {reason}, such as http://127.0.0.1:8080"),
+    ));
+}
+
+fn port_mapping_errors(spec: &PortMappingSpec) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    match spec.mode {
+        PortMappingMode::Server => match &spec.config.server {
+            Some(server) => {
+                let prefix = "port_mapping.config.server";
+                check_host_port(
+                    &mut errors,
+                    &format!("{prefix}.bind_addr"),
+                    &server.bind_addr,
+                );
+                let mut services: Vec<_> = server.services.iter().collect();
+                services.sort_by_key(|(name, _)| name.as_str());
+                for (name, service) in services {
+                    let prefix = format!("{prefix}.services.{name}");
+                    if service.token.is_none() && server.default_token.is_none() {
+                        errors.push(missing_token(&prefix));
+                    }
+                    check_host_port(
+                        &mut errors,
+                        &format!("{prefix}.bind_addr"),
+                        &service.bind_addr,
+                    );
+                }
+                let needs_pkcs12 = spec.certificate.is_none();
+                check_transport(&mut errors, prefix, &server.transport, needs_pkcs12);
+            }
+            None => errors.push(FieldError::new(
+                "port_mapping.config.server",
+                "required when the mode is server",
+            )),
+        },
+        PortMappingMode::Client => match &spec.config.client {
+            Some(client) => {
+                let prefix = "port_mapping.config.client";
+                check_host_port(
+                    &mut errors,
+                    &format!("{prefix}.remote_addr"),
+                    &client.remote_addr,
+                );
+                let mut services: Vec<_> = client.services.iter().collect();
+                services.sort_by_key(|(name, _)| name.as_str());
+                for (name, service) in services {
+                    let prefix = format!("{prefix}.services.{name}");
+                    if service.token.is_none() && client.default_token.is_none() {
+                        errors.push(missing_token(&prefix));
+                    }
+                    check_host_port(
+                        &mut errors,
+                        &format!("{prefix}.local_addr"),
+                        &service.local_addr,
+                    );
+                }
+                check_transport(&mut errors, prefix, &client.transport, false);
+            }
+            None => errors.push(FieldError::new(
+                "port_mapping.config.client",
+                "required when the mode is client",
+            )),
+        },
+    }
+
+    // The identity of a stored certificate is only built on the node, so
+    // Rathole is shown an empty one in its place.
+    let mut spec = spec.clone();
+    if spec.certificate.is_some() {
+        if let Err(err) = spec.set_identity(Vec::new(), "") {
+            errors.push(FieldError::new("port_mapping.certificate", err.to_string()));
+        }
+    }
+
+    // Rathole's own checks are authoritative; report anything they reject
+    // that the field checks above did not already explain.
+    if errors.is_empty() {
+        if let Err(err) = spec.into_rathole() {
+            errors.push(FieldError::new("port_mapping", format!("{err:#}")));
+        }
+    }
+
+    errors
+}
+
+fn missing_token(service: &str) -> FieldError {
+    FieldError::new(
+        format!("{service}.token"),
+        "must be set when there is no default_token",
+    )
+}
+
+// Rathole resolves these when it binds or connects, so only the shape can be
+// checked here.
+pub(crate) fn check_host_port(errors: &mut Vec<FieldError>, field: &str, addr: &str) {
+    let valid = addr
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if !valid {
+        errors.push(FieldError::new(
+            field,
+            format!("'{addr}' is not a host:port address"),
+        ));
+    }
+}
+
+fn check_transport(
+    errors: &mut Vec<FieldError>,
+    prefix: &str,
+    transport: &TransportConfig,
+    needs_pkcs12: bool,
+) {
+    let prefix = format!("{prefix}.transport");
+
+    if let Some(proxy) = &transport.tcp.proxy {
+        if !matches!(proxy.scheme(), "socks5" | "http") {
+            errors.push(FieldError::new(
+                format!("{prefix}.tcp.proxy"),
+                format!(
+                    "unsupported proxy scheme '{}'; use socks5 or http",
+                    proxy.scheme()
+                ),
+            ));
+        }
+    }
+
+    if transport.transport_type == TransportType::Tls {
+        match &transport.tls {
+            Some(tls)
+                if needs_pkcs12 && (tls.pkcs12.is_none() || tls.pkcs12_password.is_none()) =>
+            {
+                errors.push(FieldError::new(
+                    format!("{prefix}.tls"),
+                    "pkcs12 and pkcs12_password are required on the server without a certificate",
+                ))
+            }
+            Some(_) => {}
+            None => errors.push(FieldError::new(
+                format!("{prefix}.tls"),
+                "required when the transport type is tls",
+            )),
+        }
+    }
+}
@@ -1,8 +1,14 @@
-use anyhow::{anyhow, Result};
-use rathole::{config::Config as RatholeConfig, InstanceMode};
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use rathole::config::{Config as RatholeConfig, TransportType};
+use rathole::InstanceMode;
 use serde::{Deserialize, Serialize};
 
+pub mod node;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum PortMappingMode {
     Server,
@@ -10,9 +16,14 @@ pub enum PortMappingMode {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct PortMappingSpec {
     pub mode: PortMappingMode,
     pub config: RatholeConfig,
+    /// Name of a certificate stored in the manager, served by the TLS
+    /// transport of a server in place of its `pkcs12` file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
 }
 
 impl PortMappingSpec {
@@ -38,4 +49,50 @@ impl PortMappingSpec {
         let config = rathole::sanitize_config(config, mode)?;
         Ok((config, mode))
     }
+
+    /// Gives the TLS transport of the server `pkcs12`, the identity built
+    /// from [`Self::certificate`], in place of a `pkcs12` file.
+    pub fn set_identity(&mut self, pkcs12: Vec<u8>, password: &str) -> Result<()> {
+        if self.mode != PortMappingMode::Server {
+            bail!("only a port mapping server serves a certificate");
+        }
+        let tls = self
+            .config
+            .server
+            .as_mut()
+            .filter(|server| server.transport.transport_type == TransportType::Tls)
+            .and_then(|server| server.transport.tls.as_mut())
+            .ok_or_else(|| anyhow!("a certificate is only served by the tls transport"))?;
+        tls.pkcs12_der = Some(pkcs12.into());
+        tls.pkcs12_password = Some(password.into());
+        Ok(())
+    }
+}
+
+/// Reverse-proxy settings of a node that are managed by the manager. The
+/// address the proxy binds to stays in the node's local configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReverseProxySpec {
+    /// Upstream URL of each hostname, such as `http://127.0.0.1:8080`.
+    #[serde(default)]
+    pub routes: BTreeMap<String, String>,
+    /// Upstream URL of hostnames without a route.
+    #[serde(default)]
+    pub default_upstream: Option<String>,
+    #[serde(default)]
+    pub tls: Option<ReverseProxyTls>,
+    /// Name of a certificate stored in the manager, served instead of the
+    /// files of `tls`.
+    #[serde(default)]
+    pub certificate: Option<String>,
+}
+
+/// Paths of the certificate and key on the node. Relative paths are read
+/// from the directory of the node's config file, as those of the file are.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReverseProxyTls {
+    pub cert: String,
+    pub key: String,
 }
@@ -0,0 +1,155 @@
+//! The node's own configuration file, `node.toml`. The node reads it at
+//! startup, and the manager renders it for nodes that cannot reach it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{PortMappingSpec, ReverseProxySpec};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NodeConfig {
+    #[serde(default)]
+    pub reverse_proxy: ReverseProxyConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_mapping: Option<PortMappingSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manager: Option<ManagerLinkConfig>,
+}
+
+/// Routes, default upstream and TLS settings are replaced by those of the
+/// manager when it has some for the node. A certificate stored in the
+/// manager takes the place of `tls`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReverseProxyConfig {
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub routes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_upstream: Option<String>,
+    /// Delivered by the manager, never read from the file.
+    #[serde(skip)]
+    pub certificate: Option<CertificatePem>,
+    /// Directory of the config file, which relative `tls` paths are read
+    /// from, whether they come from the file or from the manager.
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+/// Relative paths are read from the directory of the config file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// A certificate chain and its key as received from the manager.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CertificatePem {
+    pub name: String,
+    pub chain: String,
+    pub key: String,
+}
+
+impl fmt::Debug for CertificatePem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificatePem")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManagerLinkConfig {
+    pub endpoint: String,
+    pub node_name: String,
+    /// Project of `node_name` on the manager; the default project if empty.
+    #[serde(default)]
+    pub project: String,
+    /// Node credential issued by the manager for `node_name`.
+    pub token: String,
+}
+
+impl Default for ReverseProxyConfig {
+    fn default() -> Self {
+        Self {
+            bind: default_bind(),
+            tls: None,
+            routes: BTreeMap::new(),
+            default_upstream: None,
+            certificate: None,
+            dir: None,
+        }
+    }
+}
+
+impl ReverseProxyConfig {
+    /// This configuration with the routes of `spec`, if the manager has any
+    /// for the node; the bind address always comes from the local file.
+    /// `certificate` is the one `spec` names, as sent by the manager.
+    pub fn with_manager(
+        &self,
+        spec: Option<&ReverseProxySpec>,
+        certificate: Option<&CertificatePem>,
+    ) -> Self {
+        let Some(spec) = spec else {
+            return self.clone();
+        };
+        let certificate = spec.certificate.as_ref().and(certificate.cloned());
+        Self {
+            bind: self.bind.clone(),
+            tls: spec.tls.as_ref().map(|tls| {
+                self.resolve(TlsConfig {
+                    cert: PathBuf::from(&tls.cert),
+                    key: PathBuf::from(&tls.key),
+                })
+            }),
+            routes: spec.routes.clone(),
+            default_upstream: spec.default_upstream.clone(),
+            certificate,
+            dir: self.dir.clone(),
+        }
+    }
+
+    fn resolve(&self, tls: TlsConfig) -> TlsConfig {
+        match &self.dir {
+            Some(dir) => TlsConfig {
+                cert: dir.join(tls.cert),
+                key: dir.join(tls.key),
+            },
+            None => tls,
+        }
+    }
+}
+
+fn default_bind() -> String {
+    "0.0.0.0:8443".to_string()
+}
+
+impl NodeConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let raw = fs::read_to_string(&path).with_context(|| {
+            format!("failed to read node config at {}", path.as_ref().display())
+        })?;
+        let mut config = Self::parse(&raw)?;
+        // Relative certificate paths are read next to this file, where a
+        // config rendered by the manager expects its certificate.
+        let proxy = &mut config.reverse_proxy;
+        proxy.dir = path.as_ref().parent().map(Path::to_path_buf);
+        proxy.tls = proxy.tls.take().map(|tls| proxy.resolve(tls));
+        Ok(config)
+    }
+
+    /// Parses the contents of a config file, whose relative paths are left
+    /// as they are.
+    pub fn parse(raw: &str) -> Result<Self> {
+        toml::from_str(raw).context("invalid node configuration")
+    }
+}
@@ -1,18 +1,22 @@
-mod config;
+mod manager_link;
 mod proxy;
 mod rathole_runner;
+mod tls;
 
-use std::convert::TryFrom;
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use proxy::ReverseProxy;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
-use crate::config::{ManagerLinkConfig, NodeConfig};
-use laval_model::{PortMappingMode, PortMappingSpec};
+use laval_model::node::{NodeConfig, ReverseProxyConfig};
+
+use crate::manager_link::{LocalStatus, ProxyLink, RemoteConfig};
+use crate::tls::TlsFront;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Laval edge node service", long_about = None)]
@@ -32,14 +36,33 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
     let config = NodeConfig::from_file(&cli.config)?;
 
-    let proxy = ReverseProxy::from_config(&config.reverse_proxy)?;
-    let port_mapping = load_port_mapping(&config)?;
-    let rathole = port_mapping
-        .as_ref()
-        .map(rathole_runner::spawn_rathole)
-        .transpose()?;
+    let remote = load_remote_config(&config)?;
+
+    let proxy_config = config
+        .reverse_proxy
+        .with_manager(remote.reverse_proxy.as_ref(), remote.certificate.as_ref());
+    let local_status = LocalStatus::new(proxy_config.bind.clone());
+    let proxy = ReverseProxy::from_config(&proxy_config)?;
+    let tls = TlsFront::new(&proxy_config)?;
+    let port_mapping = remote.port_mapping.or_else(|| config.port_mapping.clone());
+    let rathole = if port_mapping.is_some() || config.manager.is_some() {
+        Some(rathole_runner::spawn_rathole(
+            port_mapping.as_ref(),
+            remote.port_mapping_certificate.as_ref(),
+            config.manager.clone(),
+            ProxyLink::new(
+                config.reverse_proxy.clone(),
+                proxy_config.clone(),
+                proxy.clone(),
+                tls.clone(),
+            ),
+            local_status,
+        )?)
+    } else {
+        None
+    };
 
-    run_proxy_service(&config, proxy)?;
+    run_proxy_service(&proxy_config, proxy, tls)?;
 
     if let Some(handle) = rathole {
         handle.shutdown();
@@ -48,75 +71,62 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn load_port_mapping(config: &NodeConfig) -> Result<Option<PortMappingSpec>> {
-    let mut spec = config.port_mapping.clone();
-
-    if let Some(manager) = &config.manager {
-        match fetch_port_mapping_from_manager(manager)? {
-            Some(remote) => {
-                info!(
-                    endpoint = %manager.endpoint,
-                    node = %manager.node_name,
-                    "loaded port mapping from manager",
-                );
-                spec = Some(remote);
-            }
-            None => {
-                info!(
-                    endpoint = %manager.endpoint,
-                    node = %manager.node_name,
-                    "manager did not provide port mapping configuration",
-                );
-            }
-        }
-    }
-
-    Ok(spec)
-}
-
-fn fetch_port_mapping_from_manager(manager: &ManagerLinkConfig) -> Result<Option<PortMappingSpec>> {
-    use laval_proto::manager::v1::{
-        node_manager_client::NodeManagerClient, GetNodeConfigRequest, PortMappingMode as ProtoMode,
+/// The node's configuration on the manager, if linked to one. What the
+/// manager does not provide is taken from the local file.
+fn load_remote_config(config: &NodeConfig) -> Result<RemoteConfig> {
+    let Some(manager) = &config.manager else {
+        return Ok(RemoteConfig {
+            port_mapping: None,
+            reverse_proxy: None,
+            certificate: None,
+            port_mapping_certificate: None,
+        });
     };
 
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()?;
-
-    runtime.block_on(async {
-        let mut client = NodeManagerClient::connect(manager.endpoint.clone()).await?;
-        let response = client
-            .get_node_config(GetNodeConfigRequest {
-                name: manager.node_name.clone(),
-            })
-            .await?
-            .into_inner();
-
-        if let Some(port_mapping) = response.port_mapping {
-            let mode = ProtoMode::try_from(port_mapping.mode)
-                .map_err(|_| anyhow!("unknown port mapping mode from manager"))?;
-            let mode = match mode {
-                ProtoMode::Server => PortMappingMode::Server,
-                ProtoMode::Client => PortMappingMode::Client,
-                ProtoMode::Unspecified => {
-                    return Err(anyhow!("manager returned unspecified port mapping mode"))
-                }
-            };
-
-            let config = serde_json::from_str(&port_mapping.config_json)
-                .with_context(|| "failed to parse port mapping configuration from manager")?;
-
-            Ok(Some(PortMappingSpec { mode, config }))
+    let remote = manager_link::fetch_node_config(manager)?;
+    for (part, provided) in [
+        ("port mapping", remote.port_mapping.is_some()),
+        ("reverse proxy routes", remote.reverse_proxy.is_some()),
+    ] {
+        if provided {
+            info!(
+                endpoint = %manager.endpoint,
+                node = %manager.node_name,
+                "loaded {part} from manager",
+            );
         } else {
-            Ok(None)
+            info!(
+                endpoint = %manager.endpoint,
+                node = %manager.node_name,
+                "manager did not provide {part}; using the local configuration",
+            );
+        }
+    }
+
+    for (part, certificate) in [
+        ("reverse proxy", &remote.certificate),
+        ("port mapping", &remote.port_mapping_certificate),
+    ] {
+        if let Some(certificate) = certificate {
+            info!(
+                certificate = %certificate.name,
+                "loaded {part} TLS certificate from manager",
+            );
         }
-    })
+    }
+
+    Ok(remote)
 }
 
 #[allow(unreachable_code)]
-fn run_proxy_service(config: &NodeConfig, proxy: ReverseProxy) -> Result<()> {
+fn run_proxy_service(
+    config: &ReverseProxyConfig,
+    proxy: ReverseProxy,
+    tls: Option<TlsFront>,
+) -> Result<()> {
     use pingora_core::server::configuration::Opt;
     use pingora_core::server::Server;
+    use pingora_core::services::background::background_service;
 
     info!("bootstrapping Pingora reverse proxy");
 
@@ -124,7 +134,11 @@ fn run_proxy_service(config: &NodeConfig, proxy: ReverseProxy) -> Result<()> {
     server.bootstrap();
 
     let mut service = pingora_proxy::http_proxy_service(&server.configuration, proxy);
-    if let Some(tls) = &config.reverse_proxy.tls {
+    if let Some(tls) = tls {
+        service.add_uds(&tls.socket()?, Some(Permissions::from_mode(0o600)));
+        let listener = tls.listener(&config.bind)?;
+        server.add_service(background_service("reverse proxy TLS", listener));
+    } else if let Some(tls) = &config.tls {
         let cert = tls
             .cert
             .to_str()
@@ -133,13 +147,13 @@ fn run_proxy_service(config: &NodeConfig, proxy: ReverseProxy) -> Result<()> {
             .key
             .to_str()
             .ok_or_else(|| anyhow!("key path contains invalid UTF-8"))?;
-        service.add_tls(&config.reverse_proxy.bind, cert, key)?;
+        service.add_tls(&config.bind, cert, key)?;
     } else {
-        service.add_tcp(&config.reverse_proxy.bind);
+        service.add_tcp(&config.bind);
     }
 
     server.add_service(service);
-    info!(bind = %config.reverse_proxy.bind, "reverse proxy listening");
+    info!(bind = %config.bind, "reverse proxy listening");
     server.run_forever();
     Ok(())
 }
@@ -0,0 +1,446 @@
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use parking_lot::Mutex;
+use rathole::{calculate_events, Config as RatholeConfig, ConfigChange, ControlChannelState};
+use tokio::sync::{broadcast, mpsc};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+use tracing::{error, info, warn};
+
+use laval_model::node::{CertificatePem, ManagerLinkConfig, ReverseProxyConfig};
+use laval_model::{PortMappingMode, PortMappingSpec, ReverseProxySpec, ReverseProxyTls};
+use laval_proto::manager::v1::{
+    node_manager_client::NodeManagerClient, ControlChannelState as ProtoControlChannelState,
+    GetNodeConfigRequest, PortMappingConfig as ProtoPortMappingConfig,
+    PortMappingMode as ProtoMode, ReportStatusRequest,
+    ReverseProxyConfig as ProtoReverseProxyConfig, ServiceStatus as ProtoServiceStatus,
+    TlsCertificate as ProtoTlsCertificate, WatchNodeConfigRequest,
+};
+
+use crate::proxy::ReverseProxy;
+use crate::rathole_runner::RatholeConfigs;
+use crate::tls::TlsFront;
+
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+type ManagerClient = NodeManagerClient<InterceptedService<Channel, NodeCredential>>;
+
+/// Attaches the node's credential to every call to the manager.
+#[derive(Clone)]
+struct NodeCredential {
+    authorization: MetadataValue<Ascii>,
+}
+
+impl Interceptor for NodeCredential {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request
+            .metadata_mut()
+            .insert("authorization", self.authorization.clone());
+        Ok(request)
+    }
+}
+
+async fn connect(manager: &ManagerLinkConfig) -> Result<ManagerClient> {
+    let authorization = format!("Bearer {}", manager.token)
+        .parse()
+        .context("manager token contains invalid characters")?;
+    let channel = Endpoint::from_shared(manager.endpoint.clone())?
+        .connect()
+        .await?;
+    Ok(NodeManagerClient::with_interceptor(
+        channel,
+        NodeCredential { authorization },
+    ))
+}
+
+/// Runtime state of the node that is reported to the manager.
+#[derive(Clone)]
+pub struct LocalStatus {
+    started: Instant,
+    proxy_bind: String,
+    rathole: Arc<Mutex<Option<RatholeConfig>>>,
+    services: rathole::ServiceStatus,
+}
+
+impl LocalStatus {
+    pub fn new(proxy_bind: String) -> Self {
+        Self {
+            started: Instant::now(),
+            proxy_bind,
+            rathole: Arc::new(Mutex::new(None)),
+            services: rathole::ServiceStatus::new(),
+        }
+    }
+
+    pub fn services(&self) -> rathole::ServiceStatus {
+        self.services.clone()
+    }
+
+    pub fn set_rathole_config(&self, config: RatholeConfig) {
+        *self.rathole.lock() = Some(config);
+    }
+
+    fn listen_addrs(&self) -> Vec<String> {
+        let mut addrs = vec![self.proxy_bind.clone()];
+        if let Some(server) = self.rathole.lock().as_ref().and_then(|c| c.server.as_ref()) {
+            addrs.push(server.bind_addr.clone());
+            let mut services: Vec<_> = server.services.values().collect();
+            services.sort_by(|a, b| a.name.cmp(&b.name));
+            addrs.extend(
+                services
+                    .into_iter()
+                    .map(|service| service.bind_addr.clone()),
+            );
+        }
+        addrs
+    }
+
+    fn report(&self, manager: &ManagerLinkConfig) -> ReportStatusRequest {
+        let mut services: Vec<_> = self
+            .services
+            .snapshot()
+            .into_iter()
+            .map(|(name, state)| {
+                let state = match state {
+                    ControlChannelState::Connecting => ProtoControlChannelState::Connecting,
+                    ControlChannelState::Connected => ProtoControlChannelState::Connected,
+                    ControlChannelState::Disconnected => ProtoControlChannelState::Disconnected,
+                } as i32;
+                ProtoServiceStatus { name, state }
+            })
+            .collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+
+        ReportStatusRequest {
+            project: manager.project.clone(),
+            name: manager.node_name.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: self.started.elapsed().as_secs(),
+            listen_addrs: self.listen_addrs(),
+            services,
+        }
+    }
+}
+
+/// The running reverse proxy, with its local configuration, so that the
+/// routes and certificate of new revisions from the manager can be applied
+/// to it.
+pub struct ProxyLink {
+    local: ReverseProxyConfig,
+    running: ReverseProxyConfig,
+    proxy: ReverseProxy,
+    /// Set if the proxy serves a certificate from the manager.
+    tls: Option<TlsFront>,
+}
+
+impl ProxyLink {
+    pub fn new(
+        local: ReverseProxyConfig,
+        running: ReverseProxyConfig,
+        proxy: ReverseProxy,
+        tls: Option<TlsFront>,
+    ) -> Self {
+        Self {
+            local,
+            running,
+            proxy,
+            tls,
+        }
+    }
+
+    /// Routes the proxy with `spec`, or with the local routes if the
+    /// manager has none for the node, and serves the certificate it names.
+    fn apply(
+        &mut self,
+        revision: u64,
+        spec: Option<ReverseProxySpec>,
+        certificate: Option<CertificatePem>,
+    ) {
+        let mut config = self.local.with_manager(spec.as_ref(), certificate.as_ref());
+        if config == self.running {
+            return;
+        }
+        if config.tls != self.running.tls || config.certificate != self.running.certificate {
+            match self.reload_tls(&config) {
+                Ok(()) => {
+                    info!(revision, "applied reverse proxy certificate from manager");
+                    self.running.tls = config.tls.clone();
+                    self.running.certificate = config.certificate.clone();
+                }
+                Err(err) => {
+                    warn!(revision, "kept the reverse proxy certificate: {err:#}");
+                    config.tls = self.running.tls.clone();
+                    config.certificate = self.running.certificate.clone();
+                }
+            }
+            if config == self.running {
+                return;
+            }
+        }
+        if let Err(err) = self.proxy.reload(&config) {
+            // Keep routing with the last good configuration.
+            error!(revision, "ignored invalid reverse proxy routes: {err:#}");
+            return;
+        }
+        info!(
+            revision,
+            routes = config.routes.len(),
+            "applied reverse proxy routes from manager",
+        );
+        self.running = config;
+    }
+
+    // Only a certificate from the manager can be replaced in the running
+    // listener: Pingora serves local certificate files as it started with
+    // them, and turning TLS on or off changes the listener itself.
+    fn reload_tls(&self, config: &ReverseProxyConfig) -> Result<()> {
+        match (&self.tls, &config.certificate) {
+            (Some(tls), Some(certificate)) => tls.reload(certificate),
+            _ => Err(anyhow!(
+                "TLS settings changed on manager; they apply after a restart"
+            )),
+        }
+    }
+}
+
+/// What the manager holds for the node; unset parts are left to the local
+/// configuration.
+pub struct RemoteConfig {
+    pub port_mapping: Option<PortMappingSpec>,
+    pub reverse_proxy: Option<ReverseProxySpec>,
+    pub certificate: Option<CertificatePem>,
+    /// The certificate named by the port mapping.
+    pub port_mapping_certificate: Option<CertificatePem>,
+}
+
+pub fn fetch_node_config(manager: &ManagerLinkConfig) -> Result<RemoteConfig> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        let mut client = connect(manager).await?;
+        let response = client
+            .get_node_config(GetNodeConfigRequest {
+                name: manager.node_name.clone(),
+                project: manager.project.clone(),
+            })
+            .await?
+            .into_inner();
+
+        Ok(RemoteConfig {
+            port_mapping: response
+                .port_mapping
+                .map(port_mapping_from_proto)
+                .transpose()?,
+            reverse_proxy: response.reverse_proxy.map(reverse_proxy_from_proto),
+            certificate: response.certificate.map(certificate_from_proto),
+            port_mapping_certificate: response
+                .port_mapping_certificate
+                .map(certificate_from_proto),
+        })
+    })
+}
+
+/// Follows the node's configuration on the manager, turning every new
+/// revision into Rathole config changes and reverse proxy routes.
+///
+/// `current` is the configuration the instance was started with, if any,
+/// built by `configs`.
+pub async fn watch_node_config(
+    manager: ManagerLinkConfig,
+    mut current: Option<RatholeConfig>,
+    mut configs: RatholeConfigs,
+    mut proxy: ProxyLink,
+    events: mpsc::Sender<ConfigChange>,
+    local: LocalStatus,
+    mut shutdown_rx: broadcast::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            result = follow_revisions(&manager, &mut current, &mut configs, &mut proxy, &events, &local) => {
+                if let Err(err) = result {
+                    warn!(
+                        endpoint = %manager.endpoint,
+                        node = %manager.node_name,
+                        "lost config watch on manager: {err:#}",
+                    );
+                }
+            }
+            _ = shutdown_rx.recv() => break,
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(WATCH_RETRY_INTERVAL) => {}
+            _ = shutdown_rx.recv() => break,
+        }
+    }
+}
+
+async fn follow_revisions(
+    manager: &ManagerLinkConfig,
+    current: &mut Option<RatholeConfig>,
+    configs: &mut RatholeConfigs,
+    proxy: &mut ProxyLink,
+    events: &mpsc::Sender<ConfigChange>,
+    local: &LocalStatus,
+) -> Result<()> {
+    let mut client = connect(manager).await?;
+    let mut stream = client
+        .watch_node_config(WatchNodeConfigRequest {
+            name: manager.node_name.clone(),
+            project: manager.project.clone(),
+        })
+        .await?
+        .into_inner();
+
+    while let Some(revision) = stream.message().await? {
+        proxy.apply(
+            revision.revision,
+            revision.reverse_proxy.map(reverse_proxy_from_proto),
+            revision.certificate.map(certificate_from_proto),
+        );
+
+        let Some(port_mapping) = revision.port_mapping else {
+            if current.is_some() {
+                warn!(
+                    revision = revision.revision,
+                    "manager removed the port mapping; keeping the running instance until restart",
+                );
+            }
+            continue;
+        };
+
+        let certificate = revision
+            .port_mapping_certificate
+            .map(certificate_from_proto);
+        let config = match port_mapping_from_proto(port_mapping)
+            .and_then(|spec| configs.build(spec, certificate.as_ref()))
+        {
+            Ok((config, _)) => config,
+            Err(err) => {
+                // Keep running the last good configuration.
+                error!(
+                    revision = revision.revision,
+                    "ignored invalid port mapping: {err:#}"
+                );
+                continue;
+            }
+        };
+
+        let changes = match current.as_ref() {
+            Some(old) => calculate_events(old, &config).unwrap_or_default(),
+            None => vec![ConfigChange::General(Box::new(config.clone()))],
+        };
+        if !changes.is_empty() {
+            info!(
+                revision = revision.revision,
+                changes = changes.len(),
+                "applying port mapping revision from manager",
+            );
+        }
+        for change in changes {
+            events
+                .send(change)
+                .await
+                .map_err(|_| anyhow!("Rathole instance is no longer running"))?;
+        }
+        local.set_rathole_config(config.clone());
+        *current = Some(config);
+    }
+
+    Err(anyhow!("manager closed the config stream"))
+}
+
+/// Sends a heartbeat with the node's status to the manager every
+/// `HEARTBEAT_INTERVAL` until shutdown.
+pub async fn report_status(
+    manager: ManagerLinkConfig,
+    local: LocalStatus,
+    mut shutdown_rx: broadcast::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut client: Option<ManagerClient> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown_rx.recv() => break,
+        }
+
+        if let Err(err) = send_heartbeat(&manager, &local, &mut client).await {
+            warn!(
+                endpoint = %manager.endpoint,
+                node = %manager.node_name,
+                "failed to report status to manager: {err:#}",
+            );
+            // Reconnect on the next tick.
+            client = None;
+        }
+    }
+}
+
+async fn send_heartbeat(
+    manager: &ManagerLinkConfig,
+    local: &LocalStatus,
+    client: &mut Option<ManagerClient>,
+) -> Result<()> {
+    let client = match client {
+        Some(client) => client,
+        None => client.insert(connect(manager).await?),
+    };
+    client.report_status(local.report(manager)).await?;
+    Ok(())
+}
+
+fn port_mapping_from_proto(port_mapping: ProtoPortMappingConfig) -> Result<PortMappingSpec> {
+    let mode = ProtoMode::try_from(port_mapping.mode)
+        .map_err(|_| anyhow!("unknown port mapping mode from manager"))?;
+    let mode = match mode {
+        ProtoMode::Server => PortMappingMode::Server,
+        ProtoMode::Client => PortMappingMode::Client,
+        ProtoMode::Unspecified => {
+            return Err(anyhow!("manager returned unspecified port mapping mode"))
+        }
+    };
+
+    let config = serde_json::from_str(&port_mapping.config_json)
+        .with_context(|| "failed to parse port mapping configuration from manager")?;
+
+    Ok(PortMappingSpec {
+        mode,
+        config,
+        certificate: port_mapping.certificate,
+    })
+}
+
+fn reverse_proxy_from_proto(config: ProtoReverseProxyConfig) -> ReverseProxySpec {
+    ReverseProxySpec {
+        routes: config
+            .routes
+            .into_iter()
+            .map(|route| (route.hostname, route.upstream))
+            .collect(),
+        default_upstream: config.default_upstream,
+        tls: config.tls.map(|tls| ReverseProxyTls {
+            cert: tls.cert,
+            key: tls.key,
+        }),
+        certificate: config.certificate,
+    }
+}
+
+fn certificate_from_proto(certificate: ProtoTlsCertificate) -> CertificatePem {
+    CertificatePem {
+        name: certificate.name,
+        chain: certificate.chain_pem,
+        key: certificate.key_pem,
+    }
+}
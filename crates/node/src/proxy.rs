@@ -1,8 +1,12 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use parking_lot::RwLock;
+use pingora::http::RequestHeader;
+use pingora_core::protocols::l4::socket::SocketAddr as PingoraSocketAddr;
 use pingora_core::upstreams::peer::HttpPeer;
 use pingora_core::Result as PingoraResult;
 use pingora_error::{Error, ErrorType};
@@ -10,20 +14,28 @@ use pingora_proxy::{ProxyHttp, Session};
 use tracing::{debug, warn};
 use url::Url;
 
-use crate::config::ReverseProxyConfig;
+use laval_model::node::ReverseProxyConfig;
 
+use crate::tls;
+
+/// Clones share their routes, so that [`ReverseProxy::reload`] on one of
+/// them applies to the running service.
 #[derive(Clone)]
 pub struct ReverseProxy {
-    routes: Arc<HashMap<String, HttpPeer>>,
+    upstreams: Arc<RwLock<Upstreams>>,
+}
+
+struct Upstreams {
+    routes: HashMap<String, HttpPeer>,
     default: Option<HttpPeer>,
 }
 
-impl ReverseProxy {
-    pub fn from_config(config: &ReverseProxyConfig) -> anyhow::Result<Self> {
-        let mut peers = HashMap::new();
+impl Upstreams {
+    fn from_config(config: &ReverseProxyConfig) -> anyhow::Result<Self> {
+        let mut routes = HashMap::new();
         for (hostname, target) in &config.routes {
             let peer = build_peer(target)?;
-            peers.insert(hostname.to_lowercase(), peer);
+            routes.insert(hostname.to_lowercase(), peer);
         }
 
         let default = match &config.default_upstream {
@@ -31,18 +43,33 @@ impl ReverseProxy {
             None => None,
         };
 
+        Ok(Self { routes, default })
+    }
+}
+
+impl ReverseProxy {
+    pub fn from_config(config: &ReverseProxyConfig) -> anyhow::Result<Self> {
         Ok(Self {
-            routes: Arc::new(peers),
-            default,
+            upstreams: Arc::new(RwLock::new(Upstreams::from_config(config)?)),
         })
     }
 
+    /// Replaces the routes and default upstream with those of `config`.
+    /// The previous ones are kept if any upstream is invalid.
+    pub fn reload(&self, config: &ReverseProxyConfig) -> anyhow::Result<()> {
+        let upstreams = Upstreams::from_config(config)?;
+        *self.upstreams.write() = upstreams;
+        Ok(())
+    }
+
     fn resolve_route(&self, hostname: &str) -> Option<HttpPeer> {
         let normalized = hostname.to_lowercase();
-        self.routes
+        let upstreams = self.upstreams.read();
+        upstreams
+            .routes
             .get(&normalized)
             .cloned()
-            .or_else(|| self.default.clone())
+            .or_else(|| upstreams.default.clone())
     }
 
     fn extract_hostname(session: &Session) -> Option<String> {
@@ -101,6 +128,27 @@ impl ProxyHttp for ReverseProxy {
             "no upstream configured for hostname",
         )?)
     }
+
+    async fn upstream_request_filter(
+        &self,
+        session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        _ctx: &mut Self::CTX,
+    ) -> PingoraResult<()> {
+        if let Some(client) = client_addr(session) {
+            upstream_request.append_header("X-Forwarded-For", client.ip().to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// The address of the client, also when the connection was relayed by the
+/// node's own TLS listener.
+fn client_addr(session: &Session) -> Option<SocketAddr> {
+    match session.client_addr()? {
+        PingoraSocketAddr::Inet(addr) => Some(*addr),
+        PingoraSocketAddr::Unix(addr) => tls::relayed_client(addr),
+    }
 }
 
 fn build_peer(target: &str) -> anyhow::Result<HttpPeer> {
@@ -1,13 +1,22 @@
 use std::thread::{self, JoinHandle};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use rathole::{Config as RatholeConfig, ConfigChange};
 use tokio::runtime::Builder;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{error, info};
 
+use laval_model::node::{CertificatePem, ManagerLinkConfig};
 use laval_model::PortMappingSpec;
 use rathole::InstanceMode;
 
+use crate::manager_link::{self, LocalStatus, ProxyLink};
+use crate::tls;
+
+/// Password of the PKCS#12 identities handed to Rathole. They never leave
+/// the node's memory, so it only satisfies the format.
+const PKCS12_PASSWORD: &str = "laval-node";
+
 pub struct RatholeHandle {
     shutdown: broadcast::Sender<bool>,
     join: Option<JoinHandle<()>>,
@@ -24,18 +33,46 @@ impl RatholeHandle {
     }
 }
 
-pub fn spawn_rathole(spec: &PortMappingSpec) -> Result<RatholeHandle> {
-    let (config, mode) = spec.clone().into_rathole()?;
+/// Starts Rathole with `spec`, serving `certificate` if it names one, and,
+/// when a manager link is configured, keeps it and the routes of `proxy` in
+/// sync with the node's configuration on the manager, to which it also
+/// reports `local` status.
+pub fn spawn_rathole(
+    spec: Option<&PortMappingSpec>,
+    certificate: Option<&CertificatePem>,
+    manager: Option<ManagerLinkConfig>,
+    proxy: ProxyLink,
+    local: LocalStatus,
+) -> Result<RatholeHandle> {
+    let mut configs = RatholeConfigs::default();
+    let initial = spec
+        .map(|spec| configs.build(spec.clone(), certificate))
+        .transpose()?;
     let (shutdown_tx, shutdown_rx) = broadcast::channel(4);
+    let (event_tx, event_rx) = mpsc::channel(16);
 
-    info!(
-        mode = %match mode {
-            InstanceMode::Server => "server",
-            InstanceMode::Client => "client",
-        },
-        "starting Rathole instance",
-    );
+    let config = match initial {
+        Some((config, mode)) => {
+            info!(
+                mode = %match mode {
+                    InstanceMode::Server => "server",
+                    InstanceMode::Client => "client",
+                },
+                "starting Rathole instance",
+            );
+            event_tx.try_send(ConfigChange::General(Box::new(config.clone())))?;
+            local.set_rathole_config(config.clone());
+            Some(config)
+        }
+        None => {
+            info!("waiting for port mapping configuration from manager");
+            None
+        }
+    };
 
+    let watch_shutdown = shutdown_tx.subscribe();
+    let heartbeat_shutdown = shutdown_tx.subscribe();
+    let services = local.services();
     let handle = thread::Builder::new()
         .name("rathole-runner".into())
         .spawn(move || {
@@ -43,11 +80,29 @@ pub fn spawn_rathole(spec: &PortMappingSpec) -> Result<RatholeHandle> {
                 .enable_all()
                 .build()
                 .expect("failed to create Rathole runtime");
+            if let Some(manager) = manager {
+                runtime.spawn(manager_link::report_status(
+                    manager.clone(),
+                    local.clone(),
+                    heartbeat_shutdown,
+                ));
+                runtime.spawn(manager_link::watch_node_config(
+                    manager,
+                    config,
+                    configs,
+                    proxy,
+                    event_tx.clone(),
+                    local,
+                    watch_shutdown,
+                ));
+            }
             runtime.block_on(async move {
-                if let Err(err) = rathole::run_with_config(config, mode, shutdown_rx).await {
+                if let Err(err) = rathole::run_with_updates(event_rx, shutdown_rx, services).await {
                     error!(?err, "Rathole terminated with error");
                 }
             });
+            // Keep the event channel open for as long as the instance runs.
+            drop(event_tx);
         })?;
 
     Ok(RatholeHandle {
@@ -55,3 +110,43 @@ pub fn spawn_rathole(spec: &PortMappingSpec) -> Result<RatholeHandle> {
         join: Some(handle),
     })
 }
+
+/// Builds Rathole's configs from port mappings, handing the certificate a
+/// server names to Rathole as a PKCS#12 identity.
+#[derive(Default)]
+pub struct RatholeConfigs {
+    /// The identity last built, and its certificate. Each build is salted
+    /// afresh, so it is reused while the certificate is unchanged rather
+    /// than restarting the server on every revision.
+    identity: Option<(CertificatePem, Vec<u8>)>,
+}
+
+impl RatholeConfigs {
+    /// Rathole's config for `spec`, whose server serves the certificate it
+    /// names from `certificate`, as sent by the manager with it.
+    pub fn build(
+        &mut self,
+        mut spec: PortMappingSpec,
+        certificate: Option<&CertificatePem>,
+    ) -> Result<(RatholeConfig, InstanceMode)> {
+        if let Some(name) = &spec.certificate {
+            let certificate = certificate
+                .filter(|certificate| &certificate.name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "certificate '{name}' of the port mapping is only served from the manager"
+                    )
+                })?;
+            let identity = match &self.identity {
+                Some((built, identity)) if built == certificate => identity.clone(),
+                _ => {
+                    let identity = tls::pkcs12_identity(certificate, PKCS12_PASSWORD)?;
+                    self.identity = Some((certificate.clone(), identity.clone()));
+                    identity
+                }
+            };
+            spec.set_identity(identity, PKCS12_PASSWORD)?;
+        }
+        spec.into_rathole()
+    }
+}
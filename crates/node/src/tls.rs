@@ -0,0 +1,233 @@
+//! TLS in front of the reverse proxy for a certificate stored in the
+//! manager. Pingora loads its certificate once, from files, when its
+//! listener starts, so for a certificate the manager may renew the node
+//! terminates TLS itself and hands the decrypted connections to Pingora over
+//! a Unix socket only its user can reach. Certificates from local files are
+//! served by Pingora directly.
+//!
+//! Each relayed connection comes from a socket named after the address of
+//! the client, which [`relayed_client`] reads back, so that the proxy still
+//! knows who it serves.
+//!
+//! A certificate from the manager served by a Rathole server is handed to
+//! it as a PKCS#12 identity, built by [`pkcs12_identity`] in memory as well.
+
+use std::fs;
+use std::net::SocketAddr;
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use parking_lot::{Mutex, RwLock};
+use pingora_core::server::ShutdownWatch;
+use pingora_core::services::background::BackgroundService;
+use pingora_core::tls::{version, CertificateDer, PrivateKeyDer, ServerConfig, TlsAcceptor};
+use tempfile::TempDir;
+use tokio::net::{TcpListener, TcpStream, UnixSocket, UnixStream};
+use tracing::{debug, error, warn};
+
+use laval_model::node::{CertificatePem, ReverseProxyConfig};
+
+const SOCKET: &str = "proxy.sock";
+
+/// The certificate served by the reverse proxy. Clones share it, so that
+/// [`TlsFront::reload`] on one of them applies to the running listener.
+#[derive(Clone)]
+pub struct TlsFront {
+    /// Holds the sockets; removed when the listener shuts down.
+    dir: Arc<TempDir>,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl TlsFront {
+    /// The front of `config`, if the reverse proxy serves a certificate
+    /// from the manager.
+    pub fn new(config: &ReverseProxyConfig) -> Result<Option<Self>> {
+        let Some(certificate) = &config.certificate else {
+            return Ok(None);
+        };
+        // Random, and only readable by the node's user.
+        let dir = tempfile::Builder::new()
+            .prefix("laval-node-")
+            .tempdir()
+            .context("failed to create a directory for the TLS socket")?;
+        let acceptor = acceptor(certificate)?;
+        Ok(Some(Self {
+            dir: Arc::new(dir),
+            acceptor: Arc::new(RwLock::new(acceptor)),
+        }))
+    }
+
+    /// Where Pingora listens for the decrypted connections.
+    pub fn socket(&self) -> Result<String> {
+        self.dir
+            .path()
+            .join(SOCKET)
+            .into_os_string()
+            .into_string()
+            .map_err(|_| anyhow!("temporary directory path contains invalid UTF-8"))
+    }
+
+    /// Serves `certificate` to new connections; established ones keep the
+    /// certificate they were opened with. The current certificate is kept
+    /// if the new one cannot be loaded.
+    pub fn reload(&self, certificate: &CertificatePem) -> Result<()> {
+        let acceptor = acceptor(certificate)?;
+        *self.acceptor.write() = acceptor;
+        Ok(())
+    }
+
+    /// Listens on `bind` right away, so that an address in use fails the
+    /// start of the node rather than the service.
+    pub fn listener(&self, bind: &str) -> Result<TlsListener> {
+        let listener = std::net::TcpListener::bind(bind)
+            .with_context(|| format!("failed to listen on {bind}"))?;
+        listener.set_nonblocking(true)?;
+        Ok(TlsListener {
+            front: self.clone(),
+            listener: Mutex::new(Some(listener)),
+        })
+    }
+}
+
+/// The client a connection relayed by a [`TlsListener`] comes from, given
+/// the address Pingora sees it from.
+pub fn relayed_client(addr: &UnixSocketAddr) -> Option<SocketAddr> {
+    addr.as_pathname()?.file_name()?.to_str()?.parse().ok()
+}
+
+/// Accepts the TLS connections of the reverse proxy, as a Pingora
+/// background service.
+pub struct TlsListener {
+    front: TlsFront,
+    listener: Mutex<Option<std::net::TcpListener>>,
+}
+
+#[async_trait]
+impl BackgroundService for TlsListener {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let Some(listener) = self.listener.lock().take() else {
+            return;
+        };
+        let listener = match TcpListener::from_std(listener) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("failed to start the TLS listener: {err}");
+                return;
+            }
+        };
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        let acceptor = self.front.acceptor.read().clone();
+                        tokio::spawn(serve(acceptor, stream, peer, self.front.dir.clone()));
+                    }
+                    Err(err) => warn!("failed to accept TLS connection: {err}"),
+                },
+                _ = shutdown.changed() => break,
+            }
+        }
+        // Pingora exits without dropping the services, so the directory is
+        // removed here rather than when the front is dropped.
+        if let Err(err) = fs::remove_dir_all(self.front.dir.path()) {
+            warn!(
+                "failed to remove {}: {err}",
+                self.front.dir.path().display()
+            );
+        }
+    }
+}
+
+async fn serve(acceptor: TlsAcceptor, stream: TcpStream, peer: SocketAddr, dir: Arc<TempDir>) {
+    let result = async {
+        let mut tls = acceptor
+            .accept(stream)
+            .await
+            .context("TLS handshake failed")?;
+        let mut proxy = connect_as(dir.path(), peer).await?;
+        tokio::io::copy_bidirectional(&mut tls, &mut proxy).await?;
+        anyhow::Ok(())
+    };
+    if let Err(err) = result.await {
+        debug!(%peer, "TLS connection closed: {err:#}");
+    }
+}
+
+// Connects to Pingora from a socket bound to the name of `peer`. The name is
+// unlinked once connected; Pingora still sees it as the peer address.
+async fn connect_as(dir: &Path, peer: SocketAddr) -> Result<UnixStream> {
+    let name = dir.join(peer.to_string());
+    let socket = UnixSocket::new_stream()?;
+    socket
+        .bind(&name)
+        .with_context(|| format!("failed to bind {}", name.display()))?;
+    let connected = socket.connect(dir.join(SOCKET)).await;
+    let _ = fs::remove_file(&name);
+    connected.context("failed to reach the reverse proxy")
+}
+
+/// The chain and PKCS#8 key of `certificate` as a PKCS#12 identity, leaf
+/// first, for the TLS transport of a Rathole server.
+pub fn pkcs12_identity(certificate: &CertificatePem, password: &str) -> Result<Vec<u8>> {
+    let (certs, key) = parse(certificate)?;
+    let PrivateKeyDer::Pkcs8(key) = key else {
+        bail!(
+            "certificate '{}' must have a PKCS#8 key to be served by Rathole",
+            certificate.name
+        );
+    };
+    let (leaf, intermediates) = certs.split_first().expect("parse returns a chain");
+    let intermediates: Vec<&[u8]> = intermediates.iter().map(|cert| cert.as_ref()).collect();
+    let pfx = p12::PFX::new_with_cas(
+        leaf,
+        key.secret_pkcs8_der(),
+        &intermediates,
+        password,
+        &certificate.name,
+    )
+    .ok_or_else(|| {
+        anyhow!(
+            "failed to build an identity of certificate '{}'",
+            certificate.name
+        )
+    })?;
+    Ok(pfx.to_der())
+}
+
+// The chain and key from the manager are parsed in memory and never touch
+// the disk.
+fn acceptor(certificate: &CertificatePem) -> Result<TlsAcceptor> {
+    let (certs, key) = parse(certificate)?;
+    server_config(certs, key)
+}
+
+fn parse(
+    certificate: &CertificatePem,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let certs = rustls_pemfile::certs(&mut certificate.chain.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid chain in certificate '{}'", certificate.name))?;
+    let key = rustls_pemfile::private_key(&mut certificate.key.as_bytes())
+        .with_context(|| format!("invalid key in certificate '{}'", certificate.name))?;
+    match (certs.is_empty(), key) {
+        (false, Some(key)) => Ok((certs, key)),
+        _ => bail!(
+            "certificate '{}' has no certificate chain or private key",
+            certificate.name
+        ),
+    }
+}
+
+fn server_config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<TlsAcceptor> {
+    let config = ServerConfig::builder_with_protocol_versions(&[&version::TLS12, &version::TLS13])
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("invalid TLS certificate or key")?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
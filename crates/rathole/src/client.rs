@@ -1,4 +1,6 @@
-use crate::config::{ClientConfig, ClientServiceConfig, Config, ServiceType, TransportType};
+use crate::config::{
+    ClientConfig, ClientServiceConfig, Config, MaskedString, ServiceType, TransportType,
+};
 use crate::config_watcher::{ClientServiceChange, ConfigChange};
 use crate::helper::udp_connect;
 use crate::protocol::Hello::{self, *};
@@ -6,6 +8,7 @@ use crate::protocol::{
     self, read_ack, read_control_cmd, read_data_cmd, read_hello, Ack, Auth, ControlChannelCmd,
     DataChannelCmd, UdpTraffic, CURRENT_PROTO_VERSION, HASH_WIDTH_IN_BYTES,
 };
+use crate::status::{ControlChannelState, ServiceStatus};
 use crate::transport::{AddrMaybeCached, SocketOpts, TcpTransport, Transport};
 use anyhow::{anyhow, bail, Context, Result};
 use backoff::backoff::Backoff;
@@ -17,7 +20,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{self, copy_bidirectional, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpStream, UdpSocket};
-use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, RwLock};
 use tokio::time::{self, Duration, Instant};
 use tracing::{debug, error, info, instrument, trace, warn, Instrument, Span};
 
@@ -35,6 +38,7 @@ pub async fn run_client(
     config: Config,
     shutdown_rx: broadcast::Receiver<bool>,
     update_rx: mpsc::Receiver<ConfigChange>,
+    status: ServiceStatus,
 ) -> Result<()> {
     let config = config.client.ok_or_else(|| {
         anyhow!(
@@ -44,13 +48,13 @@ pub async fn run_client(
 
     match config.transport.transport_type {
         TransportType::Tcp => {
-            let mut client = Client::<TcpTransport>::from(config).await?;
+            let mut client = Client::<TcpTransport>::from(config, status).await?;
             client.run(shutdown_rx, update_rx).await
         }
         TransportType::Tls => {
             #[cfg(any(feature = "native-tls", feature = "rustls"))]
             {
-                let mut client = Client::<TlsTransport>::from(config).await?;
+                let mut client = Client::<TlsTransport>::from(config, status).await?;
                 client.run(shutdown_rx, update_rx).await
             }
             #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
@@ -59,7 +63,7 @@ pub async fn run_client(
         TransportType::Noise => {
             #[cfg(feature = "noise")]
             {
-                let mut client = Client::<NoiseTransport>::from(config).await?;
+                let mut client = Client::<NoiseTransport>::from(config, status).await?;
                 client.run(shutdown_rx, update_rx).await
             }
             #[cfg(not(feature = "noise"))]
@@ -68,7 +72,7 @@ pub async fn run_client(
         TransportType::Websocket => {
             #[cfg(any(feature = "websocket-native-tls", feature = "websocket-rustls"))]
             {
-                let mut client = Client::<WebsocketTransport>::from(config).await?;
+                let mut client = Client::<WebsocketTransport>::from(config, status).await?;
                 client.run(shutdown_rx, update_rx).await
             }
             #[cfg(not(any(feature = "websocket-native-tls", feature = "websocket-rustls")))]
@@ -85,17 +89,19 @@ struct Client<T: Transport> {
     config: ClientConfig,
     service_handles: HashMap<String, ControlChannelHandle>,
     transport: Arc<T>,
+    status: ServiceStatus,
 }
 
 impl<T: 'static + Transport> Client<T> {
     // Create a Client from `[client]` config block
-    async fn from(config: ClientConfig) -> Result<Client<T>> {
+    async fn from(config: ClientConfig, status: ServiceStatus) -> Result<Client<T>> {
         let transport =
             Arc::new(T::new(&config.transport).with_context(|| "Failed to create the transport")?);
         Ok(Client {
             config,
             service_handles: HashMap::new(),
             transport,
+            status,
         })
     }
 
@@ -112,6 +118,7 @@ impl<T: 'static + Transport> Client<T> {
                 self.config.remote_addr.clone(),
                 self.transport.clone(),
                 self.config.heartbeat_timeout,
+                self.status.clone(),
             );
             self.service_handles.insert(name.clone(), handle);
         }
@@ -137,8 +144,9 @@ impl<T: 'static + Transport> Client<T> {
         }
 
         // Shutdown all services
-        for (_, handle) in self.service_handles.drain() {
+        for (name, handle) in self.service_handles.drain() {
             handle.shutdown();
+            self.status.remove(&name);
         }
 
         Ok(())
@@ -148,19 +156,34 @@ impl<T: 'static + Transport> Client<T> {
         match e {
             ConfigChange::ClientChange(client_change) => match client_change {
                 ClientServiceChange::Add(cfg) => {
+                    if let Some(handle) = self.service_handles.get_mut(&cfg.name) {
+                        if handle.service.differs_only_in_token(&cfg) {
+                            handle.update_token(cfg);
+                            return;
+                        }
+                    }
+
                     let name = cfg.name.clone();
                     let handle = ControlChannelHandle::new(
                         cfg,
                         self.config.remote_addr.clone(),
                         self.transport.clone(),
                         self.config.heartbeat_timeout,
+                        self.status.clone(),
                     );
                     let _ = self.service_handles.insert(name, handle);
                 }
                 ClientServiceChange::Delete(s) => {
                     let _ = self.service_handles.remove(&s);
+                    self.status.remove(&s);
                 }
             },
+            ConfigChange::NoiseKeys(noise) => {
+                // Established connections keep the keys they were made with
+                if let Err(e) = self.transport.update_keys(&noise) {
+                    error!("Failed to update the transport keys: {:#}", e);
+                }
+            }
             ignored => warn!("Ignored {:?} since running as a client", ignored),
         }
     }
@@ -391,23 +414,30 @@ async fn run_udp_forwarder(
 
 // Control channel, using T as the transport layer
 struct ControlChannel<T: Transport> {
-    digest: ServiceDigest,              // SHA256 of the service name
-    service: ClientServiceConfig,       // `[client.services.foo]` config block
-    shutdown_rx: oneshot::Receiver<u8>, // Receives the shutdown signal
-    remote_addr: String,                // `client.remote_addr`
-    transport: Arc<T>,                  // Wrapper around the transport layer
-    heartbeat_timeout: u64,             // Application layer heartbeat timeout in secs
+    digest: ServiceDigest,                // SHA256 of the service name
+    service: ClientServiceConfig,         // `[client.services.foo]` config block
+    shutdown_rx: oneshot::Receiver<u8>,   // Receives the shutdown signal
+    remote_addr: String,                  // `client.remote_addr`
+    transport: Arc<T>,                    // Wrapper around the transport layer
+    heartbeat_timeout: u64,               // Application layer heartbeat timeout in secs
+    status: ServiceStatus,                // Where the state of the channel is reported
+    generation: u64,                      // Generation of this channel in `status`
+    token: watch::Receiver<MaskedString>, // Token for the next handshake
 }
 
 // Handle of a control channel
 // Dropping it will also drop the actual control channel
 struct ControlChannelHandle {
     shutdown_tx: oneshot::Sender<u8>,
+    service: ClientServiceConfig,
+    token_tx: watch::Sender<MaskedString>,
 }
 
 impl<T: 'static + Transport> ControlChannel<T> {
     #[instrument(skip_all)]
     async fn run(&mut self) -> Result<()> {
+        self.set_state(ControlChannelState::Connecting);
+
         let mut remote_addr = AddrMaybeCached::new(&self.remote_addr);
         remote_addr.resolve().await?;
 
@@ -437,7 +467,8 @@ impl<T: 'static + Transport> ControlChannel<T> {
 
         // Send auth
         debug!("Sending auth");
-        let mut concat = Vec::from(self.service.token.as_ref().unwrap().as_bytes());
+        let token = self.token.borrow().clone();
+        let mut concat = Vec::from(token.as_bytes());
         concat.extend_from_slice(&nonce);
 
         let session_key = protocol::digest(&concat);
@@ -457,6 +488,7 @@ impl<T: 'static + Transport> ControlChannel<T> {
 
         // Channel ready
         info!("Control channel established");
+        self.set_state(ControlChannelState::Connected);
 
         // Socket options for the data channel
         let socket_opts = SocketOpts::from_client_cfg(&self.service);
@@ -497,6 +529,11 @@ impl<T: 'static + Transport> ControlChannel<T> {
         info!("Control channel shutdown");
         Ok(())
     }
+
+    fn set_state(&self, state: ControlChannelState) {
+        self.status
+            .set_if_current(&self.service.name, self.generation, state);
+    }
 }
 
 impl ControlChannelHandle {
@@ -506,21 +543,27 @@ impl ControlChannelHandle {
         remote_addr: String,
         transport: Arc<T>,
         heartbeat_timeout: u64,
+        status: ServiceStatus,
     ) -> ControlChannelHandle {
         let digest = protocol::digest(service.name.as_bytes());
+        let generation = status.set(&service.name, ControlChannelState::Connecting);
 
         info!("Starting {}", hex::encode(digest));
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
         let mut retry_backoff = run_control_chan_backoff(service.retry_interval.unwrap());
+        let (token_tx, token) = watch::channel(service.token.clone().unwrap());
 
         let mut s = ControlChannel {
             digest,
-            service,
+            service: service.clone(),
             shutdown_rx,
             remote_addr,
             transport,
             heartbeat_timeout,
+            status,
+            generation,
+            token,
         };
 
         tokio::spawn(
@@ -536,6 +579,8 @@ impl ControlChannelHandle {
                         break;
                     }
 
+                    s.set_state(ControlChannelState::Disconnected);
+
                     if start.elapsed() > Duration::from_secs(3) {
                         // The client runs for at least 3 secs and then disconnects
                         retry_backoff.reset();
@@ -555,7 +600,20 @@ impl ControlChannelHandle {
             .instrument(Span::current()),
         );
 
-        ControlChannelHandle { shutdown_tx }
+        ControlChannelHandle {
+            shutdown_tx,
+            service,
+            token_tx,
+        }
+    }
+
+    // Switch to a rotated token without dropping the running control channel.
+    // The token is only sent again on the next handshake
+    fn update_token(&mut self, service: ClientServiceConfig) {
+        if let Some(token) = service.token.clone() {
+            let _ = self.token_tx.send(token);
+        }
+        self.service = service;
     }
 
     fn shutdown(self) {
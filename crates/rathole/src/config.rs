@@ -19,6 +19,7 @@ const DEFAULT_CLIENT_RETRY_INTERVAL_SECS: u64 = 1;
 /// String with Debug implementation that emits "MASKED"
 /// Used to mask sensitive strings when logging
 #[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct MaskedString(String);
 
 impl Debug for MaskedString {
@@ -40,7 +41,31 @@ impl From<&str> for MaskedString {
     }
 }
 
+/// Used to mask sensitive bytes when logging
+#[derive(Default, PartialEq, Eq, Clone)]
+pub struct MaskedBytes(Vec<u8>);
+
+impl Debug for MaskedBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str("MASKED")
+    }
+}
+
+impl Deref for MaskedBytes {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for MaskedBytes {
+    fn from(bytes: Vec<u8>) -> MaskedBytes {
+        MaskedBytes(bytes)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum TransportType {
     #[default]
     #[serde(rename = "tcp")]
@@ -56,6 +81,7 @@ pub enum TransportType {
 /// Per service config
 /// All Option are optional in configuration but must be Some value in runtime
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct ClientServiceConfig {
     #[serde(rename = "type", default = "default_service_type")]
@@ -77,9 +103,21 @@ impl ClientServiceConfig {
             ..Default::default()
         }
     }
+
+    // Whether a running service can switch to `other` without reconnecting
+    pub(crate) fn differs_only_in_token(&self, other: &ClientServiceConfig) -> bool {
+        ClientServiceConfig {
+            token: None,
+            ..self.clone()
+        } == ClientServiceConfig {
+            token: None,
+            ..other.clone()
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum ServiceType {
     #[serde(rename = "tcp")]
     #[default]
@@ -95,6 +133,7 @@ fn default_service_type() -> ServiceType {
 /// Per service config
 /// All Option are optional in configuration but must be Some value in runtime
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct ServerServiceConfig {
     #[serde(rename = "type", default = "default_service_type")]
@@ -103,6 +142,10 @@ pub struct ServerServiceConfig {
     pub name: String,
     pub bind_addr: String,
     pub token: Option<MaskedString>,
+    // Also accepted from clients, so that a token can be rotated without
+    // locking out clients that still use the old one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_tokens: Vec<MaskedString>,
     pub nodelay: Option<bool>,
 }
 
@@ -113,13 +156,32 @@ impl ServerServiceConfig {
             ..Default::default()
         }
     }
+
+    // Whether a running service can switch to `other` without dropping its
+    // control channel
+    pub(crate) fn differs_only_in_tokens(&self, other: &ServerServiceConfig) -> bool {
+        ServerServiceConfig {
+            token: None,
+            previous_tokens: Vec::new(),
+            ..self.clone()
+        } == ServerServiceConfig {
+            token: None,
+            previous_tokens: Vec::new(),
+            ..other.clone()
+        }
+    }
 }
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct TlsConfig {
     pub hostname: Option<String>,
     pub trusted_root: Option<String>,
     pub pkcs12: Option<String>,
+    /// The contents of a `pkcs12` file, for programs that build the identity
+    /// in memory. Takes precedence over `pkcs12`; never read from a config.
+    #[serde(skip)]
+    pub pkcs12_der: Option<MaskedBytes>,
     pub pkcs12_password: Option<MaskedString>,
 }
 
@@ -127,17 +189,34 @@ fn default_noise_pattern() -> String {
     String::from("Noise_NK_25519_ChaChaPoly_BLAKE2s")
 }
 
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            pattern: default_noise_pattern(),
+            local_private_key: None,
+            previous_private_keys: Vec::new(),
+            remote_public_key: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct NoiseConfig {
     #[serde(default = "default_noise_pattern")]
     pub pattern: String,
     pub local_private_key: Option<MaskedString>,
+    // Also accepted from initiators, so that a key can be rotated without
+    // locking out clients that still know the old public key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_private_keys: Vec<MaskedString>,
     pub remote_public_key: Option<String>,
     // TODO: Maybe psk can be added
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct WebsocketConfig {
     pub tls: bool,
@@ -156,6 +235,7 @@ fn default_keepalive_interval() -> u64 {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct TcpConfig {
     #[serde(default = "default_nodelay")]
@@ -164,6 +244,7 @@ pub struct TcpConfig {
     pub keepalive_secs: u64,
     #[serde(default = "default_keepalive_interval")]
     pub keepalive_interval: u64,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, format = Uri))]
     pub proxy: Option<Url>,
 }
 
@@ -179,6 +260,7 @@ impl Default for TcpConfig {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct TransportConfig {
     #[serde(rename = "type")]
@@ -190,6 +272,23 @@ pub struct TransportConfig {
     pub websocket: Option<WebsocketConfig>,
 }
 
+impl TransportConfig {
+    // Whether a running Noise transport can switch to the keys of `other`
+    // without dropping its connections
+    pub(crate) fn differs_only_in_noise_keys(&self, other: &TransportConfig) -> bool {
+        let without_keys = |config: &TransportConfig| TransportConfig {
+            noise: config.noise.as_ref().map(|noise| NoiseConfig {
+                pattern: noise.pattern.clone(),
+                ..Default::default()
+            }),
+            ..config.clone()
+        };
+        self.transport_type == TransportType::Noise
+            && self != other
+            && without_keys(self) == without_keys(other)
+    }
+}
+
 fn default_heartbeat_timeout() -> u64 {
     DEFAULT_HEARTBEAT_TIMEOUT_SECS
 }
@@ -199,6 +298,7 @@ fn default_client_retry_interval() -> u64 {
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct ClientConfig {
     pub remote_addr: String,
@@ -218,6 +318,7 @@ fn default_heartbeat_interval() -> u64 {
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct ServerConfig {
     pub bind_addr: String,
@@ -230,6 +331,8 @@ pub struct ServerConfig {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(as = RatholeConfig))]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub server: Option<ServerConfig>,
@@ -312,12 +415,9 @@ impl Config {
                     .tls
                     .as_ref()
                     .ok_or_else(|| anyhow!("Missing TLS configuration"))?;
-                if is_server {
-                    tls_config
-                        .pkcs12
-                        .as_ref()
-                        .and(tls_config.pkcs12_password.as_ref())
-                        .ok_or_else(|| anyhow!("Missing `pkcs12` or `pkcs12_password`"))?;
+                let has_identity = tls_config.pkcs12.is_some() || tls_config.pkcs12_der.is_some();
+                if is_server && (!has_identity || tls_config.pkcs12_password.is_none()) {
+                    bail!("Missing `pkcs12` or `pkcs12_password`");
                 }
                 Ok(())
             }
@@ -337,6 +437,11 @@ impl Config {
             "Configuration is invalid. Please refer to the configuration specification."
         })
     }
+
+    /// Parses and validates a config as `from_file` does with the file's contents.
+    pub fn parse(s: &str) -> Result<Config> {
+        Config::from_str(s)
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,8 @@
 use crate::{
-    config::{ClientConfig, ClientServiceConfig, ServerConfig, ServerServiceConfig},
+    config::{
+        ClientConfig, ClientServiceConfig, NoiseConfig, ServerConfig, ServerServiceConfig,
+        TransportConfig,
+    },
     Config,
 };
 use anyhow::{Context, Result};
@@ -17,7 +20,8 @@ use notify::{EventKind, RecursiveMode, Watcher};
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[allow(dead_code)]
 pub enum ConfigChange {
-    General(Box<Config>), // Trigger a full restart
+    General(Box<Config>),   // Trigger a full restart
+    NoiseKeys(NoiseConfig), // Swap the keys of the running transport
     ServerChange(ServerServiceChange),
     ClientChange(ClientServiceChange),
 }
@@ -40,6 +44,8 @@ pub enum ServerServiceChange {
 trait InstanceConfig: Clone {
     type ServiceConfig: PartialEq + Eq + Clone;
     fn equal_without_service(&self, rhs: &Self) -> bool;
+    fn transport(&self) -> &TransportConfig;
+    fn transport_mut(&mut self) -> &mut TransportConfig;
     fn service_delete_change(s: String) -> ConfigChange;
     fn service_add_change(cfg: Self::ServiceConfig) -> ConfigChange;
     fn get_services(&self) -> &HashMap<String, Self::ServiceConfig>;
@@ -60,6 +66,12 @@ impl InstanceConfig for ServerConfig {
 
         left == right
     }
+    fn transport(&self) -> &TransportConfig {
+        &self.transport
+    }
+    fn transport_mut(&mut self) -> &mut TransportConfig {
+        &mut self.transport
+    }
     fn service_delete_change(s: String) -> ConfigChange {
         ConfigChange::ServerChange(ServerServiceChange::Delete(s))
     }
@@ -86,6 +98,12 @@ impl InstanceConfig for ClientConfig {
 
         left == right
     }
+    fn transport(&self) -> &TransportConfig {
+        &self.transport
+    }
+    fn transport_mut(&mut self) -> &mut TransportConfig {
+        &mut self.transport
+    }
     fn service_delete_change(s: String) -> ConfigChange {
         ConfigChange::ClientChange(ClientServiceChange::Delete(s))
     }
@@ -203,8 +221,10 @@ async fn config_watcher(
     Ok(())
 }
 
-#[allow(dead_code)]
-fn calculate_events(old: &Config, new: &Config) -> Option<Vec<ConfigChange>> {
+/// Computes the changes needed to move a running instance from `old` to `new`.
+///
+/// Returns `None` if both configurations are equal.
+pub fn calculate_events(old: &Config, new: &Config) -> Option<Vec<ConfigChange>> {
     if old == new {
         return None;
     }
@@ -246,8 +266,18 @@ fn calculate_instance_config_events<T: InstanceConfig>(
     old: &T,
     new: &T,
 ) -> Option<Vec<ConfigChange>> {
+    let mut ret = vec![];
     if !old.equal_without_service(new) {
-        return None;
+        // Rotated Noise keys are swapped into the running transport
+        let mut rekeyed = old.clone();
+        *rekeyed.transport_mut() = new.transport().clone();
+        if !old.transport().differs_only_in_noise_keys(new.transport())
+            || !rekeyed.equal_without_service(new)
+        {
+            return None;
+        }
+        let noise = new.transport().noise.clone().unwrap_or_default();
+        ret.push(ConfigChange::NoiseKeys(noise));
     }
 
     let old = old.get_services();
@@ -263,7 +293,8 @@ fn calculate_instance_config_events<T: InstanceConfig>(
         .filter(|(name, c)| old.get(*name) != Some(*c))
         .map(|(_, c)| T::service_add_change(c.clone()));
 
-    Some(deletions.chain(addition).collect())
+    ret.extend(deletions.chain(addition));
+    Some(ret)
 }
 
 #[cfg(test)]
@@ -399,6 +430,7 @@ mod test {
             let get_key = |x: &ConfigChange| -> String {
                 match x {
                     ConfigChange::General(_) => String::from("g"),
+                    ConfigChange::NoiseKeys(_) => String::from("k"),
                     ConfigChange::ServerChange(sc) => match sc {
                         ServerServiceChange::Add(c) => "s_add_".to_owned() + &c.name,
                         ServerServiceChange::Delete(s) => "s_del_".to_owned() + s,
@@ -431,4 +463,45 @@ mod test {
             None
         );
     }
+
+    #[test]
+    fn test_noise_key_change() {
+        use crate::config::TransportType;
+
+        let noise = |key: &str, previous: &[&str]| TransportConfig {
+            transport_type: TransportType::Noise,
+            noise: Some(NoiseConfig {
+                local_private_key: Some(key.into()),
+                previous_private_keys: previous.iter().map(|&key| key.into()).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let server = |transport: TransportConfig, bind_addr: &str| Config {
+            server: Some(ServerConfig {
+                bind_addr: bind_addr.to_string(),
+                services: collection!(String::from("foo") => ServerServiceConfig::with_name("foo")),
+                transport,
+                ..Default::default()
+            }),
+            client: None,
+        };
+
+        // Only the keys changed, so the services keep running
+        let rotated = noise("new", &["old"]);
+        assert_eq!(
+            calculate_events(
+                &server(noise("old", &[]), "0.0.0.0:2333"),
+                &server(rotated.clone(), "0.0.0.0:2333"),
+            ),
+            Some(vec![ConfigChange::NoiseKeys(rotated.noise.unwrap())])
+        );
+
+        // Anything else still restarts the instance
+        let new = server(noise("new", &["old"]), "0.0.0.0:2334");
+        assert_eq!(
+            calculate_events(&server(noise("old", &[]), "0.0.0.0:2333"), &new),
+            Some(vec![ConfigChange::General(Box::new(new))])
+        );
+    }
 }
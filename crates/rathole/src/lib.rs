@@ -5,17 +5,21 @@ mod constants;
 mod helper;
 mod multi_map;
 mod protocol;
+mod status;
 mod transport;
 
-pub use cli::Cli;
-use cli::KeypairType;
+pub use cli::{Cli, KeypairType};
 pub use config::Config;
 use config::Config as RatholeConfig;
+pub use config_watcher::{
+    calculate_events, ClientServiceChange, ConfigChange, ServerServiceChange,
+};
 pub use constants::UDP_BUFFER_SIZE;
+pub use status::{ControlChannelState, ServiceStatus};
 
 use anyhow::{anyhow, Result};
 use tokio::sync::{broadcast, mpsc};
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 #[cfg(feature = "client")]
 mod client;
@@ -27,7 +31,7 @@ mod server;
 #[cfg(feature = "server")]
 use server::run_server;
 
-use crate::config_watcher::{ConfigChange, ConfigWatcherHandle};
+use crate::config_watcher::ConfigWatcherHandle;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InstanceMode {
@@ -66,6 +70,110 @@ pub async fn run_with_config(
     let config = sanitize_config(config, mode)?;
     let (_update_tx, update_rx) = mpsc::channel(1);
 
+    run_instance_with_mode(config, mode, shutdown_rx, update_rx, ServiceStatus::new()).await
+}
+
+/// Runs an embedded instance driven by configuration events instead of a file.
+///
+/// Like [`run`], the first `ConfigChange::General` event starts the instance and
+/// every later one restarts it, while service changes are applied in place.
+/// A configuration that cannot run is logged and skipped, and an instance that
+/// failed is replaced by the next general change, so only `shutdown_rx` or
+/// closing `event_rx` ends the loop.
+/// The control channel state of every service is kept up to date in `status`.
+pub async fn run_with_updates(
+    mut event_rx: mpsc::Receiver<ConfigChange>,
+    mut shutdown_rx: broadcast::Receiver<bool>,
+    status: ServiceStatus,
+) -> Result<()> {
+    fdlimit::raise_fd_limit();
+
+    // shutdown_tx owns the instance
+    let (shutdown_tx, _) = broadcast::channel(1);
+
+    // (The join handle of the last instance, The service update channel sender)
+    let mut last_instance: Option<(tokio::task::JoinHandle<_>, mpsc::Sender<ConfigChange>)> = None;
+
+    loop {
+        let e = tokio::select! {
+            e = event_rx.recv() => match e {
+                Some(e) => e,
+                None => break,
+            },
+            _ = shutdown_rx.recv() => break,
+        };
+
+        match e {
+            ConfigChange::General(config) => {
+                if let Some((i, _)) = last_instance.take() {
+                    info!("General configuration change detected. Restarting...");
+                    let _ = shutdown_tx.send(true);
+                    // A failed instance must not stop the next one from starting
+                    match i.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => {
+                            error!("The previous instance exited with an error: {:#}", e)
+                        }
+                        Err(e) => error!("The previous instance panicked: {}", e),
+                    }
+                }
+
+                debug!("{:?}", config);
+
+                // An invalid configuration leaves the instance stopped until
+                // the next general change
+                let mode = match (config.server.is_some(), config.client.is_some()) {
+                    (true, false) => InstanceMode::Server,
+                    (false, true) => InstanceMode::Client,
+                    _ => {
+                        error!("Ignored a configuration that is neither a server nor a client");
+                        continue;
+                    }
+                };
+                let config = match sanitize_config(*config, mode) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        error!("Ignored an invalid configuration: {:#}", e);
+                        continue;
+                    }
+                };
+                let (service_update_tx, service_update_rx) = mpsc::channel(1024);
+
+                last_instance = Some((
+                    tokio::spawn(run_instance_with_mode(
+                        config,
+                        mode,
+                        shutdown_tx.subscribe(),
+                        service_update_rx,
+                        status.clone(),
+                    )),
+                    service_update_tx,
+                ));
+            }
+            ev => {
+                info!("Service change detected. {:?}", ev);
+                if let Some((_, service_update_tx)) = &last_instance {
+                    let _ = service_update_tx.send(ev).await;
+                }
+            }
+        }
+    }
+
+    let _ = shutdown_tx.send(true);
+    if let Some((i, _)) = last_instance {
+        i.await??;
+    }
+
+    Ok(())
+}
+
+async fn run_instance_with_mode(
+    config: RatholeConfig,
+    mode: InstanceMode,
+    shutdown_rx: broadcast::Receiver<bool>,
+    update_rx: mpsc::Receiver<ConfigChange>,
+    status: ServiceStatus,
+) -> Result<()> {
     match mode {
         InstanceMode::Client => {
             #[cfg(not(feature = "client"))]
@@ -74,7 +182,7 @@ pub async fn run_with_config(
             }
             #[cfg(feature = "client")]
             {
-                run_client(config, shutdown_rx, update_rx).await
+                run_client(config, shutdown_rx, update_rx, status).await
             }
         }
         InstanceMode::Server => {
@@ -84,7 +192,7 @@ pub async fn run_with_config(
             }
             #[cfg(feature = "server")]
             {
-                run_server(config, shutdown_rx, update_rx).await
+                run_server(config, shutdown_rx, update_rx, status).await
             }
         }
     }
@@ -99,9 +207,16 @@ fn get_str_from_keypair_type(curve: KeypairType) -> &'static str {
     }
 }
 
+/// A base64 encoded Noise keypair, as used by `local_private_key` and
+/// `remote_public_key`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keypair {
+    pub private: String,
+    pub public: String,
+}
+
 #[cfg(feature = "noise")]
-fn genkey(curve: Option<KeypairType>) -> Result<()> {
-    let curve = curve.unwrap_or(DEFAULT_CURVE);
+pub fn generate_keypair(curve: KeypairType) -> Result<Keypair> {
     let builder = snowstorm::Builder::new(
         format!(
             "Noise_KK_{}_ChaChaPoly_BLAKE2s",
@@ -111,8 +226,18 @@ fn genkey(curve: Option<KeypairType>) -> Result<()> {
     );
     let keypair = builder.generate_keypair()?;
 
-    println!("Private Key:\n{}\n", base64::encode(keypair.private));
-    println!("Public Key:\n{}", base64::encode(keypair.public));
+    Ok(Keypair {
+        private: base64::encode(keypair.private),
+        public: base64::encode(keypair.public),
+    })
+}
+
+#[cfg(feature = "noise")]
+fn genkey(curve: Option<KeypairType>) -> Result<()> {
+    let keypair = generate_keypair(curve.unwrap_or(DEFAULT_CURVE))?;
+
+    println!("Private Key:\n{}\n", keypair.private);
+    println!("Public Key:\n{}", keypair.public);
     Ok(())
 }
 
@@ -188,13 +313,13 @@ async fn run_instance(
             #[cfg(not(feature = "client"))]
             crate::helper::feature_not_compile("client");
             #[cfg(feature = "client")]
-            run_client(config, shutdown_rx, service_update).await
+            run_client(config, shutdown_rx, service_update, ServiceStatus::new()).await
         }
         RunMode::Server => {
             #[cfg(not(feature = "server"))]
             crate::helper::feature_not_compile("server");
             #[cfg(feature = "server")]
-            run_server(config, shutdown_rx, service_update).await
+            run_server(config, shutdown_rx, service_update, ServiceStatus::new()).await
         }
     }
 }
@@ -314,4 +439,61 @@ mod tests {
             assert_eq!(determine_run_mode(&config, &args), t.run_mode);
         }
     }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_run_with_updates_skips_invalid_config() {
+        use config::*;
+        use std::time::Duration;
+
+        let (event_tx, event_rx) = mpsc::channel(8);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let status = ServiceStatus::new();
+        let instance = tokio::spawn(run_with_updates(event_rx, shutdown_rx, status.clone()));
+
+        // Neither a server nor a client
+        let undetermined = Config {
+            server: None,
+            client: None,
+        };
+        // A service without a token
+        let tokenless: Config = toml::from_str(
+            r#"
+            [server]
+            bind_addr = "127.0.0.1:0"
+            [server.services.ignored]
+            bind_addr = "127.0.0.1:0"
+            "#,
+        )
+        .unwrap();
+        let valid = Config::parse(
+            r#"
+            [server]
+            bind_addr = "127.0.0.1:0"
+            default_token = "token"
+            [server.services.echo]
+            bind_addr = "127.0.0.1:0"
+            "#,
+        )
+        .unwrap();
+        for config in [undetermined, tokenless, valid] {
+            event_tx
+                .send(ConfigChange::General(Box::new(config)))
+                .await
+                .unwrap();
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !status.snapshot().contains_key("echo") {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("the valid configuration was not started");
+        assert!(!status.snapshot().contains_key("ignored"));
+        assert!(!instance.is_finished());
+
+        shutdown_tx.send(true).unwrap();
+        instance.await.unwrap().unwrap();
+    }
 }
@@ -8,6 +8,7 @@ use crate::protocol::{
     self, read_auth, read_hello, Ack, ControlChannelCmd, DataChannelCmd, Hello, UdpTraffic,
     HASH_WIDTH_IN_BYTES,
 };
+use crate::status::{ControlChannelState, ServiceStatus};
 use crate::transport::{SocketOpts, TcpTransport, Transport};
 use anyhow::{anyhow, bail, Context, Result};
 use backoff::backoff::Backoff;
@@ -43,6 +44,7 @@ pub async fn run_server(
     config: Config,
     shutdown_rx: broadcast::Receiver<bool>,
     update_rx: mpsc::Receiver<ConfigChange>,
+    status: ServiceStatus,
 ) -> Result<()> {
     let config = match config.server {
             Some(config) => config,
@@ -53,13 +55,13 @@ pub async fn run_server(
 
     match config.transport.transport_type {
         TransportType::Tcp => {
-            let mut server = Server::<TcpTransport>::from(config).await?;
+            let mut server = Server::<TcpTransport>::from(config, status).await?;
             server.run(shutdown_rx, update_rx).await?;
         }
         TransportType::Tls => {
             #[cfg(any(feature = "native-tls", feature = "rustls"))]
             {
-                let mut server = Server::<TlsTransport>::from(config).await?;
+                let mut server = Server::<TlsTransport>::from(config, status).await?;
                 server.run(shutdown_rx, update_rx).await?;
             }
             #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
@@ -68,7 +70,7 @@ pub async fn run_server(
         TransportType::Noise => {
             #[cfg(feature = "noise")]
             {
-                let mut server = Server::<NoiseTransport>::from(config).await?;
+                let mut server = Server::<NoiseTransport>::from(config, status).await?;
                 server.run(shutdown_rx, update_rx).await?;
             }
             #[cfg(not(feature = "noise"))]
@@ -77,7 +79,7 @@ pub async fn run_server(
         TransportType::Websocket => {
             #[cfg(any(feature = "websocket-native-tls", feature = "websocket-rustls"))]
             {
-                let mut server = Server::<WebsocketTransport>::from(config).await?;
+                let mut server = Server::<WebsocketTransport>::from(config, status).await?;
                 server.run(shutdown_rx, update_rx).await?;
             }
             #[cfg(not(any(feature = "websocket-native-tls", feature = "websocket-rustls")))]
@@ -103,6 +105,8 @@ struct Server<T: Transport> {
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
     // Wrapper around the transport layer
     transport: Arc<T>,
+    // Control channel state of every service
+    status: ServiceStatus,
 }
 
 // Generate a hash map of services which is indexed by ServiceDigest
@@ -118,8 +122,12 @@ fn generate_service_hashmap(
 
 impl<T: 'static + Transport> Server<T> {
     // Create a server from `[server]`
-    pub async fn from(config: ServerConfig) -> Result<Server<T>> {
+    pub async fn from(config: ServerConfig, status: ServiceStatus) -> Result<Server<T>> {
         let config = Arc::new(config);
+        // Every service waits for its client at first
+        for name in config.services.keys() {
+            status.set(name, ControlChannelState::Disconnected);
+        }
         let services = Arc::new(RwLock::new(generate_service_hashmap(&config)));
         let control_channels = Arc::new(RwLock::new(ControlChannelMap::new()));
         let transport = Arc::new(T::new(&config.transport)?);
@@ -128,6 +136,7 @@ impl<T: 'static + Transport> Server<T> {
             services,
             control_channels,
             transport,
+            status,
         })
     }
 
@@ -187,8 +196,9 @@ impl<T: 'static + Transport> Server<T> {
                                             let services = self.services.clone();
                                             let control_channels = self.control_channels.clone();
                                             let server_config = self.config.clone();
+                                            let status = self.status.clone();
                                             tokio::spawn(async move {
-                                                if let Err(err) = handle_connection(conn, services, control_channels, server_config).await {
+                                                if let Err(err) = handle_connection(conn, services, control_channels, server_config, status).await {
                                                     error!("{:#}", err);
                                                 }
                                             }.instrument(info_span!("connection", %addr)));
@@ -217,6 +227,10 @@ impl<T: 'static + Transport> Server<T> {
             }
         }
 
+        for service in self.services.read().await.values() {
+            self.status.remove(&service.name);
+        }
+
         info!("Shutdown");
 
         Ok(())
@@ -228,19 +242,37 @@ impl<T: 'static + Transport> Server<T> {
                 ServerServiceChange::Add(cfg) => {
                     let hash = protocol::digest(cfg.name.as_bytes());
                     let mut wg = self.services.write().await;
+                    // Rotated tokens are only checked on the next handshake,
+                    // so the running control channel is kept
+                    let tokens_only = wg
+                        .get(&hash)
+                        .is_some_and(|old| old.differs_only_in_tokens(&cfg));
+                    if !tokens_only {
+                        self.status
+                            .set(&cfg.name, ControlChannelState::Disconnected);
+                    }
                     let _ = wg.insert(hash, cfg);
 
-                    let mut wg = self.control_channels.write().await;
-                    let _ = wg.remove1(&hash);
+                    if !tokens_only {
+                        let mut wg = self.control_channels.write().await;
+                        let _ = wg.remove1(&hash);
+                    }
                 }
                 ServerServiceChange::Delete(s) => {
                     let hash = protocol::digest(s.as_bytes());
                     let _ = self.services.write().await.remove(&hash);
+                    self.status.remove(&s);
 
                     let mut wg = self.control_channels.write().await;
                     let _ = wg.remove1(&hash);
                 }
             },
+            ConfigChange::NoiseKeys(noise) => {
+                // Established connections keep the keys they were made with
+                if let Err(e) = self.transport.update_keys(&noise) {
+                    error!("Failed to update the transport keys: {:#}", e);
+                }
+            }
             ignored => warn!("Ignored {:?} since running as a server", ignored),
         }
     }
@@ -252,6 +284,7 @@ async fn handle_connection<T: 'static + Transport>(
     services: Arc<RwLock<HashMap<ServiceDigest, ServerServiceConfig>>>,
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
     server_config: Arc<ServerConfig>,
+    status: ServiceStatus,
 ) -> Result<()> {
     // Read hello
     let hello = read_hello(&mut conn).await?;
@@ -263,6 +296,7 @@ async fn handle_connection<T: 'static + Transport>(
                 control_channels,
                 service_digest,
                 server_config,
+                status,
             )
             .await?;
         }
@@ -279,6 +313,7 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
     control_channels: Arc<RwLock<ControlChannelMap<T>>>,
     service_digest: ServiceDigest,
     server_config: Arc<ServerConfig>,
+    status: ServiceStatus,
 ) -> Result<()> {
     info!("Try to handshake a control channel");
 
@@ -310,23 +345,21 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
 
     let service_name = &service_config.name;
 
-    // Calculate the checksum
-    let mut concat = Vec::from(service_config.token.as_ref().unwrap().as_bytes());
-    concat.append(&mut nonce);
-
     // Read auth
     let protocol::Auth(d) = read_auth(&mut conn).await?;
 
-    // Validate
-    let session_key = protocol::digest(&concat);
-    if session_key != d {
+    // Validate against the current token, then any previous one still accepted
+    let accepted = std::iter::once(service_config.token.as_ref().unwrap())
+        .chain(service_config.previous_tokens.iter())
+        .any(|token| {
+            let mut concat = Vec::from(token.as_bytes());
+            concat.extend_from_slice(&nonce);
+            protocol::digest(&concat) == d
+        });
+    if !accepted {
         conn.write_all(&bincode::serialize(&Ack::AuthFailed).unwrap())
             .await?;
-        debug!(
-            "Expect {}, but got {}",
-            hex::encode(session_key),
-            hex::encode(d)
-        );
+        debug!("Got unexpected digest {}", hex::encode(d));
         bail!("Service {} failed the authentication", service_name);
     } else {
         let mut h = control_channels.write().await;
@@ -348,11 +381,15 @@ async fn do_control_channel_handshake<T: 'static + Transport>(
         conn.flush().await?;
 
         info!(service = %service_config.name, "Control channel established");
-        let handle =
-            ControlChannelHandle::new(conn, service_config, server_config.heartbeat_interval);
+        let handle = ControlChannelHandle::new(
+            conn,
+            service_config,
+            server_config.heartbeat_interval,
+            status,
+        );
 
-        // Insert the new handle
-        let _ = h.insert(service_digest, session_key, handle);
+        // Insert the new handle. The digest the client sent is the session key
+        let _ = h.insert(service_digest, d, handle);
     }
 
     Ok(())
@@ -403,6 +440,7 @@ where
         conn: T::Stream,
         service: ServerServiceConfig,
         heartbeat_interval: u64,
+        status: ServiceStatus,
     ) -> ControlChannelHandle<T> {
         // Create a shutdown channel
         let (shutdown_tx, shutdown_rx) = broadcast::channel::<bool>(1);
@@ -471,11 +509,14 @@ where
         };
 
         // Run the control channel
+        let generation = status.set(&service.name, ControlChannelState::Connected);
+        let name = service.name.clone();
         tokio::spawn(
             async move {
                 if let Err(err) = ch.run().await {
                     error!("{:#}", err);
                 }
+                status.set_if_current(&name, generation, ControlChannelState::Disconnected);
             }
             .instrument(Span::current()),
         );
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The state of a service's control channel, as seen by the local instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlChannelState {
+    // The client is trying to establish the control channel
+    Connecting,
+    Connected,
+    // The client lost the channel, or the server is waiting for a client
+    Disconnected,
+}
+
+/// Shared view of the control channel state of every running service
+#[derive(Debug, Clone, Default)]
+pub struct ServiceStatus {
+    services: Arc<Mutex<HashMap<String, (ControlChannelState, u64)>>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl ServiceStatus {
+    pub fn new() -> ServiceStatus {
+        Default::default()
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, ControlChannelState> {
+        self.services
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, (state, _))| (name.clone(), *state))
+            .collect()
+    }
+
+    // Record a new state and return its generation, so that a stale control
+    // channel cannot overwrite the state of the one that replaced it
+    pub(crate) fn set(&self, service: &str, state: ControlChannelState) -> u64 {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+        self.services
+            .lock()
+            .unwrap()
+            .insert(service.to_owned(), (state, generation));
+        generation
+    }
+
+    pub(crate) fn set_if_current(
+        &self,
+        service: &str,
+        generation: u64,
+        state: ControlChannelState,
+    ) {
+        if let Some(entry) = self.services.lock().unwrap().get_mut(service) {
+            if entry.1 == generation {
+                entry.0 = state;
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, service: &str) {
+        self.services.lock().unwrap().remove(service);
+    }
+}
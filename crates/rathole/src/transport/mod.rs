@@ -1,6 +1,8 @@
-use crate::config::{ClientServiceConfig, ServerServiceConfig, TcpConfig, TransportConfig};
+use crate::config::{
+    ClientServiceConfig, NoiseConfig, ServerServiceConfig, TcpConfig, TransportConfig,
+};
 use crate::helper::{to_socket_addr, try_set_tcp_keepalive};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use std::fmt::{Debug, Display};
 use std::net::SocketAddr;
@@ -58,6 +60,10 @@ pub trait Transport: Debug + Send + Sync {
     fn new(config: &TransportConfig) -> Result<Self>
     where
         Self: Sized;
+    /// Switch to rotated keys for new connections, keeping the established ones
+    fn update_keys(&self, _config: &NoiseConfig) -> Result<()> {
+        bail!("The transport has no keys to update")
+    }
     /// Provide the transport with socket options, which can be handled at the need of the transport
     fn hint(conn: &Self::Stream, opts: SocketOpts);
     async fn bind<T: ToSocketAddrs + Send + Sync>(&self, addr: T) -> Result<Self::Acceptor>;
@@ -49,13 +49,16 @@ impl Transport for TlsTransport {
             }
         };
 
-        let tls_acceptor = match config.pkcs12.as_ref() {
-            Some(path) => {
-                let ident = Identity::from_pkcs12(
-                    &fs::read(path)?,
-                    config.pkcs12_password.as_ref().unwrap(),
-                )
-                .with_context(|| "Failed to create identitiy")?;
+        let pkcs12 = match (config.pkcs12_der.as_ref(), config.pkcs12.as_ref()) {
+            (Some(der), _) => Some(der.to_vec()),
+            (None, Some(path)) => Some(fs::read(path)?),
+            (None, None) => None,
+        };
+        let tls_acceptor = match pkcs12 {
+            Some(pkcs12) => {
+                let ident =
+                    Identity::from_pkcs12(&pkcs12, config.pkcs12_password.as_ref().unwrap())
+                        .with_context(|| "Failed to create identitiy")?;
                 Some(TlsAcceptor::from(
                     native_tls::TlsAcceptor::new(ident).unwrap(),
                 ))
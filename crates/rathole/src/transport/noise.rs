@@ -1,17 +1,30 @@
 use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::time::Duration;
 
 use super::{AddrMaybeCached, SocketOpts, TcpTransport, Transport};
 use crate::config::{NoiseConfig, TransportConfig};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use snowstorm::{Builder, NoiseParams, NoiseStream};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 
+// Snowstorm frames every handshake message with its length as a big-endian u16
+const LENGTH_FIELD_LEN: usize = 2;
+const MAX_MESSAGE_LEN: usize = u16::MAX as usize;
+
 pub struct NoiseTransport {
     tcp: TcpTransport,
     config: NoiseConfig,
     params: NoiseParams,
+    keys: RwLock<NoiseKeys>,
+}
+
+// Decoded keys, replaced as a whole when they are rotated
+#[derive(Clone)]
+struct NoiseKeys {
     local_private_key: Vec<u8>,
+    previous_private_keys: Vec<Vec<u8>>,
     remote_public_key: Option<Vec<u8>>,
 }
 
@@ -21,14 +34,89 @@ impl std::fmt::Debug for NoiseTransport {
     }
 }
 
+impl NoiseKeys {
+    fn new(config: &NoiseConfig, params: &NoiseParams) -> Result<Self> {
+        let remote_public_key = match &config.remote_public_key {
+            Some(x) => {
+                Some(base64::decode(x).with_context(|| "Failed to decode remote_public_key")?)
+            }
+            None => None,
+        };
+
+        let local_private_key = match &config.local_private_key {
+            Some(x) => base64::decode(x.as_bytes())
+                .with_context(|| "Failed to decode local_private_key")?,
+            None => Builder::new(params.clone()).generate_keypair()?.private,
+        };
+
+        let previous_private_keys = config
+            .previous_private_keys
+            .iter()
+            .map(|x| base64::decode(x.as_bytes()))
+            .collect::<Result<_, _>>()
+            .with_context(|| "Failed to decode previous_private_keys")?;
+
+        Ok(NoiseKeys {
+            local_private_key,
+            previous_private_keys,
+            remote_public_key,
+        })
+    }
+}
+
 impl NoiseTransport {
-    fn builder<'a>(&'a self) -> Builder<'a> {
-        let builder = Builder::new(self.params.clone()).local_private_key(&self.local_private_key);
-        match &self.remote_public_key {
+    fn builder<'a>(&'a self, keys: &'a NoiseKeys, local_private_key: &'a [u8]) -> Builder<'a> {
+        let builder = Builder::new(self.params.clone()).local_private_key(local_private_key);
+        match &keys.remote_public_key {
             Some(x) => builder.remote_public_key(x),
             None => builder,
         }
     }
+
+    fn keys(&self) -> NoiseKeys {
+        self.keys.read().unwrap().clone()
+    }
+
+    // The first message of the initiator can only be read with the private key
+    // matching the public key it was given. Peeking at it picks that key among
+    // the current and previous ones without consuming the message
+    async fn responder_key(&self, conn: &TcpStream, keys: &NoiseKeys) -> Result<Vec<u8>> {
+        if keys.previous_private_keys.is_empty() {
+            return Ok(keys.local_private_key.clone());
+        }
+
+        let message = peek_message(conn).await?;
+        let mut payload = vec![0u8; message.len()];
+        for key in std::iter::once(&keys.local_private_key).chain(&keys.previous_private_keys) {
+            let mut responder = self.builder(keys, key).build_responder()?;
+            if responder.read_message(&message, &mut payload).is_ok() {
+                return Ok(key.clone());
+            }
+        }
+
+        // No key fits, so the handshake fails as it would with a single key
+        Ok(keys.local_private_key.clone())
+    }
+}
+
+// Waits until the first handshake message has arrived in full, and returns it
+async fn peek_message(conn: &TcpStream) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; LENGTH_FIELD_LEN + MAX_MESSAGE_LEN];
+    loop {
+        let n = conn.peek(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the noise handshake");
+        }
+        if n >= LENGTH_FIELD_LEN {
+            let len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+            if n >= LENGTH_FIELD_LEN + len {
+                return Ok(buf[LENGTH_FIELD_LEN..LENGTH_FIELD_LEN + len].to_vec());
+            }
+        }
+        // Peeking returns at once while data is pending, so back off until
+        // the rest of the message arrives
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
 }
 
 #[async_trait]
@@ -44,32 +132,24 @@ impl Transport for NoiseTransport {
             Some(v) => v.clone(),
             None => return Err(anyhow!("Missing noise config")),
         };
-        let builder = Builder::new(config.pattern.parse()?);
-
-        let remote_public_key = match &config.remote_public_key {
-            Some(x) => {
-                Some(base64::decode(x).with_context(|| "Failed to decode remote_public_key")?)
-            }
-            None => None,
-        };
-
-        let local_private_key = match &config.local_private_key {
-            Some(x) => base64::decode(x.as_bytes())
-                .with_context(|| "Failed to decode local_private_key")?,
-            None => builder.generate_keypair()?.private,
-        };
 
         let params: NoiseParams = config.pattern.parse()?;
+        let keys = RwLock::new(NoiseKeys::new(&config, &params)?);
 
         Ok(NoiseTransport {
             tcp,
             config,
             params,
-            local_private_key,
-            remote_public_key,
+            keys,
         })
     }
 
+    fn update_keys(&self, config: &NoiseConfig) -> Result<()> {
+        let keys = NoiseKeys::new(config, &self.params)?;
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+
     fn hint(conn: &Self::Stream, opt: SocketOpts) {
         opt.apply(conn.get_inner());
     }
@@ -86,7 +166,10 @@ impl Transport for NoiseTransport {
     }
 
     async fn handshake(&self, conn: Self::RawStream) -> Result<Self::Stream> {
-        let conn = NoiseStream::handshake(conn, self.builder().build_responder()?)
+        let keys = self.keys();
+        let local_private_key = self.responder_key(&conn, &keys).await?;
+        let responder = self.builder(&keys, &local_private_key).build_responder()?;
+        let conn = NoiseStream::handshake(conn, responder)
             .await
             .with_context(|| "Failed to do noise handshake")?;
         Ok(conn)
@@ -99,7 +182,11 @@ impl Transport for NoiseTransport {
             .await
             .with_context(|| "Failed to connect TCP socket")?;
 
-        let conn = NoiseStream::handshake(conn, self.builder().build_initiator()?)
+        let keys = self.keys();
+        let initiator = self
+            .builder(&keys, &keys.local_private_key)
+            .build_initiator()?;
+        let conn = NoiseStream::handshake(conn, initiator)
             .await
             .with_context(|| "Failed to do noise handshake")?;
         return Ok(conn);
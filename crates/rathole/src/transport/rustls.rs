@@ -33,8 +33,12 @@ impl Debug for TlsTransport {
 }
 
 fn load_server_config(config: &TlsConfig) -> Result<Option<ServerConfig>> {
-    if let Some(pkcs12_path) = config.pkcs12.as_ref() {
-        let buf = fs::read(pkcs12_path)?;
+    let buf = match (config.pkcs12_der.as_ref(), config.pkcs12.as_ref()) {
+        (Some(der), _) => Some(der.to_vec()),
+        (None, Some(pkcs12_path)) => Some(fs::read(pkcs12_path)?),
+        (None, None) => None,
+    };
+    if let Some(buf) = buf {
         let pfx = PFX::parse(buf.as_slice())?;
         let pass = config.pkcs12_password.as_ref().unwrap();
 
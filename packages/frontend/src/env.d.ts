@@ -12,6 +12,7 @@ declare module '*.svg?react' {
 
 interface ImportMetaEnv {
   readonly VITE_MANAGER_GRPC_URL?: string;
+  readonly VITE_MANAGER_API_TOKEN?: string;
 }
 
 interface ImportMeta {
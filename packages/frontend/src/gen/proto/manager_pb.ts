@@ -10,7 +10,7 @@ import type { Message } from "@bufbuild/protobuf";
  * Describes the file proto/manager.proto.
  */
 export const file_proto_manager: GenFile = /*@__PURE__*/
-  fileDesc("ChNwcm90by9tYW5hZ2VyLnByb3RvEhBsYXZhbC5tYW5hZ2VyLnYxIlkKEVBvcnRNYXBwaW5nQ29uZmlnEi8KBG1vZGUYASABKA4yIS5sYXZhbC5tYW5hZ2VyLnYxLlBvcnRNYXBwaW5nTW9kZRITCgtjb25maWdfanNvbhgCIAEoCSIkChRHZXROb2RlQ29uZmlnUmVxdWVzdBIMCgRuYW1lGAEgASgJInYKFUdldE5vZGVDb25maWdSZXNwb25zZRIMCgRuYW1lGAEgASgJEj4KDHBvcnRfbWFwcGluZxgCIAEoCzIjLmxhdmFsLm1hbmFnZXIudjEuUG9ydE1hcHBpbmdDb25maWdIAIgBAUIPCg1fcG9ydF9tYXBwaW5nKnAKD1BvcnRNYXBwaW5nTW9kZRIhCh1QT1JUX01BUFBJTkdfTU9ERV9VTlNQRUNJRklFRBAAEhwKGFBPUlRfTUFQUElOR19NT0RFX1NFUlZFUhABEhwKGFBPUlRfTUFQUElOR19NT0RFX0NMSUVOVBACMm8KC05vZGVNYW5hZ2VyEmAKDUdldE5vZGVDb25maWcSJi5sYXZhbC5tYW5hZ2VyLnYxLkdldE5vZGVDb25maWdSZXF1ZXN0GicubGF2YWwubWFuYWdlci52MS5HZXROb2RlQ29uZmlnUmVzcG9uc2ViBnByb3RvMw");
+  fileDesc("ChNwcm90by9tYW5hZ2VyLnByb3RvEhBsYXZhbC5tYW5hZ2VyLnYxIlkKEVBvcnRNYXBwaW5nQ29uZmlnEi8KBG1vZGUYASABKA4yIS5sYXZhbC5tYW5hZ2VyLnYxLlBvcnRNYXBwaW5nTW9kZRITCgtjb25maWdfanNvbhgCIAEoCSIwCgpQcm94eVJvdXRlEhAKCGhvc3RuYW1lGAEgASgJEhAKCHVwc3RyZWFtGAIgASgJIiwKD1JldmVyc2VQcm94eVRscxIMCgRjZXJ0GAEgASgJEgsKA2tleRgCIAEoCSLdAQoSUmV2ZXJzZVByb3h5Q29uZmlnEiwKBnJvdXRlcxgBIAMoCzIcLmxhdmFsLm1hbmFnZXIudjEuUHJveHlSb3V0ZRIdChBkZWZhdWx0X3Vwc3RyZWFtGAIgASgJSACIAQESMwoDdGxzGAMgASgLMiEubGF2YWwubWFuYWdlci52MS5SZXZlcnNlUHJveHlUbHNIAYgBARIYCgtjZXJ0aWZpY2F0ZRgEIAEoCUgCiAEBQhMKEV9kZWZhdWx0X3Vwc3RyZWFtQgYKBF90bHNCDgoMX2NlcnRpZmljYXRlIlUKDlRsc0NlcnRpZmljYXRlEgwKBG5hbWUYASABKAkSEQoJY2hhaW5fcGVtGAIgASgJEg8KB2tleV9wZW0YAyABKAkSEQoJbm90X2FmdGVyGAQgASgDIpIECgROb2RlEgwKBG5hbWUYASABKAkSHwoScmV2ZXJzZV9wcm94eV9iaW5kGAIgASgJSACIAQESHgoRcG9ydF9tYXBwaW5nX3JvbGUYAyABKAlIAYgBARIbCg5tYW5hZ2VtZW50X3VybBgEIAEoCUgCiAEBEhgKC2Rlc2NyaXB0aW9uGAUgASgJSAOIAQESDAoEdGFncxgGIAMoCRI+Cgxwb3J0X21hcHBpbmcYByABKAsyIy5sYXZhbC5tYW5hZ2VyLnYxLlBvcnRNYXBwaW5nQ29uZmlnSASIAQESLAoGc3RhdHVzGAggASgLMhwubGF2YWwubWFuYWdlci52MS5Ob2RlU3RhdHVzEg8KB3ZlcnNpb24YCSABKAMSDwoHcHJvamVjdBgKIAEoCRJACg1yZXZlcnNlX3Byb3h5GAsgASgLMiQubGF2YWwubWFuYWdlci52MS5SZXZlcnNlUHJveHlDb25maWdIBYgBARIxCgl2YXJpYWJsZXMYDCADKAsyHi5sYXZhbC5tYW5hZ2VyLnYxLk5vZGVWYXJpYWJsZUIVChNfcmV2ZXJzZV9wcm94eV9iaW5kQhQKEl9wb3J0X21hcHBpbmdfcm9sZUIRCg9fbWFuYWdlbWVudF91cmxCDgoMX2Rlc2NyaXB0aW9uQg8KDV9wb3J0X21hcHBpbmdCEAoOX3JldmVyc2VfcHJveHkiKwoMTm9kZVZhcmlhYmxlEgwKBG5hbWUYASABKAkSDQoFdmFsdWUYAiABKAkiUwoNU2VydmljZVN0YXR1cxIMCgRuYW1lGAEgASgJEjQKBXN0YXRlGAIgASgOMiUubGF2YWwubWFuYWdlci52MS5Db250cm9sQ2hhbm5lbFN0YXRlIt0BCgpOb2RlU3RhdHVzEg4KBm9ubGluZRgBIAEoCBIWCglsYXN0X3NlZW4YAiABKANIAIgBARIUCgd2ZXJzaW9uGAMgASgJSAGIAQESGwoOdXB0aW1lX3NlY29uZHMYBCABKARIAogBARIUCgxsaXN0ZW5fYWRkcnMYBSADKAkSMQoIc2VydmljZXMYBiADKAsyHy5sYXZhbC5tYW5hZ2VyLnYxLlNlcnZpY2VTdGF0dXNCDAoKX2xhc3Rfc2VlbkIKCghfdmVyc2lvbkIRCg9fdXB0aW1lX3NlY29uZHMiNQoUR2V0Tm9kZUNvbmZpZ1JlcXVlc3QSDAoEbmFtZRgBIAEoCRIPCgdwcm9qZWN0GAIgASgJIpYCChVHZXROb2RlQ29uZmlnUmVzcG9uc2USDAoEbmFtZRgBIAEoCRI+Cgxwb3J0X21hcHBpbmcYAiABKAsyIy5sYXZhbC5tYW5hZ2VyLnYxLlBvcnRNYXBwaW5nQ29uZmlnSACIAQESQAoNcmV2ZXJzZV9wcm94eRgDIAEoCzIkLmxhdmFsLm1hbmFnZXIudjEuUmV2ZXJzZVByb3h5Q29uZmlnSAGIAQESOgoLY2VydGlmaWNhdGUYBCABKAsyIC5sYXZhbC5tYW5hZ2VyLnYxLlRsc0NlcnRpZmljYXRlSAKIAQFCDwoNX3BvcnRfbWFwcGluZ0IQCg5fcmV2ZXJzZV9wcm94eUIOCgxfY2VydGlmaWNhdGUiNwoWV2F0Y2hOb2RlQ29uZmlnUmVxdWVzdBIMCgRuYW1lGAEgASgJEg8KB3Byb2plY3QYAiABKAkiqgIKF1dhdGNoTm9kZUNvbmZpZ1Jlc3BvbnNlEhAKCHJldmlzaW9uGAEgASgEEgwKBG5hbWUYAiABKAkSPgoMcG9ydF9tYXBwaW5nGAMgASgLMiMubGF2YWwubWFuYWdlci52MS5Qb3J0TWFwcGluZ0NvbmZpZ0gAiAEBEkAKDXJldmVyc2VfcHJveHkYBCABKAsyJC5sYXZhbC5tYW5hZ2VyLnYxLlJldmVyc2VQcm94eUNvbmZpZ0gBiAEBEjoKC2NlcnRpZmljYXRlGAUgASgLMiAubGF2YWwubWFuYWdlci52MS5UbHNDZXJ0aWZpY2F0ZUgCiAEBQg8KDV9wb3J0X21hcHBpbmdCEAoOX3JldmVyc2VfcHJveHlCDgoMX2NlcnRpZmljYXRlIroBChBMaXN0Tm9kZXNSZXF1ZXN0EgwKBHRhZ3MYASADKAkSLwoEbW9kZRgCIAEoDjIhLmxhdmFsLm1hbmFnZXIudjEuUG9ydE1hcHBpbmdNb2RlEhEKBHJvbGUYAyABKAlIAIgBARINCgVxdWVyeRgEIAEoCRIMCgRzb3J0GAUgASgJEg0KBWxpbWl0GAYgASgNEg4KBmN1cnNvchgHIAEoCRIPCgdwcm9qZWN0GAggASgJQgcKBV9yb2xlIk8KEUxpc3ROb2Rlc1Jlc3BvbnNlEiUKBW5vZGVzGAEgAygLMhYubGF2YWwubWFuYWdlci52MS5Ob2RlEhMKC25leHRfY3Vyc29yGAIgASgJIkoKEUNyZWF0ZU5vZGVSZXF1ZXN0EiQKBG5vZGUYASABKAsyFi5sYXZhbC5tYW5hZ2VyLnYxLk5vZGUSDwoHcHJvamVjdBgCIAEoCSI6ChJDcmVhdGVOb2RlUmVzcG9uc2USJAoEbm9kZRgBIAEoCzIWLmxhdmFsLm1hbmFnZXIudjEuTm9kZSJyChFVcGRhdGVOb2RlUmVxdWVzdBIMCgRuYW1lGAEgASgJEiQKBG5vZGUYAiABKAsyFi5sYXZhbC5tYW5hZ2VyLnYxLk5vZGUSGAoQZXhwZWN0ZWRfdmVyc2lvbhgDIAEoAxIPCgdwcm9qZWN0GAQgASgJIjoKElVwZGF0ZU5vZGVSZXNwb25zZRIkCgRub2RlGAEgASgLMhYubGF2YWwubWFuYWdlci52MS5Ob2RlIkwKEURlbGV0ZU5vZGVSZXF1ZXN0EgwKBG5hbWUYASABKAkSGAoQZXhwZWN0ZWRfdmVyc2lvbhgCIAEoAxIPCgdwcm9qZWN0GAMgASgJIhQKEkRlbGV0ZU5vZGVSZXNwb25zZSKmAQoTUmVwb3J0U3RhdHVzUmVxdWVzdBIMCgRuYW1lGAEgASgJEg8KB3ZlcnNpb24YAiABKAkSFgoOdXB0aW1lX3NlY29uZHMYAyABKAQSFAoMbGlzdGVuX2FkZHJzGAQgAygJEjEKCHNlcnZpY2VzGAUgAygLMh8ubGF2YWwubWFuYWdlci52MS5TZXJ2aWNlU3RhdHVzEg8KB3Byb2plY3QYBiABKAkiFgoUUmVwb3J0U3RhdHVzUmVzcG9uc2UimAEKDE5vZGVSZXZpc2lvbhIQCghyZXZpc2lvbhgBIAEoAxIOCgZhY3Rpb24YAiABKAkSEwoGYXV0aG9yGAMgASgJSACIAQESEgoKY3JlYXRlZF9hdBgEIAEoAxIpCgRub2RlGAUgASgLMhYubGF2YWwubWFuYWdlci52MS5Ob2RlSAGIAQFCCQoHX2F1dGhvckIHCgVfbm9kZSJjCgtGaWVsZENoYW5nZRIMCgRwYXRoGAEgASgJEhYKCWZyb21fanNvbhgCIAEoCUgAiAEBEhQKB3RvX2pzb24YAyABKAlIAYgBAUIMCgpfZnJvbV9qc29uQgoKCF90b19qc29uIjkKGExpc3ROb2RlUmV2aXNpb25zUmVxdWVzdBIMCgRuYW1lGAEgASgJEg8KB3Byb2plY3QYAiABKAkiTgoZTGlzdE5vZGVSZXZpc2lvbnNSZXNwb25zZRIxCglyZXZpc2lvbnMYASADKAsyHi5sYXZhbC5tYW5hZ2VyLnYxLk5vZGVSZXZpc2lvbiJJChZHZXROb2RlUmV2aXNpb25SZXF1ZXN0EgwKBG5hbWUYASABKAkSEAoIcmV2aXNpb24YAiABKAMSDwoHcHJvamVjdBgDIAEoCSJLChdHZXROb2RlUmV2aXNpb25SZXNwb25zZRIwCghyZXZpc2lvbhgBIAEoCzIeLmxhdmFsLm1hbmFnZXIudjEuTm9kZVJldmlzaW9uIlMKGERpZmZOb2RlUmV2aXNpb25zUmVxdWVzdBIMCgRuYW1lGAEgASgJEgwKBGZyb20YAiABKAMSCgoCdG8YAyABKAMSDwoHcHJvamVjdBgEIAEoCSJLChlEaWZmTm9kZVJldmlzaW9uc1Jlc3BvbnNlEi4KB2NoYW5nZXMYASADKAsyHS5sYXZhbC5tYW5hZ2VyLnYxLkZpZWxkQ2hhbmdlImAKE1JvbGxiYWNrTm9kZVJlcXVlc3QSDAoEbmFtZRgBIAEoCRIQCghyZXZpc2lvbhgCIAEoAxIPCgdwcm9qZWN0GAMgASgJEhgKEGV4cGVjdGVkX3ZlcnNpb24YBCABKAMiPAoUUm9sbGJhY2tOb2RlUmVzcG9uc2USJAoEbm9kZRgBIAEoCzIWLmxhdmFsLm1hbmFnZXIudjEuTm9kZSLOAgoGVHVubmVsEgwKBG5hbWUYASABKAkSEwoLc2VydmVyX25vZGUYAiABKAkSEwoLY2xpZW50X25vZGUYAyABKAkSEQoJYmluZF9hZGRyGAQgASgJEhIKCmxvY2FsX2FkZHIYBSABKAkSKgoEdHlwZRgGIAEoDjIcLmxhdmFsLm1hbmFnZXIudjEuVHVubmVsVHlwZRISCgpjcmVhdGVkX2F0GAcgASgDEh4KEXJvdGF0ZV9ldmVyeV9zZWNzGAggASgESACIAQESGAoQdG9rZW5fcm90YXRlZF9hdBgJIAEoAxImChlwcmV2aW91c190b2tlbl9leHBpcmVzX2F0GAogASgDSAGIAQESDwoHcHJvamVjdBgLIAEoCUIUChJfcm90YXRlX2V2ZXJ5X3NlY3NCHAoaX3ByZXZpb3VzX3Rva2VuX2V4cGlyZXNfYXQiJQoSTGlzdFR1bm5lbHNSZXF1ZXN0Eg8KB3Byb2plY3QYASABKAkiQAoTTGlzdFR1bm5lbHNSZXNwb25zZRIpCgd0dW5uZWxzGAEgAygLMhgubGF2YWwubWFuYWdlci52MS5UdW5uZWwiUAoTQ3JlYXRlVHVubmVsUmVxdWVzdBIoCgZ0dW5uZWwYASABKAsyGC5sYXZhbC5tYW5hZ2VyLnYxLlR1bm5lbBIPCgdwcm9qZWN0GAIgASgJIkAKFENyZWF0ZVR1bm5lbFJlc3BvbnNlEigKBnR1bm5lbBgBIAEoCzIYLmxhdmFsLm1hbmFnZXIudjEuVHVubmVsIjQKE0RlbGV0ZVR1bm5lbFJlcXVlc3QSDAoEbmFtZRgBIAEoCRIPCgdwcm9qZWN0GAIgASgJIhYKFERlbGV0ZVR1bm5lbFJlc3BvbnNlIjkKGFJvdGF0ZVR1bm5lbFRva2VuUmVxdWVzdBIMCgRuYW1lGAEgASgJEg8KB3Byb2plY3QYAiABKAkiRQoZUm90YXRlVHVubmVsVG9rZW5SZXNwb25zZRIoCgZ0dW5uZWwYASABKAsyGC5sYXZhbC5tYW5hZ2VyLnYxLlR1bm5lbCJ6CghOb2lzZUtleRIMCgRub2RlGAEgASgJEhIKCnB1YmxpY19rZXkYAiABKAkSEgoKY3JlYXRlZF9hdBgDIAEoAxIgChNwcmV2aW91c19leHBpcmVzX2F0GAQgASgDSACIAQFCFgoUX3ByZXZpb3VzX2V4cGlyZXNfYXQiNgoVUm90YXRlTm9pc2VLZXlSZXF1ZXN0EgwKBG5vZGUYASABKAkSDwoHcHJvamVjdBgCIAEoCSJBChZSb3RhdGVOb2lzZUtleVJlc3BvbnNlEicKA2tleRgBIAEoCzIaLmxhdmFsLm1hbmFnZXIudjEuTm9pc2VLZXkqcAoPUG9ydE1hcHBpbmdNb2RlEiEKHVBPUlRfTUFQUElOR19NT0RFX1VOU1BFQ0lGSUVEEAASHAoYUE9SVF9NQVBQSU5HX01PREVfU0VSVkVSEAESHAoYUE9SVF9NQVBQSU5HX01PREVfQ0xJRU5UEAIqrwEKE0NvbnRyb2xDaGFubmVsU3RhdGUSJQohQ09OVFJPTF9DSEFOTkVMX1NUQVRFX1VOU1BFQ0lGSUVEEAASJAogQ09OVFJPTF9DSEFOTkVMX1NUQVRFX0NPTk5FQ1RJTkcQARIjCh9DT05UUk9MX0NIQU5ORUxfU1RBVEVfQ09OTkVDVEVEEAISJgoiQ09OVFJPTF9DSEFOTkVMX1NUQVRFX0RJU0NPTk5FQ1RFRBADKlMKClR1bm5lbFR5cGUSGwoXVFVOTkVMX1RZUEVfVU5TUEVDSUZJRUQQABITCg9UVU5ORUxfVFlQRV9UQ1AQARITCg9UVU5ORUxfVFlQRV9VRFAQAjKpDAoLTm9kZU1hbmFnZXISYAoNR2V0Tm9kZUNvbmZpZxImLmxhdmFsLm1hbmFnZXIudjEuR2V0Tm9kZUNvbmZpZ1JlcXVlc3QaJy5sYXZhbC5tYW5hZ2VyLnYxLkdldE5vZGVDb25maWdSZXNwb25zZRJoCg9XYXRjaE5vZGVDb25maWcSKC5sYXZhbC5tYW5hZ2VyLnYxLldhdGNoTm9kZUNvbmZpZ1JlcXVlc3QaKS5sYXZhbC5tYW5hZ2VyLnYxLldhdGNoTm9kZUNvbmZpZ1Jlc3BvbnNlMAESVAoJTGlzdE5vZGVzEiIubGF2YWwubWFuYWdlci52MS5MaXN0Tm9kZXNSZXF1ZXN0GiMubGF2YWwubWFuYWdlci52MS5MaXN0Tm9kZXNSZXNwb25zZRJXCgpDcmVhdGVOb2RlEiMubGF2YWwubWFuYWdlci52MS5DcmVhdGVOb2RlUmVxdWVzdBokLmxhdmFsLm1hbmFnZXIudjEuQ3JlYXRlTm9kZVJlc3BvbnNlElcKClVwZGF0ZU5vZGUSIy5sYXZhbC5tYW5hZ2VyLnYxLlVwZGF0ZU5vZGVSZXF1ZXN0GiQubGF2YWwubWFuYWdlci52MS5VcGRhdGVOb2RlUmVzcG9uc2USVwoKRGVsZXRlTm9kZRIjLmxhdmFsLm1hbmFnZXIudjEuRGVsZXRlTm9kZVJlcXVlc3QaJC5sYXZhbC5tYW5hZ2VyLnYxLkRlbGV0ZU5vZGVSZXNwb25zZRJdCgxSZXBvcnRTdGF0dXMSJS5sYXZhbC5tYW5hZ2VyLnYxLlJlcG9ydFN0YXR1c1JlcXVlc3QaJi5sYXZhbC5tYW5hZ2VyLnYxLlJlcG9ydFN0YXR1c1Jlc3BvbnNlEmwKEUxpc3ROb2RlUmV2aXNpb25zEioubGF2YWwubWFuYWdlci52MS5MaXN0Tm9kZVJldmlzaW9uc1JlcXVlc3QaKy5sYXZhbC5tYW5hZ2VyLnYxLkxpc3ROb2RlUmV2aXNpb25zUmVzcG9uc2USZgoPR2V0Tm9kZVJldmlzaW9uEigubGF2YWwubWFuYWdlci52MS5HZXROb2RlUmV2aXNpb25SZXF1ZXN0GikubGF2YWwubWFuYWdlci52MS5HZXROb2RlUmV2aXNpb25SZXNwb25zZRJsChFEaWZmTm9kZVJldmlzaW9ucxIqLmxhdmFsLm1hbmFnZXIudjEuRGlmZk5vZGVSZXZpc2lvbnNSZXF1ZXN0GisubGF2YWwubWFuYWdlci52MS5EaWZmTm9kZVJldmlzaW9uc1Jlc3BvbnNlEl0KDFJvbGxiYWNrTm9kZRIlLmxhdmFsLm1hbmFnZXIudjEuUm9sbGJhY2tOb2RlUmVxdWVzdBomLmxhdmFsLm1hbmFnZXIudjEuUm9sbGJhY2tOb2RlUmVzcG9uc2USWgoLTGlzdFR1bm5lbHMSJC5sYXZhbC5tYW5hZ2VyLnYxLkxpc3RUdW5uZWxzUmVxdWVzdBolLmxhdmFsLm1hbmFnZXIudjEuTGlzdFR1bm5lbHNSZXNwb25zZRJdCgxDcmVhdGVUdW5uZWwSJS5sYXZhbC5tYW5hZ2VyLnYxLkNyZWF0ZVR1bm5lbFJlcXVlc3QaJi5sYXZhbC5tYW5hZ2VyLnYxLkNyZWF0ZVR1bm5lbFJlc3BvbnNlEl0KDERlbGV0ZVR1bm5lbBIlLmxhdmFsLm1hbmFnZXIudjEuRGVsZXRlVHVubmVsUmVxdWVzdBomLmxhdmFsLm1hbmFnZXIudjEuRGVsZXRlVHVubmVsUmVzcG9uc2USbAoRUm90YXRlVHVubmVsVG9rZW4SKi5sYXZhbC5tYW5hZ2VyLnYxLlJvdGF0ZVR1bm5lbFRva2VuUmVxdWVzdBorLmxhdmFsLm1hbmFnZXIudjEuUm90YXRlVHVubmVsVG9rZW5SZXNwb25zZRJjCg5Sb3RhdGVOb2lzZUtleRInLmxhdmFsLm1hbmFnZXIudjEuUm90YXRlTm9pc2VLZXlSZXF1ZXN0GigubGF2YWwubWFuYWdlci52MS5Sb3RhdGVOb2lzZUtleVJlc3BvbnNlYgZwcm90bzM");
 
 /**
  * @generated from message laval.manager.v1.PortMappingConfig
@@ -34,44 +34,1171 @@ export type PortMappingConfig = Message<"laval.manager.v1.PortMappingConfig"> &
 export const PortMappingConfigSchema: GenMessage<PortMappingConfig> = /*@__PURE__*/
   messageDesc(file_proto_manager, 0);
 
+/**
+ * @generated from message laval.manager.v1.ProxyRoute
+ */
+export type ProxyRoute = Message<"laval.manager.v1.ProxyRoute"> & {
+  /**
+   * @generated from field: string hostname = 1;
+   */
+  hostname: string;
+
+  /**
+   * @generated from field: string upstream = 2;
+   */
+  upstream: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ProxyRoute.
+ * Use `create(ProxyRouteSchema)` to create a new message.
+ */
+export const ProxyRouteSchema: GenMessage<ProxyRoute> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 1);
+
+/**
+ * @generated from message laval.manager.v1.ReverseProxyTls
+ */
+export type ReverseProxyTls = Message<"laval.manager.v1.ReverseProxyTls"> & {
+  /**
+   * @generated from field: string cert = 1;
+   */
+  cert: string;
+
+  /**
+   * @generated from field: string key = 2;
+   */
+  key: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ReverseProxyTls.
+ * Use `create(ReverseProxyTlsSchema)` to create a new message.
+ */
+export const ReverseProxyTlsSchema: GenMessage<ReverseProxyTls> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 2);
+
+/**
+ * @generated from message laval.manager.v1.ReverseProxyConfig
+ */
+export type ReverseProxyConfig = Message<"laval.manager.v1.ReverseProxyConfig"> & {
+  /**
+   * @generated from field: repeated laval.manager.v1.ProxyRoute routes = 1;
+   */
+  routes: ProxyRoute[];
+
+  /**
+   * @generated from field: optional string default_upstream = 2;
+   */
+  defaultUpstream?: string;
+
+  /**
+   * @generated from field: optional laval.manager.v1.ReverseProxyTls tls = 3;
+   */
+  tls?: ReverseProxyTls;
+
+  /**
+   * @generated from field: optional string certificate = 4;
+   */
+  certificate?: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ReverseProxyConfig.
+ * Use `create(ReverseProxyConfigSchema)` to create a new message.
+ */
+export const ReverseProxyConfigSchema: GenMessage<ReverseProxyConfig> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 3);
+
+/**
+ * @generated from message laval.manager.v1.TlsCertificate
+ */
+export type TlsCertificate = Message<"laval.manager.v1.TlsCertificate"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string chain_pem = 2;
+   */
+  chainPem: string;
+
+  /**
+   * @generated from field: string key_pem = 3;
+   */
+  keyPem: string;
+
+  /**
+   * @generated from field: int64 not_after = 4;
+   */
+  notAfter: bigint;
+};
+
+/**
+ * Describes the message laval.manager.v1.TlsCertificate.
+ * Use `create(TlsCertificateSchema)` to create a new message.
+ */
+export const TlsCertificateSchema: GenMessage<TlsCertificate> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 4);
+
+/**
+ * @generated from message laval.manager.v1.Node
+ */
+export type Node = Message<"laval.manager.v1.Node"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: optional string reverse_proxy_bind = 2;
+   */
+  reverseProxyBind?: string;
+
+  /**
+   * @generated from field: optional string port_mapping_role = 3;
+   */
+  portMappingRole?: string;
+
+  /**
+   * @generated from field: optional string management_url = 4;
+   */
+  managementUrl?: string;
+
+  /**
+   * @generated from field: optional string description = 5;
+   */
+  description?: string;
+
+  /**
+   * @generated from field: repeated string tags = 6;
+   */
+  tags: string[];
+
+  /**
+   * @generated from field: optional laval.manager.v1.PortMappingConfig port_mapping = 7;
+   */
+  portMapping?: PortMappingConfig;
+
+  /**
+   * @generated from field: laval.manager.v1.NodeStatus status = 8;
+   */
+  status?: NodeStatus;
+
+  /**
+   * @generated from field: int64 version = 9;
+   */
+  version: bigint;
+
+  /**
+   * @generated from field: string project = 10;
+   */
+  project: string;
+
+  /**
+   * @generated from field: optional laval.manager.v1.ReverseProxyConfig reverse_proxy = 11;
+   */
+  reverseProxy?: ReverseProxyConfig;
+
+  /**
+   * @generated from field: repeated laval.manager.v1.NodeVariable variables = 12;
+   */
+  variables: NodeVariable[];
+};
+
+/**
+ * Describes the message laval.manager.v1.Node.
+ * Use `create(NodeSchema)` to create a new message.
+ */
+export const NodeSchema: GenMessage<Node> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 5);
+
+/**
+ * @generated from message laval.manager.v1.NodeVariable
+ */
+export type NodeVariable = Message<"laval.manager.v1.NodeVariable"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string value = 2;
+   */
+  value: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.NodeVariable.
+ * Use `create(NodeVariableSchema)` to create a new message.
+ */
+export const NodeVariableSchema: GenMessage<NodeVariable> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 6);
+
+/**
+ * @generated from message laval.manager.v1.ServiceStatus
+ */
+export type ServiceStatus = Message<"laval.manager.v1.ServiceStatus"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: laval.manager.v1.ControlChannelState state = 2;
+   */
+  state: ControlChannelState;
+};
+
+/**
+ * Describes the message laval.manager.v1.ServiceStatus.
+ * Use `create(ServiceStatusSchema)` to create a new message.
+ */
+export const ServiceStatusSchema: GenMessage<ServiceStatus> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 7);
+
+/**
+ * @generated from message laval.manager.v1.NodeStatus
+ */
+export type NodeStatus = Message<"laval.manager.v1.NodeStatus"> & {
+  /**
+   * @generated from field: bool online = 1;
+   */
+  online: boolean;
+
+  /**
+   * @generated from field: optional int64 last_seen = 2;
+   */
+  lastSeen?: bigint;
+
+  /**
+   * @generated from field: optional string version = 3;
+   */
+  version?: string;
+
+  /**
+   * @generated from field: optional uint64 uptime_seconds = 4;
+   */
+  uptimeSeconds?: bigint;
+
+  /**
+   * @generated from field: repeated string listen_addrs = 5;
+   */
+  listenAddrs: string[];
+
+  /**
+   * @generated from field: repeated laval.manager.v1.ServiceStatus services = 6;
+   */
+  services: ServiceStatus[];
+};
+
+/**
+ * Describes the message laval.manager.v1.NodeStatus.
+ * Use `create(NodeStatusSchema)` to create a new message.
+ */
+export const NodeStatusSchema: GenMessage<NodeStatus> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 8);
+
 /**
  * @generated from message laval.manager.v1.GetNodeConfigRequest
  */
-export type GetNodeConfigRequest = Message<"laval.manager.v1.GetNodeConfigRequest"> & {
+export type GetNodeConfigRequest = Message<"laval.manager.v1.GetNodeConfigRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.GetNodeConfigRequest.
+ * Use `create(GetNodeConfigRequestSchema)` to create a new message.
+ */
+export const GetNodeConfigRequestSchema: GenMessage<GetNodeConfigRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 9);
+
+/**
+ * @generated from message laval.manager.v1.GetNodeConfigResponse
+ */
+export type GetNodeConfigResponse = Message<"laval.manager.v1.GetNodeConfigResponse"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: optional laval.manager.v1.PortMappingConfig port_mapping = 2;
+   */
+  portMapping?: PortMappingConfig;
+
+  /**
+   * @generated from field: optional laval.manager.v1.ReverseProxyConfig reverse_proxy = 3;
+   */
+  reverseProxy?: ReverseProxyConfig;
+
+  /**
+   * @generated from field: optional laval.manager.v1.TlsCertificate certificate = 4;
+   */
+  certificate?: TlsCertificate;
+};
+
+/**
+ * Describes the message laval.manager.v1.GetNodeConfigResponse.
+ * Use `create(GetNodeConfigResponseSchema)` to create a new message.
+ */
+export const GetNodeConfigResponseSchema: GenMessage<GetNodeConfigResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 10);
+
+/**
+ * @generated from message laval.manager.v1.WatchNodeConfigRequest
+ */
+export type WatchNodeConfigRequest = Message<"laval.manager.v1.WatchNodeConfigRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.WatchNodeConfigRequest.
+ * Use `create(WatchNodeConfigRequestSchema)` to create a new message.
+ */
+export const WatchNodeConfigRequestSchema: GenMessage<WatchNodeConfigRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 11);
+
+/**
+ * @generated from message laval.manager.v1.WatchNodeConfigResponse
+ */
+export type WatchNodeConfigResponse = Message<"laval.manager.v1.WatchNodeConfigResponse"> & {
+  /**
+   * @generated from field: uint64 revision = 1;
+   */
+  revision: bigint;
+
+  /**
+   * @generated from field: string name = 2;
+   */
+  name: string;
+
+  /**
+   * @generated from field: optional laval.manager.v1.PortMappingConfig port_mapping = 3;
+   */
+  portMapping?: PortMappingConfig;
+
+  /**
+   * @generated from field: optional laval.manager.v1.ReverseProxyConfig reverse_proxy = 4;
+   */
+  reverseProxy?: ReverseProxyConfig;
+
+  /**
+   * @generated from field: optional laval.manager.v1.TlsCertificate certificate = 5;
+   */
+  certificate?: TlsCertificate;
+};
+
+/**
+ * Describes the message laval.manager.v1.WatchNodeConfigResponse.
+ * Use `create(WatchNodeConfigResponseSchema)` to create a new message.
+ */
+export const WatchNodeConfigResponseSchema: GenMessage<WatchNodeConfigResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 12);
+
+/**
+ * @generated from message laval.manager.v1.ListNodesRequest
+ */
+export type ListNodesRequest = Message<"laval.manager.v1.ListNodesRequest"> & {
+  /**
+   * @generated from field: repeated string tags = 1;
+   */
+  tags: string[];
+
+  /**
+   * @generated from field: laval.manager.v1.PortMappingMode mode = 2;
+   */
+  mode: PortMappingMode;
+
+  /**
+   * @generated from field: optional string role = 3;
+   */
+  role?: string;
+
+  /**
+   * @generated from field: string query = 4;
+   */
+  query: string;
+
+  /**
+   * @generated from field: string sort = 5;
+   */
+  sort: string;
+
+  /**
+   * @generated from field: uint32 limit = 6;
+   */
+  limit: number;
+
+  /**
+   * @generated from field: string cursor = 7;
+   */
+  cursor: string;
+
+  /**
+   * @generated from field: string project = 8;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ListNodesRequest.
+ * Use `create(ListNodesRequestSchema)` to create a new message.
+ */
+export const ListNodesRequestSchema: GenMessage<ListNodesRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 13);
+
+/**
+ * @generated from message laval.manager.v1.ListNodesResponse
+ */
+export type ListNodesResponse = Message<"laval.manager.v1.ListNodesResponse"> & {
+  /**
+   * @generated from field: repeated laval.manager.v1.Node nodes = 1;
+   */
+  nodes: Node[];
+
+  /**
+   * @generated from field: string next_cursor = 2;
+   */
+  nextCursor: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ListNodesResponse.
+ * Use `create(ListNodesResponseSchema)` to create a new message.
+ */
+export const ListNodesResponseSchema: GenMessage<ListNodesResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 14);
+
+/**
+ * @generated from message laval.manager.v1.CreateNodeRequest
+ */
+export type CreateNodeRequest = Message<"laval.manager.v1.CreateNodeRequest"> & {
+  /**
+   * @generated from field: laval.manager.v1.Node node = 1;
+   */
+  node?: Node;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.CreateNodeRequest.
+ * Use `create(CreateNodeRequestSchema)` to create a new message.
+ */
+export const CreateNodeRequestSchema: GenMessage<CreateNodeRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 15);
+
+/**
+ * @generated from message laval.manager.v1.CreateNodeResponse
+ */
+export type CreateNodeResponse = Message<"laval.manager.v1.CreateNodeResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.Node node = 1;
+   */
+  node?: Node;
+};
+
+/**
+ * Describes the message laval.manager.v1.CreateNodeResponse.
+ * Use `create(CreateNodeResponseSchema)` to create a new message.
+ */
+export const CreateNodeResponseSchema: GenMessage<CreateNodeResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 16);
+
+/**
+ * @generated from message laval.manager.v1.UpdateNodeRequest
+ */
+export type UpdateNodeRequest = Message<"laval.manager.v1.UpdateNodeRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: laval.manager.v1.Node node = 2;
+   */
+  node?: Node;
+
+  /**
+   * @generated from field: int64 expected_version = 3;
+   */
+  expectedVersion: bigint;
+
+  /**
+   * @generated from field: string project = 4;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.UpdateNodeRequest.
+ * Use `create(UpdateNodeRequestSchema)` to create a new message.
+ */
+export const UpdateNodeRequestSchema: GenMessage<UpdateNodeRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 17);
+
+/**
+ * @generated from message laval.manager.v1.UpdateNodeResponse
+ */
+export type UpdateNodeResponse = Message<"laval.manager.v1.UpdateNodeResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.Node node = 1;
+   */
+  node?: Node;
+};
+
+/**
+ * Describes the message laval.manager.v1.UpdateNodeResponse.
+ * Use `create(UpdateNodeResponseSchema)` to create a new message.
+ */
+export const UpdateNodeResponseSchema: GenMessage<UpdateNodeResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 18);
+
+/**
+ * @generated from message laval.manager.v1.DeleteNodeRequest
+ */
+export type DeleteNodeRequest = Message<"laval.manager.v1.DeleteNodeRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: int64 expected_version = 2;
+   */
+  expectedVersion: bigint;
+
+  /**
+   * @generated from field: string project = 3;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.DeleteNodeRequest.
+ * Use `create(DeleteNodeRequestSchema)` to create a new message.
+ */
+export const DeleteNodeRequestSchema: GenMessage<DeleteNodeRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 19);
+
+/**
+ * @generated from message laval.manager.v1.DeleteNodeResponse
+ */
+export type DeleteNodeResponse = Message<"laval.manager.v1.DeleteNodeResponse"> & {
+};
+
+/**
+ * Describes the message laval.manager.v1.DeleteNodeResponse.
+ * Use `create(DeleteNodeResponseSchema)` to create a new message.
+ */
+export const DeleteNodeResponseSchema: GenMessage<DeleteNodeResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 20);
+
+/**
+ * @generated from message laval.manager.v1.ReportStatusRequest
+ */
+export type ReportStatusRequest = Message<"laval.manager.v1.ReportStatusRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string version = 2;
+   */
+  version: string;
+
+  /**
+   * @generated from field: uint64 uptime_seconds = 3;
+   */
+  uptimeSeconds: bigint;
+
+  /**
+   * @generated from field: repeated string listen_addrs = 4;
+   */
+  listenAddrs: string[];
+
+  /**
+   * @generated from field: repeated laval.manager.v1.ServiceStatus services = 5;
+   */
+  services: ServiceStatus[];
+
+  /**
+   * @generated from field: string project = 6;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ReportStatusRequest.
+ * Use `create(ReportStatusRequestSchema)` to create a new message.
+ */
+export const ReportStatusRequestSchema: GenMessage<ReportStatusRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 21);
+
+/**
+ * @generated from message laval.manager.v1.ReportStatusResponse
+ */
+export type ReportStatusResponse = Message<"laval.manager.v1.ReportStatusResponse"> & {
+};
+
+/**
+ * Describes the message laval.manager.v1.ReportStatusResponse.
+ * Use `create(ReportStatusResponseSchema)` to create a new message.
+ */
+export const ReportStatusResponseSchema: GenMessage<ReportStatusResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 22);
+
+/**
+ * @generated from message laval.manager.v1.NodeRevision
+ */
+export type NodeRevision = Message<"laval.manager.v1.NodeRevision"> & {
+  /**
+   * @generated from field: int64 revision = 1;
+   */
+  revision: bigint;
+
+  /**
+   * @generated from field: string action = 2;
+   */
+  action: string;
+
+  /**
+   * @generated from field: optional string author = 3;
+   */
+  author?: string;
+
+  /**
+   * @generated from field: int64 created_at = 4;
+   */
+  createdAt: bigint;
+
+  /**
+   * @generated from field: optional laval.manager.v1.Node node = 5;
+   */
+  node?: Node;
+};
+
+/**
+ * Describes the message laval.manager.v1.NodeRevision.
+ * Use `create(NodeRevisionSchema)` to create a new message.
+ */
+export const NodeRevisionSchema: GenMessage<NodeRevision> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 23);
+
+/**
+ * @generated from message laval.manager.v1.FieldChange
+ */
+export type FieldChange = Message<"laval.manager.v1.FieldChange"> & {
+  /**
+   * @generated from field: string path = 1;
+   */
+  path: string;
+
+  /**
+   * @generated from field: optional string from_json = 2;
+   */
+  fromJson?: string;
+
+  /**
+   * @generated from field: optional string to_json = 3;
+   */
+  toJson?: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.FieldChange.
+ * Use `create(FieldChangeSchema)` to create a new message.
+ */
+export const FieldChangeSchema: GenMessage<FieldChange> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 24);
+
+/**
+ * @generated from message laval.manager.v1.ListNodeRevisionsRequest
+ */
+export type ListNodeRevisionsRequest = Message<"laval.manager.v1.ListNodeRevisionsRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ListNodeRevisionsRequest.
+ * Use `create(ListNodeRevisionsRequestSchema)` to create a new message.
+ */
+export const ListNodeRevisionsRequestSchema: GenMessage<ListNodeRevisionsRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 25);
+
+/**
+ * @generated from message laval.manager.v1.ListNodeRevisionsResponse
+ */
+export type ListNodeRevisionsResponse = Message<"laval.manager.v1.ListNodeRevisionsResponse"> & {
+  /**
+   * @generated from field: repeated laval.manager.v1.NodeRevision revisions = 1;
+   */
+  revisions: NodeRevision[];
+};
+
+/**
+ * Describes the message laval.manager.v1.ListNodeRevisionsResponse.
+ * Use `create(ListNodeRevisionsResponseSchema)` to create a new message.
+ */
+export const ListNodeRevisionsResponseSchema: GenMessage<ListNodeRevisionsResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 26);
+
+/**
+ * @generated from message laval.manager.v1.GetNodeRevisionRequest
+ */
+export type GetNodeRevisionRequest = Message<"laval.manager.v1.GetNodeRevisionRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: int64 revision = 2;
+   */
+  revision: bigint;
+
+  /**
+   * @generated from field: string project = 3;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.GetNodeRevisionRequest.
+ * Use `create(GetNodeRevisionRequestSchema)` to create a new message.
+ */
+export const GetNodeRevisionRequestSchema: GenMessage<GetNodeRevisionRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 27);
+
+/**
+ * @generated from message laval.manager.v1.GetNodeRevisionResponse
+ */
+export type GetNodeRevisionResponse = Message<"laval.manager.v1.GetNodeRevisionResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.NodeRevision revision = 1;
+   */
+  revision?: NodeRevision;
+};
+
+/**
+ * Describes the message laval.manager.v1.GetNodeRevisionResponse.
+ * Use `create(GetNodeRevisionResponseSchema)` to create a new message.
+ */
+export const GetNodeRevisionResponseSchema: GenMessage<GetNodeRevisionResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 28);
+
+/**
+ * @generated from message laval.manager.v1.DiffNodeRevisionsRequest
+ */
+export type DiffNodeRevisionsRequest = Message<"laval.manager.v1.DiffNodeRevisionsRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: int64 from = 2;
+   */
+  from: bigint;
+
+  /**
+   * @generated from field: int64 to = 3;
+   */
+  to: bigint;
+
+  /**
+   * @generated from field: string project = 4;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.DiffNodeRevisionsRequest.
+ * Use `create(DiffNodeRevisionsRequestSchema)` to create a new message.
+ */
+export const DiffNodeRevisionsRequestSchema: GenMessage<DiffNodeRevisionsRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 29);
+
+/**
+ * @generated from message laval.manager.v1.DiffNodeRevisionsResponse
+ */
+export type DiffNodeRevisionsResponse = Message<"laval.manager.v1.DiffNodeRevisionsResponse"> & {
+  /**
+   * @generated from field: repeated laval.manager.v1.FieldChange changes = 1;
+   */
+  changes: FieldChange[];
+};
+
+/**
+ * Describes the message laval.manager.v1.DiffNodeRevisionsResponse.
+ * Use `create(DiffNodeRevisionsResponseSchema)` to create a new message.
+ */
+export const DiffNodeRevisionsResponseSchema: GenMessage<DiffNodeRevisionsResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 30);
+
+/**
+ * @generated from message laval.manager.v1.RollbackNodeRequest
+ */
+export type RollbackNodeRequest = Message<"laval.manager.v1.RollbackNodeRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: int64 revision = 2;
+   */
+  revision: bigint;
+
+  /**
+   * @generated from field: string project = 3;
+   */
+  project: string;
+
+  /**
+   * @generated from field: int64 expected_version = 4;
+   */
+  expectedVersion: bigint;
+};
+
+/**
+ * Describes the message laval.manager.v1.RollbackNodeRequest.
+ * Use `create(RollbackNodeRequestSchema)` to create a new message.
+ */
+export const RollbackNodeRequestSchema: GenMessage<RollbackNodeRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 31);
+
+/**
+ * @generated from message laval.manager.v1.RollbackNodeResponse
+ */
+export type RollbackNodeResponse = Message<"laval.manager.v1.RollbackNodeResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.Node node = 1;
+   */
+  node?: Node;
+};
+
+/**
+ * Describes the message laval.manager.v1.RollbackNodeResponse.
+ * Use `create(RollbackNodeResponseSchema)` to create a new message.
+ */
+export const RollbackNodeResponseSchema: GenMessage<RollbackNodeResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 32);
+
+/**
+ * @generated from message laval.manager.v1.Tunnel
+ */
+export type Tunnel = Message<"laval.manager.v1.Tunnel"> & {
   /**
    * @generated from field: string name = 1;
    */
   name: string;
+
+  /**
+   * @generated from field: string server_node = 2;
+   */
+  serverNode: string;
+
+  /**
+   * @generated from field: string client_node = 3;
+   */
+  clientNode: string;
+
+  /**
+   * @generated from field: string bind_addr = 4;
+   */
+  bindAddr: string;
+
+  /**
+   * @generated from field: string local_addr = 5;
+   */
+  localAddr: string;
+
+  /**
+   * @generated from field: laval.manager.v1.TunnelType type = 6;
+   */
+  type: TunnelType;
+
+  /**
+   * @generated from field: int64 created_at = 7;
+   */
+  createdAt: bigint;
+
+  /**
+   * @generated from field: optional uint64 rotate_every_secs = 8;
+   */
+  rotateEverySecs?: bigint;
+
+  /**
+   * @generated from field: int64 token_rotated_at = 9;
+   */
+  tokenRotatedAt: bigint;
+
+  /**
+   * @generated from field: optional int64 previous_token_expires_at = 10;
+   */
+  previousTokenExpiresAt?: bigint;
+
+  /**
+   * @generated from field: string project = 11;
+   */
+  project: string;
 };
 
 /**
- * Describes the message laval.manager.v1.GetNodeConfigRequest.
- * Use `create(GetNodeConfigRequestSchema)` to create a new message.
+ * Describes the message laval.manager.v1.Tunnel.
+ * Use `create(TunnelSchema)` to create a new message.
  */
-export const GetNodeConfigRequestSchema: GenMessage<GetNodeConfigRequest> = /*@__PURE__*/
-  messageDesc(file_proto_manager, 1);
+export const TunnelSchema: GenMessage<Tunnel> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 33);
 
 /**
- * @generated from message laval.manager.v1.GetNodeConfigResponse
+ * @generated from message laval.manager.v1.ListTunnelsRequest
  */
-export type GetNodeConfigResponse = Message<"laval.manager.v1.GetNodeConfigResponse"> & {
+export type ListTunnelsRequest = Message<"laval.manager.v1.ListTunnelsRequest"> & {
+  /**
+   * @generated from field: string project = 1;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.ListTunnelsRequest.
+ * Use `create(ListTunnelsRequestSchema)` to create a new message.
+ */
+export const ListTunnelsRequestSchema: GenMessage<ListTunnelsRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 34);
+
+/**
+ * @generated from message laval.manager.v1.ListTunnelsResponse
+ */
+export type ListTunnelsResponse = Message<"laval.manager.v1.ListTunnelsResponse"> & {
+  /**
+   * @generated from field: repeated laval.manager.v1.Tunnel tunnels = 1;
+   */
+  tunnels: Tunnel[];
+};
+
+/**
+ * Describes the message laval.manager.v1.ListTunnelsResponse.
+ * Use `create(ListTunnelsResponseSchema)` to create a new message.
+ */
+export const ListTunnelsResponseSchema: GenMessage<ListTunnelsResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 35);
+
+/**
+ * @generated from message laval.manager.v1.CreateTunnelRequest
+ */
+export type CreateTunnelRequest = Message<"laval.manager.v1.CreateTunnelRequest"> & {
+  /**
+   * @generated from field: laval.manager.v1.Tunnel tunnel = 1;
+   */
+  tunnel?: Tunnel;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.CreateTunnelRequest.
+ * Use `create(CreateTunnelRequestSchema)` to create a new message.
+ */
+export const CreateTunnelRequestSchema: GenMessage<CreateTunnelRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 36);
+
+/**
+ * @generated from message laval.manager.v1.CreateTunnelResponse
+ */
+export type CreateTunnelResponse = Message<"laval.manager.v1.CreateTunnelResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.Tunnel tunnel = 1;
+   */
+  tunnel?: Tunnel;
+};
+
+/**
+ * Describes the message laval.manager.v1.CreateTunnelResponse.
+ * Use `create(CreateTunnelResponseSchema)` to create a new message.
+ */
+export const CreateTunnelResponseSchema: GenMessage<CreateTunnelResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 37);
+
+/**
+ * @generated from message laval.manager.v1.DeleteTunnelRequest
+ */
+export type DeleteTunnelRequest = Message<"laval.manager.v1.DeleteTunnelRequest"> & {
   /**
    * @generated from field: string name = 1;
    */
   name: string;
 
   /**
-   * @generated from field: optional laval.manager.v1.PortMappingConfig port_mapping = 2;
+   * @generated from field: string project = 2;
    */
-  portMapping?: PortMappingConfig;
+  project: string;
 };
 
 /**
- * Describes the message laval.manager.v1.GetNodeConfigResponse.
- * Use `create(GetNodeConfigResponseSchema)` to create a new message.
+ * Describes the message laval.manager.v1.DeleteTunnelRequest.
+ * Use `create(DeleteTunnelRequestSchema)` to create a new message.
  */
-export const GetNodeConfigResponseSchema: GenMessage<GetNodeConfigResponse> = /*@__PURE__*/
-  messageDesc(file_proto_manager, 2);
+export const DeleteTunnelRequestSchema: GenMessage<DeleteTunnelRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 38);
+
+/**
+ * @generated from message laval.manager.v1.DeleteTunnelResponse
+ */
+export type DeleteTunnelResponse = Message<"laval.manager.v1.DeleteTunnelResponse"> & {
+};
+
+/**
+ * Describes the message laval.manager.v1.DeleteTunnelResponse.
+ * Use `create(DeleteTunnelResponseSchema)` to create a new message.
+ */
+export const DeleteTunnelResponseSchema: GenMessage<DeleteTunnelResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 39);
+
+/**
+ * @generated from message laval.manager.v1.RotateTunnelTokenRequest
+ */
+export type RotateTunnelTokenRequest = Message<"laval.manager.v1.RotateTunnelTokenRequest"> & {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name: string;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.RotateTunnelTokenRequest.
+ * Use `create(RotateTunnelTokenRequestSchema)` to create a new message.
+ */
+export const RotateTunnelTokenRequestSchema: GenMessage<RotateTunnelTokenRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 40);
+
+/**
+ * @generated from message laval.manager.v1.RotateTunnelTokenResponse
+ */
+export type RotateTunnelTokenResponse = Message<"laval.manager.v1.RotateTunnelTokenResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.Tunnel tunnel = 1;
+   */
+  tunnel?: Tunnel;
+};
+
+/**
+ * Describes the message laval.manager.v1.RotateTunnelTokenResponse.
+ * Use `create(RotateTunnelTokenResponseSchema)` to create a new message.
+ */
+export const RotateTunnelTokenResponseSchema: GenMessage<RotateTunnelTokenResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 41);
+
+/**
+ * @generated from message laval.manager.v1.NoiseKey
+ */
+export type NoiseKey = Message<"laval.manager.v1.NoiseKey"> & {
+  /**
+   * @generated from field: string node = 1;
+   */
+  node: string;
+
+  /**
+   * @generated from field: string public_key = 2;
+   */
+  publicKey: string;
+
+  /**
+   * @generated from field: int64 created_at = 3;
+   */
+  createdAt: bigint;
+
+  /**
+   * @generated from field: optional int64 previous_expires_at = 4;
+   */
+  previousExpiresAt?: bigint;
+};
+
+/**
+ * Describes the message laval.manager.v1.NoiseKey.
+ * Use `create(NoiseKeySchema)` to create a new message.
+ */
+export const NoiseKeySchema: GenMessage<NoiseKey> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 42);
+
+/**
+ * @generated from message laval.manager.v1.RotateNoiseKeyRequest
+ */
+export type RotateNoiseKeyRequest = Message<"laval.manager.v1.RotateNoiseKeyRequest"> & {
+  /**
+   * @generated from field: string node = 1;
+   */
+  node: string;
+
+  /**
+   * @generated from field: string project = 2;
+   */
+  project: string;
+};
+
+/**
+ * Describes the message laval.manager.v1.RotateNoiseKeyRequest.
+ * Use `create(RotateNoiseKeyRequestSchema)` to create a new message.
+ */
+export const RotateNoiseKeyRequestSchema: GenMessage<RotateNoiseKeyRequest> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 43);
+
+/**
+ * @generated from message laval.manager.v1.RotateNoiseKeyResponse
+ */
+export type RotateNoiseKeyResponse = Message<"laval.manager.v1.RotateNoiseKeyResponse"> & {
+  /**
+   * @generated from field: laval.manager.v1.NoiseKey key = 1;
+   */
+  key?: NoiseKey;
+};
+
+/**
+ * Describes the message laval.manager.v1.RotateNoiseKeyResponse.
+ * Use `create(RotateNoiseKeyResponseSchema)` to create a new message.
+ */
+export const RotateNoiseKeyResponseSchema: GenMessage<RotateNoiseKeyResponse> = /*@__PURE__*/
+  messageDesc(file_proto_manager, 44);
 
 /**
  * @generated from enum laval.manager.v1.PortMappingMode
@@ -99,6 +1226,63 @@ export enum PortMappingMode {
 export const PortMappingModeSchema: GenEnum<PortMappingMode> = /*@__PURE__*/
   enumDesc(file_proto_manager, 0);
 
+/**
+ * @generated from enum laval.manager.v1.ControlChannelState
+ */
+export enum ControlChannelState {
+  /**
+   * @generated from enum value: CONTROL_CHANNEL_STATE_UNSPECIFIED = 0;
+   */
+  UNSPECIFIED = 0,
+
+  /**
+   * @generated from enum value: CONTROL_CHANNEL_STATE_CONNECTING = 1;
+   */
+  CONNECTING = 1,
+
+  /**
+   * @generated from enum value: CONTROL_CHANNEL_STATE_CONNECTED = 2;
+   */
+  CONNECTED = 2,
+
+  /**
+   * @generated from enum value: CONTROL_CHANNEL_STATE_DISCONNECTED = 3;
+   */
+  DISCONNECTED = 3,
+}
+
+/**
+ * Describes the enum laval.manager.v1.ControlChannelState.
+ */
+export const ControlChannelStateSchema: GenEnum<ControlChannelState> = /*@__PURE__*/
+  enumDesc(file_proto_manager, 1);
+
+/**
+ * @generated from enum laval.manager.v1.TunnelType
+ */
+export enum TunnelType {
+  /**
+   * @generated from enum value: TUNNEL_TYPE_UNSPECIFIED = 0;
+   */
+  UNSPECIFIED = 0,
+
+  /**
+   * @generated from enum value: TUNNEL_TYPE_TCP = 1;
+   */
+  TCP = 1,
+
+  /**
+   * @generated from enum value: TUNNEL_TYPE_UDP = 2;
+   */
+  UDP = 2,
+}
+
+/**
+ * Describes the enum laval.manager.v1.TunnelType.
+ */
+export const TunnelTypeSchema: GenEnum<TunnelType> = /*@__PURE__*/
+  enumDesc(file_proto_manager, 2);
+
 /**
  * @generated from service laval.manager.v1.NodeManager
  */
@@ -111,6 +1295,126 @@ export const NodeManager: GenService<{
     input: typeof GetNodeConfigRequestSchema;
     output: typeof GetNodeConfigResponseSchema;
   },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.WatchNodeConfig
+   */
+  watchNodeConfig: {
+    methodKind: "server_streaming";
+    input: typeof WatchNodeConfigRequestSchema;
+    output: typeof WatchNodeConfigResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.ListNodes
+   */
+  listNodes: {
+    methodKind: "unary";
+    input: typeof ListNodesRequestSchema;
+    output: typeof ListNodesResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.CreateNode
+   */
+  createNode: {
+    methodKind: "unary";
+    input: typeof CreateNodeRequestSchema;
+    output: typeof CreateNodeResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.UpdateNode
+   */
+  updateNode: {
+    methodKind: "unary";
+    input: typeof UpdateNodeRequestSchema;
+    output: typeof UpdateNodeResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.DeleteNode
+   */
+  deleteNode: {
+    methodKind: "unary";
+    input: typeof DeleteNodeRequestSchema;
+    output: typeof DeleteNodeResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.ReportStatus
+   */
+  reportStatus: {
+    methodKind: "unary";
+    input: typeof ReportStatusRequestSchema;
+    output: typeof ReportStatusResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.ListNodeRevisions
+   */
+  listNodeRevisions: {
+    methodKind: "unary";
+    input: typeof ListNodeRevisionsRequestSchema;
+    output: typeof ListNodeRevisionsResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.GetNodeRevision
+   */
+  getNodeRevision: {
+    methodKind: "unary";
+    input: typeof GetNodeRevisionRequestSchema;
+    output: typeof GetNodeRevisionResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.DiffNodeRevisions
+   */
+  diffNodeRevisions: {
+    methodKind: "unary";
+    input: typeof DiffNodeRevisionsRequestSchema;
+    output: typeof DiffNodeRevisionsResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.RollbackNode
+   */
+  rollbackNode: {
+    methodKind: "unary";
+    input: typeof RollbackNodeRequestSchema;
+    output: typeof RollbackNodeResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.ListTunnels
+   */
+  listTunnels: {
+    methodKind: "unary";
+    input: typeof ListTunnelsRequestSchema;
+    output: typeof ListTunnelsResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.CreateTunnel
+   */
+  createTunnel: {
+    methodKind: "unary";
+    input: typeof CreateTunnelRequestSchema;
+    output: typeof CreateTunnelResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.DeleteTunnel
+   */
+  deleteTunnel: {
+    methodKind: "unary";
+    input: typeof DeleteTunnelRequestSchema;
+    output: typeof DeleteTunnelResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.RotateTunnelToken
+   */
+  rotateTunnelToken: {
+    methodKind: "unary";
+    input: typeof RotateTunnelTokenRequestSchema;
+    output: typeof RotateTunnelTokenResponseSchema;
+  },
+  /**
+   * @generated from rpc laval.manager.v1.NodeManager.RotateNoiseKey
+   */
+  rotateNoiseKey: {
+    methodKind: "unary";
+    input: typeof RotateNoiseKeyRequestSchema;
+    output: typeof RotateNoiseKeyResponseSchema;
+  },
 }> = /*@__PURE__*/
   serviceDesc(file_proto_manager, 0);
 
@@ -1,4 +1,4 @@
-import { createClient } from '@connectrpc/connect'
+import { createClient, type Interceptor } from '@connectrpc/connect'
 import { createGrpcWebTransport } from '@connectrpc/connect-web'
 
 import { NodeManager } from '@/gen/proto/manager_pb'
@@ -8,8 +8,18 @@ const DEFAULT_BASE_URL = 'http://localhost:50051'
 export const MANAGER_GRPC_BASE_URL =
   import.meta.env.VITE_MANAGER_GRPC_URL ?? DEFAULT_BASE_URL
 
+const MANAGER_API_TOKEN = import.meta.env.VITE_MANAGER_API_TOKEN
+
+const authenticate: Interceptor = (next) => async (req) => {
+  if (MANAGER_API_TOKEN) {
+    req.header.set('authorization', `Bearer ${MANAGER_API_TOKEN}`)
+  }
+  return next(req)
+}
+
 const transport = createGrpcWebTransport({
   baseUrl: MANAGER_GRPC_BASE_URL,
+  interceptors: [authenticate],
 })
 
 export const nodeManagerClient = createClient(NodeManager, transport)